@@ -0,0 +1,54 @@
+//! Helpers for plugin build scripts.
+
+use std::{collections::BTreeSet, env, fs, path::PathBuf};
+
+const HANDLER_MACRO: &str = "generate_handler![";
+
+/// Ensures the command list passed to `tauri_plugin::Builder` matches the commands
+/// registered with `tauri::generate_handler!` in the crate's `src/lib.rs`.
+///
+/// Panics with the commands missing from either side, so a command can't be added
+/// to the invoke handler without also getting its generated permissions.
+pub fn verify_commands(commands: &[&str]) {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is not set");
+    let lib_path = PathBuf::from(manifest_dir).join("src").join("lib.rs");
+    println!("cargo:rerun-if-changed={}", lib_path.display());
+
+    let source = fs::read_to_string(&lib_path)
+        .unwrap_or_else(|e| panic!("failed to read {}: {e}", lib_path.display()));
+
+    let registered = handler_commands(&source);
+    let declared: BTreeSet<String> = commands.iter().map(|c| c.to_string()).collect();
+
+    let missing: Vec<_> = registered.difference(&declared).cloned().collect();
+    let unknown: Vec<_> = declared.difference(&registered).cloned().collect();
+
+    if !missing.is_empty() || !unknown.is_empty() {
+        panic!(
+            "COMMANDS in build.rs is out of sync with generate_handler! in src/lib.rs\n  \
+             missing from COMMANDS: [{}]\n  \
+             not registered in the invoke handler: [{}]",
+            missing.join(", "),
+            unknown.join(", "),
+        );
+    }
+}
+
+/// Returns the command names registered in the first `generate_handler!` invocation
+/// of `source`, with any module path stripped.
+pub fn handler_commands(source: &str) -> BTreeSet<String> {
+    let Some(start) = source.find(HANDLER_MACRO) else {
+        return BTreeSet::new();
+    };
+
+    let body = &source[start + HANDLER_MACRO.len()..];
+    let body = &body[..body.find(']').unwrap_or(body.len())];
+
+    body.lines()
+        .map(|line| line.split("//").next().unwrap_or_default())
+        .flat_map(|line| line.split(','))
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .map(|path| path.rsplit("::").next().unwrap_or(path).to_string())
+        .collect()
+}
@@ -1,3 +1,4 @@
+pub mod build;
 pub mod date;
 pub mod permissions;
 
@@ -12,6 +13,107 @@ pub struct PluginError {
     pub details: Option<serde_json::Value>,
 }
 
+impl PluginError {
+    pub fn new(code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            code: code.into(),
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Builds an error from a rejection returned by the native side of a plugin.
+    ///
+    /// Native codes that match an [`ErrorCode`] are kept as-is; any other code is
+    /// reported as [`ErrorCode::PluginInvoke`] with the original in `details.nativeCode`.
+    pub fn from_native(code: Option<&str>, message: impl Into<String>) -> Self {
+        match code {
+            Some(native) => match native.parse::<ErrorCode>() {
+                Ok(code) => Self::new(code, message),
+                Err(_) => Self::new(ErrorCode::PluginInvoke, message)
+                    .with_details(serde_json::json!({ "nativeCode": native })),
+            },
+            None => Self::new(ErrorCode::PluginInvoke, message),
+        }
+    }
+}
+
+impl std::fmt::Display for PluginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+/// Stable, machine-readable error codes shared by all plugins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCode {
+    /// The feature is not available on this device or platform
+    NotAvailable,
+    /// The user or system denied access
+    PermissionDenied,
+    /// The arguments passed to the command were rejected
+    InvalidInput,
+    /// The operation was attempted but did not succeed
+    OperationFailed,
+    /// A filesystem or I/O error occurred
+    Io,
+    /// The native plugin rejected the call without a more specific code
+    PluginInvoke,
+    /// An unexpected error inside Tauri or the plugin itself
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NotAvailable => "notAvailable",
+            Self::PermissionDenied => "permissionDenied",
+            Self::InvalidInput => "invalidInput",
+            Self::OperationFailed => "operationFailed",
+            Self::Io => "io",
+            Self::PluginInvoke => "pluginInvoke",
+            Self::Internal => "internal",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for ErrorCode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "notAvailable" => Ok(Self::NotAvailable),
+            "permissionDenied" => Ok(Self::PermissionDenied),
+            "invalidInput" => Ok(Self::InvalidInput),
+            "operationFailed" => Ok(Self::OperationFailed),
+            "io" => Ok(Self::Io),
+            "pluginInvoke" => Ok(Self::PluginInvoke),
+            "internal" => Ok(Self::Internal),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<ErrorCode> for String {
+    fn from(code: ErrorCode) -> Self {
+        code.as_str().to_string()
+    }
+}
+
 /// Common result type for plugin operations
 pub type PluginResult<T> = Result<T, PluginError>;
 
@@ -1,6 +1,8 @@
-const COMMANDS: &[&str] = &["ping"];
+const COMMANDS: &[&str] = &[];
 
 fn main() {
+  tauri_ios_plugin_shared::build::verify_commands(COMMANDS);
+
   tauri_plugin::Builder::new(COMMANDS)
     .android_path("android")
     .ios_path("ios")
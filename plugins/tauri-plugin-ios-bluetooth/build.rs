@@ -1,6 +1,49 @@
-const COMMANDS: &[&str] = &["ping"];
+const COMMANDS: &[&str] = &[
+    "request_authorization",
+    "get_authorization_status",
+    "is_bluetooth_enabled",
+    "start_central_scan",
+    "stop_central_scan",
+    "connect_peripheral",
+    "disconnect_peripheral",
+    "get_connected_peripherals",
+    "get_discovered_peripherals",
+    "discover_services",
+    "discover_characteristics",
+    "read_characteristic",
+    "write_characteristic",
+    "subscribe_to_characteristic",
+    "unsubscribe_from_characteristic",
+    "read_descriptor",
+    "write_descriptor",
+    "get_peripheral_rssi",
+    "start_peripheral_advertising",
+    "stop_peripheral_advertising",
+    "add_service",
+    "remove_service",
+    "remove_all_services",
+    "respond_to_request",
+    "update_characteristic_value",
+    "get_maximum_write_length",
+    "set_notify_value",
+    "watch_central_scan",
+    "watch_characteristic",
+    "listen",
+    "unlisten",
+    "read_decoded_characteristic",
+    "decode_characteristic_value",
+    "read_device_information",
+    "write_chunked",
+    "cancel_transfer",
+    "get_connection_state",
+    "get_connection_history",
+    "set_reconnect_policy",
+    "parse_advertisement",
+];
 
 fn main() {
+  tauri_ios_plugin_shared::build::verify_commands(COMMANDS);
+
   tauri_plugin::Builder::new(COMMANDS)
     .android_path("android")
     .ios_path("ios")
@@ -1,4 +1,22 @@
-import { invoke } from '@tauri-apps/api/core'
+import { Channel, invoke } from '@tauri-apps/api/core'
+
+// Errors
+/** Stable machine-readable code of a rejected command */
+export type ErrorCode =
+  | 'notAvailable'
+  | 'permissionDenied'
+  | 'invalidInput'
+  | 'operationFailed'
+  | 'io'
+  | 'pluginInvoke'
+  | 'internal'
+
+/** Shape of the error every command rejects with */
+export interface PluginError {
+  code: ErrorCode
+  message: string
+  details?: Record<string, unknown> | null
+}
 
 // Enums
 export enum AuthorizationStatus {
@@ -74,6 +92,26 @@ export interface ScanOptions {
   serviceUuids?: string[]
   allowDuplicates?: boolean
   scanMode?: ScanMode
+  namePrefix?: string
+  /** Regular expression in Rust `regex` syntax */
+  namePattern?: string
+  manufacturerData?: ManufacturerDataFilter
+  /** Minimum signal strength in dBm */
+  minRssi?: number
+  serviceData?: ServiceDataFilter
+}
+
+/** Matches data whose leading bytes equal `data` wherever `mask` has bits set */
+export interface ManufacturerDataFilter {
+  companyId: number
+  data: number[]
+  mask?: number[]
+}
+
+export interface ServiceDataFilter {
+  serviceUuid: string
+  data: number[]
+  mask?: number[]
 }
 
 export interface Peripheral {
@@ -130,10 +168,25 @@ export interface WriteOptions {
 }
 
 export interface ConnectionOptions {
+  /** Retry failed attempts and re-establish dropped connections, restoring notifications */
   autoConnect?: boolean
+  /** Timeout of each connection attempt, 10 seconds by default */
   timeoutMs?: number
 }
 
+export interface ReconnectPolicy {
+  maxAttempts?: number
+  initialDelayMs?: number
+  maxDelayMs?: number
+}
+
+export interface ConnectionRecord {
+  state: PeripheralState
+  timestamp: string
+  attempt: number
+  error?: string
+}
+
 export interface AdvertisingData {
   localName?: string
   serviceUuids?: string[]
@@ -170,12 +223,17 @@ export interface PeripheralService {
 }
 
 export interface ReadRequest {
+  requestId: string
   centralUuid: string
   characteristicUuid: string
   offset: number
+  /** Most bytes of the value one response carries; longer values take several reads */
+  maxLength: number
 }
 
+/** One value of a write batch; all values of a batch share its `requestId`. */
 export interface WriteRequest {
+  requestId: string
   centralUuid: string
   characteristicUuid: string
   value: number[]
@@ -188,12 +246,170 @@ export interface RequestResponse {
   value?: number[]
 }
 
-export interface BluetoothEvent {
-  eventType: BluetoothEventType
+export type BluetoothEventPayload =
+  | { eventType: BluetoothEventType.StateChanged; data: { state: BluetoothState } }
+  | { eventType: BluetoothEventType.PeripheralDiscovered; data: Peripheral }
+  | { eventType: BluetoothEventType.PeripheralConnected; data: { peripheralUuid: string } }
+  | { eventType: BluetoothEventType.PeripheralDisconnected; data: { peripheralUuid: string; error?: string } }
+  | { eventType: BluetoothEventType.ServiceDiscovered; data: { peripheralUuid: string; service: Service } }
+  | { eventType: BluetoothEventType.CharacteristicDiscovered; data: { peripheralUuid: string; characteristic: Characteristic } }
+  | { eventType: BluetoothEventType.CharacteristicValueUpdated; data: { peripheralUuid: string; characteristicUuid: string; value: number[] } }
+  | { eventType: BluetoothEventType.CharacteristicSubscriptionChanged; data: { peripheralUuid: string; characteristicUuid: string; isNotifying: boolean } }
+  | { eventType: BluetoothEventType.DescriptorValueUpdated; data: { peripheralUuid: string; descriptorUuid: string; value: number[] } }
+  | { eventType: BluetoothEventType.CentralSubscribed; data: { centralUuid: string; characteristicUuid: string } }
+  | { eventType: BluetoothEventType.CentralUnsubscribed; data: { centralUuid: string; characteristicUuid: string } }
+  | { eventType: BluetoothEventType.ReadRequestReceived; data: ReadRequest }
+  | { eventType: BluetoothEventType.WriteRequestReceived; data: { requestId: string; requests: WriteRequest[] } }
+
+export type BluetoothEvent = BluetoothEventPayload & {
   timestamp: string
-  data: any
 }
 
+// GATT
+export interface HeartRateMeasurement {
+  heartRate: number
+  sensorContact?: boolean
+  energyExpended?: number
+  /** RR intervals in units of 1/1024 second */
+  rrIntervals: number[]
+}
+
+export type BodySensorLocation = 'other' | 'chest' | 'wrist' | 'finger' | 'hand' | 'earLobe' | 'foot'
+
+export interface CscMeasurement {
+  wheel?: { cumulativeRevolutions: number; lastEventTime: number }
+  crank?: { cumulativeRevolutions: number; lastEventTime: number }
+}
+
+export interface CscFeature {
+  wheelRevolutionData: boolean
+  crankRevolutionData: boolean
+  multipleSensorLocations: boolean
+}
+
+export interface TemperatureMeasurement {
+  value: number
+  unit: 'celsius' | 'fahrenheit'
+  timestamp?: string
+  temperatureType?: number
+}
+
+export interface PnpId {
+  vendorIdSource: number
+  vendorId: number
+  productId: number
+  productVersion: number
+}
+
+export interface SystemId {
+  manufacturerIdentifier: number
+  organizationallyUniqueIdentifier: number
+}
+
+export type GattReading =
+  | { type: 'heartRateMeasurement'; value: HeartRateMeasurement }
+  | { type: 'bodySensorLocation'; value: BodySensorLocation }
+  | { type: 'batteryLevel'; value: number }
+  | { type: 'cscMeasurement'; value: CscMeasurement }
+  | { type: 'cscFeature'; value: CscFeature }
+  | { type: 'temperatureMeasurement'; value: TemperatureMeasurement }
+  | { type: 'pnpId'; value: PnpId }
+  | { type: 'systemId'; value: SystemId }
+  | { type: 'text'; value: string }
+
+export interface DeviceInformation {
+  manufacturerName?: string
+  modelNumber?: string
+  serialNumber?: string
+  hardwareRevision?: string
+  firmwareRevision?: string
+  softwareRevision?: string
+  systemId?: SystemId
+  pnpId?: PnpId
+}
+
+export interface Framing {
+  lengthPrefix?: 'u16' | 'u32'
+  checksum?: 'crc16Ccitt' | 'crc32'
+}
+
+export interface TransferOptions {
+  writeType?: WriteType
+  chunkSize?: number
+  framing?: Framing
+  resumeFrom?: number
+  maxRetries?: number
+}
+
+export interface TransferProgress {
+  bytesSent: number
+  totalBytes: number
+  chunksSent: number
+}
+
+export interface TransferReport {
+  bytesSent: number
+  totalBytes: number
+  completed: boolean
+}
+
+export interface ChunkedWriteOptions extends TransferOptions {
+  onProgress?: (progress: TransferProgress) => void
+  /** Cancels the transfer; the returned report then has `completed: false` */
+  signal?: AbortSignal
+}
+
+export interface IBeacon {
+  proximityUuid: string
+  major: number
+  minor: number
+  measuredPower: number
+}
+
+export interface EddystoneUid {
+  txPower: number
+  namespace: string
+  instance: string
+}
+
+export interface EddystoneUrl {
+  txPower: number
+  url: string
+}
+
+export interface EddystoneTlm {
+  batteryVoltage?: number
+  temperature?: number
+  advertisingCount: number
+  uptime: number
+}
+
+export type Beacon =
+  | { type: 'iBeacon'; value: IBeacon }
+  | { type: 'eddystoneUid'; value: EddystoneUid }
+  | { type: 'eddystoneUrl'; value: EddystoneUrl }
+  | { type: 'eddystoneTlm'; value: EddystoneTlm }
+
+export type Proximity = 'immediate' | 'near' | 'far'
+
+export interface AdvertisementInfo {
+  beacon?: Beacon
+  /** Estimated distance in metres */
+  distance?: number
+  proximity?: Proximity
+}
+
+/** Expands a 16- or 32-bit UUID to its 128-bit form on the Bluetooth base UUID */
+export function expandUuid(uuid: string): string {
+  const upper = uuid.trim().toUpperCase()
+  if (/^[0-9A-F]{4}$/.test(upper)) return `0000${upper}-0000-1000-8000-00805F9B34FB`
+  if (/^[0-9A-F]{8}$/.test(upper)) return `${upper}-0000-1000-8000-00805F9B34FB`
+  return upper
+}
+
+/** Stops a subscription; the native scan or notification stops once its last listener is gone */
+export type UnlistenFn = () => Promise<void>
+
 // API Functions
 export async function requestAuthorization(): Promise<AuthorizationStatus> {
   return await invoke('plugin:ios-bluetooth-v2|request_authorization')
@@ -223,6 +439,18 @@ export async function disconnectPeripheral(uuid: string): Promise<void> {
   return await invoke('plugin:ios-bluetooth-v2|disconnect_peripheral', { uuid })
 }
 
+export async function getConnectionState(uuid: string): Promise<PeripheralState> {
+  return await invoke('plugin:ios-bluetooth-v2|get_connection_state', { uuid })
+}
+
+export async function getConnectionHistory(uuid: string): Promise<ConnectionRecord[]> {
+  return await invoke('plugin:ios-bluetooth-v2|get_connection_history', { uuid })
+}
+
+export async function setReconnectPolicy(policy: ReconnectPolicy): Promise<void> {
+  return await invoke('plugin:ios-bluetooth-v2|set_reconnect_policy', { policy })
+}
+
 export async function getConnectedPeripherals(): Promise<Peripheral[]> {
   return await invoke('plugin:ios-bluetooth-v2|get_connected_peripherals')
 }
@@ -299,6 +527,75 @@ export async function getMaximumWriteLength(peripheralUuid: string, writeType: W
   return await invoke('plugin:ios-bluetooth-v2|get_maximum_write_length', { peripheralUuid, writeType })
 }
 
+/** Turns the notifications of a published characteristic on or off; while off, updates only change its value */
 export async function setNotifyValue(characteristicUuid: string, enabled: boolean): Promise<void> {
   return await invoke('plugin:ios-bluetooth-v2|set_notify_value', { characteristicUuid, enabled })
-}
\ No newline at end of file
+}
+
+export async function readDecodedCharacteristic(peripheralUuid: string, characteristicUuid: string): Promise<GattReading> {
+  return await invoke('plugin:ios-bluetooth-v2|read_decoded_characteristic', { peripheralUuid, characteristicUuid })
+}
+
+/** Decodes a value received e.g. in a `characteristicValueUpdated` event */
+export async function decodeCharacteristicValue(characteristicUuid: string, value: number[]): Promise<GattReading> {
+  return await invoke('plugin:ios-bluetooth-v2|decode_characteristic_value', { characteristicUuid, value })
+}
+
+export async function readDeviceInformation(peripheralUuid: string): Promise<DeviceInformation> {
+  return await invoke('plugin:ios-bluetooth-v2|read_device_information', { peripheralUuid })
+}
+
+// Event subscriptions
+async function subscribe(command: string, args: Record<string, unknown>, handler: (event: BluetoothEvent) => void): Promise<UnlistenFn> {
+  const onEvent = new Channel<BluetoothEvent>()
+  onEvent.onmessage = handler
+  const id = await invoke<number>(`plugin:ios-bluetooth-v2|${command}`, { ...args, onEvent })
+  return async () => {
+    await invoke('plugin:ios-bluetooth-v2|unlisten', { id })
+  }
+}
+
+export async function watchCentralScan(handler: (event: BluetoothEvent) => void, options?: ScanOptions): Promise<UnlistenFn> {
+  return await subscribe('watch_central_scan', { options }, handler)
+}
+
+export async function watchCharacteristic(peripheralUuid: string, characteristicUuid: string, handler: (event: BluetoothEvent) => void): Promise<UnlistenFn> {
+  return await subscribe('watch_characteristic', { peripheralUuid, characteristicUuid }, handler)
+}
+
+export async function listen(handler: (event: BluetoothEvent) => void): Promise<UnlistenFn> {
+  return await subscribe('listen', {}, handler)
+}
+
+let nextTransferId = 0
+
+/** Writes a payload larger than the negotiated maximum write length in chunks */
+export async function writeChunked(peripheralUuid: string, characteristicUuid: string, payload: number[], options: ChunkedWriteOptions = {}): Promise<TransferReport> {
+  const { onProgress, signal, ...transferOptions } = options
+  signal?.throwIfAborted()
+  const transferId = `transfer-${nextTransferId++}`
+  const channel = new Channel<TransferProgress>()
+  if (onProgress) channel.onmessage = onProgress
+
+  const cancel = () => {
+    void invoke('plugin:ios-bluetooth-v2|cancel_transfer', { transferId }).catch(() => {})
+  }
+  signal?.addEventListener('abort', cancel, { once: true })
+  try {
+    return await invoke<TransferReport>('plugin:ios-bluetooth-v2|write_chunked', {
+      peripheralUuid,
+      characteristicUuid,
+      payload,
+      options: transferOptions,
+      transferId,
+      onProgress: channel,
+    })
+  } finally {
+    signal?.removeEventListener('abort', cancel)
+  }
+}
+
+/** Parses beacon frames and estimates the distance of a discovered peripheral */
+export async function parseAdvertisement(peripheral: Peripheral): Promise<AdvertisementInfo> {
+  return await invoke('plugin:ios-bluetooth-v2|parse_advertisement', { peripheral })
+}
@@ -78,6 +78,17 @@ struct RequestResponseData: Decodable {
     let value: [UInt8]?
 }
 
+struct EventHandlerArgs: Decodable {
+    let handler: Channel
+}
+
+/// A write without response waiting for room in CoreBluetooth's transmit queue.
+struct QueuedWrite {
+    let data: Data
+    let characteristic: CBCharacteristic
+    let invoke: Invoke
+}
+
 class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, CBPeripheralManagerDelegate {
     private var centralManager: CBCentralManager?
     private var peripheralManager: CBPeripheralManager?
@@ -85,8 +96,16 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
     private var connectedPeripherals: [UUID: CBPeripheral] = [:]
     private var peripheralDelegates: [UUID: PeripheralDelegate] = [:]
     private var pendingRequests: [String: CBATTRequest] = [:]
+    private var pendingWrites: [String: Invoke] = [:]
+    private var queuedWritesWithoutResponse: [UUID: [QueuedWrite]] = [:]
+    private var publishedServices: [CBMutableService] = []
+    private var subscribedCentrals: [UUID: CBCentral] = [:]
+    // Expanded UUIDs of published characteristics whose updates aren't sent.
+    private var mutedCharacteristics: Set<String> = []
     private var isScanning = false
     private var scanOptions: ScanOptionsData?
+    private var eventChannel: Channel?
+    private let timestampFormatter = ISO8601DateFormatter()
     
     class PeripheralDelegate: NSObject, CBPeripheralDelegate {
         weak var plugin: BluetoothPlugin?
@@ -103,8 +122,12 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
             plugin?.peripheral(peripheral, didUpdateValueFor: characteristic, error: error)
         }
         
+        func peripheral(_ peripheral: CBPeripheral, didUpdateValueFor descriptor: CBDescriptor, error: Error?) {
+            plugin?.peripheral(peripheral, didUpdateValueFor: descriptor, error: error)
+        }
+        
         func peripheral(_ peripheral: CBPeripheral, didWriteValueFor characteristic: CBCharacteristic, error: Error?) {
-            // Handle write completion if needed
+            plugin?.peripheral(peripheral, didWriteValueFor: characteristic, error: error)
         }
         
         func peripheral(_ peripheral: CBPeripheral, didUpdateNotificationStateFor characteristic: CBCharacteristic, error: Error?) {
@@ -114,6 +137,10 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
         func peripheral(_ peripheral: CBPeripheral, didReadRSSI RSSI: NSNumber, error: Error?) {
             plugin?.peripheral(peripheral, didReadRSSI: RSSI, error: error)
         }
+        
+        func peripheralIsReady(toSendWriteWithoutResponse peripheral: CBPeripheral) {
+            plugin?.peripheralIsReady(toSendWriteWithoutResponse: peripheral)
+        }
     }
     
     override init() {
@@ -122,6 +149,12 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
         peripheralManager = CBPeripheralManager(delegate: self, queue: nil)
     }
     
+    @objc public func setEventHandler(_ invoke: Invoke) throws {
+        let args = try invoke.parseArgs(EventHandlerArgs.self)
+        eventChannel = args.handler
+        invoke.resolve()
+    }
+    
     @objc public func requestAuthorization(_ invoke: Invoke) throws {
         // iOS doesn't require explicit Bluetooth permission for BLE
         // Permission is requested automatically when needed
@@ -204,8 +237,9 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
         
         let args = try invoke.parseArgs(DisconnectArgs.self)
         
+        // Also cancels a connection attempt that is still pending.
         guard let uuid = UUID(uuidString: args.uuid),
-              let peripheral = connectedPeripherals[uuid] else {
+              let peripheral = connectedPeripherals[uuid] ?? discoveredPeripherals[uuid] else {
             invoke.reject("Peripheral not connected")
             return
         }
@@ -279,9 +313,20 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
         let args = try invoke.parseArgs(ReadCharArgs.self)
         
         guard let peripheralUuid = UUID(uuidString: args.peripheralUuid),
-              let peripheral = connectedPeripherals[peripheralUuid],
-              let characteristic = findCharacteristic(peripheral: peripheral, uuid: args.characteristicUuid) else {
-            invoke.reject("Characteristic not found")
+              let peripheral = connectedPeripherals[peripheralUuid] else {
+            invoke.reject("Peripheral not connected")
+            return
+        }
+        
+        // These codes tell the Rust side the characteristic is missing or
+        // unreadable, as opposed to the read failing.
+        guard let characteristic = findCharacteristic(peripheral: peripheral, uuid: args.characteristicUuid) else {
+            invoke.reject("Characteristic not found", code: "characteristicNotFound")
+            return
+        }
+        
+        guard characteristic.properties.contains(.read) else {
+            invoke.reject("Read not permitted", code: "readNotPermitted")
             return
         }
         
@@ -313,10 +358,26 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
         }
         
         let data = Data(args.value)
-        let type: CBCharacteristicWriteType = args.options.withResponse ? .withResponse : .withoutResponse
         
-        peripheral.writeValue(data, for: characteristic, type: type)
-        invoke.resolve()
+        // Writes without response resolve once CoreBluetooth has room for them,
+        // so callers sending many in a row are paced by the link instead of
+        // having them silently dropped.
+        guard args.options.withResponse else {
+            queuedWritesWithoutResponse[peripheral.identifier, default: []].append(
+                QueuedWrite(data: data, characteristic: characteristic, invoke: invoke)
+            )
+            sendQueuedWrites(peripheral)
+            return
+        }
+        
+        // Writes with response resolve once the peripheral acknowledges them.
+        let key = writeKey(peripheral, characteristic)
+        guard pendingWrites[key] == nil else {
+            invoke.reject("A write to this characteristic is already in progress")
+            return
+        }
+        pendingWrites[key] = invoke
+        peripheral.writeValue(data, for: characteristic, type: .withResponse)
     }
     
     @objc public func subscribeToCharacteristic(_ invoke: Invoke) throws {
@@ -464,6 +525,8 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
         }
         
         service.characteristics = characteristics
+        publishedServices.removeAll { $0.uuid == service.uuid }
+        publishedServices.append(service)
         peripheralManager?.add(service)
         invoke.resolve()
     }
@@ -475,22 +538,18 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
         
         let args = try invoke.parseArgs(RemoveServiceArgs.self)
         
-        // CBPeripheralManager doesn't have a services property
-        // You need to track services manually when adding them
-        /*if false {
-            for service in services {
-                if service.uuid.uuidString == args.serviceUuid {
-                    peripheralManager?.remove(service)
-                    break
-                }
-            }
-        }*/
+        guard let index = publishedServices.firstIndex(where: { sameUUID($0.uuid, args.serviceUuid) }) else {
+            invoke.reject("Service not found")
+            return
+        }
         
+        peripheralManager?.remove(publishedServices.remove(at: index))
         invoke.resolve()
     }
     
     @objc public func removeAllServices(_ invoke: Invoke) throws {
         peripheralManager?.removeAllServices()
+        publishedServices.removeAll()
         invoke.resolve()
     }
     
@@ -522,7 +581,35 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
         
         let args = try invoke.parseArgs(UpdateValueArgs.self)
         
-        // This would require tracking characteristics added to peripheral manager
+        let characteristic = publishedServices
+            .flatMap { $0.characteristics ?? [] }
+            .compactMap { $0 as? CBMutableCharacteristic }
+            .first { sameUUID($0.uuid, args.characteristicUuid) }
+        guard let characteristic = characteristic, let manager = peripheralManager else {
+            invoke.reject("Characteristic not found")
+            return
+        }
+        
+        let data = Data(args.value)
+        characteristic.value = data
+        if mutedCharacteristics.contains(expandUUID(characteristic.uuid.uuidString)) {
+            invoke.resolve()
+            return
+        }
+        
+        // nil notifies every subscribed central
+        let centrals = args.centralUuids.map { uuids in
+            uuids.compactMap { UUID(uuidString: $0).flatMap { subscribedCentrals[$0] } }
+        }
+        if let centrals = centrals, centrals.isEmpty {
+            invoke.resolve()
+            return
+        }
+        
+        guard manager.updateValue(data, for: characteristic, onSubscribedCentrals: centrals) else {
+            invoke.reject("Transmit queue is full")
+            return
+        }
         invoke.resolve()
     }
     
@@ -554,21 +641,38 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
         
         let args = try invoke.parseArgs(NotifyArgs.self)
         
-        // This would be used for peripheral mode
+        let characteristic = publishedServices
+            .flatMap { $0.characteristics ?? [] }
+            .first { sameUUID($0.uuid, args.characteristicUuid) }
+        guard let characteristic = characteristic else {
+            invoke.reject("Characteristic not found")
+            return
+        }
+        guard !characteristic.properties.isDisjoint(with: [.notify, .indicate]) else {
+            invoke.reject("Characteristic does not support notifications")
+            return
+        }
+        
+        let key = expandUUID(characteristic.uuid.uuidString)
+        if args.enabled {
+            mutedCharacteristics.remove(key)
+        } else {
+            mutedCharacteristics.insert(key)
+        }
         invoke.resolve()
     }
     
     // MARK: - CBCentralManagerDelegate
     
     func centralManagerDidUpdateState(_ central: CBCentralManager) {
-        trigger("stateChanged", data: ["state": getBluetoothStateString()])
+        emit("stateChanged", ["state": getBluetoothStateString()])
     }
     
     func centralManager(_ central: CBCentralManager, didDiscover peripheral: CBPeripheral, advertisementData: [String : Any], rssi RSSI: NSNumber) {
         discoveredPeripherals[peripheral.identifier] = peripheral
         
         let peripheralData = peripheralToDict(peripheral, advertisementData: advertisementData, rssi: RSSI.intValue)
-        trigger("peripheralDiscovered", data: convertToJSObject(peripheralData))
+        emit("peripheralDiscovered", convertToJSObject(peripheralData))
     }
     
     func centralManager(_ central: CBCentralManager, didConnect peripheral: CBPeripheral) {
@@ -580,15 +684,30 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
         peripheralDelegates[peripheral.identifier] = delegate
         peripheral.delegate = delegate
         
-        trigger("peripheralConnected", data: ["uuid": peripheral.identifier.uuidString])
+        emit("peripheralConnected", ["peripheralUuid": peripheral.identifier.uuidString])
+    }
+    
+    func centralManager(_ central: CBCentralManager, didFailToConnect peripheral: CBPeripheral, error: Error?) {
+        emit("peripheralDisconnected", convertToJSObject([
+            "peripheralUuid": peripheral.identifier.uuidString,
+            "error": error?.localizedDescription ?? "Failed to connect"
+        ]))
     }
     
     func centralManager(_ central: CBCentralManager, didDisconnectPeripheral peripheral: CBPeripheral, error: Error?) {
         connectedPeripherals.removeValue(forKey: peripheral.identifier)
         peripheralDelegates.removeValue(forKey: peripheral.identifier)
         
-        trigger("peripheralDisconnected", data: convertToJSObject([
-            "uuid": peripheral.identifier.uuidString,
+        let prefix = "\(peripheral.identifier.uuidString)|"
+        for key in pendingWrites.keys where key.hasPrefix(prefix) {
+            pendingWrites.removeValue(forKey: key)?.reject("Peripheral disconnected")
+        }
+        for write in queuedWritesWithoutResponse.removeValue(forKey: peripheral.identifier) ?? [] {
+            write.invoke.reject("Peripheral disconnected")
+        }
+        
+        emit("peripheralDisconnected", convertToJSObject([
+            "peripheralUuid": peripheral.identifier.uuidString,
             "error": error?.localizedDescription ?? NSNull()
         ]))
     }
@@ -598,7 +717,10 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
     func peripheral(_ peripheral: CBPeripheral, didDiscoverServices error: Error?) {
         if let services = peripheral.services {
             for service in services {
-                trigger("serviceDiscovered", data: convertToJSObject(serviceToDict(service)))
+                emit("serviceDiscovered", convertToJSObject([
+                    "peripheralUuid": peripheral.identifier.uuidString,
+                    "service": serviceToDict(service)
+                ]))
             }
         }
     }
@@ -606,7 +728,10 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
     func peripheral(_ peripheral: CBPeripheral, didDiscoverCharacteristicsFor service: CBService, error: Error?) {
         if let characteristics = service.characteristics {
             for characteristic in characteristics {
-                trigger("characteristicDiscovered", data: convertToJSObject(characteristicToDict(characteristic, serviceUuid: service.uuid.uuidString)))
+                emit("characteristicDiscovered", convertToJSObject([
+                    "peripheralUuid": peripheral.identifier.uuidString,
+                    "characteristic": characteristicToDict(characteristic, serviceUuid: service.uuid.uuidString)
+                ]))
             }
         }
     }
@@ -617,7 +742,35 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
             "characteristicUuid": characteristic.uuid.uuidString,
             "value": characteristic.value.map { Array($0) } ?? []
         ]
-        trigger("characteristicValueUpdated", data: convertToJSObject(data))
+        emit("characteristicValueUpdated", convertToJSObject(data))
+    }
+    
+    func peripheral(_ peripheral: CBPeripheral, didUpdateValueFor descriptor: CBDescriptor, error: Error?) {
+        let value: [UInt8]
+        if let data = descriptor.value as? Data {
+            value = Array(data)
+        } else {
+            value = []
+        }
+        
+        let data: [String: Any] = [
+            "peripheralUuid": peripheral.identifier.uuidString,
+            "descriptorUuid": descriptor.uuid.uuidString,
+            "value": value
+        ]
+        emit("descriptorValueUpdated", convertToJSObject(data))
+    }
+    
+    func peripheral(_ peripheral: CBPeripheral, didWriteValueFor characteristic: CBCharacteristic, error: Error?) {
+        guard let invoke = pendingWrites.removeValue(forKey: writeKey(peripheral, characteristic)) else {
+            return
+        }
+        
+        if let error = error {
+            invoke.reject(error.localizedDescription)
+        } else {
+            invoke.resolve()
+        }
     }
     
     func peripheral(_ peripheral: CBPeripheral, didUpdateNotificationStateFor characteristic: CBCharacteristic, error: Error?) {
@@ -626,7 +779,7 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
             "characteristicUuid": characteristic.uuid.uuidString,
             "isNotifying": characteristic.isNotifying
         ]
-        trigger("characteristicSubscriptionChanged", data: convertToJSObject(data))
+        emit("characteristicSubscriptionChanged", convertToJSObject(data))
     }
     
     func peripheral(_ peripheral: CBPeripheral, didReadRSSI RSSI: NSNumber, error: Error?) {
@@ -639,35 +792,75 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
         // Handle peripheral manager state
     }
     
+    func peripheralManager(_ peripheral: CBPeripheralManager, central: CBCentral, didSubscribeTo characteristic: CBCharacteristic) {
+        subscribedCentrals[central.identifier] = central
+        emit("centralSubscribed", [
+            "centralUuid": central.identifier.uuidString,
+            "characteristicUuid": characteristic.uuid.uuidString
+        ])
+    }
+    
+    func peripheralManager(_ peripheral: CBPeripheralManager, central: CBCentral, didUnsubscribeFrom characteristic: CBCharacteristic) {
+        emit("centralUnsubscribed", [
+            "centralUuid": central.identifier.uuidString,
+            "characteristicUuid": characteristic.uuid.uuidString
+        ])
+    }
+    
     func peripheralManager(_ peripheral: CBPeripheralManager, didReceiveRead request: CBATTRequest) {
         let requestId = UUID().uuidString
         pendingRequests[requestId] = request
         
         let data: [String: Any] = [
+            "requestId": requestId,
             "centralUuid": request.central.identifier.uuidString,
             "characteristicUuid": request.characteristic.uuid.uuidString,
-            "offset": request.offset
+            "offset": request.offset,
+            // A read response holds MTU - 1 bytes, notifications MTU - 3.
+            "maxLength": request.central.maximumUpdateValueLength + 2
         ]
-        trigger("readRequestReceived", data: convertToJSObject(data))
+        emit("readRequestReceived", convertToJSObject(data))
     }
     
     func peripheralManager(_ peripheral: CBPeripheralManager, didReceiveWrite requests: [CBATTRequest]) {
-        for request in requests {
-            let requestId = UUID().uuidString
-            pendingRequests[requestId] = request
-            
-            let data: [String: Any] = [
+        // CoreBluetooth expects a single response for the whole batch, given
+        // with its first request, so the batch shares one request id.
+        guard let first = requests.first else {
+            return
+        }
+        let requestId = UUID().uuidString
+        pendingRequests[requestId] = first
+        
+        let writes: [JSObject] = requests.map { request in
+            convertToJSObject([
+                "requestId": requestId,
                 "centralUuid": request.central.identifier.uuidString,
                 "characteristicUuid": request.characteristic.uuid.uuidString,
                 "value": request.value.map { Array($0) } ?? [],
                 "offset": request.offset
-            ]
-            trigger("writeRequestReceived", data: convertToJSObject(data))
+            ])
         }
+        let data: [String: Any] = [
+            "requestId": requestId,
+            "requests": writes
+        ]
+        emit("writeRequestReceived", convertToJSObject(data))
     }
     
     // MARK: - Helper Methods
     
+    /// Sends an event to plugin listeners and, once registered, to the Rust event channel
+    /// as `{ timestamp, eventType, data }`.
+    private func emit(_ eventType: String, _ data: JSObject) {
+        trigger(eventType, data: data)
+        
+        eventChannel?.send(.dictionary([
+            "timestamp": timestampFormatter.string(from: Date()),
+            "eventType": eventType,
+            "data": data
+        ]))
+    }
+    
     private func convertToJSObject(_ dict: [String: Any]) -> JSObject {
         var jsObject: JSObject = [:]
         for (key, value) in dict {
@@ -743,7 +936,9 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
             "rssi": rssi,
             "isConnectable": advertisementData?[CBAdvertisementDataIsConnectable] as? Bool ?? true,
             "state": peripheralStateToString(peripheral.state),
-            "services": peripheral.services?.map { $0.uuid.uuidString } ?? []
+            "services": peripheral.services?.map { $0.uuid.uuidString } ?? [],
+            "solicitedServiceUuids": [String](),
+            "overflowServiceUuids": [String]()
         ]
         
         if let advData = advertisementData {
@@ -751,13 +946,33 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
                 dict["services"] = serviceUUIDs.map { $0.uuidString }
             }
             
-            if let manufacturerData = advData[CBAdvertisementDataManufacturerDataKey] as? Data {
-                // Parse manufacturer data
+            // The first two bytes are the little-endian company identifier.
+            if let manufacturerData = advData[CBAdvertisementDataManufacturerDataKey] as? Data,
+               manufacturerData.count >= 2 {
+                let bytes = [UInt8](manufacturerData)
+                let companyId = UInt16(bytes[0]) | UInt16(bytes[1]) << 8
+                dict["manufacturerData"] = ["\(companyId)": Array(bytes[2...])]
+            }
+            
+            if let serviceData = advData[CBAdvertisementDataServiceDataKey] as? [CBUUID: Data] {
+                var data: [String: [UInt8]] = [:]
+                for (uuid, value) in serviceData {
+                    data[uuid.uuidString] = [UInt8](value)
+                }
+                dict["serviceData"] = data
             }
             
             if let txPower = advData[CBAdvertisementDataTxPowerLevelKey] as? NSNumber {
                 dict["txPowerLevel"] = txPower.intValue
             }
+            
+            if let solicited = advData[CBAdvertisementDataSolicitedServiceUUIDsKey] as? [CBUUID] {
+                dict["solicitedServiceUuids"] = solicited.map { $0.uuidString }
+            }
+            
+            if let overflow = advData[CBAdvertisementDataOverflowServiceUUIDsKey] as? [CBUUID] {
+                dict["overflowServiceUuids"] = overflow.map { $0.uuidString }
+            }
         }
         
         return dict
@@ -813,13 +1028,51 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
         ]
     }
     
+    func peripheralIsReady(toSendWriteWithoutResponse peripheral: CBPeripheral) {
+        sendQueuedWrites(peripheral)
+    }
+    
+    /// Hands queued writes without response to CoreBluetooth while its transmit
+    /// queue has room.
+    private func sendQueuedWrites(_ peripheral: CBPeripheral) {
+        while peripheral.canSendWriteWithoutResponse,
+              var queue = queuedWritesWithoutResponse[peripheral.identifier],
+              !queue.isEmpty {
+            let write = queue.removeFirst()
+            queuedWritesWithoutResponse[peripheral.identifier] = queue.isEmpty ? nil : queue
+            peripheral.writeValue(write.data, for: write.characteristic, type: .withoutResponse)
+            write.invoke.resolve()
+        }
+    }
+    
+    private func writeKey(_ peripheral: CBPeripheral, _ characteristic: CBCharacteristic) -> String {
+        return "\(peripheral.identifier.uuidString)|\(characteristic.uuid.uuidString)"
+    }
+    
+    /// Compares UUIDs given in short (`2A37`) or full 128-bit form, ignoring case.
+    private func sameUUID(_ cbuuid: CBUUID, _ uuid: String) -> Bool {
+        return expandUUID(cbuuid.uuidString) == expandUUID(uuid)
+    }
+    
+    private func expandUUID(_ uuid: String) -> String {
+        let upper = uuid.uppercased()
+        switch upper.count {
+        case 4:
+            return "0000\(upper)-0000-1000-8000-00805F9B34FB"
+        case 8:
+            return "\(upper)-0000-1000-8000-00805F9B34FB"
+        default:
+            return upper
+        }
+    }
+    
     private func findCharacteristic(peripheral: CBPeripheral, uuid: String) -> CBCharacteristic? {
         guard let services = peripheral.services else { return nil }
         
         for service in services {
             if let characteristics = service.characteristics {
                 for characteristic in characteristics {
-                    if characteristic.uuid.uuidString == uuid {
+                    if sameUUID(characteristic.uuid, uuid) {
                         return characteristic
                     }
                 }
@@ -837,7 +1090,7 @@ class BluetoothPlugin: Plugin, CBCentralManagerDelegate, CBPeripheralDelegate, C
                 for characteristic in characteristics {
                     if let descriptors = characteristic.descriptors {
                         for descriptor in descriptors {
-                            if descriptor.uuid.uuidString == uuid {
+                            if sameUUID(descriptor.uuid, uuid) {
                                 return descriptor
                             }
                         }
@@ -0,0 +1,470 @@
+//! Scan filtering, beacon frame parsing and RSSI-based distance estimation.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{gatt::uuids_equal, Error, Peripheral, Result, ScanOptions};
+
+/// Bluetooth SIG company identifier of Apple, which carries iBeacon frames.
+pub const APPLE_COMPANY_ID: u16 = 0x004C;
+/// 16-bit service UUID carrying Eddystone frames in service data.
+pub const EDDYSTONE_SERVICE_UUID: &str = "FEAA";
+/// Path loss exponent of free space. Indoors values between 2 and 4 are common.
+pub const DEFAULT_PATH_LOSS_EXPONENT: f64 = 2.0;
+/// RSSI CoreBluetooth reports when the signal strength is unavailable.
+const RSSI_UNAVAILABLE: i32 = 127;
+/// Typical loss between 0 m and 1 m, for converting a transmit power to the
+/// RSSI expected at one metre.
+const LOSS_AT_ONE_METRE: i32 = 41;
+
+const EDDYSTONE_URL_SCHEMES: [&str; 4] = ["http://www.", "https://www.", "http://", "https://"];
+const EDDYSTONE_URL_EXPANSIONS: [&str; 14] = [
+    ".com/", ".org/", ".edu/", ".net/", ".info/", ".biz/", ".gov/", ".com", ".org", ".edu", ".net", ".info", ".biz",
+    ".gov",
+];
+
+/// The filters of a [`ScanOptions`], compiled once and applied to every
+/// discovered peripheral.
+#[derive(Debug, Clone)]
+pub struct ScanFilter {
+    options: ScanOptions,
+    name_pattern: Option<Regex>,
+}
+
+impl ScanFilter {
+    /// Fails with `InvalidInput` when `name_pattern` is not a valid regular expression.
+    pub fn new(options: ScanOptions) -> Result<Self> {
+        let name_pattern = options
+            .name_pattern
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| Error::InvalidInput(format!("Invalid name pattern: {e}")))?;
+        Ok(Self { options, name_pattern })
+    }
+
+    pub fn options(&self) -> &ScanOptions {
+        &self.options
+    }
+
+    pub fn matches(&self, peripheral: &Peripheral) -> bool {
+        let options = &self.options;
+
+        let services = options.service_uuids.is_empty()
+            || options.service_uuids.iter().any(|wanted| {
+                peripheral
+                    .services
+                    .iter()
+                    .any(|advertised| uuids_equal(advertised, wanted))
+            });
+        let name = peripheral.name.as_deref();
+        let prefix = options
+            .name_prefix
+            .as_ref()
+            .map_or(true, |prefix| name.is_some_and(|name| name.starts_with(prefix.as_str())));
+        let pattern = self
+            .name_pattern
+            .as_ref()
+            .map_or(true, |pattern| name.is_some_and(|name| pattern.is_match(name)));
+        let rssi = options
+            .min_rssi
+            .map_or(true, |min| peripheral.rssi != RSSI_UNAVAILABLE && peripheral.rssi >= min);
+        let manufacturer = options.manufacturer_data.as_ref().map_or(true, |filter| {
+            peripheral
+                .manufacturer_data
+                .as_ref()
+                .and_then(|data| data.get(&filter.company_id))
+                .is_some_and(|data| masked_eq(data, &filter.data, filter.mask.as_deref()))
+        });
+        let service_data = options.service_data.as_ref().map_or(true, |filter| {
+            peripheral.service_data.as_ref().is_some_and(|data| {
+                data.iter().any(|(uuid, data)| {
+                    uuids_equal(uuid, &filter.service_uuid) && masked_eq(data, &filter.data, filter.mask.as_deref())
+                })
+            })
+        });
+
+        services && prefix && pattern && rssi && manufacturer && service_data
+    }
+}
+
+fn masked_eq(value: &[u8], expected: &[u8], mask: Option<&[u8]>) -> bool {
+    value.len() >= expected.len()
+        && expected.iter().enumerate().all(|(i, expected)| {
+            let mask = mask.and_then(|mask| mask.get(i)).copied().unwrap_or(0xFF);
+            value[i] & mask == expected & mask
+        })
+}
+
+/// An Apple iBeacon frame.
+///
+/// iOS removes iBeacon frames from advertisement data handed to CoreBluetooth,
+/// so these are only seen from other platforms or simulated peripherals.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IBeacon {
+    pub proximity_uuid: String,
+    pub major: u16,
+    pub minor: u16,
+    /// Calibrated RSSI at one metre, in dBm.
+    pub measured_power: i8,
+}
+
+impl IBeacon {
+    /// Parses Apple manufacturer data, without the company identifier.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 23 || data[0] != 0x02 || data[1] != 0x15 {
+            return None;
+        }
+        let uuid = &data[2..18];
+        Some(Self {
+            proximity_uuid: format!(
+                "{}-{}-{}-{}-{}",
+                hex(&uuid[0..4]),
+                hex(&uuid[4..6]),
+                hex(&uuid[6..8]),
+                hex(&uuid[8..10]),
+                hex(&uuid[10..16])
+            ),
+            major: u16::from_be_bytes([data[18], data[19]]),
+            minor: u16::from_be_bytes([data[20], data[21]]),
+            measured_power: data[22] as i8,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EddystoneUid {
+    /// Calibrated transmit power at 0 m, in dBm.
+    pub tx_power: i8,
+    pub namespace: String,
+    pub instance: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EddystoneUrl {
+    /// Calibrated transmit power at 0 m, in dBm.
+    pub tx_power: i8,
+    pub url: String,
+}
+
+/// Unencrypted Eddystone telemetry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EddystoneTlm {
+    /// Battery voltage in millivolts, if the beacon reports it.
+    pub battery_voltage: Option<u16>,
+    /// Beacon temperature in °C, if the beacon reports it.
+    pub temperature: Option<f64>,
+    pub advertising_count: u32,
+    /// Time since the beacon booted, in seconds.
+    pub uptime: f64,
+}
+
+/// A beacon frame found in a peripheral's advertisement.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum Beacon {
+    IBeacon(IBeacon),
+    EddystoneUid(EddystoneUid),
+    EddystoneUrl(EddystoneUrl),
+    EddystoneTlm(EddystoneTlm),
+}
+
+impl Beacon {
+    /// Parses an Eddystone frame from the service data of `FEAA`.
+    pub fn parse_eddystone(data: &[u8]) -> Option<Self> {
+        match *data.first()? {
+            0x00 if data.len() >= 18 => Some(Beacon::EddystoneUid(EddystoneUid {
+                tx_power: data[1] as i8,
+                namespace: hex(&data[2..12]),
+                instance: hex(&data[12..18]),
+            })),
+            0x10 if data.len() >= 3 => {
+                let mut url = EDDYSTONE_URL_SCHEMES.get(data[2] as usize)?.to_string();
+                for &byte in &data[3..] {
+                    match EDDYSTONE_URL_EXPANSIONS.get(byte as usize) {
+                        Some(expansion) => url.push_str(expansion),
+                        None if (0x21..0x7F).contains(&byte) => url.push(byte as char),
+                        None => return None,
+                    }
+                }
+                Some(Beacon::EddystoneUrl(EddystoneUrl {
+                    tx_power: data[1] as i8,
+                    url,
+                }))
+            }
+            // Only version 0x00 (unencrypted) telemetry is readable.
+            0x20 if data.len() >= 14 && data[1] == 0x00 => {
+                let battery = u16::from_be_bytes([data[2], data[3]]);
+                let temperature = i16::from_be_bytes([data[4], data[5]]);
+                Some(Beacon::EddystoneTlm(EddystoneTlm {
+                    battery_voltage: (battery != 0).then_some(battery),
+                    // 8.8 fixed point, with 0x8000 meaning "not supported".
+                    temperature: (temperature != i16::MIN).then(|| temperature as f64 / 256.0),
+                    advertising_count: u32::from_be_bytes([data[6], data[7], data[8], data[9]]),
+                    uptime: u32::from_be_bytes([data[10], data[11], data[12], data[13]]) as f64 / 10.0,
+                }))
+            }
+            _ => None,
+        }
+    }
+
+    /// Expected RSSI at one metre, if the frame carries a calibrated power.
+    fn power_at_one_metre(&self) -> Option<i32> {
+        match self {
+            Beacon::IBeacon(beacon) => Some(beacon.measured_power as i32),
+            Beacon::EddystoneUid(beacon) => Some(beacon.tx_power as i32 - LOSS_AT_ONE_METRE),
+            Beacon::EddystoneUrl(beacon) => Some(beacon.tx_power as i32 - LOSS_AT_ONE_METRE),
+            Beacon::EddystoneTlm(_) => None,
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02X}")).collect()
+}
+
+/// Coarse distance bands, as used by CoreLocation for beacons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Proximity {
+    /// Closer than half a metre.
+    Immediate,
+    /// Closer than three metres.
+    Near,
+    Far,
+}
+
+impl Proximity {
+    pub fn from_distance(metres: f64) -> Self {
+        if metres < 0.5 {
+            Proximity::Immediate
+        } else if metres < 3.0 {
+            Proximity::Near
+        } else {
+            Proximity::Far
+        }
+    }
+}
+
+/// Estimates the distance in metres from a received signal strength and the
+/// RSSI expected at one metre, using the log-distance path loss model.
+pub fn estimate_distance(rssi: i32, power_at_one_metre: i32, path_loss_exponent: f64) -> f64 {
+    10f64.powf((power_at_one_metre - rssi) as f64 / (10.0 * path_loss_exponent))
+}
+
+/// What the plugin could make of a peripheral's advertisement.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdvertisementInfo {
+    pub beacon: Option<Beacon>,
+    /// Estimated distance in metres.
+    pub distance: Option<f64>,
+    pub proximity: Option<Proximity>,
+}
+
+impl Peripheral {
+    /// The iBeacon or Eddystone frame in this peripheral's advertisement, if any.
+    pub fn beacon(&self) -> Option<Beacon> {
+        let ibeacon = self
+            .manufacturer_data
+            .as_ref()
+            .and_then(|data| data.get(&APPLE_COMPANY_ID))
+            .and_then(|data| IBeacon::parse(data))
+            .map(Beacon::IBeacon);
+
+        ibeacon.or_else(|| {
+            self.service_data
+                .as_ref()?
+                .iter()
+                .find(|(uuid, _)| uuids_equal(uuid, EDDYSTONE_SERVICE_UUID))
+                .and_then(|(_, data)| Beacon::parse_eddystone(data))
+        })
+    }
+
+    /// Estimated distance in metres, from a beacon's calibrated power or else the
+    /// advertised transmit power level.
+    pub fn estimated_distance(&self) -> Option<f64> {
+        if self.rssi == RSSI_UNAVAILABLE {
+            return None;
+        }
+        let power = self
+            .beacon()
+            .and_then(|beacon| beacon.power_at_one_metre())
+            .or_else(|| self.tx_power_level.map(|tx_power| tx_power - LOSS_AT_ONE_METRE))?;
+        Some(estimate_distance(self.rssi, power, DEFAULT_PATH_LOSS_EXPONENT))
+    }
+
+    pub fn advertisement_info(&self) -> AdvertisementInfo {
+        let distance = self.estimated_distance();
+        AdvertisementInfo {
+            beacon: self.beacon(),
+            distance,
+            proximity: distance.map(Proximity::from_distance),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::PeripheralState;
+
+    const IBEACON: [u8; 23] = [
+        0x02, 0x15, 0xE2, 0xC5, 0x6D, 0xB5, 0xDF, 0xFB, 0x48, 0xD2, 0xB0, 0x60, 0xD0, 0xF5, 0xA7, 0x10, 0x96, 0xE0,
+        0x00, 0x01, 0x00, 0x02, 0xC5,
+    ];
+
+    fn peripheral(rssi: i32) -> Peripheral {
+        Peripheral {
+            uuid: "peripheral".to_string(),
+            name: None,
+            rssi,
+            is_connectable: false,
+            state: PeripheralState::Disconnected,
+            services: vec![],
+            manufacturer_data: None,
+            service_data: None,
+            tx_power_level: None,
+            solicited_service_uuids: vec![],
+            overflow_service_uuids: vec![],
+        }
+    }
+
+    #[test]
+    fn parses_ibeacon_frames() {
+        assert_eq!(
+            IBeacon::parse(&IBEACON),
+            Some(IBeacon {
+                proximity_uuid: "E2C56DB5-DFFB-48D2-B060-D0F5A71096E0".to_string(),
+                major: 1,
+                minor: 2,
+                measured_power: -59,
+            })
+        );
+        assert_eq!(IBeacon::parse(&IBEACON[..22]), None);
+        let mut other = IBEACON;
+        other[0] = 0x10;
+        assert_eq!(IBeacon::parse(&other), None);
+    }
+
+    #[test]
+    fn parses_eddystone_uid_frames() {
+        let frame = [
+            0x00, 0xE7, 0x8B, 0x0C, 0xA7, 0x50, 0xE7, 0xA1, 0x79, 0x9D, 0x1C, 0x3A, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06,
+        ];
+        assert_eq!(
+            Beacon::parse_eddystone(&frame),
+            Some(Beacon::EddystoneUid(EddystoneUid {
+                tx_power: -25,
+                namespace: "8B0CA750E7A1799D1C3A".to_string(),
+                instance: "010203040506".to_string(),
+            }))
+        );
+        assert_eq!(Beacon::parse_eddystone(&frame[..17]), None);
+    }
+
+    #[test]
+    fn parses_eddystone_url_frames() {
+        let url = |frame: &[u8]| match Beacon::parse_eddystone(frame) {
+            Some(Beacon::EddystoneUrl(beacon)) => Some((beacon.tx_power, beacon.url)),
+            _ => None,
+        };
+        assert_eq!(
+            url(&[0x10, 0xEB, 0x00, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0x00]),
+            Some((-21, "http://www.example.com/".to_string()))
+        );
+        assert_eq!(
+            url(&[0x10, 0x00, 0x03, b'g', b'o', b'o', b'.', b'g', b'l', b'/', b'S', b'6', b'z']),
+            Some((0, "https://goo.gl/S6z".to_string()))
+        );
+        assert_eq!(
+            url(&[0x10, 0x00, 0x02, b'a', 0x04, b'x']),
+            Some((0, "http://a.info/x".to_string()))
+        );
+        // Unknown scheme and bytes that are neither expansions nor printable.
+        assert_eq!(url(&[0x10, 0x00, 0x04, b'a']), None);
+        assert_eq!(url(&[0x10, 0x00, 0x00, b'a', 0x20]), None);
+    }
+
+    #[test]
+    fn parses_eddystone_tlm_frames() {
+        let frame = [0x20, 0x00, 0x0B, 0xB8, 0x18, 0x80, 0x00, 0x00, 0x00, 0x64, 0x00, 0x00, 0x03, 0xE8];
+        assert_eq!(
+            Beacon::parse_eddystone(&frame),
+            Some(Beacon::EddystoneTlm(EddystoneTlm {
+                battery_voltage: Some(3000),
+                temperature: Some(24.5),
+                advertising_count: 100,
+                uptime: 100.0,
+            }))
+        );
+
+        let unsupported = [0x20, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x0A];
+        assert_eq!(
+            Beacon::parse_eddystone(&unsupported),
+            Some(Beacon::EddystoneTlm(EddystoneTlm {
+                battery_voltage: None,
+                temperature: None,
+                advertising_count: 1,
+                uptime: 1.0,
+            }))
+        );
+
+        let mut encrypted = frame;
+        encrypted[1] = 0x01;
+        assert_eq!(Beacon::parse_eddystone(&encrypted), None);
+        assert_eq!(Beacon::parse_eddystone(&frame[..13]), None);
+    }
+
+    #[test]
+    fn finds_beacons_in_advertisements() {
+        let mut ibeacon = peripheral(-59);
+        ibeacon.manufacturer_data = Some(HashMap::from([(APPLE_COMPANY_ID, IBEACON.to_vec())]));
+        assert!(matches!(ibeacon.beacon(), Some(Beacon::IBeacon(_))));
+        let info = ibeacon.advertisement_info();
+        assert!((info.distance.unwrap() - 1.0).abs() < 1e-9);
+        assert_eq!(info.proximity, Some(Proximity::Near));
+
+        let mut eddystone = peripheral(127);
+        eddystone.service_data = Some(HashMap::from([(
+            "0000FEAA-0000-1000-8000-00805F9B34FB".to_string(),
+            vec![0x10, 0xEB, 0x01, b'a', 0x07],
+        )]));
+        assert!(matches!(eddystone.beacon(), Some(Beacon::EddystoneUrl(_))));
+        assert_eq!(eddystone.estimated_distance(), None);
+    }
+
+    #[test]
+    fn estimates_distance_with_the_path_loss_model() {
+        assert!((estimate_distance(-59, -59, 2.0) - 1.0).abs() < 1e-9);
+        assert!((estimate_distance(-79, -59, 2.0) - 10.0).abs() < 1e-9);
+        assert!((estimate_distance(-79, -59, 4.0) - 10f64.sqrt()).abs() < 1e-9);
+        assert_eq!(Proximity::from_distance(0.2), Proximity::Immediate);
+        assert_eq!(Proximity::from_distance(5.0), Proximity::Far);
+    }
+
+    #[test]
+    fn scan_filters_match_masked_manufacturer_data() {
+        let filter = ScanFilter::new(ScanOptions {
+            manufacturer_data: Some(crate::ManufacturerDataFilter {
+                company_id: APPLE_COMPANY_ID,
+                data: vec![0x02, 0x15, 0xE2, 0x00],
+                mask: Some(vec![0xFF, 0xFF, 0xFF, 0x00]),
+            }),
+            ..ScanOptions::default()
+        })
+        .unwrap();
+        let mut beacon = peripheral(-59);
+        assert!(!filter.matches(&beacon));
+        beacon.manufacturer_data = Some(HashMap::from([(APPLE_COMPANY_ID, IBEACON.to_vec())]));
+        assert!(filter.matches(&beacon));
+        beacon.manufacturer_data = Some(HashMap::from([(0x0059, IBEACON.to_vec())]));
+        assert!(!filter.matches(&beacon));
+    }
+}
@@ -1,6 +1,13 @@
-use tauri::{command, AppHandle, Runtime};
+use tauri::{command, ipc::Channel, AppHandle, Manager, Runtime};
 
-use crate::{BluetoothExt, ScanOptions, ConnectionOptions, WriteOptions, AdvertisingData, PeripheralService, RequestResponse, WriteType, Result};
+use crate::{
+    advertisement::AdvertisementInfo,
+    events::JsSubscriptions,
+    gatt::{self, DeviceInformation, GattReading},
+    transfer::{JsTransfers, TransferOptions, TransferProgress, TransferReport},
+    AdvertisingData, BluetoothEvent, BluetoothExt, ConnectionOptions, ConnectionRecord, Peripheral,
+    PeripheralService, PeripheralState, ReconnectPolicy, RequestResponse, Result, ScanOptions, WriteOptions, WriteType,
+};
 
 #[command]
 pub(crate) async fn request_authorization<R: Runtime>(
@@ -44,7 +51,9 @@ pub(crate) async fn connect_peripheral<R: Runtime>(
     uuid: String,
     options: Option<ConnectionOptions>,
 ) -> Result<()> {
-    app.bluetooth().connect_peripheral(uuid, options.unwrap_or_default())
+    // Waits for the connection, possibly across several attempts.
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || handle.bluetooth().connect(uuid, options.unwrap_or_default())).await?
 }
 
 #[command]
@@ -52,7 +61,7 @@ pub(crate) async fn disconnect_peripheral<R: Runtime>(
     app: AppHandle<R>,
     uuid: String,
 ) -> Result<()> {
-    app.bluetooth().disconnect_peripheral(uuid)
+    app.bluetooth().disconnect(uuid)
 }
 
 #[command]
@@ -225,4 +234,144 @@ pub(crate) async fn set_notify_value<R: Runtime>(
     enabled: bool,
 ) -> Result<()> {
     app.bluetooth().set_notify_value(characteristic_uuid, enabled)
-}
\ No newline at end of file
+}
+
+#[command]
+pub(crate) async fn watch_central_scan<R: Runtime>(
+    app: AppHandle<R>,
+    options: Option<ScanOptions>,
+    on_event: Channel<BluetoothEvent>,
+) -> Result<u32> {
+    let subscription = app.bluetooth().watch_central_scan(options.unwrap_or_default(), move |event| {
+        let _ = on_event.send(event.clone());
+    })?;
+    Ok(app.state::<JsSubscriptions>().insert(subscription))
+}
+
+#[command]
+pub(crate) async fn watch_characteristic<R: Runtime>(
+    app: AppHandle<R>,
+    peripheral_uuid: String,
+    characteristic_uuid: String,
+    on_event: Channel<BluetoothEvent>,
+) -> Result<u32> {
+    let subscription = app.bluetooth().watch_characteristic(peripheral_uuid, characteristic_uuid, move |event| {
+        let _ = on_event.send(event.clone());
+    })?;
+    Ok(app.state::<JsSubscriptions>().insert(subscription))
+}
+
+#[command]
+pub(crate) async fn listen<R: Runtime>(
+    app: AppHandle<R>,
+    on_event: Channel<BluetoothEvent>,
+) -> Result<u32> {
+    let subscription = app.bluetooth().listen(move |event| {
+        let _ = on_event.send(event.clone());
+    });
+    Ok(app.state::<JsSubscriptions>().insert(subscription))
+}
+
+#[command]
+pub(crate) async fn unlisten<R: Runtime>(
+    app: AppHandle<R>,
+    id: u32,
+) -> Result<()> {
+    app.state::<JsSubscriptions>().remove(id);
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn read_decoded_characteristic<R: Runtime>(
+    app: AppHandle<R>,
+    peripheral_uuid: String,
+    characteristic_uuid: String,
+) -> Result<GattReading> {
+    app.bluetooth().read_decoded(peripheral_uuid, characteristic_uuid)
+}
+
+#[command]
+pub(crate) async fn parse_advertisement(
+    peripheral: Peripheral,
+) -> Result<AdvertisementInfo> {
+    Ok(peripheral.advertisement_info())
+}
+
+#[command]
+pub(crate) async fn decode_characteristic_value(
+    characteristic_uuid: String,
+    value: Vec<u8>,
+) -> Result<GattReading> {
+    gatt::decode(&characteristic_uuid, &value)
+}
+
+#[command]
+pub(crate) async fn read_device_information<R: Runtime>(
+    app: AppHandle<R>,
+    peripheral_uuid: String,
+) -> Result<DeviceInformation> {
+    app.bluetooth().read_device_information(peripheral_uuid)
+}
+
+#[command]
+pub(crate) async fn write_chunked<R: Runtime>(
+    app: AppHandle<R>,
+    peripheral_uuid: String,
+    characteristic_uuid: String,
+    payload: Vec<u8>,
+    options: Option<TransferOptions>,
+    transfer_id: String,
+    on_progress: Channel<TransferProgress>,
+) -> Result<TransferReport> {
+    let cancel = app.state::<JsTransfers>().insert(transfer_id.clone())?;
+    // The transfer blocks between chunks, so keep it off the async workers.
+    let handle = app.clone();
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        handle.bluetooth().write_chunked(
+            peripheral_uuid,
+            characteristic_uuid,
+            &payload,
+            options.unwrap_or_default(),
+            &cancel,
+            |progress| {
+                let _ = on_progress.send(progress);
+            },
+        )
+    })
+    .await;
+    app.state::<JsTransfers>().remove(&transfer_id);
+    report?
+}
+
+#[command]
+pub(crate) async fn cancel_transfer<R: Runtime>(
+    app: AppHandle<R>,
+    transfer_id: String,
+) -> Result<()> {
+    app.state::<JsTransfers>().cancel(&transfer_id)
+}
+
+#[command]
+pub(crate) async fn get_connection_state<R: Runtime>(
+    app: AppHandle<R>,
+    uuid: String,
+) -> Result<PeripheralState> {
+    Ok(app.bluetooth().connection_state(&uuid))
+}
+
+#[command]
+pub(crate) async fn get_connection_history<R: Runtime>(
+    app: AppHandle<R>,
+    uuid: String,
+) -> Result<Vec<ConnectionRecord>> {
+    Ok(app.bluetooth().connection_history(&uuid))
+}
+
+#[command]
+pub(crate) async fn set_reconnect_policy<R: Runtime>(
+    app: AppHandle<R>,
+    policy: ReconnectPolicy,
+) -> Result<()> {
+    app.bluetooth().set_reconnect_policy(policy);
+    Ok(())
+}
@@ -0,0 +1,673 @@
+//! Connection state tracking, timeouts and automatic reconnection.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Runtime};
+
+use crate::{
+    events::EventBus, gatt::expand_uuid, BluetoothEvent, BluetoothEventPayload, BluetoothExt, ConnectionOptions,
+    Error, PeripheralState, Result, Subscription,
+};
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_HISTORY: usize = 100;
+
+/// Backoff applied between connection attempts when `auto_connect` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReconnectPolicy {
+    /// Attempts made per connect call or dropped connection, including the first.
+    pub max_attempts: u32,
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl ReconnectPolicy {
+    /// Delay before retry number `retry` (starting at 1), doubling every time.
+    fn delay(&self, retry: u32) -> Duration {
+        let factor = 1u64.checked_shl(retry.saturating_sub(1)).unwrap_or(u64::MAX);
+        Duration::from_millis(self.initial_delay_ms.saturating_mul(factor).min(self.max_delay_ms))
+    }
+}
+
+/// One state transition of a peripheral connection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionRecord {
+    pub state: PeripheralState,
+    pub timestamp: DateTime<Utc>,
+    /// Connection attempt the transition belongs to, starting at 1.
+    pub attempt: u32,
+    pub error: Option<String>,
+}
+
+struct PeripheralConnection {
+    state: PeripheralState,
+    options: ConnectionOptions,
+    attempt: u32,
+    error: Option<String>,
+    // Bumped by explicit disconnects so pending retries give up.
+    generation: u64,
+    user_disconnect: bool,
+    // Expanded UUIDs of the characteristics with notifications enabled, kept
+    // across dropped connections so they can be restored.
+    subscriptions: HashSet<String>,
+    pending_restore: HashSet<String>,
+    history: VecDeque<ConnectionRecord>,
+}
+
+impl Default for PeripheralConnection {
+    fn default() -> Self {
+        Self {
+            state: PeripheralState::Disconnected,
+            options: ConnectionOptions::default(),
+            attempt: 0,
+            error: None,
+            generation: 0,
+            user_disconnect: false,
+            subscriptions: HashSet::new(),
+            pending_restore: HashSet::new(),
+            history: VecDeque::new(),
+        }
+    }
+}
+
+impl PeripheralConnection {
+    fn transition(&mut self, state: PeripheralState, error: Option<String>) {
+        if self.history.len() == MAX_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(ConnectionRecord {
+            state: state.clone(),
+            timestamp: Utc::now(),
+            attempt: self.attempt,
+            error: error.clone(),
+        });
+        self.state = state;
+        self.error = error;
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    // Keyed by uppercased peripheral UUID.
+    peripherals: Mutex<HashMap<String, PeripheralConnection>>,
+    changed: Condvar,
+    policy: Mutex<ReconnectPolicy>,
+}
+
+impl Shared {
+    fn peripherals(&self) -> MutexGuard<'_, HashMap<String, PeripheralConnection>> {
+        self.peripherals.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn policy(&self) -> ReconnectPolicy {
+        self.policy.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// Tracks the connection state machine of every peripheral from the native
+/// connection events.
+pub(crate) struct ConnectionManager {
+    shared: Arc<Shared>,
+    _subscription: Subscription,
+}
+
+impl ConnectionManager {
+    pub(crate) fn new<R: Runtime>(app: &AppHandle<R>, events: &Arc<EventBus>) -> Self {
+        let shared = Arc::new(Shared::default());
+        let app = app.clone();
+        let observed = shared.clone();
+        let subscription = events.listen(Arc::new(move |event: &BluetoothEvent| {
+            observe(&app, &observed, event)
+        }));
+
+        Self {
+            shared,
+            _subscription: subscription,
+        }
+    }
+}
+
+fn key(uuid: &str) -> String {
+    uuid.to_uppercase()
+}
+
+/// Runs a backend call off the event dispatch path.
+fn spawn<R: Runtime>(app: &AppHandle<R>, f: impl FnOnce(&AppHandle<R>) + Send + 'static) {
+    let app = app.clone();
+    thread::spawn(move || f(&app));
+}
+
+fn observe<R: Runtime>(app: &AppHandle<R>, shared: &Shared, event: &BluetoothEvent) {
+    let mut peripherals = shared.peripherals();
+    match &event.payload {
+        BluetoothEventPayload::PeripheralConnected { peripheral_uuid } => {
+            let connection = peripherals.entry(key(peripheral_uuid)).or_default();
+            connection.transition(PeripheralState::Connected, None);
+            if !connection.subscriptions.is_empty() {
+                connection.pending_restore = connection.subscriptions.clone();
+                let uuid = peripheral_uuid.clone();
+                spawn(app, move |app| {
+                    let _ = app.bluetooth().discover_services(uuid, None);
+                });
+            }
+            shared.changed.notify_all();
+        }
+        BluetoothEventPayload::PeripheralDisconnected { peripheral_uuid, error } => {
+            let connection = peripherals.entry(key(peripheral_uuid)).or_default();
+            let was_connected = connection.state == PeripheralState::Connected;
+            connection.transition(PeripheralState::Disconnected, error.clone());
+            connection.pending_restore.clear();
+
+            if connection.user_disconnect {
+                connection.user_disconnect = false;
+                connection.subscriptions.clear();
+            } else if was_connected && connection.options.auto_connect {
+                let uuid = peripheral_uuid.clone();
+                let options = connection.options.clone();
+                let generation = connection.generation;
+                spawn(app, move |app| {
+                    let _ = app.bluetooth().run_attempts(&uuid, options, generation, true);
+                });
+            }
+            shared.changed.notify_all();
+        }
+        BluetoothEventPayload::CharacteristicSubscriptionChanged {
+            peripheral_uuid,
+            characteristic_uuid,
+            is_notifying,
+        } => {
+            let connection = peripherals.entry(key(peripheral_uuid)).or_default();
+            if *is_notifying {
+                connection.subscriptions.insert(expand_uuid(characteristic_uuid));
+            } else {
+                connection.subscriptions.remove(&expand_uuid(characteristic_uuid));
+            }
+        }
+        BluetoothEventPayload::ServiceDiscovered { peripheral_uuid, service } => {
+            let restoring = peripherals
+                .get(&key(peripheral_uuid))
+                .is_some_and(|connection| !connection.pending_restore.is_empty());
+            if restoring {
+                let uuid = peripheral_uuid.clone();
+                let service_uuid = service.uuid.clone();
+                spawn(app, move |app| {
+                    let _ = app.bluetooth().discover_characteristics(uuid, service_uuid, None);
+                });
+            }
+        }
+        BluetoothEventPayload::CharacteristicDiscovered {
+            peripheral_uuid,
+            characteristic,
+        } => {
+            let restore = peripherals
+                .get_mut(&key(peripheral_uuid))
+                .is_some_and(|connection| connection.pending_restore.remove(&expand_uuid(&characteristic.uuid)));
+            if restore {
+                let uuid = peripheral_uuid.clone();
+                let characteristic_uuid = characteristic.uuid.clone();
+                spawn(app, move |app| {
+                    let _ = app.bluetooth().subscribe_to_characteristic(uuid, characteristic_uuid);
+                });
+            }
+        }
+        _ => {}
+    }
+}
+
+impl<R: Runtime> crate::Bluetooth<R> {
+    fn connections(&self) -> &Shared {
+        &self.connections.shared
+    }
+
+    /// Connects to a peripheral and waits until the connection is established.
+    ///
+    /// Each attempt fails after `timeout_ms` (10 seconds by default). With
+    /// `auto_connect`, failed attempts are retried following the
+    /// [`ReconnectPolicy`], and a connection that drops later is re-established
+    /// in the background with its notification subscriptions restored.
+    pub fn connect(&self, uuid: String, options: ConnectionOptions) -> Result<()> {
+        let generation = {
+            let mut peripherals = self.connections().peripherals();
+            let connection = peripherals.entry(key(&uuid)).or_default();
+            connection.options = options.clone();
+            connection.user_disconnect = false;
+            connection.attempt = 0;
+            connection.generation
+        };
+        self.run_attempts(&uuid, options, generation, false)
+    }
+
+    /// Disconnects from a peripheral, cancelling any reconnection in progress.
+    /// Its notification subscriptions are forgotten.
+    pub fn disconnect(&self, uuid: String) -> Result<()> {
+        {
+            let mut peripherals = self.connections().peripherals();
+            let connection = peripherals.entry(key(&uuid)).or_default();
+            connection.generation += 1;
+            if connection.state == PeripheralState::Disconnected {
+                connection.subscriptions.clear();
+            } else {
+                connection.user_disconnect = true;
+                connection.transition(PeripheralState::Disconnecting, None);
+            }
+        }
+
+        self.disconnect_peripheral(uuid.clone()).inspect_err(|_| {
+            let mut peripherals = self.connections().peripherals();
+            if let Some(connection) = peripherals.get_mut(&key(&uuid)) {
+                if connection.state == PeripheralState::Disconnecting {
+                    connection.user_disconnect = false;
+                    connection.subscriptions.clear();
+                    connection.transition(PeripheralState::Disconnected, None);
+                }
+            }
+        })
+    }
+
+    /// Current state of a peripheral's connection.
+    pub fn connection_state(&self, uuid: &str) -> PeripheralState {
+        self.connections()
+            .peripherals()
+            .get(&key(uuid))
+            .map_or(PeripheralState::Disconnected, |connection| connection.state.clone())
+    }
+
+    /// Recorded state transitions of a peripheral, oldest first. Only the most
+    /// recent 100 are kept.
+    pub fn connection_history(&self, uuid: &str) -> Vec<ConnectionRecord> {
+        self.connections()
+            .peripherals()
+            .get(&key(uuid))
+            .map(|connection| connection.history.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn set_reconnect_policy(&self, policy: ReconnectPolicy) {
+        *self.connections().policy.lock().unwrap_or_else(|e| e.into_inner()) = policy;
+    }
+
+    fn run_attempts(&self, uuid: &str, options: ConnectionOptions, generation: u64, reconnecting: bool) -> Result<()> {
+        let policy = self.connections().policy();
+        let attempts = if options.auto_connect { policy.max_attempts.max(1) } else { 1 };
+        let timeout = options
+            .timeout_ms
+            .map_or(DEFAULT_CONNECT_TIMEOUT, |ms| Duration::from_millis(ms.into()));
+
+        let mut last_error = None;
+        for attempt in 1..=attempts {
+            // After a dropped connection even the first attempt is a retry.
+            let retry = if reconnecting { attempt } else { attempt - 1 };
+            if retry > 0 {
+                thread::sleep(policy.delay(retry));
+            }
+            let cancelled = self
+                .connections()
+                .peripherals()
+                .get(&key(uuid))
+                .is_some_and(|connection| connection.generation != generation);
+            if cancelled {
+                return Err(Error::OperationFailed("Connection cancelled".to_string()));
+            }
+
+            match self.attempt_connection(uuid, &options, timeout) {
+                Ok(()) => return Ok(()),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| Error::OperationFailed("Connection failed".to_string())))
+    }
+
+    fn attempt_connection(&self, uuid: &str, options: &ConnectionOptions, timeout: Duration) -> Result<()> {
+        {
+            let mut peripherals = self.connections().peripherals();
+            let connection = peripherals.entry(key(uuid)).or_default();
+            if connection.state == PeripheralState::Connected {
+                return Ok(());
+            }
+            connection.attempt += 1;
+            connection.transition(PeripheralState::Connecting, None);
+        }
+
+        if let Err(e) = self.connect_peripheral(uuid.to_string(), options.clone()) {
+            let mut peripherals = self.connections().peripherals();
+            if let Some(connection) = peripherals.get_mut(&key(uuid)) {
+                if connection.state == PeripheralState::Connecting {
+                    connection.transition(PeripheralState::Disconnected, Some(e.to_string()));
+                }
+            }
+            return Err(e);
+        }
+
+        let deadline = Instant::now() + timeout;
+        let shared = self.connections();
+        let mut peripherals = shared.peripherals();
+        loop {
+            let connection = peripherals.entry(key(uuid)).or_default();
+            match connection.state {
+                PeripheralState::Connected => return Ok(()),
+                PeripheralState::Connecting => {}
+                _ => {
+                    let message = connection.error.clone().unwrap_or_else(|| "Connection failed".to_string());
+                    return Err(Error::OperationFailed(message));
+                }
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                connection.transition(PeripheralState::Disconnected, Some("Connection timed out".to_string()));
+                drop(peripherals);
+                // Cancels the pending native connection.
+                let _ = self.disconnect_peripheral(uuid.to_string());
+                return Err(Error::OperationFailed("Connection timed out".to_string()));
+            }
+            peripherals = shared
+                .changed
+                .wait_timeout(peripherals, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_delay_ms: 500,
+            max_delay_ms: 30_000,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tauri::{test::MockRuntime, App, Manager};
+
+    use super::*;
+    use crate::{
+        AdvertisingData, CharacteristicPermissions, CharacteristicProperties, PeripheralCharacteristic,
+        PeripheralService, ScanOptions, VirtualPeripheral,
+    };
+
+    use PeripheralState::*;
+
+    const MEASUREMENT: &str = "2A37";
+
+    /// An app with a discovered heart rate monitor "A" that retries quickly.
+    fn app(max_attempts: u32, delay_ms: u64) -> App<MockRuntime> {
+        let app = tauri::test::mock_app();
+        app.manage(crate::Bluetooth::new(app.handle()));
+        let advertising = AdvertisingData {
+            local_name: Some("Polar H10".to_string()),
+            service_uuids: vec!["180D".to_string()],
+            manufacturer_data: None,
+            service_data: None,
+            tx_power_level: None,
+            is_connectable: true,
+        };
+        let measurement = PeripheralCharacteristic {
+            uuid: MEASUREMENT.to_string(),
+            properties: CharacteristicProperties {
+                read: true,
+                notify: true,
+                ..Default::default()
+            },
+            permissions: CharacteristicPermissions {
+                readable: true,
+                ..Default::default()
+            },
+            value: None,
+            descriptors: vec![],
+        };
+        let bluetooth = app.bluetooth();
+        bluetooth
+            .simulator()
+            .add_peripheral(VirtualPeripheral::new("A", advertising).with_service(PeripheralService {
+                uuid: "180D".to_string(),
+                is_primary: true,
+                characteristics: vec![measurement],
+            }));
+        bluetooth.start_central_scan(ScanOptions::default()).unwrap();
+        bluetooth.stop_central_scan().unwrap();
+        bluetooth.set_reconnect_policy(ReconnectPolicy {
+            max_attempts,
+            initial_delay_ms: delay_ms,
+            max_delay_ms: delay_ms * 2,
+        });
+        app
+    }
+
+    fn options(auto_connect: bool, timeout_ms: Option<u32>) -> ConnectionOptions {
+        ConnectionOptions {
+            auto_connect,
+            timeout_ms,
+        }
+    }
+
+    /// The states and attempts recorded for "A".
+    fn history(app: &impl Manager<MockRuntime>) -> Vec<(PeripheralState, u32)> {
+        app.bluetooth()
+            .connection_history("A")
+            .into_iter()
+            .map(|record| (record.state, record.attempt))
+            .collect()
+    }
+
+    /// Waits for a reconnection running in the background.
+    fn wait_for(condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out waiting");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    /// Records the value notifications the app receives.
+    fn notifications(app: &App<MockRuntime>) -> (Subscription, Arc<Mutex<Vec<Vec<u8>>>>) {
+        let values = Arc::new(Mutex::new(Vec::new()));
+        let sink = values.clone();
+        let subscription = app.bluetooth().listen(move |event| {
+            if let BluetoothEventPayload::CharacteristicValueUpdated { value, .. } = &event.payload {
+                sink.lock().unwrap().push(value.clone());
+            }
+        });
+        (subscription, values)
+    }
+
+    #[test]
+    fn connections_move_through_the_state_machine() {
+        let app = app(1, 10);
+        let bluetooth = app.bluetooth();
+        assert_eq!(bluetooth.connection_state("A"), Disconnected);
+        assert!(bluetooth.connection_history("A").is_empty());
+
+        bluetooth.connect("A".to_string(), options(false, None)).unwrap();
+        assert_eq!(bluetooth.connection_state("a"), Connected);
+        bluetooth.disconnect("A".to_string()).unwrap();
+        assert_eq!(
+            history(&app),
+            [(Connecting, 1), (Connected, 1), (Disconnecting, 1), (Disconnected, 1)]
+        );
+
+        // Without auto_connect, a dropped connection stays down.
+        bluetooth.connect("A".to_string(), options(false, None)).unwrap();
+        bluetooth.simulator().drop_connection("A", Some("Link lost".to_string())).unwrap();
+        let records = bluetooth.connection_history("A");
+        let last = records.last().unwrap();
+        assert_eq!((&last.state, last.error.as_deref()), (&Disconnected, Some("Link lost")));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(bluetooth.connection_state("A"), Disconnected);
+
+        // A failed attempt ends where it started, with the reason.
+        bluetooth.simulator().set_in_range("A", false).unwrap();
+        let result = bluetooth.connect("A".to_string(), options(false, None));
+        assert!(matches!(&result, Err(Error::OperationFailed(message)) if message.contains("out of range")), "{result:?}");
+        let records = bluetooth.connection_history("A");
+        assert_eq!(records.len(), 9);
+        assert!(records[8].error.as_deref().is_some_and(|error| error.contains("out of range")));
+        assert_eq!(bluetooth.connection_state("A"), Disconnected);
+    }
+
+    #[test]
+    fn attempts_time_out_after_timeout_ms() {
+        let app = app(2, 10);
+        let bluetooth = app.bluetooth();
+        bluetooth.simulator().hold_connections("A", true).unwrap();
+
+        let started = Instant::now();
+        let result = bluetooth.connect("A".to_string(), options(false, Some(50)));
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert!(matches!(&result, Err(Error::OperationFailed(message)) if message == "Connection timed out"), "{result:?}");
+        assert_eq!(history(&app), [(Connecting, 1), (Disconnected, 1)]);
+        let records = bluetooth.connection_history("A");
+        assert_eq!(records[1].error.as_deref(), Some("Connection timed out"));
+
+        // Each retry gets the full timeout.
+        let started = Instant::now();
+        assert!(bluetooth.connect("A".to_string(), options(true, Some(50))).is_err());
+        assert!(started.elapsed() >= Duration::from_millis(110));
+        assert_eq!(history(&app)[2..], [(Connecting, 1), (Disconnected, 1), (Connecting, 2), (Disconnected, 2)]);
+
+        // The timed out request was cancelled, so answering late connects nothing.
+        bluetooth.simulator().hold_connections("A", false).unwrap();
+        assert_eq!(bluetooth.connection_state("A"), Disconnected);
+        assert!(bluetooth.get_connected_peripherals().unwrap().is_empty());
+    }
+
+    #[test]
+    fn auto_connect_retries_with_backoff() {
+        let app = app(3, 40);
+        let bluetooth = app.bluetooth();
+        bluetooth.simulator().set_in_range("A", false).unwrap();
+
+        assert!(bluetooth.connect("A".to_string(), options(false, None)).is_err());
+        assert_eq!(history(&app), [(Connecting, 1), (Disconnected, 1)]);
+
+        // Waits 40ms, then 80ms, before the retries.
+        let started = Instant::now();
+        let result = bluetooth.connect("A".to_string(), options(true, None));
+        assert!(started.elapsed() >= Duration::from_millis(120));
+        assert!(matches!(&result, Err(Error::OperationFailed(message)) if message.contains("out of range")), "{result:?}");
+        assert_eq!(
+            history(&app)[2..],
+            [(Connecting, 1), (Disconnected, 1), (Connecting, 2), (Disconnected, 2), (Connecting, 3), (Disconnected, 3)]
+        );
+
+        // A retry succeeds once the peripheral is back.
+        let handle = app.handle().clone();
+        let returning = thread::spawn(move || {
+            wait_for(|| history(&handle).len() == 10);
+            handle.bluetooth().simulator().set_in_range("A", true).unwrap();
+        });
+        bluetooth.connect("A".to_string(), options(true, None)).unwrap();
+        returning.join().unwrap();
+        assert_eq!(history(&app)[8..], [(Connecting, 1), (Disconnected, 1), (Connecting, 2), (Connected, 2)]);
+    }
+
+    #[test]
+    fn dropped_connections_reconnect_with_their_subscriptions() {
+        let app = app(3, 10);
+        let bluetooth = app.bluetooth();
+        let (_subscription, values) = notifications(&app);
+        bluetooth.connect("A".to_string(), options(true, None)).unwrap();
+        bluetooth
+            .subscribe_to_characteristic("A".to_string(), MEASUREMENT.to_string())
+            .unwrap();
+        bluetooth.simulator().notify("A", MEASUREMENT, vec![1]).unwrap();
+
+        // The link drops in the middle of a read.
+        bluetooth
+            .simulator()
+            .script_disconnect("A", 0, Some("Link lost".to_string()))
+            .unwrap();
+        assert!(bluetooth.read_characteristic("A".to_string(), MEASUREMENT.to_string()).is_err());
+        bluetooth.simulator().notify("A", MEASUREMENT, vec![2]).unwrap();
+        wait_for(|| history(&app).len() == 5);
+        let records = bluetooth.connection_history("A");
+        assert_eq!(records[2].error.as_deref(), Some("Link lost"));
+        assert_eq!(history(&app)[3..], [(Connecting, 2), (Connected, 2)]);
+
+        // Notifications are enabled again once the characteristic is rediscovered.
+        wait_for(|| {
+            bluetooth.simulator().notify("A", MEASUREMENT, vec![3]).unwrap();
+            values.lock().unwrap().len() > 1
+        });
+        assert_eq!(values.lock().unwrap()[..2], [vec![1], vec![3]]);
+
+        // A peripheral that doesn't come back is given up on after the retries.
+        bluetooth.simulator().set_in_range("A", false).unwrap();
+        wait_for(|| history(&app).len() == 12);
+        assert_eq!(
+            history(&app)[5..],
+            [
+                (Disconnected, 2),
+                (Connecting, 3),
+                (Disconnected, 3),
+                (Connecting, 4),
+                (Disconnected, 4),
+                (Connecting, 5),
+                (Disconnected, 5)
+            ]
+        );
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(history(&app).len(), 12);
+    }
+
+    #[test]
+    fn explicit_disconnects_forget_subscriptions_and_stay_down() {
+        let app = app(3, 10);
+        let bluetooth = app.bluetooth();
+        let (_subscription, values) = notifications(&app);
+        bluetooth.connect("A".to_string(), options(true, None)).unwrap();
+        bluetooth
+            .subscribe_to_characteristic("A".to_string(), MEASUREMENT.to_string())
+            .unwrap();
+
+        bluetooth.disconnect("A".to_string()).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(bluetooth.connection_state("A"), Disconnected);
+
+        bluetooth.connect("A".to_string(), options(true, None)).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        bluetooth.simulator().notify("A", MEASUREMENT, vec![1]).unwrap();
+        assert!(values.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn history_keeps_the_most_recent_transitions() {
+        let app = app(1, 10);
+        let bluetooth = app.bluetooth();
+        for _ in 0..30 {
+            bluetooth.connect("A".to_string(), options(false, None)).unwrap();
+            bluetooth.disconnect("A".to_string()).unwrap();
+        }
+
+        let records = bluetooth.connection_history("A");
+        assert_eq!(records.len(), MAX_HISTORY);
+        // The oldest 20 of the 120 transitions were dropped.
+        assert_eq!(records[0].state, Connecting);
+        assert_eq!(records[MAX_HISTORY - 1].state, Disconnected);
+        assert!(records.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp));
+    }
+
+    #[test]
+    fn backoff_starts_at_the_initial_delay_and_doubles_up_to_the_maximum() {
+        let policy = ReconnectPolicy {
+            max_attempts: 10,
+            initial_delay_ms: 500,
+            max_delay_ms: 3_000,
+        };
+        let delays: Vec<u64> = (1..=5).map(|retry| policy.delay(retry).as_millis() as u64).collect();
+        assert_eq!(delays, [500, 1_000, 2_000, 3_000, 3_000]);
+        assert_eq!(policy.delay(200), Duration::from_millis(3_000));
+    }
+}
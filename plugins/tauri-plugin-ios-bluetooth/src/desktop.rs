@@ -1,124 +1,153 @@
+use std::sync::{Arc, Mutex};
+
 use serde::de::DeserializeOwned;
 use tauri::{plugin::PluginApi, AppHandle, Runtime};
 
-use crate::{models::*, Result, Error};
+use crate::{connection::ConnectionManager, events::EventBus, models::*, simulator::Simulator, Result, Subscription};
 
 pub fn init<R: Runtime, C: DeserializeOwned>(
     app: &AppHandle<R>,
     _api: PluginApi<R, C>,
 ) -> Result<Bluetooth<R>> {
-    Ok(Bluetooth(app.clone()))
+    Ok(Bluetooth::new(app))
 }
 
-/// Access to the Bluetooth APIs on desktop (returns errors as not available).
-pub struct Bluetooth<R: Runtime>(AppHandle<R>);
+/// Access to the Bluetooth APIs on desktop, backed by an in-memory simulated stack.
+pub struct Bluetooth<R: Runtime> {
+    pub(crate) app: AppHandle<R>,
+    pub(crate) events: Arc<EventBus>,
+    pub(crate) connections: ConnectionManager,
+    // The scan started with `start_central_scan`.
+    pub(crate) direct_scan: Mutex<Option<Subscription>>,
+    simulator: Simulator,
+}
 
 impl<R: Runtime> Bluetooth<R> {
+    pub(crate) fn new(app: &AppHandle<R>) -> Self {
+        let events = Arc::new(EventBus::default());
+        Self {
+            app: app.clone(),
+            simulator: Simulator::new(events.clone()),
+            connections: ConnectionManager::new(app, &events),
+            direct_scan: Mutex::default(),
+            events,
+        }
+    }
+
+    /// The simulated stack, used to register virtual peripherals and script their
+    /// behaviour.
+    pub fn simulator(&self) -> &Simulator {
+        &self.simulator
+    }
+    
     pub fn request_authorization(&self) -> Result<AuthorizationStatus> {
-        Err(Error::NotAvailable)
+        Ok(self.simulator.authorization_status())
     }
     
     pub fn get_authorization_status(&self) -> Result<AuthorizationStatus> {
-        Err(Error::NotAvailable)
+        Ok(self.simulator.authorization_status())
     }
     
     pub fn is_bluetooth_enabled(&self) -> Result<bool> {
-        Ok(false)
+        Ok(self.simulator.is_bluetooth_enabled())
     }
     
-    pub fn start_central_scan(&self, _options: ScanOptions) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub(crate) fn start_native_scan(&self, options: ScanOptions) -> Result<()> {
+        self.simulator.start_central_scan(options)
     }
     
-    pub fn stop_central_scan(&self) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub(crate) fn stop_native_scan(&self) -> Result<()> {
+        self.simulator.stop_central_scan()
     }
     
-    pub fn connect_peripheral(&self, _uuid: String, _options: ConnectionOptions) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub fn connect_peripheral(&self, uuid: String, _options: ConnectionOptions) -> Result<()> {
+        self.simulator.connect_peripheral(&uuid)
     }
     
-    pub fn disconnect_peripheral(&self, _uuid: String) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub fn disconnect_peripheral(&self, uuid: String) -> Result<()> {
+        self.simulator.disconnect_peripheral(&uuid)
     }
     
     pub fn get_connected_peripherals(&self) -> Result<Vec<Peripheral>> {
-        Err(Error::NotAvailable)
+        Ok(self.simulator.connected_peripherals())
     }
     
     pub fn get_discovered_peripherals(&self) -> Result<Vec<Peripheral>> {
-        Err(Error::NotAvailable)
+        Ok(self.simulator.discovered_peripherals())
     }
     
-    pub fn discover_services(&self, _peripheral_uuid: String, _service_uuids: Option<Vec<String>>) -> Result<Vec<Service>> {
-        Err(Error::NotAvailable)
+    pub fn discover_services(&self, peripheral_uuid: String, service_uuids: Option<Vec<String>>) -> Result<Vec<Service>> {
+        self.simulator.discover_services(&peripheral_uuid, service_uuids)
     }
     
-    pub fn discover_characteristics(&self, _peripheral_uuid: String, _service_uuid: String, _characteristic_uuids: Option<Vec<String>>) -> Result<Vec<Characteristic>> {
-        Err(Error::NotAvailable)
+    pub fn discover_characteristics(&self, peripheral_uuid: String, service_uuid: String, characteristic_uuids: Option<Vec<String>>) -> Result<Vec<Characteristic>> {
+        self.simulator.discover_characteristics(&peripheral_uuid, &service_uuid, characteristic_uuids)
     }
     
-    pub fn read_characteristic(&self, _peripheral_uuid: String, _characteristic_uuid: String) -> Result<Vec<u8>> {
-        Err(Error::NotAvailable)
+    pub fn read_characteristic(&self, peripheral_uuid: String, characteristic_uuid: String) -> Result<Vec<u8>> {
+        self.simulator.read_characteristic(&peripheral_uuid, &characteristic_uuid)
     }
     
-    pub fn write_characteristic(&self, _peripheral_uuid: String, _characteristic_uuid: String, _value: Vec<u8>, _options: WriteOptions) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub fn write_characteristic(&self, peripheral_uuid: String, characteristic_uuid: String, value: Vec<u8>, options: WriteOptions) -> Result<()> {
+        self.simulator.write_characteristic(&peripheral_uuid, &characteristic_uuid, value, options)
     }
     
-    pub fn subscribe_to_characteristic(&self, _peripheral_uuid: String, _characteristic_uuid: String) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub fn subscribe_to_characteristic(&self, peripheral_uuid: String, characteristic_uuid: String) -> Result<()> {
+        self.simulator.set_characteristic_notifications(&peripheral_uuid, &characteristic_uuid, true)
     }
     
-    pub fn unsubscribe_from_characteristic(&self, _peripheral_uuid: String, _characteristic_uuid: String) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub fn unsubscribe_from_characteristic(&self, peripheral_uuid: String, characteristic_uuid: String) -> Result<()> {
+        self.simulator.set_characteristic_notifications(&peripheral_uuid, &characteristic_uuid, false)
     }
     
-    pub fn read_descriptor(&self, _peripheral_uuid: String, _descriptor_uuid: String) -> Result<Vec<u8>> {
-        Err(Error::NotAvailable)
+    pub fn read_descriptor(&self, peripheral_uuid: String, descriptor_uuid: String) -> Result<Vec<u8>> {
+        self.simulator.read_descriptor(&peripheral_uuid, &descriptor_uuid)
     }
     
-    pub fn write_descriptor(&self, _peripheral_uuid: String, _descriptor_uuid: String, _value: Vec<u8>) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub fn write_descriptor(&self, peripheral_uuid: String, descriptor_uuid: String, value: Vec<u8>) -> Result<()> {
+        self.simulator.write_descriptor(&peripheral_uuid, &descriptor_uuid, value)
     }
     
-    pub fn get_peripheral_rssi(&self, _peripheral_uuid: String) -> Result<i32> {
-        Err(Error::NotAvailable)
+    pub fn get_peripheral_rssi(&self, peripheral_uuid: String) -> Result<i32> {
+        self.simulator.peripheral_rssi(&peripheral_uuid)
     }
     
-    pub fn start_peripheral_advertising(&self, _advertising_data: AdvertisingData) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub fn start_peripheral_advertising(&self, advertising_data: AdvertisingData) -> Result<()> {
+        self.simulator.start_peripheral_advertising(advertising_data)
     }
     
     pub fn stop_peripheral_advertising(&self) -> Result<()> {
-        Err(Error::NotAvailable)
+        self.simulator.stop_peripheral_advertising();
+        Ok(())
     }
     
-    pub fn add_service(&self, _service: PeripheralService) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub fn add_service(&self, service: PeripheralService) -> Result<()> {
+        self.simulator.add_service(service);
+        Ok(())
     }
     
-    pub fn remove_service(&self, _service_uuid: String) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub fn remove_service(&self, service_uuid: String) -> Result<()> {
+        self.simulator.remove_service(&service_uuid)
     }
     
     pub fn remove_all_services(&self) -> Result<()> {
-        Err(Error::NotAvailable)
+        self.simulator.remove_all_services();
+        Ok(())
     }
     
-    pub fn respond_to_request(&self, _response: RequestResponse) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub fn respond_to_request(&self, response: RequestResponse) -> Result<()> {
+        self.simulator.respond_to_request(response)
     }
     
-    pub fn update_characteristic_value(&self, _characteristic_uuid: String, _value: Vec<u8>, _central_uuids: Option<Vec<String>>) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub fn update_characteristic_value(&self, characteristic_uuid: String, value: Vec<u8>, central_uuids: Option<Vec<String>>) -> Result<()> {
+        self.simulator.update_characteristic_value(&characteristic_uuid, value, central_uuids)
     }
     
-    pub fn get_maximum_write_length(&self, _peripheral_uuid: String, _write_type: WriteType) -> Result<usize> {
-        Err(Error::NotAvailable)
+    pub fn get_maximum_write_length(&self, peripheral_uuid: String, write_type: WriteType) -> Result<usize> {
+        self.simulator.maximum_write_length(&peripheral_uuid, &write_type)
     }
     
-    pub fn set_notify_value(&self, _characteristic_uuid: String, _enabled: bool) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub fn set_notify_value(&self, characteristic_uuid: String, enabled: bool) -> Result<()> {
+        self.simulator.set_notify_value(&characteristic_uuid, enabled)
     }
 }
\ No newline at end of file
@@ -0,0 +1,673 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+};
+
+use tauri::Runtime;
+
+use crate::{
+    advertisement::ScanFilter,
+    gatt::{expand_uuid, uuids_equal},
+    BluetoothEvent, BluetoothEventPayload, BluetoothExt, Error, Result, ScanOptions,
+};
+
+type Handler = Arc<dyn Fn(&BluetoothEvent) + Send + Sync>;
+type Stop = Box<dyn FnOnce() + Send>;
+
+/// A native operation shared by every listener that needs it. UUIDs are stored
+/// expanded so differently-written requests share one operation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Operation {
+    Scan,
+    Notifications {
+        peripheral_uuid: String,
+        characteristic_uuid: String,
+    },
+}
+
+impl Operation {
+    fn matches(&self, payload: &BluetoothEventPayload) -> bool {
+        match (self, payload) {
+            (Operation::Scan, BluetoothEventPayload::PeripheralDiscovered(_)) => true,
+            (
+                Operation::Notifications {
+                    peripheral_uuid,
+                    characteristic_uuid,
+                },
+                BluetoothEventPayload::CharacteristicValueUpdated {
+                    peripheral_uuid: peripheral,
+                    characteristic_uuid: characteristic,
+                    ..
+                }
+                | BluetoothEventPayload::CharacteristicSubscriptionChanged {
+                    peripheral_uuid: peripheral,
+                    characteristic_uuid: characteristic,
+                    ..
+                },
+            ) => {
+                uuids_equal(peripheral, peripheral_uuid) && uuids_equal(characteristic, characteristic_uuid)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The events a listener receives.
+enum Target {
+    /// Every event as it was dispatched, for the plugin's own bookkeeping.
+    All,
+    /// Every event, except that discoveries only come from the scan started
+    /// with `start_central_scan`, and only those passing its filters.
+    App,
+    Operation(Operation),
+}
+
+struct Listener {
+    target: Target,
+    handler: Handler,
+}
+
+impl Listener {
+    fn operation(&self) -> Option<&Operation> {
+        match &self.target {
+            Target::Operation(operation) => Some(operation),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: u32,
+    listeners: HashMap<u32, Listener>,
+    // `None` while the operation is still being started.
+    operations: HashMap<Operation, Option<Stop>>,
+    // Why the operation of a listener that was waiting for it failed to start.
+    failures: HashMap<u32, Error>,
+}
+
+/// Fans native Bluetooth events out to the registered listeners.
+#[derive(Default)]
+pub(crate) struct EventBus {
+    registry: Mutex<Registry>,
+    // Notified when an operation has finished starting, or failed to.
+    started: Condvar,
+}
+
+/// Copies a start failure for each listener that was waiting on it.
+fn start_failure(error: &Error) -> Error {
+    match error {
+        Error::NotAvailable => Error::NotAvailable,
+        Error::PermissionDenied => Error::PermissionDenied,
+        Error::InvalidInput(message) => Error::InvalidInput(message.clone()),
+        _ => Error::OperationFailed(error.to_string()),
+    }
+}
+
+impl EventBus {
+    fn registry(&self) -> MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Delivers `event` to every listener interested in it.
+    pub(crate) fn dispatch(&self, event: &BluetoothEvent) {
+        let discovery = matches!(event.payload, BluetoothEventPayload::PeripheralDiscovered(_));
+        self.deliver(event, |target| match target {
+            Target::All => true,
+            Target::App => !discovery,
+            Target::Operation(operation) => operation.matches(&event.payload),
+        });
+    }
+
+    fn deliver(&self, event: &BluetoothEvent, wanted: impl Fn(&Target) -> bool) {
+        let handlers: Vec<Handler> = self
+            .registry()
+            .listeners
+            .values()
+            .filter(|listener| wanted(&listener.target))
+            .map(|listener| listener.handler.clone())
+            .collect();
+
+        for handler in handlers {
+            handler(event);
+        }
+    }
+
+    /// Registers a listener for every event as it is dispatched.
+    pub(crate) fn listen(self: &Arc<Self>, handler: Handler) -> Subscription {
+        self.add_listener(Target::All, handler).0
+    }
+
+    /// Registers a listener and reports whether its operation still has to be
+    /// started, i.e. no other listener is keeping it alive yet.
+    fn add_listener(self: &Arc<Self>, target: Target, handler: Handler) -> (Subscription, bool) {
+        let mut registry = self.registry();
+        let id = registry.next_id;
+        registry.next_id = registry.next_id.wrapping_add(1);
+
+        let needs_start = match &target {
+            Target::Operation(operation) if !registry.operations.contains_key(operation) => {
+                registry.operations.insert(operation.clone(), None);
+                true
+            }
+            _ => false,
+        };
+        registry.listeners.insert(id, Listener { target, handler });
+
+        let subscription = Subscription {
+            id,
+            bus: self.clone(),
+        };
+        (subscription, needs_start)
+    }
+
+    /// Registers a listener for `operation`, running `start` if it isn't active yet.
+    /// `start` runs without the registry locked so backends may dispatch events
+    /// while starting. Listeners added meanwhile wait for it to finish, and share
+    /// its error if it fails.
+    fn subscribe<S>(self: &Arc<Self>, operation: Operation, handler: Handler, start: S) -> Result<Subscription>
+    where
+        S: FnOnce() -> Result<Stop>,
+    {
+        let (subscription, needs_start) = self.add_listener(Target::Operation(operation.clone()), handler);
+        if !needs_start {
+            return self.wait_for_start(&operation, subscription);
+        }
+
+        let stop = match start() {
+            Ok(stop) => stop,
+            Err(error) => {
+                self.fail(&operation, subscription.id, &error);
+                return Err(error);
+            }
+        };
+        let mut registry = self.registry();
+        match registry.operations.get_mut(&operation) {
+            Some(slot) => {
+                *slot = Some(stop);
+                drop(registry);
+                self.started.notify_all();
+            }
+            // Every listener went away while the operation was starting.
+            None => {
+                drop(registry);
+                stop();
+            }
+        }
+        Ok(subscription)
+    }
+
+    /// Blocks until the operation `subscription` joined is running, or fails
+    /// with the error it failed to start with.
+    fn wait_for_start(&self, operation: &Operation, subscription: Subscription) -> Result<Subscription> {
+        let mut registry = self.registry();
+        loop {
+            if let Some(error) = registry.failures.remove(&subscription.id) {
+                drop(registry);
+                return Err(error);
+            }
+            match registry.operations.get(operation) {
+                Some(None) => {
+                    registry = self.started.wait(registry).unwrap_or_else(|e| e.into_inner());
+                }
+                _ => return Ok(subscription),
+            }
+        }
+    }
+
+    /// Forgets an operation that failed to start and removes the listeners
+    /// waiting for it, handing each of them the error.
+    fn fail(&self, operation: &Operation, starter: u32, error: &Error) {
+        let mut registry = self.registry();
+        registry.operations.remove(operation);
+        let waiting: Vec<u32> = registry
+            .listeners
+            .iter()
+            .filter(|(_, listener)| listener.operation() == Some(operation))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in waiting {
+            registry.listeners.remove(&id);
+            if id != starter {
+                registry.failures.insert(id, start_failure(error));
+            }
+        }
+        drop(registry);
+        self.started.notify_all();
+    }
+
+    fn unsubscribe(&self, id: u32) {
+        let stop = {
+            let mut registry = self.registry();
+            let Some(listener) = registry.listeners.remove(&id) else {
+                return;
+            };
+            let Target::Operation(operation) = listener.target else {
+                return;
+            };
+            let still_needed = registry
+                .listeners
+                .values()
+                .any(|other| other.operation() == Some(&operation));
+            if still_needed {
+                return;
+            }
+            registry.operations.remove(&operation).flatten()
+        };
+
+        if let Some(stop) = stop {
+            stop();
+        }
+    }
+}
+
+/// Handle to a registered event listener.
+///
+/// Dropping it removes the listener. When it was the last listener of a scan or
+/// characteristic subscription, the underlying native operation is stopped too.
+pub struct Subscription {
+    id: u32,
+    bus: Arc<EventBus>,
+}
+
+impl Subscription {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Removes the listener. Equivalent to dropping the handle.
+    pub fn unsubscribe(self) {}
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.bus.unsubscribe(self.id);
+    }
+}
+
+/// Subscriptions created from the frontend, keyed by the id handed back to JS.
+#[derive(Default)]
+pub(crate) struct JsSubscriptions(Mutex<HashMap<u32, Subscription>>);
+
+impl JsSubscriptions {
+    pub(crate) fn insert(&self, subscription: Subscription) -> u32 {
+        let id = subscription.id();
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, subscription);
+        id
+    }
+
+    pub(crate) fn remove(&self, id: u32) {
+        let subscription = self.0.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+        drop(subscription);
+    }
+}
+
+/// Options for the native scan shared by every scan subscription: none of the
+/// filters, so no subscription misses the peripherals it is looking for.
+fn shared_scan_options(options: &ScanOptions) -> ScanOptions {
+    ScanOptions {
+        allow_duplicates: options.allow_duplicates,
+        scan_mode: options.scan_mode.clone(),
+        ..ScanOptions::default()
+    }
+}
+
+/// Wraps the handler of a scan subscription so it only sees the discoveries
+/// passing its filters, and each peripheral once unless duplicates are allowed.
+fn scan_handler<F>(options: &ScanOptions, handler: F) -> Result<Handler>
+where
+    F: Fn(&BluetoothEvent) + Send + Sync + 'static,
+{
+    let filter = ScanFilter::new(options.clone())?;
+    let allow_duplicates = options.allow_duplicates;
+    let seen = Mutex::new(HashSet::new());
+    Ok(Arc::new(move |event: &BluetoothEvent| match &event.payload {
+        BluetoothEventPayload::PeripheralDiscovered(peripheral) => {
+            if !filter.matches(peripheral) {
+                return;
+            }
+            let first = seen
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(peripheral.uuid.clone());
+            if allow_duplicates || first {
+                handler(event);
+            }
+        }
+        _ => handler(event),
+    }))
+}
+
+impl<R: Runtime> crate::Bluetooth<R> {
+    /// Registers `handler` for every Bluetooth event without starting any native
+    /// operation. Discoveries are only delivered while a scan started with
+    /// [`start_central_scan`](Self::start_central_scan) runs, and only those
+    /// passing its filters; scan subscriptions get theirs separately.
+    pub fn listen<F>(&self, handler: F) -> Subscription
+    where
+        F: Fn(&BluetoothEvent) + Send + Sync + 'static,
+    {
+        self.events.add_listener(Target::App, Arc::new(handler)).0
+    }
+
+    /// Scans for peripherals, delivering the discoveries that pass the filters in
+    /// `options` to the [`listen`](Self::listen) handlers. Starting another scan
+    /// replaces its filters.
+    ///
+    /// The scan joins the native scan of any [`watch_central_scan`](Self::watch_central_scan)
+    /// subscriptions, with its filters applied as events are dispatched.
+    pub fn start_central_scan(&self, options: ScanOptions) -> Result<()> {
+        let events = Arc::downgrade(&self.events);
+        let handler = scan_handler(&options, move |event| {
+            if let Some(events) = events.upgrade() {
+                events.deliver(event, |target| matches!(target, Target::App));
+            }
+        })?;
+        let subscription = self.scan(&options, handler)?;
+        let previous = self
+            .direct_scan
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .replace(subscription);
+        // Dropped after the new scan joined, so the native scan keeps running.
+        drop(previous);
+        Ok(())
+    }
+
+    /// Stops the scan started with `start_central_scan`. The native scan keeps
+    /// running for any `watch_central_scan` subscriptions.
+    pub fn stop_central_scan(&self) -> Result<()> {
+        let subscription = self.direct_scan.lock().unwrap_or_else(|e| e.into_inner()).take();
+        subscription
+            .map(drop)
+            .ok_or_else(|| Error::OperationFailed("Scan not started".to_string()))
+    }
+
+    /// Joins the shared native scan with `handler`, starting it if needed.
+    fn scan(&self, options: &ScanOptions, handler: Handler) -> Result<Subscription> {
+        let native = shared_scan_options(options);
+        let app = self.app.clone();
+        self.events.subscribe(Operation::Scan, handler, || {
+            app.bluetooth().start_native_scan(native)?;
+            Ok(Box::new(move || {
+                let _ = app.bluetooth().stop_native_scan();
+            }))
+        })
+    }
+
+    /// Scans for peripherals and delivers each `PeripheralDiscovered` event that
+    /// passes the filters in `options` to `handler`. Scanning stops once every scan
+    /// subscription has been dropped.
+    ///
+    /// The native scan is shared by every subscription and by
+    /// `start_central_scan`, so it runs without filters
+    /// and each subscription's filters are applied as events are dispatched. Its
+    /// `scan_mode` and `allow_duplicates` are those of the subscription that
+    /// started it; subscriptions not allowing duplicates never see them.
+    pub fn watch_central_scan<F>(&self, options: ScanOptions, handler: F) -> Result<Subscription>
+    where
+        F: Fn(&BluetoothEvent) + Send + Sync + 'static,
+    {
+        let handler = scan_handler(&options, handler)?;
+        self.scan(&options, handler)
+    }
+
+    /// Enables notifications for a characteristic and delivers its value and
+    /// subscription updates to `handler`. Notifications are disabled once every
+    /// subscription to the characteristic has been dropped.
+    pub fn watch_characteristic<F>(
+        &self,
+        peripheral_uuid: String,
+        characteristic_uuid: String,
+        handler: F,
+    ) -> Result<Subscription>
+    where
+        F: Fn(&BluetoothEvent) + Send + Sync + 'static,
+    {
+        let app = self.app.clone();
+        let operation = Operation::Notifications {
+            peripheral_uuid: expand_uuid(&peripheral_uuid),
+            characteristic_uuid: expand_uuid(&characteristic_uuid),
+        };
+        self.events
+            .subscribe(operation, Arc::new(handler), || {
+                app.bluetooth()
+                    .subscribe_to_characteristic(peripheral_uuid.clone(), characteristic_uuid.clone())?;
+                Ok(Box::new(move || {
+                    let _ = app
+                        .bluetooth()
+                        .unsubscribe_from_characteristic(peripheral_uuid, characteristic_uuid);
+                }))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::AtomicUsize, atomic::Ordering, mpsc},
+        thread,
+    };
+
+    use super::*;
+
+    fn handler() -> Handler {
+        Arc::new(|_| {})
+    }
+
+    /// Subscribes to a scan from another thread once `start` has begun, and waits
+    /// until its listener is registered.
+    fn join_scan(bus: &Arc<EventBus>) -> thread::JoinHandle<Result<u32>> {
+        let joiner = bus.clone();
+        let handle = thread::spawn(move || {
+            joiner
+                .subscribe(Operation::Scan, handler(), || panic!("the scan was started twice"))
+                .map(|subscription| subscription.id())
+        });
+        while bus.registry().listeners.len() < 2 {
+            thread::yield_now();
+        }
+        handle
+    }
+
+    #[test]
+    fn listeners_joining_a_failing_start_get_its_error() {
+        let bus = Arc::new(EventBus::default());
+        let (release, gate) = mpsc::channel::<()>();
+        let starter = {
+            let bus = bus.clone();
+            thread::spawn(move || {
+                bus.subscribe(Operation::Scan, handler(), move || {
+                    gate.recv().ok();
+                    Err(Error::PermissionDenied)
+                })
+                .map(|subscription| subscription.id())
+            })
+        };
+        while !bus.registry().operations.contains_key(&Operation::Scan) {
+            thread::yield_now();
+        }
+        let joiner = join_scan(&bus);
+        release.send(()).unwrap();
+
+        assert!(matches!(starter.join().unwrap(), Err(Error::PermissionDenied)));
+        assert!(matches!(joiner.join().unwrap(), Err(Error::PermissionDenied)));
+        let registry = bus.registry();
+        assert!(registry.listeners.is_empty());
+        assert!(registry.operations.is_empty());
+        assert!(registry.failures.is_empty());
+    }
+
+    #[test]
+    fn listeners_joining_a_start_share_the_operation() {
+        let bus = Arc::new(EventBus::default());
+        let stops = Arc::new(AtomicUsize::new(0));
+        let (release, gate) = mpsc::channel::<()>();
+        let starter = {
+            let (bus, stops) = (bus.clone(), stops.clone());
+            thread::spawn(move || {
+                bus.subscribe(Operation::Scan, handler(), move || {
+                    gate.recv().ok();
+                    Ok(Box::new(move || {
+                        stops.fetch_add(1, Ordering::SeqCst);
+                    }))
+                })
+            })
+        };
+        while !bus.registry().operations.contains_key(&Operation::Scan) {
+            thread::yield_now();
+        }
+        let joiner = join_scan(&bus);
+        release.send(()).unwrap();
+
+        let first = starter.join().unwrap().unwrap();
+        assert!(joiner.join().unwrap().is_ok());
+        assert_eq!(stops.load(Ordering::SeqCst), 0);
+        drop(first);
+        assert_eq!(stops.load(Ordering::SeqCst), 1);
+    }
+
+    fn discovered(uuid: &str, name: &str, services: &[&str]) -> BluetoothEvent {
+        BluetoothEvent {
+            timestamp: chrono::Utc::now(),
+            payload: BluetoothEventPayload::PeripheralDiscovered(crate::Peripheral {
+                uuid: uuid.to_string(),
+                name: Some(name.to_string()),
+                rssi: -60,
+                is_connectable: true,
+                state: crate::PeripheralState::Disconnected,
+                services: services.iter().map(|uuid| uuid.to_string()).collect(),
+                manufacturer_data: None,
+                service_data: None,
+                tx_power_level: None,
+                solicited_service_uuids: vec![],
+                overflow_service_uuids: vec![],
+            }),
+        }
+    }
+
+    fn scan_recorder(options: ScanOptions) -> (Handler, Arc<Mutex<Vec<String>>>) {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let recorded = seen.clone();
+        let handler = scan_handler(&options, move |event| {
+            if let BluetoothEventPayload::PeripheralDiscovered(peripheral) = &event.payload {
+                recorded.lock().unwrap().push(peripheral.uuid.clone());
+            }
+        })
+        .unwrap();
+        (handler, seen)
+    }
+
+    #[test]
+    fn scan_subscriptions_apply_their_own_filters() {
+        let heart_rate = ScanOptions {
+            service_uuids: vec!["180D".to_string()],
+            ..ScanOptions::default()
+        };
+        let named = ScanOptions {
+            name_prefix: Some("Sensor".to_string()),
+            allow_duplicates: true,
+            ..ScanOptions::default()
+        };
+        let native = shared_scan_options(&heart_rate);
+        assert!(native.service_uuids.is_empty());
+        assert!(native.name_prefix.is_none());
+
+        let bus = Arc::new(EventBus::default());
+        let (handler, heart_rate_seen) = scan_recorder(heart_rate);
+        let _first = bus.subscribe(Operation::Scan, handler, || Ok(Box::new(|| {}))).unwrap();
+        let (handler, named_seen) = scan_recorder(named);
+        let _second = bus
+            .subscribe(Operation::Scan, handler, || panic!("the scan was started twice"))
+            .unwrap();
+
+        for event in [
+            discovered("A", "Sensor 1", &["180D"]),
+            discovered("B", "Sensor 2", &["180F"]),
+            discovered("A", "Sensor 1", &["180D"]),
+            discovered("C", "Strap", &["0000180d-0000-1000-8000-00805f9b34fb"]),
+        ] {
+            bus.dispatch(&event);
+        }
+
+        assert_eq!(*heart_rate_seen.lock().unwrap(), ["A", "C"]);
+        assert_eq!(*named_seen.lock().unwrap(), ["A", "B", "A"]);
+    }
+
+    #[test]
+    fn direct_scans_only_filter_what_listeners_see() {
+        use tauri::Manager;
+
+        let app = tauri::test::mock_app();
+        app.manage(crate::Bluetooth::new(app.handle()));
+        let bluetooth = app.bluetooth();
+        let heart_rate = || ScanOptions {
+            service_uuids: vec!["180D".to_string()],
+            ..ScanOptions::default()
+        };
+        let listened = Arc::new(Mutex::new(vec![]));
+        let recorded = listened.clone();
+        let _listener = bluetooth.listen(move |event| {
+            if let BluetoothEventPayload::PeripheralDiscovered(peripheral) = &event.payload {
+                recorded.lock().unwrap().push(peripheral.uuid.clone());
+            }
+        });
+        assert!(bluetooth.stop_central_scan().is_err());
+
+        // Without a direct scan, listeners see no discoveries.
+        let (handler, watched) = scan_recorder(ScanOptions {
+            name_prefix: Some("Sensor".to_string()),
+            ..ScanOptions::default()
+        });
+        let watch = bluetooth.watch_central_scan(ScanOptions::default(), move |event| handler(event)).unwrap();
+        bluetooth.events.dispatch(&discovered("A", "Sensor 1", &["180D"]));
+        assert!(listened.lock().unwrap().is_empty());
+
+        bluetooth.start_central_scan(heart_rate()).unwrap();
+        for event in [
+            discovered("B", "Sensor 2", &["180F"]),
+            discovered("C", "Strap", &["180D"]),
+        ] {
+            bluetooth.events.dispatch(&event);
+        }
+        assert_eq!(*listened.lock().unwrap(), ["C"]);
+        assert_eq!(*watched.lock().unwrap(), ["A", "B"]);
+
+        // Stopping it leaves the native scan running for the subscription.
+        bluetooth.stop_central_scan().unwrap();
+        bluetooth.events.dispatch(&discovered("D", "Sensor 3", &["180D"]));
+        assert_eq!(*listened.lock().unwrap(), ["C"]);
+        assert_eq!(*watched.lock().unwrap(), ["A", "B", "D"]);
+        assert!(bluetooth.simulator().start_central_scan(ScanOptions::default()).is_err());
+        drop(watch);
+        assert!(bluetooth.simulator().stop_central_scan().is_err());
+
+        // A direct scan of its own starts and stops the native scan, and starting
+        // another replaces its filters.
+        bluetooth.start_central_scan(heart_rate()).unwrap();
+        bluetooth
+            .start_central_scan(ScanOptions {
+                name_prefix: Some("Strap".to_string()),
+                ..ScanOptions::default()
+            })
+            .unwrap();
+        bluetooth.events.dispatch(&discovered("E", "Sensor 4", &["180D"]));
+        bluetooth.events.dispatch(&discovered("F", "Strap 2", &["180F"]));
+        assert_eq!(*listened.lock().unwrap(), ["C", "F"]);
+        bluetooth.stop_central_scan().unwrap();
+        assert!(bluetooth.simulator().stop_central_scan().is_err());
+        assert!(bluetooth.stop_central_scan().is_err());
+    }
+
+    #[test]
+    fn invalid_scan_filters_are_rejected() {
+        let options = ScanOptions {
+            name_pattern: Some("(".to_string()),
+            ..ScanOptions::default()
+        };
+        assert!(matches!(scan_handler(&options, |_| {}), Err(Error::InvalidInput(_))));
+    }
+}
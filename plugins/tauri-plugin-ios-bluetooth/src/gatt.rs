@@ -0,0 +1,1007 @@
+//! Decoders and encoders for standard Bluetooth SIG services and characteristics.
+//!
+//! UUIDs may be given in their 16-bit short form (`"2A37"`) or as full 128-bit UUIDs;
+//! short forms are expanded against the Bluetooth base UUID before comparing.
+
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use tauri::Runtime;
+
+use crate::{Characteristic, Error, Result, Service, WriteOptions};
+
+/// Suffix shared by every UUID derived from the Bluetooth base UUID.
+const BASE_UUID_SUFFIX: &str = "-0000-1000-8000-00805F9B34FB";
+
+/// Messages of the desktop backend, and codes the iOS backend rejects with, when
+/// a characteristic can't be read at all rather than the read failing.
+pub(crate) const CHARACTERISTIC_NOT_FOUND: &str = "Characteristic not found";
+pub(crate) const READ_NOT_PERMITTED: &str = "Read not permitted";
+#[cfg(mobile)]
+const NATIVE_UNREADABLE_CODES: [&str; 2] = ["characteristicNotFound", "readNotPermitted"];
+
+/// 16-bit UUIDs of SIG-assigned services.
+pub mod services {
+    pub const GENERIC_ACCESS: u16 = 0x1800;
+    pub const GENERIC_ATTRIBUTE: u16 = 0x1801;
+    pub const HEALTH_THERMOMETER: u16 = 0x1809;
+    pub const DEVICE_INFORMATION: u16 = 0x180A;
+    pub const HEART_RATE: u16 = 0x180D;
+    pub const BATTERY: u16 = 0x180F;
+    pub const CYCLING_SPEED_AND_CADENCE: u16 = 0x1816;
+}
+
+/// 16-bit UUIDs of SIG-assigned characteristics.
+pub mod characteristics {
+    pub const DEVICE_NAME: u16 = 0x2A00;
+    pub const APPEARANCE: u16 = 0x2A01;
+    pub const BATTERY_LEVEL: u16 = 0x2A19;
+    pub const TEMPERATURE_MEASUREMENT: u16 = 0x2A1C;
+    pub const SYSTEM_ID: u16 = 0x2A23;
+    pub const MODEL_NUMBER: u16 = 0x2A24;
+    pub const SERIAL_NUMBER: u16 = 0x2A25;
+    pub const FIRMWARE_REVISION: u16 = 0x2A26;
+    pub const HARDWARE_REVISION: u16 = 0x2A27;
+    pub const SOFTWARE_REVISION: u16 = 0x2A28;
+    pub const MANUFACTURER_NAME: u16 = 0x2A29;
+    pub const HEART_RATE_MEASUREMENT: u16 = 0x2A37;
+    pub const BODY_SENSOR_LOCATION: u16 = 0x2A38;
+    pub const HEART_RATE_CONTROL_POINT: u16 = 0x2A39;
+    pub const PNP_ID: u16 = 0x2A50;
+    pub const CSC_MEASUREMENT: u16 = 0x2A5B;
+    pub const CSC_FEATURE: u16 = 0x2A5C;
+}
+
+/// 16-bit UUIDs of SIG-assigned descriptors.
+pub mod descriptors {
+    pub const CHARACTERISTIC_EXTENDED_PROPERTIES: u16 = 0x2900;
+    pub const CHARACTERISTIC_USER_DESCRIPTION: u16 = 0x2901;
+    pub const CLIENT_CHARACTERISTIC_CONFIGURATION: u16 = 0x2902;
+    pub const CHARACTERISTIC_PRESENTATION_FORMAT: u16 = 0x2904;
+}
+
+/// Expands a 16- or 32-bit UUID to its 128-bit form on the Bluetooth base UUID.
+/// Full UUIDs are returned uppercased; anything else is returned unchanged.
+pub fn expand_uuid(uuid: &str) -> String {
+    let uuid = uuid.trim();
+    let is_hex = uuid.chars().all(|c| c.is_ascii_hexdigit());
+    match uuid.len() {
+        4 if is_hex => format!("0000{}{BASE_UUID_SUFFIX}", uuid.to_ascii_uppercase()),
+        8 if is_hex => format!("{}{BASE_UUID_SUFFIX}", uuid.to_ascii_uppercase()),
+        _ => uuid.to_ascii_uppercase(),
+    }
+}
+
+/// Returns the 16-bit form of a UUID on the Bluetooth base UUID.
+pub fn short_uuid(uuid: &str) -> Option<u16> {
+    let expanded = expand_uuid(uuid);
+    let prefix = expanded.strip_suffix(BASE_UUID_SUFFIX)?;
+    prefix
+        .strip_prefix("0000")
+        .and_then(|short| u16::from_str_radix(short, 16).ok())
+}
+
+/// Compares two UUIDs regardless of case or short/long form.
+pub fn uuids_equal(a: &str, b: &str) -> bool {
+    expand_uuid(a) == expand_uuid(b)
+}
+
+/// Human-readable name of a SIG-assigned service.
+pub fn service_name(uuid: &str) -> Option<&'static str> {
+    Some(match short_uuid(uuid)? {
+        services::GENERIC_ACCESS => "Generic Access",
+        services::GENERIC_ATTRIBUTE => "Generic Attribute",
+        services::HEALTH_THERMOMETER => "Health Thermometer",
+        services::DEVICE_INFORMATION => "Device Information",
+        services::HEART_RATE => "Heart Rate",
+        services::BATTERY => "Battery",
+        services::CYCLING_SPEED_AND_CADENCE => "Cycling Speed and Cadence",
+        _ => return None,
+    })
+}
+
+/// Human-readable name of a SIG-assigned characteristic.
+pub fn characteristic_name(uuid: &str) -> Option<&'static str> {
+    use characteristics::*;
+
+    Some(match short_uuid(uuid)? {
+        DEVICE_NAME => "Device Name",
+        APPEARANCE => "Appearance",
+        BATTERY_LEVEL => "Battery Level",
+        TEMPERATURE_MEASUREMENT => "Temperature Measurement",
+        SYSTEM_ID => "System ID",
+        MODEL_NUMBER => "Model Number String",
+        SERIAL_NUMBER => "Serial Number String",
+        FIRMWARE_REVISION => "Firmware Revision String",
+        HARDWARE_REVISION => "Hardware Revision String",
+        SOFTWARE_REVISION => "Software Revision String",
+        MANUFACTURER_NAME => "Manufacturer Name String",
+        HEART_RATE_MEASUREMENT => "Heart Rate Measurement",
+        BODY_SENSOR_LOCATION => "Body Sensor Location",
+        HEART_RATE_CONTROL_POINT => "Heart Rate Control Point",
+        PNP_ID => "PnP ID",
+        CSC_MEASUREMENT => "CSC Measurement",
+        CSC_FEATURE => "CSC Feature",
+        _ => return None,
+    })
+}
+
+/// A characteristic value with a standard binary encoding.
+pub trait GattValue: Sized {
+    fn decode(bytes: &[u8]) -> Result<Self>;
+    fn encode(&self) -> Vec<u8>;
+}
+
+/// Little-endian cursor over a characteristic value.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    what: &'static str,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], what: &'static str) -> Self {
+        Self { bytes, what }
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        if self.bytes.len() < N {
+            return Err(Error::MalformedValue(format!("{} is truncated", self.what)));
+        }
+        let (head, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        Ok(head.try_into().expect("split_at returns N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        self.take::<1>().map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+/// Heart Rate Measurement (0x2A37).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HeartRateMeasurement {
+    /// Beats per minute.
+    pub heart_rate: u16,
+    /// Whether skin contact is detected, if the sensor supports contact detection.
+    pub sensor_contact: Option<bool>,
+    /// Accumulated energy expended in kilojoules.
+    pub energy_expended: Option<u16>,
+    /// RR intervals in units of 1/1024 second, oldest first.
+    pub rr_intervals: Vec<u16>,
+}
+
+impl HeartRateMeasurement {
+    /// RR intervals converted to seconds.
+    pub fn rr_intervals_seconds(&self) -> Vec<f64> {
+        self.rr_intervals
+            .iter()
+            .map(|&interval| f64::from(interval) / 1024.0)
+            .collect()
+    }
+}
+
+impl GattValue for HeartRateMeasurement {
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes, "Heart Rate Measurement");
+        let flags = reader.u8()?;
+        let heart_rate = if flags & 0x01 != 0 {
+            reader.u16()?
+        } else {
+            u16::from(reader.u8()?)
+        };
+        let sensor_contact = (flags & 0x04 != 0).then_some(flags & 0x02 != 0);
+        let energy_expended = if flags & 0x08 != 0 {
+            Some(reader.u16()?)
+        } else {
+            None
+        };
+        let mut rr_intervals = vec![];
+        if flags & 0x10 != 0 {
+            while !reader.is_empty() {
+                rr_intervals.push(reader.u16()?);
+            }
+        }
+
+        Ok(Self {
+            heart_rate,
+            sensor_contact,
+            energy_expended,
+            rr_intervals,
+        })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut flags = 0u8;
+        let mut body = vec![];
+        match u8::try_from(self.heart_rate) {
+            Ok(heart_rate) => body.push(heart_rate),
+            Err(_) => {
+                flags |= 0x01;
+                body.extend(self.heart_rate.to_le_bytes());
+            }
+        }
+        match self.sensor_contact {
+            Some(true) => flags |= 0x06,
+            Some(false) => flags |= 0x04,
+            None => {}
+        }
+        if let Some(energy) = self.energy_expended {
+            flags |= 0x08;
+            body.extend(energy.to_le_bytes());
+        }
+        if !self.rr_intervals.is_empty() {
+            flags |= 0x10;
+            for interval in &self.rr_intervals {
+                body.extend(interval.to_le_bytes());
+            }
+        }
+
+        let mut bytes = vec![flags];
+        bytes.extend(body);
+        bytes
+    }
+}
+
+/// Body Sensor Location (0x2A38).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum BodySensorLocation {
+    Other,
+    Chest,
+    Wrist,
+    Finger,
+    Hand,
+    EarLobe,
+    Foot,
+}
+
+impl GattValue for BodySensorLocation {
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        Ok(match Reader::new(bytes, "Body Sensor Location").u8()? {
+            1 => Self::Chest,
+            2 => Self::Wrist,
+            3 => Self::Finger,
+            4 => Self::Hand,
+            5 => Self::EarLobe,
+            6 => Self::Foot,
+            _ => Self::Other,
+        })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        vec![*self as u8]
+    }
+}
+
+/// Battery Level (0x2A19) as a percentage.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct BatteryLevel(pub u8);
+
+impl GattValue for BatteryLevel {
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let level = Reader::new(bytes, "Battery Level").u8()?;
+        if level > 100 {
+            return Err(Error::MalformedValue(format!("Battery Level {level} is above 100%")));
+        }
+        Ok(Self(level))
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        vec![self.0]
+    }
+}
+
+/// Cumulative wheel data of a CSC Measurement.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WheelRevolutions {
+    pub cumulative_revolutions: u32,
+    /// Time of the last wheel event in units of 1/1024 second, rolling over.
+    pub last_event_time: u16,
+}
+
+/// Cumulative crank data of a CSC Measurement.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CrankRevolutions {
+    pub cumulative_revolutions: u16,
+    /// Time of the last crank event in units of 1/1024 second, rolling over.
+    pub last_event_time: u16,
+}
+
+/// CSC Measurement (0x2A5B) from a Cycling Speed and Cadence sensor.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CscMeasurement {
+    pub wheel: Option<WheelRevolutions>,
+    pub crank: Option<CrankRevolutions>,
+}
+
+/// Revolutions per minute between two cumulative readings, accounting for the
+/// counters and event times rolling over.
+fn revolutions_per_minute(revolutions: u32, previous_time: u16, time: u16) -> Option<f64> {
+    let elapsed = time.wrapping_sub(previous_time);
+    (elapsed != 0).then(|| f64::from(revolutions) * 60.0 * 1024.0 / f64::from(elapsed))
+}
+
+impl CscMeasurement {
+    /// Wheel speed in revolutions per minute since `previous`, if both readings carry
+    /// wheel data and a wheel event happened in between.
+    pub fn wheel_rpm(&self, previous: &CscMeasurement) -> Option<f64> {
+        let (current, previous) = (self.wheel?, previous.wheel?);
+        revolutions_per_minute(
+            current.cumulative_revolutions.wrapping_sub(previous.cumulative_revolutions),
+            previous.last_event_time,
+            current.last_event_time,
+        )
+    }
+
+    /// Cadence in crank revolutions per minute since `previous`.
+    pub fn cadence_rpm(&self, previous: &CscMeasurement) -> Option<f64> {
+        let (current, previous) = (self.crank?, previous.crank?);
+        revolutions_per_minute(
+            u32::from(current.cumulative_revolutions.wrapping_sub(previous.cumulative_revolutions)),
+            previous.last_event_time,
+            current.last_event_time,
+        )
+    }
+
+    /// Speed in meters per second since `previous` for a wheel of the given circumference.
+    pub fn speed(&self, previous: &CscMeasurement, wheel_circumference_m: f64) -> Option<f64> {
+        self.wheel_rpm(previous)
+            .map(|rpm| rpm * wheel_circumference_m / 60.0)
+    }
+}
+
+impl GattValue for CscMeasurement {
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes, "CSC Measurement");
+        let flags = reader.u8()?;
+        let wheel = if flags & 0x01 != 0 {
+            Some(WheelRevolutions {
+                cumulative_revolutions: reader.u32()?,
+                last_event_time: reader.u16()?,
+            })
+        } else {
+            None
+        };
+        let crank = if flags & 0x02 != 0 {
+            Some(CrankRevolutions {
+                cumulative_revolutions: reader.u16()?,
+                last_event_time: reader.u16()?,
+            })
+        } else {
+            None
+        };
+        Ok(Self { wheel, crank })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![0];
+        if let Some(wheel) = self.wheel {
+            bytes[0] |= 0x01;
+            bytes.extend(wheel.cumulative_revolutions.to_le_bytes());
+            bytes.extend(wheel.last_event_time.to_le_bytes());
+        }
+        if let Some(crank) = self.crank {
+            bytes[0] |= 0x02;
+            bytes.extend(crank.cumulative_revolutions.to_le_bytes());
+            bytes.extend(crank.last_event_time.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+/// CSC Feature (0x2A5C).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CscFeature {
+    pub wheel_revolution_data: bool,
+    pub crank_revolution_data: bool,
+    pub multiple_sensor_locations: bool,
+}
+
+impl GattValue for CscFeature {
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let flags = Reader::new(bytes, "CSC Feature").u16()?;
+        Ok(Self {
+            wheel_revolution_data: flags & 0x01 != 0,
+            crank_revolution_data: flags & 0x02 != 0,
+            multiple_sensor_locations: flags & 0x04 != 0,
+        })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let flags = u16::from(self.wheel_revolution_data)
+            | u16::from(self.crank_revolution_data) << 1
+            | u16::from(self.multiple_sensor_locations) << 2;
+        flags.to_le_bytes().to_vec()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+}
+
+/// Temperature Measurement (0x2A1C) from a Health Thermometer.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TemperatureMeasurement {
+    /// Temperature in `unit`; NaN when the sensor reports no valid reading.
+    pub value: f64,
+    pub unit: TemperatureUnit,
+    pub timestamp: Option<NaiveDateTime>,
+    /// Raw temperature type (body location), see the Temperature Type characteristic.
+    pub temperature_type: Option<u8>,
+}
+
+impl GattValue for TemperatureMeasurement {
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes, "Temperature Measurement");
+        let flags = reader.u8()?;
+        let value = decode_float(reader.u32()?);
+        let unit = if flags & 0x01 != 0 {
+            TemperatureUnit::Fahrenheit
+        } else {
+            TemperatureUnit::Celsius
+        };
+        let timestamp = if flags & 0x02 != 0 {
+            decode_date_time(&mut reader)?
+        } else {
+            None
+        };
+        let temperature_type = if flags & 0x04 != 0 {
+            Some(reader.u8()?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            value,
+            unit,
+            timestamp,
+            temperature_type,
+        })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![0];
+        if self.unit == TemperatureUnit::Fahrenheit {
+            bytes[0] |= 0x01;
+        }
+        bytes.extend(encode_float(self.value).to_le_bytes());
+        if let Some(timestamp) = self.timestamp {
+            bytes[0] |= 0x02;
+            bytes.extend(encode_date_time(&timestamp));
+        }
+        if let Some(temperature_type) = self.temperature_type {
+            bytes[0] |= 0x04;
+            bytes.push(temperature_type);
+        }
+        bytes
+    }
+}
+
+/// PnP ID (0x2A50) of the Device Information service.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PnpId {
+    /// 1 for a Bluetooth SIG company identifier, 2 for a USB vendor ID.
+    pub vendor_id_source: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub product_version: u16,
+}
+
+impl GattValue for PnpId {
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes, "PnP ID");
+        Ok(Self {
+            vendor_id_source: reader.u8()?,
+            vendor_id: reader.u16()?,
+            product_id: reader.u16()?,
+            product_version: reader.u16()?,
+        })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![self.vendor_id_source];
+        bytes.extend(self.vendor_id.to_le_bytes());
+        bytes.extend(self.product_id.to_le_bytes());
+        bytes.extend(self.product_version.to_le_bytes());
+        bytes
+    }
+}
+
+/// System ID (0x2A23) of the Device Information service.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemId {
+    /// 40-bit manufacturer-defined identifier.
+    pub manufacturer_identifier: u64,
+    /// 24-bit IEEE organizationally unique identifier.
+    pub organizationally_unique_identifier: u32,
+}
+
+impl GattValue for SystemId {
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let raw = u64::from_le_bytes(Reader::new(bytes, "System ID").take()?);
+        Ok(Self {
+            manufacturer_identifier: raw & 0xFF_FFFF_FFFF,
+            organizationally_unique_identifier: (raw >> 40) as u32,
+        })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let raw = (self.manufacturer_identifier & 0xFF_FFFF_FFFF)
+            | (u64::from(self.organizationally_unique_identifier & 0xFF_FFFF) << 40);
+        raw.to_le_bytes().to_vec()
+    }
+}
+
+/// UTF-8 strings such as Device Name or the Device Information revision strings.
+/// Trailing NUL padding is dropped.
+impl GattValue for String {
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let end = bytes.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+        String::from_utf8(bytes[..end].to_vec())
+            .map_err(|_| Error::MalformedValue("string is not valid UTF-8".to_string()))
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+/// Decodes an IEEE 11073-20601 32-bit FLOAT.
+pub fn decode_float(raw: u32) -> f64 {
+    match raw {
+        0x007F_FFFE => f64::INFINITY,
+        0x0080_0002 => f64::NEG_INFINITY,
+        // NaN, NRes and the reserved value.
+        0x007F_FFFF..=0x0080_0001 => f64::NAN,
+        _ => {
+            let exponent = (raw >> 24) as i8;
+            // Sign-extend the 24-bit mantissa.
+            let mantissa = ((raw << 8) as i32) >> 8;
+            f64::from(mantissa) * 10f64.powi(i32::from(exponent))
+        }
+    }
+}
+
+/// Encodes an IEEE 11073-20601 32-bit FLOAT, keeping up to four decimals.
+pub fn encode_float(value: f64) -> u32 {
+    const MANTISSA_MAX: f64 = 8_388_605.0;
+
+    if value.is_nan() {
+        return 0x007F_FFFF;
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { 0x007F_FFFE } else { 0x0080_0002 };
+    }
+
+    for exponent in -4i32..=127 {
+        let mantissa = (value / 10f64.powi(exponent)).round();
+        if mantissa.abs() <= MANTISSA_MAX {
+            return ((exponent as u32) << 24) | ((mantissa as i32 as u32) & 0x00FF_FFFF);
+        }
+    }
+    if value > 0.0 {
+        0x007F_FFFE
+    } else {
+        0x0080_0002
+    }
+}
+
+/// Decodes an IEEE 11073-20601 16-bit SFLOAT.
+pub fn decode_sfloat(raw: u16) -> f64 {
+    match raw {
+        0x07FE => f64::INFINITY,
+        0x0802 => f64::NEG_INFINITY,
+        // NaN, NRes and the reserved value.
+        0x07FF..=0x0801 => f64::NAN,
+        _ => {
+            let exponent = (raw as i16) >> 12;
+            let mantissa = ((raw << 4) as i16) >> 4;
+            f64::from(mantissa) * 10f64.powi(i32::from(exponent))
+        }
+    }
+}
+
+/// Decodes a 7-byte Date Time, where zero fields mean "not known".
+fn decode_date_time(reader: &mut Reader<'_>) -> Result<Option<NaiveDateTime>> {
+    let year = reader.u16()?;
+    let [month, day, hour, minute, second] = reader.take::<5>()?;
+    if year == 0 || month == 0 || day == 0 {
+        return Ok(None);
+    }
+    Ok(
+        NaiveDate::from_ymd_opt(i32::from(year), u32::from(month), u32::from(day))
+            .and_then(|date| date.and_hms_opt(u32::from(hour), u32::from(minute), u32::from(second))),
+    )
+}
+
+fn encode_date_time(timestamp: &NaiveDateTime) -> Vec<u8> {
+    use chrono::{Datelike, Timelike};
+
+    let mut bytes = (timestamp.year() as u16).to_le_bytes().to_vec();
+    bytes.extend([
+        timestamp.month() as u8,
+        timestamp.day() as u8,
+        timestamp.hour() as u8,
+        timestamp.minute() as u8,
+        timestamp.second() as u8,
+    ]);
+    bytes
+}
+
+/// A characteristic value decoded according to its SIG-assigned UUID.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum GattReading {
+    HeartRateMeasurement(HeartRateMeasurement),
+    BodySensorLocation(BodySensorLocation),
+    BatteryLevel(u8),
+    CscMeasurement(CscMeasurement),
+    CscFeature(CscFeature),
+    TemperatureMeasurement(TemperatureMeasurement),
+    PnpId(PnpId),
+    SystemId(SystemId),
+    Text(String),
+}
+
+/// Decodes `bytes` read from the characteristic `characteristic_uuid`.
+pub fn decode(characteristic_uuid: &str, bytes: &[u8]) -> Result<GattReading> {
+    use characteristics::*;
+
+    let unsupported = || Error::InvalidInput(format!("No decoder for characteristic {characteristic_uuid}"));
+    Ok(match short_uuid(characteristic_uuid).ok_or_else(unsupported)? {
+        HEART_RATE_MEASUREMENT => GattReading::HeartRateMeasurement(GattValue::decode(bytes)?),
+        BODY_SENSOR_LOCATION => GattReading::BodySensorLocation(GattValue::decode(bytes)?),
+        BATTERY_LEVEL => GattReading::BatteryLevel(BatteryLevel::decode(bytes)?.0),
+        CSC_MEASUREMENT => GattReading::CscMeasurement(GattValue::decode(bytes)?),
+        CSC_FEATURE => GattReading::CscFeature(GattValue::decode(bytes)?),
+        TEMPERATURE_MEASUREMENT => GattReading::TemperatureMeasurement(GattValue::decode(bytes)?),
+        PNP_ID => GattReading::PnpId(GattValue::decode(bytes)?),
+        SYSTEM_ID => GattReading::SystemId(GattValue::decode(bytes)?),
+        DEVICE_NAME | MODEL_NUMBER | SERIAL_NUMBER | FIRMWARE_REVISION | HARDWARE_REVISION
+        | SOFTWARE_REVISION | MANUFACTURER_NAME => GattReading::Text(GattValue::decode(bytes)?),
+        _ => return Err(unsupported()),
+    })
+}
+
+/// Contents of the Device Information service. Characteristics the peripheral does
+/// not expose are left empty.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceInformation {
+    pub manufacturer_name: Option<String>,
+    pub model_number: Option<String>,
+    pub serial_number: Option<String>,
+    pub hardware_revision: Option<String>,
+    pub firmware_revision: Option<String>,
+    pub software_revision: Option<String>,
+    pub system_id: Option<SystemId>,
+    pub pnp_id: Option<PnpId>,
+}
+
+impl Service {
+    /// Whether this is the SIG-assigned service with the given 16-bit UUID.
+    pub fn is(&self, uuid: u16) -> bool {
+        short_uuid(&self.uuid) == Some(uuid)
+    }
+
+    pub fn name(&self) -> Option<&'static str> {
+        service_name(&self.uuid)
+    }
+}
+
+impl Characteristic {
+    /// Whether this is the SIG-assigned characteristic with the given 16-bit UUID.
+    pub fn is(&self, uuid: u16) -> bool {
+        short_uuid(&self.uuid) == Some(uuid)
+    }
+
+    pub fn name(&self) -> Option<&'static str> {
+        characteristic_name(&self.uuid)
+    }
+
+    /// Decodes the cached value according to the characteristic's UUID.
+    pub fn decode(&self) -> Result<GattReading> {
+        let value = self
+            .value
+            .as_deref()
+            .ok_or_else(|| Error::MalformedValue(format!("characteristic {} has no value", self.uuid)))?;
+        decode(&self.uuid, value)
+    }
+}
+
+fn short_form(uuid: u16) -> String {
+    format!("{uuid:04X}")
+}
+
+/// Whether a read failed because the peripheral doesn't expose the characteristic
+/// or doesn't allow reading it, as opposed to the link or the read itself failing.
+fn is_unreadable(error: &Error) -> bool {
+    match error {
+        Error::InvalidInput(message) | Error::OperationFailed(message) => {
+            message == CHARACTERISTIC_NOT_FOUND || message == READ_NOT_PERMITTED
+        }
+        #[cfg(mobile)]
+        Error::PluginInvoke(tauri::plugin::mobile::PluginInvokeError::InvokeRejected(response)) => response
+            .code
+            .as_deref()
+            .is_some_and(|code| NATIVE_UNREADABLE_CODES.contains(&code)),
+        _ => false,
+    }
+}
+
+impl<R: Runtime> crate::Bluetooth<R> {
+    /// Reads a characteristic and decodes it as `T`.
+    pub fn read_value<T: GattValue>(&self, peripheral_uuid: String, characteristic_uuid: String) -> Result<T> {
+        T::decode(&self.read_characteristic(peripheral_uuid, characteristic_uuid)?)
+    }
+
+    /// Encodes `value` and writes it to a characteristic.
+    pub fn write_value<T: GattValue>(
+        &self,
+        peripheral_uuid: String,
+        characteristic_uuid: String,
+        value: &T,
+        options: WriteOptions,
+    ) -> Result<()> {
+        self.write_characteristic(peripheral_uuid, characteristic_uuid, value.encode(), options)
+    }
+
+    /// Reads a characteristic and decodes it according to its SIG-assigned UUID.
+    pub fn read_decoded(&self, peripheral_uuid: String, characteristic_uuid: String) -> Result<GattReading> {
+        let bytes = self.read_characteristic(peripheral_uuid, characteristic_uuid.clone())?;
+        decode(&characteristic_uuid, &bytes)
+    }
+
+    pub fn read_heart_rate_measurement(&self, peripheral_uuid: String) -> Result<HeartRateMeasurement> {
+        self.read_value(peripheral_uuid, short_form(characteristics::HEART_RATE_MEASUREMENT))
+    }
+
+    pub fn read_body_sensor_location(&self, peripheral_uuid: String) -> Result<BodySensorLocation> {
+        self.read_value(peripheral_uuid, short_form(characteristics::BODY_SENSOR_LOCATION))
+    }
+
+    /// Battery level as a percentage.
+    pub fn read_battery_level(&self, peripheral_uuid: String) -> Result<u8> {
+        self.read_value::<BatteryLevel>(peripheral_uuid, short_form(characteristics::BATTERY_LEVEL))
+            .map(|level| level.0)
+    }
+
+    pub fn read_csc_measurement(&self, peripheral_uuid: String) -> Result<CscMeasurement> {
+        self.read_value(peripheral_uuid, short_form(characteristics::CSC_MEASUREMENT))
+    }
+
+    /// Reads every Device Information characteristic the peripheral exposes.
+    /// Characteristics the peripheral doesn't have or doesn't allow reading are
+    /// skipped; other failures, such as a disconnect, are returned.
+    pub fn read_device_information(&self, peripheral_uuid: String) -> Result<DeviceInformation> {
+        fn optional<T>(result: Result<T>) -> Result<Option<T>> {
+            match result {
+                Ok(value) => Ok(Some(value)),
+                Err(e) if is_unreadable(&e) => Ok(None),
+                Err(e) => Err(e),
+            }
+        }
+
+        let text = |uuid: u16| optional(self.read_value::<String>(peripheral_uuid.clone(), short_form(uuid)));
+        Ok(DeviceInformation {
+            manufacturer_name: text(characteristics::MANUFACTURER_NAME)?,
+            model_number: text(characteristics::MODEL_NUMBER)?,
+            serial_number: text(characteristics::SERIAL_NUMBER)?,
+            hardware_revision: text(characteristics::HARDWARE_REVISION)?,
+            firmware_revision: text(characteristics::FIRMWARE_REVISION)?,
+            software_revision: text(characteristics::SOFTWARE_REVISION)?,
+            system_id: optional(self.read_value(peripheral_uuid.clone(), short_form(characteristics::SYSTEM_ID)))?,
+            pnp_id: optional(self.read_value(peripheral_uuid.clone(), short_form(characteristics::PNP_ID)))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn uuids_are_compared_in_their_long_form() {
+        assert_eq!(expand_uuid("2a37"), "00002A37-0000-1000-8000-00805F9B34FB");
+        assert_eq!(short_uuid("00002a37-0000-1000-8000-00805f9b34fb"), Some(0x2A37));
+        assert_eq!(short_uuid("6E400001-B5A3-F393-E0A9-E50E24DCCA9E"), None);
+        assert!(uuids_equal("180d", "0000180D-0000-1000-8000-00805F9B34FB"));
+        assert_eq!(characteristic_name("2A19"), Some("Battery Level"));
+    }
+
+    #[test]
+    fn heart_rate_measurement_with_contact_and_rr_intervals() {
+        let bytes = [0x16, 60, 0x00, 0x04, 0x00, 0x02];
+        let measurement = HeartRateMeasurement::decode(&bytes).unwrap();
+        assert_eq!(
+            measurement,
+            HeartRateMeasurement {
+                heart_rate: 60,
+                sensor_contact: Some(true),
+                energy_expended: None,
+                rr_intervals: vec![1024, 512],
+            }
+        );
+        assert_eq!(measurement.rr_intervals_seconds(), vec![1.0, 0.5]);
+        assert_eq!(measurement.encode(), bytes);
+    }
+
+    #[test]
+    fn heart_rate_measurement_with_16_bit_rate_and_energy() {
+        let bytes = [0x09, 0x2C, 0x01, 0x10, 0x00];
+        let measurement = HeartRateMeasurement::decode(&bytes).unwrap();
+        assert_eq!(measurement.heart_rate, 300);
+        assert_eq!(measurement.sensor_contact, None);
+        assert_eq!(measurement.energy_expended, Some(16));
+        assert_eq!(measurement.encode(), bytes);
+
+        assert!(matches!(
+            HeartRateMeasurement::decode(&[0x01, 0x2C]),
+            Err(Error::MalformedValue(_))
+        ));
+    }
+
+    #[test]
+    fn battery_level_is_a_percentage() {
+        assert_eq!(BatteryLevel::decode(&[100]).unwrap(), BatteryLevel(100));
+        assert!(BatteryLevel::decode(&[101]).is_err());
+        assert!(BatteryLevel::decode(&[]).is_err());
+    }
+
+    #[test]
+    fn csc_measurement_rates_survive_rollover() {
+        let bytes = [0x03, 100, 0, 0, 0, 0x00, 0x04, 50, 0, 0x00, 0x08];
+        let current = CscMeasurement::decode(&bytes).unwrap();
+        assert_eq!(current.encode(), bytes);
+
+        let previous = CscMeasurement {
+            wheel: Some(WheelRevolutions {
+                cumulative_revolutions: 98,
+                last_event_time: 0,
+            }),
+            crank: Some(CrankRevolutions {
+                cumulative_revolutions: 49,
+                last_event_time: 1024,
+            }),
+        };
+        assert_close(current.wheel_rpm(&previous).unwrap(), 120.0);
+        assert_close(current.cadence_rpm(&previous).unwrap(), 60.0);
+        assert_close(current.speed(&previous, 2.1).unwrap(), 4.2);
+
+        // The event time rolls over at 64 seconds.
+        let before_rollover = CscMeasurement {
+            wheel: Some(WheelRevolutions {
+                cumulative_revolutions: 98,
+                last_event_time: 0xFC00,
+            }),
+            crank: None,
+        };
+        let after_rollover = CscMeasurement {
+            wheel: Some(WheelRevolutions {
+                cumulative_revolutions: 100,
+                last_event_time: 0x0400,
+            }),
+            crank: None,
+        };
+        assert_close(after_rollover.wheel_rpm(&before_rollover).unwrap(), 60.0);
+        assert_eq!(after_rollover.cadence_rpm(&before_rollover), None);
+        assert_eq!(current.wheel_rpm(&current), None);
+    }
+
+    #[test]
+    fn csc_feature_flags() {
+        let feature = CscFeature::decode(&[0x03, 0x00]).unwrap();
+        assert!(feature.wheel_revolution_data && feature.crank_revolution_data);
+        assert!(!feature.multiple_sensor_locations);
+        assert_eq!(feature.encode(), [0x03, 0x00]);
+    }
+
+    #[test]
+    fn ieee_11073_floats() {
+        assert_close(decode_float(0xFF00_016C), 36.4);
+        assert_close(decode_float(0x00FF_FFFF), -1.0);
+        assert!(decode_float(0x007F_FFFF).is_nan());
+        assert_eq!(decode_float(0x007F_FFFE), f64::INFINITY);
+        assert_eq!(decode_float(0x0080_0002), f64::NEG_INFINITY);
+        assert_close(decode_float(encode_float(36.4)), 36.4);
+        assert_close(decode_float(encode_float(-98.6)), -98.6);
+        assert_eq!(encode_float(f64::NAN), 0x007F_FFFF);
+
+        assert_close(decode_sfloat(0xF072), 11.4);
+        assert_close(decode_sfloat(0x0FFF), -1.0);
+        assert!(decode_sfloat(0x07FF).is_nan());
+        assert_eq!(decode_sfloat(0x07FE), f64::INFINITY);
+    }
+
+    #[test]
+    fn temperature_measurement_with_timestamp() {
+        let bytes = [0x02, 0x6C, 0x01, 0x00, 0xFF, 0xE8, 0x07, 3, 15, 10, 20, 30];
+        let measurement = TemperatureMeasurement::decode(&bytes).unwrap();
+        assert_close(measurement.value, 36.4);
+        assert_eq!(measurement.unit, TemperatureUnit::Celsius);
+        assert_eq!(
+            measurement.timestamp,
+            NaiveDate::from_ymd_opt(2024, 3, 15).and_then(|date| date.and_hms_opt(10, 20, 30))
+        );
+        assert_eq!(measurement.temperature_type, None);
+        assert_eq!(TemperatureMeasurement::decode(&measurement.encode()).unwrap(), measurement);
+
+        // A date of zeroes means the time isn't known.
+        let unknown = [0x03, 0x6C, 0x01, 0x00, 0xFF, 0, 0, 0, 0, 0, 0, 0];
+        let measurement = TemperatureMeasurement::decode(&unknown).unwrap();
+        assert_eq!(measurement.unit, TemperatureUnit::Fahrenheit);
+        assert_eq!(measurement.timestamp, None);
+    }
+
+    #[test]
+    fn device_information_values() {
+        let pnp_id = [0x01, 0x4C, 0x00, 0x34, 0x12, 0x01, 0x00];
+        assert_eq!(
+            PnpId::decode(&pnp_id).unwrap(),
+            PnpId {
+                vendor_id_source: 1,
+                vendor_id: 0x004C,
+                product_id: 0x1234,
+                product_version: 1,
+            }
+        );
+        assert_eq!(PnpId::decode(&pnp_id).unwrap().encode(), pnp_id);
+
+        let system_id = [1, 2, 3, 4, 5, 6, 7, 8];
+        let decoded = SystemId::decode(&system_id).unwrap();
+        assert_eq!(decoded.manufacturer_identifier, 0x05_0403_0201);
+        assert_eq!(decoded.organizationally_unique_identifier, 0x08_0706);
+        assert_eq!(decoded.encode(), system_id);
+
+        assert_eq!(String::decode(b"Acme\0\0").unwrap(), "Acme");
+        assert!(String::decode(&[0xFF]).is_err());
+        assert_eq!(BodySensorLocation::decode(&[1]).unwrap(), BodySensorLocation::Chest);
+        assert_eq!(BodySensorLocation::Chest.encode(), [1]);
+    }
+
+    #[test]
+    fn values_are_decoded_by_characteristic() {
+        assert_eq!(decode("2A19", &[50]).unwrap(), GattReading::BatteryLevel(50));
+        assert_eq!(decode("2a29", b"Acme").unwrap(), GattReading::Text("Acme".to_string()));
+        assert!(matches!(decode("2A01", &[0, 0]), Err(Error::InvalidInput(_))));
+        assert!(matches!(
+            decode("6E400001-B5A3-F393-E0A9-E50E24DCCA9E", &[0]),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn only_missing_or_unreadable_characteristics_are_skipped() {
+        assert!(is_unreadable(&Error::InvalidInput(CHARACTERISTIC_NOT_FOUND.to_string())));
+        assert!(is_unreadable(&Error::OperationFailed(READ_NOT_PERMITTED.to_string())));
+        assert!(!is_unreadable(&Error::OperationFailed("Peripheral disconnected".to_string())));
+        assert!(!is_unreadable(&Error::InvalidInput("Peripheral not found".to_string())));
+        assert!(!is_unreadable(&Error::NotAvailable));
+    }
+}
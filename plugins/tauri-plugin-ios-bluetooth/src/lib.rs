@@ -15,11 +15,23 @@ mod desktop;
 #[cfg(mobile)]
 mod mobile;
 
+pub mod advertisement;
 mod commands;
+mod connection;
 mod error;
+mod events;
+pub mod gatt;
 mod models;
+#[cfg(desktop)]
+mod simulator;
+pub mod server;
+pub mod transfer;
 
+pub use connection::{ConnectionRecord, ReconnectPolicy};
 pub use error::{Error, Result};
+pub use events::Subscription;
+#[cfg(desktop)]
+pub use simulator::{Simulator, VirtualPeripheral};
 
 #[cfg(desktop)]
 use desktop::Bluetooth;
@@ -66,6 +78,19 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             commands::update_characteristic_value,
             commands::get_maximum_write_length,
             commands::set_notify_value,
+            commands::watch_central_scan,
+            commands::watch_characteristic,
+            commands::listen,
+            commands::unlisten,
+            commands::read_decoded_characteristic,
+            commands::decode_characteristic_value,
+            commands::read_device_information,
+            commands::write_chunked,
+            commands::cancel_transfer,
+            commands::get_connection_state,
+            commands::get_connection_history,
+            commands::set_reconnect_policy,
+            commands::parse_advertisement,
         ])
         .setup(|app, api| {
             #[cfg(mobile)]
@@ -74,6 +99,8 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             let bluetooth = desktop::init(app, api)?;
             
             app.manage(bluetooth);
+            app.manage(events::JsSubscriptions::default());
+            app.manage(transfer::JsTransfers::default());
             Ok(())
         })
         .build()
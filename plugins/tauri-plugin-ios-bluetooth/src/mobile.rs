@@ -1,10 +1,13 @@
+use std::sync::{Arc, Mutex};
+
 use serde::de::DeserializeOwned;
 use tauri::{
+    ipc::{Channel, InvokeResponseBody},
     plugin::{PluginApi, PluginHandle},
     AppHandle, Runtime,
 };
 
-use crate::{models::*, Result};
+use crate::{connection::ConnectionManager, events::EventBus, models::*, Result, Subscription};
 
 #[cfg(target_os = "ios")]
 tauri::ios_plugin_binding!(init_plugin_ios_bluetooth);
@@ -18,39 +21,69 @@ pub fn init<R: Runtime, C: DeserializeOwned>(
     #[cfg(target_os = "android")]
     let handle = api.register_android_plugin("com.tauri.plugins.bluetooth", "BluetoothPlugin")?;
     
-    Ok(Bluetooth(handle))
+    // All native events arrive through a single channel and are fanned out to
+    // Rust and JS subscribers by the event bus, which also applies the scan
+    // filters of each subscriber.
+    let events = Arc::new(EventBus::default());
+    let bus = events.clone();
+    let handler = Channel::new(move |body: InvokeResponseBody| {
+        bus.dispatch(&body.deserialize::<BluetoothEvent>()?);
+        Ok(())
+    });
+    
+    #[derive(serde::Serialize)]
+    struct Args {
+        handler: Channel,
+    }
+    
+    handle.run_mobile_plugin::<()>("setEventHandler", Args { handler })?;
+    
+    Ok(Bluetooth {
+        app: app.clone(),
+        handle,
+        connections: ConnectionManager::new(app, &events),
+        direct_scan: Mutex::default(),
+        events,
+    })
 }
 
 /// Access to the Bluetooth APIs on mobile.
-pub struct Bluetooth<R: Runtime>(PluginHandle<R>);
+pub struct Bluetooth<R: Runtime> {
+    pub(crate) app: AppHandle<R>,
+    handle: PluginHandle<R>,
+    pub(crate) events: Arc<EventBus>,
+    pub(crate) connections: ConnectionManager,
+    // The scan started with `start_central_scan`.
+    pub(crate) direct_scan: Mutex<Option<Subscription>>,
+}
 
 impl<R: Runtime> Bluetooth<R> {
     pub fn request_authorization(&self) -> Result<AuthorizationStatus> {
-        self.0
+        self.handle
             .run_mobile_plugin("requestAuthorization", ())
             .map_err(Into::into)
     }
     
     pub fn get_authorization_status(&self) -> Result<AuthorizationStatus> {
-        self.0
+        self.handle
             .run_mobile_plugin("getAuthorizationStatus", ())
             .map_err(Into::into)
     }
     
     pub fn is_bluetooth_enabled(&self) -> Result<bool> {
-        self.0
+        self.handle
             .run_mobile_plugin("isBluetoothEnabled", ())
             .map_err(Into::into)
     }
     
-    pub fn start_central_scan(&self, options: ScanOptions) -> Result<()> {
-        self.0
+    pub(crate) fn start_native_scan(&self, options: ScanOptions) -> Result<()> {
+        self.handle
             .run_mobile_plugin("startCentralScan", options)
             .map_err(Into::into)
     }
     
-    pub fn stop_central_scan(&self) -> Result<()> {
-        self.0
+    pub(crate) fn stop_native_scan(&self) -> Result<()> {
+        self.handle
             .run_mobile_plugin("stopCentralScan", ())
             .map_err(Into::into)
     }
@@ -62,7 +95,7 @@ impl<R: Runtime> Bluetooth<R> {
             options: ConnectionOptions,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("connectPeripheral", Args { uuid, options })
             .map_err(Into::into)
     }
@@ -73,19 +106,19 @@ impl<R: Runtime> Bluetooth<R> {
             uuid: String,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("disconnectPeripheral", Args { uuid })
             .map_err(Into::into)
     }
     
     pub fn get_connected_peripherals(&self) -> Result<Vec<Peripheral>> {
-        self.0
+        self.handle
             .run_mobile_plugin("getConnectedPeripherals", ())
             .map_err(Into::into)
     }
     
     pub fn get_discovered_peripherals(&self) -> Result<Vec<Peripheral>> {
-        self.0
+        self.handle
             .run_mobile_plugin("getDiscoveredPeripherals", ())
             .map_err(Into::into)
     }
@@ -97,7 +130,7 @@ impl<R: Runtime> Bluetooth<R> {
             service_uuids: Option<Vec<String>>,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("discoverServices", Args { peripheral_uuid, service_uuids })
             .map_err(Into::into)
     }
@@ -110,7 +143,7 @@ impl<R: Runtime> Bluetooth<R> {
             characteristic_uuids: Option<Vec<String>>,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("discoverCharacteristics", Args { peripheral_uuid, service_uuid, characteristic_uuids })
             .map_err(Into::into)
     }
@@ -122,7 +155,7 @@ impl<R: Runtime> Bluetooth<R> {
             characteristic_uuid: String,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("readCharacteristic", Args { peripheral_uuid, characteristic_uuid })
             .map_err(Into::into)
     }
@@ -136,7 +169,7 @@ impl<R: Runtime> Bluetooth<R> {
             options: WriteOptions,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("writeCharacteristic", Args { peripheral_uuid, characteristic_uuid, value, options })
             .map_err(Into::into)
     }
@@ -148,7 +181,7 @@ impl<R: Runtime> Bluetooth<R> {
             characteristic_uuid: String,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("subscribeToCharacteristic", Args { peripheral_uuid, characteristic_uuid })
             .map_err(Into::into)
     }
@@ -160,7 +193,7 @@ impl<R: Runtime> Bluetooth<R> {
             characteristic_uuid: String,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("unsubscribeFromCharacteristic", Args { peripheral_uuid, characteristic_uuid })
             .map_err(Into::into)
     }
@@ -172,7 +205,7 @@ impl<R: Runtime> Bluetooth<R> {
             descriptor_uuid: String,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("readDescriptor", Args { peripheral_uuid, descriptor_uuid })
             .map_err(Into::into)
     }
@@ -185,7 +218,7 @@ impl<R: Runtime> Bluetooth<R> {
             value: Vec<u8>,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("writeDescriptor", Args { peripheral_uuid, descriptor_uuid, value })
             .map_err(Into::into)
     }
@@ -196,25 +229,25 @@ impl<R: Runtime> Bluetooth<R> {
             peripheral_uuid: String,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("getPeripheralRssi", Args { peripheral_uuid })
             .map_err(Into::into)
     }
     
     pub fn start_peripheral_advertising(&self, advertising_data: AdvertisingData) -> Result<()> {
-        self.0
+        self.handle
             .run_mobile_plugin("startPeripheralAdvertising", advertising_data)
             .map_err(Into::into)
     }
     
     pub fn stop_peripheral_advertising(&self) -> Result<()> {
-        self.0
+        self.handle
             .run_mobile_plugin("stopPeripheralAdvertising", ())
             .map_err(Into::into)
     }
     
     pub fn add_service(&self, service: PeripheralService) -> Result<()> {
-        self.0
+        self.handle
             .run_mobile_plugin("addService", service)
             .map_err(Into::into)
     }
@@ -225,19 +258,19 @@ impl<R: Runtime> Bluetooth<R> {
             service_uuid: String,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("removeService", Args { service_uuid })
             .map_err(Into::into)
     }
     
     pub fn remove_all_services(&self) -> Result<()> {
-        self.0
+        self.handle
             .run_mobile_plugin("removeAllServices", ())
             .map_err(Into::into)
     }
     
     pub fn respond_to_request(&self, response: RequestResponse) -> Result<()> {
-        self.0
+        self.handle
             .run_mobile_plugin("respondToRequest", response)
             .map_err(Into::into)
     }
@@ -250,7 +283,7 @@ impl<R: Runtime> Bluetooth<R> {
             central_uuids: Option<Vec<String>>,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("updateCharacteristicValue", Args { characteristic_uuid, value, central_uuids })
             .map_err(Into::into)
     }
@@ -262,7 +295,7 @@ impl<R: Runtime> Bluetooth<R> {
             write_type: WriteType,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("getMaximumWriteLength", Args { peripheral_uuid, write_type })
             .map_err(Into::into)
     }
@@ -274,7 +307,7 @@ impl<R: Runtime> Bluetooth<R> {
             enabled: bool,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("setNotifyValue", Args { characteristic_uuid, enabled })
             .map_err(Into::into)
     }
@@ -28,6 +28,34 @@ pub struct ScanOptions {
     pub service_uuids: Vec<String>,
     pub allow_duplicates: bool,
     pub scan_mode: ScanMode,
+    /// Only report peripherals whose name starts with this prefix.
+    pub name_prefix: Option<String>,
+    /// Only report peripherals whose name matches this regular expression.
+    pub name_pattern: Option<String>,
+    pub manufacturer_data: Option<ManufacturerDataFilter>,
+    /// Only report peripherals received at least this strong, in dBm.
+    pub min_rssi: Option<i32>,
+    pub service_data: Option<ServiceDataFilter>,
+}
+
+/// Matches manufacturer data from `company_id` whose leading bytes equal `data`
+/// wherever `mask` has bits set. Without a mask every byte must match.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ManufacturerDataFilter {
+    pub company_id: u16,
+    pub data: Vec<u8>,
+    pub mask: Option<Vec<u8>>,
+}
+
+/// Matches service data for `service_uuid` the same way as
+/// [`ManufacturerDataFilter`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceDataFilter {
+    pub service_uuid: String,
+    pub data: Vec<u8>,
+    pub mask: Option<Vec<u8>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -174,14 +202,21 @@ pub struct PeripheralDescriptor {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ReadRequest {
+    pub request_id: String,
     pub central_uuid: String,
     pub characteristic_uuid: String,
     pub offset: usize,
+    /// Most bytes of the value one response carries, set by the ATT MTU
+    /// negotiated with the central. Longer values take several reads.
+    pub max_length: usize,
 }
 
+/// One value of a write request. Every value of a batch carries the batch's
+/// `request_id`.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct WriteRequest {
+    pub request_id: String,
     pub central_uuid: String,
     pub characteristic_uuid: String,
     pub value: Vec<u8>,
@@ -216,12 +251,73 @@ pub enum RequestResult {
     UnlikelyError,
 }
 
+/// An event delivered to Bluetooth subscribers. Serialized as
+/// `{ timestamp, eventType, data }` with `data` typed by `eventType`.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct BluetoothEvent {
-    pub event_type: BluetoothEventType,
     pub timestamp: DateTime<Utc>,
-    pub data: serde_json::Value,
+    #[serde(flatten)]
+    pub payload: BluetoothEventPayload,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(
+    tag = "eventType",
+    content = "data",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum BluetoothEventPayload {
+    StateChanged {
+        state: BluetoothState,
+    },
+    PeripheralDiscovered(Peripheral),
+    PeripheralConnected {
+        peripheral_uuid: String,
+    },
+    PeripheralDisconnected {
+        peripheral_uuid: String,
+        error: Option<String>,
+    },
+    ServiceDiscovered {
+        peripheral_uuid: String,
+        service: Service,
+    },
+    CharacteristicDiscovered {
+        peripheral_uuid: String,
+        characteristic: Characteristic,
+    },
+    CharacteristicValueUpdated {
+        peripheral_uuid: String,
+        characteristic_uuid: String,
+        value: Vec<u8>,
+    },
+    CharacteristicSubscriptionChanged {
+        peripheral_uuid: String,
+        characteristic_uuid: String,
+        is_notifying: bool,
+    },
+    DescriptorValueUpdated {
+        peripheral_uuid: String,
+        descriptor_uuid: String,
+        value: Vec<u8>,
+    },
+    CentralSubscribed {
+        central_uuid: String,
+        characteristic_uuid: String,
+    },
+    CentralUnsubscribed {
+        central_uuid: String,
+        characteristic_uuid: String,
+    },
+    ReadRequestReceived(ReadRequest),
+    /// The values a central wrote together, such as an executed queue of
+    /// prepared writes. The batch is answered with a single response.
+    WriteRequestReceived {
+        request_id: String,
+        requests: Vec<WriteRequest>,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -248,6 +344,11 @@ impl Default for ScanOptions {
             service_uuids: vec![],
             allow_duplicates: false,
             scan_mode: ScanMode::Balanced,
+            name_prefix: None,
+            name_pattern: None,
+            manufacturer_data: None,
+            min_rssi: None,
+            service_data: None,
         }
     }
 }
@@ -269,6 +370,19 @@ impl Default for WriteOptions {
     }
 }
 
+impl Default for AdvertisingData {
+    fn default() -> Self {
+        Self {
+            local_name: None,
+            service_uuids: vec![],
+            manufacturer_data: None,
+            service_data: None,
+            tx_power_level: None,
+            is_connectable: true,
+        }
+    }
+}
+
 impl Default for CharacteristicProperties {
     fn default() -> Self {
         Self {
@@ -295,4 +409,38 @@ impl Default for CharacteristicPermissions {
             write_encryption_required: false,
         }
     }
-}
\ No newline at end of file
+}
+
+impl BluetoothEvent {
+    /// Creates an event stamped with the current time.
+    pub fn new(payload: BluetoothEventPayload) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            payload,
+        }
+    }
+
+    pub fn event_type(&self) -> BluetoothEventType {
+        self.payload.event_type()
+    }
+}
+
+impl BluetoothEventPayload {
+    pub fn event_type(&self) -> BluetoothEventType {
+        match self {
+            Self::StateChanged { .. } => BluetoothEventType::StateChanged,
+            Self::PeripheralDiscovered(_) => BluetoothEventType::PeripheralDiscovered,
+            Self::PeripheralConnected { .. } => BluetoothEventType::PeripheralConnected,
+            Self::PeripheralDisconnected { .. } => BluetoothEventType::PeripheralDisconnected,
+            Self::ServiceDiscovered { .. } => BluetoothEventType::ServiceDiscovered,
+            Self::CharacteristicDiscovered { .. } => BluetoothEventType::CharacteristicDiscovered,
+            Self::CharacteristicValueUpdated { .. } => BluetoothEventType::CharacteristicValueUpdated,
+            Self::CharacteristicSubscriptionChanged { .. } => BluetoothEventType::CharacteristicSubscriptionChanged,
+            Self::DescriptorValueUpdated { .. } => BluetoothEventType::DescriptorValueUpdated,
+            Self::CentralSubscribed { .. } => BluetoothEventType::CentralSubscribed,
+            Self::CentralUnsubscribed { .. } => BluetoothEventType::CentralUnsubscribed,
+            Self::ReadRequestReceived(_) => BluetoothEventType::ReadRequestReceived,
+            Self::WriteRequestReceived { .. } => BluetoothEventType::WriteRequestReceived,
+        }
+    }
+}
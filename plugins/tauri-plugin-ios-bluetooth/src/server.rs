@@ -0,0 +1,466 @@
+//! Routes peripheral-mode read and write requests to per-characteristic handlers.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use tauri::{AppHandle, Runtime};
+
+use crate::{
+    gatt::expand_uuid, BluetoothEvent, BluetoothEventPayload, BluetoothExt, ReadRequest, RequestResponse,
+    RequestResult, Result, Subscription, WriteRequest,
+};
+
+type ReadHandler = Arc<dyn Fn(&ReadRequest) -> std::result::Result<Vec<u8>, RequestResult> + Send + Sync>;
+type WriteHandler = Arc<dyn Fn(&WriteRequest) -> RequestResult + Send + Sync>;
+
+/// Read and write handlers for one published characteristic.
+///
+/// A request for an operation without a handler is answered with
+/// `ReadNotPermitted` or `WriteNotPermitted`.
+#[derive(Default, Clone)]
+pub struct CharacteristicHandler {
+    read: Option<ReadHandler>,
+    write: Option<WriteHandler>,
+}
+
+impl CharacteristicHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Produces the full characteristic value. For long reads the handler is only
+    /// called for the first chunk; the plugin answers the following offsets from
+    /// that value so a central never sees a value that changed mid-read.
+    pub fn on_read<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&ReadRequest) -> std::result::Result<Vec<u8>, RequestResult> + Send + Sync + 'static,
+    {
+        self.read = Some(Arc::new(handler));
+        self
+    }
+
+    /// Handles a write, including prepared writes at a non-zero `offset`.
+    pub fn on_write<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&WriteRequest) -> RequestResult + Send + Sync + 'static,
+    {
+        self.write = Some(Arc::new(handler));
+        self
+    }
+}
+
+#[derive(Default)]
+struct ServerState {
+    // Keyed by expanded characteristic UUID.
+    handlers: HashMap<String, CharacteristicHandler>,
+    subscribers: HashMap<String, HashSet<String>>,
+    // Value served to a central for the long read in progress, keyed by
+    // (central, characteristic). Kept until its last chunk has been served.
+    long_reads: HashMap<(String, String), Vec<u8>>,
+}
+
+/// A GATT server answering requests for the characteristics it has handlers for.
+///
+/// Requests for other characteristics are left for the app to answer with
+/// `respond_to_request`. Dropping the server stops the routing.
+pub struct GattServer<R: Runtime> {
+    app: AppHandle<R>,
+    state: Arc<Mutex<ServerState>>,
+    _subscription: Subscription,
+}
+
+impl<R: Runtime> GattServer<R> {
+    fn state(&self) -> MutexGuard<'_, ServerState> {
+        lock(&self.state)
+    }
+
+    /// Routes requests for `characteristic_uuid` to `handler`, replacing any
+    /// handler registered before.
+    pub fn register(&self, characteristic_uuid: &str, handler: CharacteristicHandler) {
+        self.state().handlers.insert(expand_uuid(characteristic_uuid), handler);
+    }
+
+    pub fn unregister(&self, characteristic_uuid: &str) {
+        let characteristic_uuid = expand_uuid(characteristic_uuid);
+        let mut state = self.state();
+        state.handlers.remove(&characteristic_uuid);
+        state
+            .long_reads
+            .retain(|(_, characteristic), _| *characteristic != characteristic_uuid);
+    }
+
+    /// Centrals currently subscribed to `characteristic_uuid`.
+    pub fn subscribed_centrals(&self, characteristic_uuid: &str) -> Vec<String> {
+        self.state()
+            .subscribers
+            .get(&expand_uuid(characteristic_uuid))
+            .map(|centrals| centrals.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Sends `value` to every central subscribed to `characteristic_uuid`.
+    /// Does nothing when no central is subscribed.
+    pub fn notify(&self, characteristic_uuid: &str, value: Vec<u8>) -> Result<()> {
+        let centrals = self.subscribed_centrals(characteristic_uuid);
+        if centrals.is_empty() {
+            return Ok(());
+        }
+        self.app
+            .bluetooth()
+            .update_characteristic_value(characteristic_uuid.to_string(), value, Some(centrals))
+    }
+}
+
+fn lock(state: &Mutex<ServerState>) -> MutexGuard<'_, ServerState> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn route<R: Runtime>(app: &AppHandle<R>, state: &Mutex<ServerState>, event: &BluetoothEvent) {
+    let response = match &event.payload {
+        BluetoothEventPayload::ReadRequestReceived(request) => read(state, request),
+        BluetoothEventPayload::WriteRequestReceived { request_id, requests } => write(state, request_id, requests),
+        BluetoothEventPayload::CentralSubscribed {
+            central_uuid,
+            characteristic_uuid,
+        } => {
+            lock(state)
+                .subscribers
+                .entry(expand_uuid(characteristic_uuid))
+                .or_default()
+                .insert(central_uuid.clone());
+            None
+        }
+        BluetoothEventPayload::CentralUnsubscribed {
+            central_uuid,
+            characteristic_uuid,
+        } => {
+            let mut state = lock(state);
+            let characteristic_uuid = expand_uuid(characteristic_uuid);
+            if let Some(centrals) = state.subscribers.get_mut(&characteristic_uuid) {
+                centrals.remove(central_uuid);
+                if centrals.is_empty() {
+                    state.subscribers.remove(&characteristic_uuid);
+                }
+            }
+            // Disconnecting isn't reported, but unsubscribes the central from
+            // everything, so its long reads won't be continued.
+            if !state.subscribers.values().any(|centrals| centrals.contains(central_uuid)) {
+                state.long_reads.retain(|(central, _), _| central != central_uuid);
+            }
+            None
+        }
+        _ => None,
+    };
+
+    if let Some(response) = response {
+        let _ = app.bluetooth().respond_to_request(response);
+    }
+}
+
+fn read(state: &Mutex<ServerState>, request: &ReadRequest) -> Option<RequestResponse> {
+    let key = (request.central_uuid.clone(), expand_uuid(&request.characteristic_uuid));
+    let (handler, cached) = {
+        let state = lock(state);
+        let handler = state.handlers.get(&key.1)?.read.clone();
+        // A read at offset 0 starts over.
+        let cached = match request.offset {
+            0 => None,
+            _ => state.long_reads.get(&key).cloned(),
+        };
+        (handler, cached)
+    };
+
+    let value = match (cached, handler) {
+        (Some(value), _) => Ok(value),
+        (None, Some(handler)) => handler(request),
+        (None, None) => Err(RequestResult::ReadNotPermitted),
+    };
+
+    let respond = |result, value| {
+        Some(RequestResponse {
+            request_id: request.request_id.clone(),
+            result,
+            value,
+        })
+    };
+    let mut state = lock(state);
+    let value = match value {
+        Ok(value) if request.offset <= value.len() => value,
+        Ok(_) => {
+            state.long_reads.remove(&key);
+            return respond(RequestResult::InvalidOffset, None);
+        }
+        Err(result) => {
+            state.long_reads.remove(&key);
+            return respond(result, None);
+        }
+    };
+    let chunk = value[request.offset..].to_vec();
+    // Only values that don't fit in this response are read further.
+    if chunk.len() > request.max_length {
+        state.long_reads.insert(key, value);
+    } else {
+        state.long_reads.remove(&key);
+    }
+    respond(RequestResult::Success, Some(chunk))
+}
+
+/// Answers a batch of writes once. A batch touching a characteristic without
+/// handlers is left to the app. Otherwise the values are applied in order and
+/// the first failure stops the batch and becomes the response.
+fn write(state: &Mutex<ServerState>, request_id: &str, requests: &[WriteRequest]) -> Option<RequestResponse> {
+    let handlers = {
+        let state = lock(state);
+        requests
+            .iter()
+            .map(|request| {
+                let handler = state.handlers.get(&expand_uuid(&request.characteristic_uuid))?;
+                Some(handler.write.clone())
+            })
+            .collect::<Option<Vec<_>>>()?
+    };
+
+    let result = match handlers.into_iter().collect::<Option<Vec<_>>>() {
+        Some(handlers) => requests
+            .iter()
+            .zip(handlers)
+            .map(|(request, handler)| handler(request))
+            .find(|result| *result != RequestResult::Success)
+            .unwrap_or(RequestResult::Success),
+        None => RequestResult::WriteNotPermitted,
+    };
+    Some(RequestResponse {
+        request_id: request_id.to_string(),
+        result,
+        value: None,
+    })
+}
+
+impl<R: Runtime> crate::Bluetooth<R> {
+    /// Starts a GATT server that answers incoming requests for the
+    /// characteristics registered on it and tracks subscribed centrals.
+    pub fn gatt_server(&self) -> GattServer<R> {
+        let state = Arc::new(Mutex::new(ServerState::default()));
+        let app = self.app.clone();
+        let routed = state.clone();
+        let subscription = self.listen(move |event| route(&app, &routed, event));
+
+        GattServer {
+            app: self.app.clone(),
+            state,
+            _subscription: subscription,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    fn request(characteristic_uuid: &str, value: &[u8]) -> WriteRequest {
+        WriteRequest {
+            request_id: "batch".to_string(),
+            central_uuid: "central".to_string(),
+            characteristic_uuid: characteristic_uuid.to_string(),
+            value: value.to_vec(),
+            offset: 0,
+        }
+    }
+
+    fn server(handlers: &[(&str, CharacteristicHandler)]) -> Mutex<ServerState> {
+        let mut state = ServerState::default();
+        for (uuid, handler) in handlers {
+            state.handlers.insert(expand_uuid(uuid), handler.clone());
+        }
+        Mutex::new(state)
+    }
+
+    fn recorder(written: &Arc<Mutex<Vec<Vec<u8>>>>, result: RequestResult) -> CharacteristicHandler {
+        let written = written.clone();
+        CharacteristicHandler::new().on_write(move |request| {
+            written.lock().unwrap().push(request.value.clone());
+            result.clone()
+        })
+    }
+
+    fn read_request(central_uuid: &str, offset: usize) -> ReadRequest {
+        ReadRequest {
+            request_id: format!("read-{offset}"),
+            central_uuid: central_uuid.to_string(),
+            characteristic_uuid: "2A37".to_string(),
+            offset,
+            max_length: 4,
+        }
+    }
+
+    /// A server whose 2A37 value is `length` bytes counting up from the number
+    /// of times the handler ran, so every call produces a different value.
+    fn counter(length: u8) -> (Mutex<ServerState>, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let handler = CharacteristicHandler::new().on_read(move |_| {
+            let call = counted.fetch_add(1, Ordering::SeqCst) as u8;
+            Ok((call..call + length).collect())
+        });
+        (server(&[("2A37", handler)]), calls)
+    }
+
+    fn read_chunk(state: &Mutex<ServerState>, central_uuid: &str, offset: usize) -> (RequestResult, Option<Vec<u8>>) {
+        let response = read(state, &read_request(central_uuid, offset)).unwrap();
+        assert_eq!(response.request_id, format!("read-{offset}"));
+        (response.result, response.value)
+    }
+
+    #[test]
+    fn values_fitting_one_response_are_not_cached() {
+        let (state, calls) = counter(4);
+        assert_eq!(read_chunk(&state, "central", 0), (RequestResult::Success, Some(vec![0, 1, 2, 3])));
+        assert!(lock(&state).long_reads.is_empty());
+        assert_eq!(read_chunk(&state, "central", 0), (RequestResult::Success, Some(vec![1, 2, 3, 4])));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn long_reads_are_served_from_the_first_value() {
+        let (state, calls) = counter(10);
+        assert_eq!(read_chunk(&state, "central", 0), (RequestResult::Success, Some((0..10).collect())));
+        assert_eq!(read_chunk(&state, "other", 0), (RequestResult::Success, Some((1..11).collect())));
+        assert_eq!(read_chunk(&state, "central", 4), (RequestResult::Success, Some((4..10).collect())));
+        assert_eq!(lock(&state).long_reads.len(), 2);
+
+        // The last chunk ends the read.
+        assert_eq!(read_chunk(&state, "central", 8), (RequestResult::Success, Some(vec![8, 9])));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        let cached: Vec<_> = lock(&state).long_reads.keys().map(|(central, _)| central.clone()).collect();
+        assert_eq!(cached, ["other"]);
+
+        // Reading on without a cached value asks the handler again.
+        assert_eq!(read_chunk(&state, "central", 8), (RequestResult::Success, Some(vec![10, 11])));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // So does starting over.
+        assert_eq!(read_chunk(&state, "other", 0), (RequestResult::Success, Some((3..13).collect())));
+        assert_eq!(read_chunk(&state, "other", 6), (RequestResult::Success, Some((9..13).collect())));
+        assert!(lock(&state).long_reads.is_empty());
+    }
+
+    #[test]
+    fn offsets_past_the_value_are_invalid() {
+        let (state, _) = counter(10);
+        read_chunk(&state, "central", 0);
+        assert_eq!(read_chunk(&state, "central", 11), (RequestResult::InvalidOffset, None));
+        assert!(lock(&state).long_reads.is_empty());
+
+        read_chunk(&state, "central", 0);
+        assert_eq!(read_chunk(&state, "central", 10), (RequestResult::Success, Some(vec![])));
+        assert!(lock(&state).long_reads.is_empty());
+
+        // Without a read handler the request is refused.
+        let state = server(&[("2A37", CharacteristicHandler::new())]);
+        assert_eq!(read_chunk(&state, "central", 0), (RequestResult::ReadNotPermitted, None));
+        assert!(read(&server(&[]), &read_request("central", 0)).is_none());
+    }
+
+    #[test]
+    fn long_reads_end_when_the_central_goes_away() {
+        use tauri::Manager;
+
+        let app = tauri::test::mock_app();
+        app.manage(crate::Bluetooth::new(app.handle()));
+        let bluetooth = app.bluetooth();
+        let published = |uuid: &str| crate::PeripheralCharacteristic {
+            uuid: uuid.to_string(),
+            properties: crate::CharacteristicProperties {
+                read: true,
+                notify: true,
+                ..Default::default()
+            },
+            permissions: crate::CharacteristicPermissions {
+                readable: true,
+                ..Default::default()
+            },
+            value: None,
+            descriptors: vec![],
+        };
+        bluetooth
+            .add_service(crate::PeripheralService {
+                uuid: "180D".to_string(),
+                is_primary: true,
+                characteristics: vec![published("2A37"), published("2A38")],
+            })
+            .unwrap();
+        let server = bluetooth.gatt_server();
+        let handler = CharacteristicHandler::new().on_read(|_| Ok(vec![7; 40]));
+        server.register("2A37", handler.clone());
+        server.register("2A38", handler);
+
+        let simulator = bluetooth.simulator();
+        simulator.set_central_mtu(23);
+        simulator.central_set_subscribed("central", "2A37", true).unwrap();
+        simulator.central_set_subscribed("central", "2A38", true).unwrap();
+        let request_id = simulator.central_read("central", "2A37", 0).unwrap();
+        assert_eq!(simulator.take_response(&request_id).unwrap().value, Some(vec![7; 40]));
+        simulator.central_read("central", "2A38", 0).unwrap();
+        simulator.central_read("other", "2A37", 0).unwrap();
+        assert_eq!(server.state().long_reads.len(), 3);
+
+        // Still subscribed to something, so still connected.
+        simulator.central_set_subscribed("central", "2A37", false).unwrap();
+        assert_eq!(server.state().long_reads.len(), 3);
+        simulator.central_set_subscribed("central", "2A38", false).unwrap();
+        let cached: Vec<_> = server.state().long_reads.keys().map(|(central, _)| central.clone()).collect();
+        assert_eq!(cached, ["other"]);
+
+        // 22 bytes fit in a response with the default MTU.
+        let request_id = simulator.central_read("other", "2A37", 22).unwrap();
+        assert_eq!(simulator.take_response(&request_id).unwrap().value, Some(vec![7; 18]));
+        assert!(server.state().long_reads.is_empty());
+    }
+
+    #[test]
+    fn a_write_batch_is_applied_and_answered_once() {
+        let written = Arc::new(Mutex::new(vec![]));
+        let state = server(&[
+            ("2A39", recorder(&written, RequestResult::Success)),
+            ("2A3A", recorder(&written, RequestResult::Success)),
+        ]);
+        let batch = [request("2A39", &[1]), request("2A3A", &[2]), request("2A39", &[3])];
+        let response = write(&state, "batch", &batch).unwrap();
+        assert_eq!(response.request_id, "batch");
+        assert_eq!(response.result, RequestResult::Success);
+        assert_eq!(*written.lock().unwrap(), [vec![1], vec![2], vec![3]]);
+    }
+
+    #[test]
+    fn the_first_failure_stops_a_write_batch() {
+        let written = Arc::new(Mutex::new(vec![]));
+        let state = server(&[
+            ("2A39", recorder(&written, RequestResult::Success)),
+            ("2A3A", recorder(&written, RequestResult::InvalidPdu)),
+        ]);
+        let batch = [request("2A39", &[1]), request("2A3A", &[2]), request("2A39", &[3])];
+        let response = write(&state, "batch", &batch).unwrap();
+        assert_eq!(response.result, RequestResult::InvalidPdu);
+        assert_eq!(*written.lock().unwrap(), [vec![1], vec![2]]);
+    }
+
+    #[test]
+    fn a_write_batch_is_only_applied_when_every_value_is_writable() {
+        let written = Arc::new(Mutex::new(vec![]));
+        let state = server(&[
+            ("2A39", recorder(&written, RequestResult::Success)),
+            ("2A37", CharacteristicHandler::new()),
+        ]);
+        let response = write(&state, "batch", &[request("2A39", &[1]), request("2A37", &[2])]).unwrap();
+        assert_eq!(response.result, RequestResult::WriteNotPermitted);
+        assert!(written.lock().unwrap().is_empty());
+
+        // Batches touching a characteristic without handlers are left to the app.
+        assert!(write(&state, "batch", &[request("2A39", &[1]), request("2A38", &[2])]).is_none());
+        assert!(written.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,1297 @@
+//! In-memory Bluetooth LE stack backing the desktop implementation.
+//!
+//! Virtual peripherals registered with the [`Simulator`] are found by scans and serve
+//! their GATT database to the regular central API, so pairing and data flows can run
+//! without a device. Virtual centrals can likewise send read and write requests to
+//! the services published in peripheral mode.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{
+    advertisement::ScanFilter,
+    events::EventBus,
+    gatt::{expand_uuid, uuids_equal, CHARACTERISTIC_NOT_FOUND, READ_NOT_PERMITTED},
+    models::*,
+    Error, Result,
+};
+
+/// Largest attribute value ATT allows, reported for writes with response.
+const MAX_ATTRIBUTE_LENGTH: usize = 512;
+/// Bytes of an ATT write command taken up by its header.
+const ATT_HEADER_LENGTH: usize = 3;
+/// Bytes of an ATT read response taken up by its header.
+const READ_RESPONSE_HEADER_LENGTH: usize = 1;
+/// ATT MTU every connection starts with.
+const DEFAULT_ATT_MTU: usize = 23;
+
+/// A peripheral served by the [`Simulator`].
+#[derive(Debug, Clone)]
+pub struct VirtualPeripheral {
+    pub uuid: String,
+    pub advertising_data: AdvertisingData,
+    pub services: Vec<PeripheralService>,
+    /// Signal strength reported by scans and `get_peripheral_rssi`.
+    pub rssi: i32,
+    /// Negotiated ATT MTU, which bounds writes without response.
+    pub mtu: usize,
+}
+
+impl VirtualPeripheral {
+    pub fn new(uuid: impl Into<String>, advertising_data: AdvertisingData) -> Self {
+        Self {
+            uuid: uuid.into(),
+            advertising_data,
+            services: vec![],
+            rssi: -60,
+            mtu: 185,
+        }
+    }
+
+    pub fn with_service(mut self, service: PeripheralService) -> Self {
+        self.services.push(service);
+        self
+    }
+
+    pub fn with_rssi(mut self, rssi: i32) -> Self {
+        self.rssi = rssi;
+        self
+    }
+
+    pub fn with_mtu(mut self, mtu: usize) -> Self {
+        self.mtu = mtu;
+        self
+    }
+}
+
+fn find_characteristic<'a>(
+    services: &'a mut [PeripheralService],
+    uuid: &str,
+) -> Result<&'a mut PeripheralCharacteristic> {
+    services
+        .iter_mut()
+        .flat_map(|service| service.characteristics.iter_mut())
+        .find(|characteristic| uuids_equal(&characteristic.uuid, uuid))
+        .ok_or_else(|| Error::InvalidInput(CHARACTERISTIC_NOT_FOUND.to_string()))
+}
+
+struct ScriptedDisconnect {
+    remaining_operations: usize,
+    error: Option<String>,
+}
+
+struct SimPeripheral {
+    config: VirtualPeripheral,
+    state: PeripheralState,
+    discovered: bool,
+    /// Whether the running scan already reported the peripheral.
+    reported: bool,
+    in_range: bool,
+    notifying: HashSet<String>,
+    scripted_disconnect: Option<ScriptedDisconnect>,
+    /// Whether connection requests stay pending instead of completing.
+    holding_connections: bool,
+}
+
+impl SimPeripheral {
+    fn to_peripheral(&self) -> Peripheral {
+        let advertising = &self.config.advertising_data;
+        Peripheral {
+            uuid: self.config.uuid.clone(),
+            name: advertising.local_name.clone(),
+            rssi: self.config.rssi,
+            is_connectable: advertising.is_connectable,
+            state: self.state.clone(),
+            services: advertising.service_uuids.clone(),
+            manufacturer_data: advertising.manufacturer_data.clone(),
+            service_data: advertising.service_data.clone(),
+            tx_power_level: advertising.tx_power_level,
+            solicited_service_uuids: vec![],
+            overflow_service_uuids: vec![],
+        }
+    }
+
+    fn matches(&self, filter: &ScanFilter) -> bool {
+        self.in_range && filter.matches(&self.to_peripheral())
+    }
+
+    fn characteristic(&mut self, uuid: &str) -> Result<&mut PeripheralCharacteristic> {
+        find_characteristic(&mut self.config.services, uuid)
+    }
+
+    fn descriptor(&mut self, uuid: &str) -> Result<&mut PeripheralDescriptor> {
+        self.config
+            .services
+            .iter_mut()
+            .flat_map(|service| service.characteristics.iter_mut())
+            .flat_map(|characteristic| characteristic.descriptors.iter_mut())
+            .find(|descriptor| uuids_equal(&descriptor.uuid, uuid))
+            .ok_or_else(|| Error::InvalidInput("Descriptor not found".to_string()))
+    }
+
+    fn is_connected(&self) -> bool {
+        self.state == PeripheralState::Connected
+    }
+
+    fn disconnect(&mut self, error: Option<String>, events: &mut Vec<BluetoothEventPayload>) {
+        self.state = PeripheralState::Disconnected;
+        self.notifying.clear();
+        self.scripted_disconnect = None;
+        events.push(BluetoothEventPayload::PeripheralDisconnected {
+            peripheral_uuid: self.config.uuid.clone(),
+            error,
+        });
+    }
+}
+
+struct SimState {
+    bluetooth_state: BluetoothState,
+    authorization: AuthorizationStatus,
+    scan: Option<ScanFilter>,
+    peripherals: Vec<SimPeripheral>,
+    advertising: Option<AdvertisingData>,
+    local_services: Vec<PeripheralService>,
+    pending_requests: HashSet<String>,
+    responses: HashMap<String, RequestResponse>,
+    // Virtual centrals subscribed to each published characteristic, keyed by
+    // expanded characteristic UUID. Central UUIDs are uppercased.
+    subscribers: HashMap<String, HashSet<String>>,
+    // Published characteristics whose updates aren't sent, from `set_notify_value`.
+    muted: HashSet<String>,
+    // Values each virtual central was notified of, oldest first.
+    notifications: HashMap<String, Vec<(String, Vec<u8>)>>,
+    next_request_id: u64,
+    /// ATT MTU negotiated with the virtual centrals.
+    central_mtu: usize,
+}
+
+impl SimState {
+    fn ensure_ready(&self) -> Result<()> {
+        match self.authorization {
+            AuthorizationStatus::Denied | AuthorizationStatus::Restricted => {
+                return Err(Error::PermissionDenied)
+            }
+            _ => {}
+        }
+        if self.bluetooth_state != BluetoothState::PoweredOn {
+            return Err(Error::OperationFailed("Bluetooth not powered on".to_string()));
+        }
+        Ok(())
+    }
+
+    fn peripheral(&mut self, uuid: &str) -> Result<&mut SimPeripheral> {
+        self.peripherals
+            .iter_mut()
+            .find(|peripheral| uuids_equal(&peripheral.config.uuid, uuid))
+            .ok_or_else(|| Error::InvalidInput("Peripheral not found".to_string()))
+    }
+
+    /// Looks up a connected peripheral for a GATT operation, applying any scripted
+    /// disconnect that is due.
+    fn connected(
+        &mut self,
+        uuid: &str,
+        events: &mut Vec<BluetoothEventPayload>,
+    ) -> Result<&mut SimPeripheral> {
+        let peripheral = self.peripheral(uuid)?;
+        if !peripheral.is_connected() {
+            return Err(Error::OperationFailed("Peripheral not connected".to_string()));
+        }
+
+        if let Some(script) = &mut peripheral.scripted_disconnect {
+            if script.remaining_operations == 0 {
+                let error = script.error.clone();
+                peripheral.disconnect(error, events);
+                return Err(Error::OperationFailed("Peripheral disconnected".to_string()));
+            }
+            script.remaining_operations -= 1;
+        }
+        Ok(peripheral)
+    }
+
+    fn discover(&mut self, events: &mut Vec<BluetoothEventPayload>) {
+        let Some(filter) = &self.scan else {
+            return;
+        };
+        for peripheral in &mut self.peripherals {
+            if peripheral.matches(filter) && (filter.options().allow_duplicates || !peripheral.reported) {
+                peripheral.discovered = true;
+                peripheral.reported = true;
+                events.push(BluetoothEventPayload::PeripheralDiscovered(peripheral.to_peripheral()));
+            }
+        }
+    }
+
+    fn local_characteristic(&mut self, uuid: &str) -> Result<&mut PeripheralCharacteristic> {
+        find_characteristic(&mut self.local_services, uuid)
+    }
+
+    /// Forgets the subscribers and notify state of characteristics that are no
+    /// longer published.
+    fn forget_unpublished(&mut self) {
+        let published: HashSet<String> = self
+            .local_services
+            .iter()
+            .flat_map(|service| service.characteristics.iter())
+            .map(|characteristic| expand_uuid(&characteristic.uuid))
+            .collect();
+        self.subscribers.retain(|uuid, _| published.contains(uuid));
+        self.muted.retain(|uuid| published.contains(uuid));
+    }
+
+    fn next_request_id(&mut self) -> String {
+        self.next_request_id += 1;
+        format!("sim-request-{}", self.next_request_id)
+    }
+}
+
+impl Default for SimState {
+    fn default() -> Self {
+        Self {
+            bluetooth_state: BluetoothState::PoweredOn,
+            authorization: AuthorizationStatus::Authorized,
+            scan: None,
+            peripherals: vec![],
+            advertising: None,
+            local_services: vec![],
+            pending_requests: HashSet::new(),
+            responses: HashMap::new(),
+            subscribers: HashMap::new(),
+            muted: HashSet::new(),
+            notifications: HashMap::new(),
+            next_request_id: 0,
+            central_mtu: DEFAULT_ATT_MTU,
+        }
+    }
+}
+
+/// Scripting interface of the simulated Bluetooth stack.
+///
+/// Obtained on desktop through `app.bluetooth().simulator()`. Changes made here
+/// produce the same events a real radio would, e.g. adding a peripheral while a scan
+/// is running reports it as discovered.
+pub struct Simulator {
+    events: Arc<EventBus>,
+    state: Mutex<SimState>,
+}
+
+impl Simulator {
+    pub(crate) fn new(events: Arc<EventBus>) -> Self {
+        Self {
+            events,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Runs `f` against the simulated stack, then dispatches the events it produced
+    /// once the lock is released so listeners may call back into the plugin.
+    fn update<T>(&self, f: impl FnOnce(&mut SimState, &mut Vec<BluetoothEventPayload>) -> T) -> T {
+        let mut events = Vec::new();
+        let result = f(&mut self.state(), &mut events);
+        for payload in events {
+            self.events.dispatch(&BluetoothEvent::new(payload));
+        }
+        result
+    }
+
+    fn state(&self) -> MutexGuard<'_, SimState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Registers a peripheral, replacing any with the same UUID. It is reported right
+    /// away if a matching scan is running.
+    pub fn add_peripheral(&self, peripheral: VirtualPeripheral) {
+        self.update(|state, events| {
+            state
+                .peripherals
+                .retain(|existing| !uuids_equal(&existing.config.uuid, &peripheral.uuid));
+            state.peripherals.push(SimPeripheral {
+                config: peripheral,
+                state: PeripheralState::Disconnected,
+                discovered: false,
+                reported: false,
+                in_range: true,
+                notifying: HashSet::new(),
+                scripted_disconnect: None,
+                holding_connections: false,
+            });
+            state.discover(events);
+        })
+    }
+
+    /// Removes a peripheral, dropping its connection first if it has one.
+    pub fn remove_peripheral(&self, uuid: &str) -> Result<()> {
+        self.update(|state, events| {
+            let peripheral = state.peripheral(uuid)?;
+            if peripheral.is_connected() {
+                peripheral.disconnect(Some("Peripheral was removed".to_string()), events);
+            }
+            state
+                .peripherals
+                .retain(|existing| !uuids_equal(&existing.config.uuid, uuid));
+            Ok(())
+        })
+    }
+
+    /// Changes the signal strength of a peripheral. Scans that allow duplicates
+    /// report it again with the new value.
+    pub fn set_rssi(&self, uuid: &str, rssi: i32) -> Result<()> {
+        self.update(|state, events| {
+            let peripheral = state.peripheral(uuid)?;
+            peripheral.config.rssi = rssi;
+            if let Some(filter) = &state.scan {
+                let peripheral = state
+                    .peripherals
+                    .iter()
+                    .find(|peripheral| uuids_equal(&peripheral.config.uuid, uuid))
+                    .filter(|peripheral| filter.options().allow_duplicates && peripheral.matches(filter));
+                if let Some(peripheral) = peripheral {
+                    events.push(BluetoothEventPayload::PeripheralDiscovered(peripheral.to_peripheral()));
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Moves a peripheral in or out of range. Out-of-range peripherals are not
+    /// discovered, refuse connections and lose any connection they had.
+    pub fn set_in_range(&self, uuid: &str, in_range: bool) -> Result<()> {
+        self.update(|state, events| {
+            let peripheral = state.peripheral(uuid)?;
+            peripheral.in_range = in_range;
+            if !in_range && peripheral.is_connected() {
+                peripheral.disconnect(Some("Peripheral went out of range".to_string()), events);
+            }
+            if in_range {
+                state.discover(events);
+            }
+            Ok(())
+        })
+    }
+
+    /// Changes the adapter state. Leaving `PoweredOn` stops the scan and drops every
+    /// connection.
+    pub fn set_bluetooth_state(&self, bluetooth_state: BluetoothState) {
+        self.update(|state, events| {
+            if bluetooth_state != BluetoothState::PoweredOn {
+                state.scan = None;
+                for peripheral in state
+                    .peripherals
+                    .iter_mut()
+                    .filter(|p| p.state != PeripheralState::Disconnected)
+                {
+                    peripheral.disconnect(Some("Bluetooth was turned off".to_string()), events);
+                }
+            }
+            state.bluetooth_state = bluetooth_state.clone();
+            events.push(BluetoothEventPayload::StateChanged {
+                state: bluetooth_state,
+            });
+        })
+    }
+
+    pub fn set_authorization_status(&self, status: AuthorizationStatus) {
+        self.state().authorization = status;
+    }
+
+    /// Drops the connection to a peripheral as if the link was lost.
+    pub fn drop_connection(&self, uuid: &str, error: Option<String>) -> Result<()> {
+        self.update(|state, events| {
+            let peripheral = state.peripheral(uuid)?;
+            if peripheral.is_connected() {
+                peripheral.disconnect(error, events);
+            }
+            Ok(())
+        })
+    }
+
+    /// Lets `operations` more GATT operations on a peripheral succeed, then drops
+    /// its connection with `error` on the next one, which fails.
+    ///
+    /// The script is cleared when the peripheral disconnects for any reason.
+    pub fn script_disconnect(&self, uuid: &str, operations: usize, error: Option<String>) -> Result<()> {
+        let mut state = self.state();
+        state.peripheral(uuid)?.scripted_disconnect = Some(ScriptedDisconnect {
+            remaining_operations: operations,
+            error,
+        });
+        Ok(())
+    }
+
+    /// Leaves connection requests to a peripheral pending, as when it stops
+    /// answering, until `holding` is turned off again. Requests still pending then
+    /// complete; those cancelled with `disconnect_peripheral` meanwhile don't.
+    pub fn hold_connections(&self, uuid: &str, holding: bool) -> Result<()> {
+        self.update(|state, events| {
+            let peripheral = state.peripheral(uuid)?;
+            peripheral.holding_connections = holding;
+            if !holding && peripheral.state == PeripheralState::Connecting {
+                peripheral.state = PeripheralState::Connected;
+                events.push(BluetoothEventPayload::PeripheralConnected {
+                    peripheral_uuid: peripheral.config.uuid.clone(),
+                });
+            }
+            Ok(())
+        })
+    }
+
+    /// Updates a characteristic from the peripheral side, notifying the central if
+    /// it subscribed to the characteristic.
+    pub fn notify(&self, peripheral_uuid: &str, characteristic_uuid: &str, value: Vec<u8>) -> Result<()> {
+        self.update(|state, events| {
+            let peripheral = state.peripheral(peripheral_uuid)?;
+            let uuid = peripheral.config.uuid.clone();
+            let characteristic = peripheral.characteristic(characteristic_uuid)?;
+            characteristic.value = Some(value.clone());
+            let characteristic_uuid = characteristic.uuid.clone();
+
+            if peripheral.is_connected() && peripheral.notifying.contains(&expand_uuid(&characteristic_uuid)) {
+                events.push(BluetoothEventPayload::CharacteristicValueUpdated {
+                    peripheral_uuid: uuid,
+                    characteristic_uuid,
+                    value,
+                });
+            }
+            Ok(())
+        })
+    }
+
+    /// Current value of a characteristic in a peripheral's GATT database, e.g. to
+    /// check what the app wrote.
+    pub fn characteristic_value(&self, peripheral_uuid: &str, characteristic_uuid: &str) -> Result<Option<Vec<u8>>> {
+        let mut state = self.state();
+        Ok(state
+            .peripheral(peripheral_uuid)?
+            .characteristic(characteristic_uuid)?
+            .value
+            .clone())
+    }
+
+    /// Advertising data published with `start_peripheral_advertising`, if advertising.
+    pub fn advertising_data(&self) -> Option<AdvertisingData> {
+        self.state().advertising.clone()
+    }
+
+    /// Services published with `add_service`.
+    pub fn published_services(&self) -> Vec<PeripheralService> {
+        self.state().local_services.clone()
+    }
+
+    /// Sets the ATT MTU of the virtual centrals, which bounds the bytes of a value
+    /// each read returns.
+    pub fn set_central_mtu(&self, mtu: usize) {
+        self.state().central_mtu = mtu;
+    }
+
+    /// Sends a read request from a virtual central to a published characteristic and
+    /// returns the id to look the response up with [`Simulator::take_response`].
+    pub fn central_read(&self, central_uuid: &str, characteristic_uuid: &str, offset: usize) -> Result<String> {
+        self.update(|state, events| {
+            let characteristic_uuid = state.local_characteristic(characteristic_uuid)?.uuid.clone();
+            let request_id = state.next_request_id();
+            state.pending_requests.insert(request_id.clone());
+            events.push(BluetoothEventPayload::ReadRequestReceived(ReadRequest {
+                request_id: request_id.clone(),
+                central_uuid: central_uuid.to_string(),
+                characteristic_uuid,
+                offset,
+                max_length: state.central_mtu.saturating_sub(READ_RESPONSE_HEADER_LENGTH),
+            }));
+            Ok(request_id)
+        })
+    }
+
+    /// Sends a write request from a virtual central to a published characteristic.
+    pub fn central_write(
+        &self,
+        central_uuid: &str,
+        characteristic_uuid: &str,
+        value: Vec<u8>,
+        offset: usize,
+    ) -> Result<String> {
+        self.central_write_batch(central_uuid, vec![(characteristic_uuid, value, offset)])
+    }
+
+    /// Sends several `(characteristic, value, offset)` writes from a virtual
+    /// central as one request, like an executed queue of prepared writes.
+    pub fn central_write_batch(&self, central_uuid: &str, writes: Vec<(&str, Vec<u8>, usize)>) -> Result<String> {
+        self.update(|state, events| {
+            let request_id = state.next_request_id();
+            let requests = writes
+                .into_iter()
+                .map(|(characteristic_uuid, value, offset)| {
+                    Ok(WriteRequest {
+                        request_id: request_id.clone(),
+                        central_uuid: central_uuid.to_string(),
+                        characteristic_uuid: state.local_characteristic(characteristic_uuid)?.uuid.clone(),
+                        value,
+                        offset,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            state.pending_requests.insert(request_id.clone());
+            events.push(BluetoothEventPayload::WriteRequestReceived {
+                request_id: request_id.clone(),
+                requests,
+            });
+            Ok(request_id)
+        })
+    }
+
+    /// Subscribes or unsubscribes a virtual central to a published characteristic.
+    pub fn central_set_subscribed(&self, central_uuid: &str, characteristic_uuid: &str, subscribed: bool) -> Result<()> {
+        self.update(|state, events| {
+            let characteristic = state.local_characteristic(characteristic_uuid)?;
+            if !characteristic.properties.notify && !characteristic.properties.indicate {
+                return Err(Error::OperationFailed(
+                    "Characteristic does not support notifications".to_string(),
+                ));
+            }
+            let characteristic_uuid = characteristic.uuid.clone();
+            let subscribers = state.subscribers.entry(expand_uuid(&characteristic_uuid)).or_default();
+            if subscribed {
+                subscribers.insert(central_uuid.to_uppercase());
+            } else {
+                subscribers.remove(&central_uuid.to_uppercase());
+            }
+
+            let central_uuid = central_uuid.to_string();
+            events.push(if subscribed {
+                BluetoothEventPayload::CentralSubscribed {
+                    central_uuid,
+                    characteristic_uuid,
+                }
+            } else {
+                BluetoothEventPayload::CentralUnsubscribed {
+                    central_uuid,
+                    characteristic_uuid,
+                }
+            });
+            Ok(())
+        })
+    }
+
+    /// Takes the values a virtual central was notified of since the last call, as
+    /// `(characteristic, value)` pairs, oldest first.
+    pub fn take_notifications(&self, central_uuid: &str) -> Vec<(String, Vec<u8>)> {
+        self.state()
+            .notifications
+            .remove(&central_uuid.to_uppercase())
+            .unwrap_or_default()
+    }
+
+    /// Takes the app's answer to a request sent with [`Simulator::central_read`] or
+    /// [`Simulator::central_write`].
+    pub fn take_response(&self, request_id: &str) -> Option<RequestResponse> {
+        self.state().responses.remove(request_id)
+    }
+
+    pub(crate) fn authorization_status(&self) -> AuthorizationStatus {
+        self.state().authorization.clone()
+    }
+
+    pub(crate) fn is_bluetooth_enabled(&self) -> bool {
+        self.state().bluetooth_state == BluetoothState::PoweredOn
+    }
+
+    pub(crate) fn start_central_scan(&self, options: ScanOptions) -> Result<()> {
+        let filter = ScanFilter::new(options)?;
+        self.update(|state, events| {
+            state.ensure_ready()?;
+            if state.scan.is_some() {
+                return Err(Error::OperationFailed("Scan already in progress".to_string()));
+            }
+            for peripheral in &mut state.peripherals {
+                peripheral.reported = false;
+            }
+            state.scan = Some(filter);
+            state.discover(events);
+            Ok(())
+        })
+    }
+
+    pub(crate) fn stop_central_scan(&self) -> Result<()> {
+        self.state()
+            .scan
+            .take()
+            .map(|_| ())
+            .ok_or_else(|| Error::OperationFailed("Scan not started".to_string()))
+    }
+
+    pub(crate) fn connect_peripheral(&self, uuid: &str) -> Result<()> {
+        self.update(|state, events| {
+            state.ensure_ready()?;
+            let peripheral = state.peripheral(uuid)?;
+            if !peripheral.discovered {
+                return Err(Error::InvalidInput("Peripheral not found".to_string()));
+            }
+            if peripheral.is_connected() || peripheral.state == PeripheralState::Connecting {
+                return Ok(());
+            }
+            if !peripheral.in_range {
+                return Err(Error::OperationFailed("Peripheral is out of range".to_string()));
+            }
+            if !peripheral.config.advertising_data.is_connectable {
+                return Err(Error::OperationFailed("Peripheral is not connectable".to_string()));
+            }
+            if peripheral.holding_connections {
+                peripheral.state = PeripheralState::Connecting;
+                return Ok(());
+            }
+
+            peripheral.state = PeripheralState::Connected;
+            events.push(BluetoothEventPayload::PeripheralConnected {
+                peripheral_uuid: peripheral.config.uuid.clone(),
+            });
+            Ok(())
+        })
+    }
+
+    pub(crate) fn disconnect_peripheral(&self, uuid: &str) -> Result<()> {
+        self.update(|state, events| {
+            let peripheral = state.peripheral(uuid)?;
+            // Cancelling a pending connection reports nothing.
+            if peripheral.state == PeripheralState::Connecting {
+                peripheral.state = PeripheralState::Disconnected;
+                return Ok(());
+            }
+            if !peripheral.is_connected() {
+                return Err(Error::OperationFailed("Peripheral not connected".to_string()));
+            }
+            peripheral.disconnect(None, events);
+            Ok(())
+        })
+    }
+
+    pub(crate) fn connected_peripherals(&self) -> Vec<Peripheral> {
+        self.state()
+            .peripherals
+            .iter()
+            .filter(|peripheral| peripheral.is_connected())
+            .map(SimPeripheral::to_peripheral)
+            .collect()
+    }
+
+    pub(crate) fn discovered_peripherals(&self) -> Vec<Peripheral> {
+        self.state()
+            .peripherals
+            .iter()
+            .filter(|peripheral| peripheral.discovered)
+            .map(SimPeripheral::to_peripheral)
+            .collect()
+    }
+
+    pub(crate) fn discover_services(&self, peripheral_uuid: &str, service_uuids: Option<Vec<String>>) -> Result<Vec<Service>> {
+        self.update(|state, events| {
+            let peripheral = state.connected(peripheral_uuid, events)?;
+            let services: Vec<Service> = peripheral
+                .config
+                .services
+                .iter()
+                .filter(|service| {
+                    service_uuids
+                        .as_ref()
+                        .map_or(true, |wanted| wanted.iter().any(|uuid| uuids_equal(uuid, &service.uuid)))
+                })
+                .map(|service| Service {
+                    uuid: service.uuid.clone(),
+                    is_primary: service.is_primary,
+                    characteristics: service.characteristics.iter().map(|c| c.uuid.clone()).collect(),
+                    included_services: vec![],
+                })
+                .collect();
+
+            for service in &services {
+                events.push(BluetoothEventPayload::ServiceDiscovered {
+                    peripheral_uuid: peripheral.config.uuid.clone(),
+                    service: service.clone(),
+                });
+            }
+            Ok(services)
+        })
+    }
+
+    pub(crate) fn discover_characteristics(
+        &self,
+        peripheral_uuid: &str,
+        service_uuid: &str,
+        characteristic_uuids: Option<Vec<String>>,
+    ) -> Result<Vec<Characteristic>> {
+        self.update(|state, events| {
+            let peripheral = state.connected(peripheral_uuid, events)?;
+            let service = peripheral
+                .config
+                .services
+                .iter()
+                .find(|service| uuids_equal(&service.uuid, service_uuid))
+                .ok_or_else(|| Error::InvalidInput("Service not found".to_string()))?;
+
+            let characteristics: Vec<Characteristic> = service
+                .characteristics
+                .iter()
+                .filter(|characteristic| {
+                    characteristic_uuids
+                        .as_ref()
+                        .map_or(true, |wanted| wanted.iter().any(|uuid| uuids_equal(uuid, &characteristic.uuid)))
+                })
+                .map(|characteristic| Characteristic {
+                    uuid: characteristic.uuid.clone(),
+                    service_uuid: service.uuid.clone(),
+                    properties: characteristic.properties.clone(),
+                    value: characteristic.value.clone(),
+                    descriptors: characteristic.descriptors.iter().map(|d| d.uuid.clone()).collect(),
+                    is_notifying: peripheral
+                        .notifying
+                        .contains(&expand_uuid(&characteristic.uuid)),
+                })
+                .collect();
+
+            for characteristic in &characteristics {
+                events.push(BluetoothEventPayload::CharacteristicDiscovered {
+                    peripheral_uuid: peripheral.config.uuid.clone(),
+                    characteristic: characteristic.clone(),
+                });
+            }
+            Ok(characteristics)
+        })
+    }
+
+    pub(crate) fn read_characteristic(&self, peripheral_uuid: &str, characteristic_uuid: &str) -> Result<Vec<u8>> {
+        self.update(|state, events| {
+            let characteristic = state
+                .connected(peripheral_uuid, events)?
+                .characteristic(characteristic_uuid)?;
+            if !characteristic.properties.read || !characteristic.permissions.readable {
+                return Err(Error::OperationFailed(READ_NOT_PERMITTED.to_string()));
+            }
+            Ok(characteristic.value.clone().unwrap_or_default())
+        })
+    }
+
+    pub(crate) fn write_characteristic(
+        &self,
+        peripheral_uuid: &str,
+        characteristic_uuid: &str,
+        value: Vec<u8>,
+        options: WriteOptions,
+    ) -> Result<()> {
+        self.update(|state, events| {
+            let peripheral = state.connected(peripheral_uuid, events)?;
+            let write_type = if options.with_response {
+                WriteType::WithResponse
+            } else {
+                WriteType::WithoutResponse
+            };
+            let max_length = max_write_length(&peripheral.config, &write_type);
+
+            let characteristic = peripheral.characteristic(characteristic_uuid)?;
+            let supported = match write_type {
+                WriteType::WithResponse => characteristic.properties.write,
+                WriteType::WithoutResponse => characteristic.properties.write_without_response,
+            };
+            if !supported || !characteristic.permissions.writeable {
+                return Err(Error::OperationFailed("Write not permitted".to_string()));
+            }
+            if value.len() > max_length {
+                return Err(Error::InvalidInput(format!(
+                    "Value of {} bytes exceeds the maximum write length of {max_length} bytes",
+                    value.len()
+                )));
+            }
+            characteristic.value = Some(value);
+            Ok(())
+        })
+    }
+
+    pub(crate) fn set_characteristic_notifications(
+        &self,
+        peripheral_uuid: &str,
+        characteristic_uuid: &str,
+        enabled: bool,
+    ) -> Result<()> {
+        self.update(|state, events| {
+            let peripheral = state.connected(peripheral_uuid, events)?;
+            let characteristic = peripheral.characteristic(characteristic_uuid)?;
+            if !characteristic.properties.notify && !characteristic.properties.indicate {
+                return Err(Error::OperationFailed(
+                    "Characteristic does not support notifications".to_string(),
+                ));
+            }
+            let characteristic_uuid = characteristic.uuid.clone();
+
+            let key = expand_uuid(&characteristic_uuid);
+            if enabled {
+                peripheral.notifying.insert(key);
+            } else {
+                peripheral.notifying.remove(&key);
+            }
+            events.push(BluetoothEventPayload::CharacteristicSubscriptionChanged {
+                peripheral_uuid: peripheral.config.uuid.clone(),
+                characteristic_uuid,
+                is_notifying: enabled,
+            });
+            Ok(())
+        })
+    }
+
+    pub(crate) fn read_descriptor(&self, peripheral_uuid: &str, descriptor_uuid: &str) -> Result<Vec<u8>> {
+        self.update(|state, events| {
+            let descriptor = state.connected(peripheral_uuid, events)?.descriptor(descriptor_uuid)?;
+            Ok(descriptor.value.clone().unwrap_or_default())
+        })
+    }
+
+    pub(crate) fn write_descriptor(&self, peripheral_uuid: &str, descriptor_uuid: &str, value: Vec<u8>) -> Result<()> {
+        self.update(|state, events| {
+            let descriptor = state.connected(peripheral_uuid, events)?.descriptor(descriptor_uuid)?;
+            descriptor.value = Some(value);
+            Ok(())
+        })
+    }
+
+    pub(crate) fn peripheral_rssi(&self, peripheral_uuid: &str) -> Result<i32> {
+        self.update(|state, events| Ok(state.connected(peripheral_uuid, events)?.config.rssi))
+    }
+
+    pub(crate) fn maximum_write_length(&self, peripheral_uuid: &str, write_type: &WriteType) -> Result<usize> {
+        let mut state = self.state();
+        let peripheral = state.peripheral(peripheral_uuid)?;
+        if !peripheral.is_connected() {
+            return Err(Error::OperationFailed("Peripheral not connected".to_string()));
+        }
+        Ok(max_write_length(&peripheral.config, write_type))
+    }
+
+    pub(crate) fn start_peripheral_advertising(&self, advertising_data: AdvertisingData) -> Result<()> {
+        let mut state = self.state();
+        state.ensure_ready()?;
+        state.advertising = Some(advertising_data);
+        Ok(())
+    }
+
+    pub(crate) fn stop_peripheral_advertising(&self) {
+        self.state().advertising = None;
+    }
+
+    pub(crate) fn add_service(&self, service: PeripheralService) {
+        let mut state = self.state();
+        state
+            .local_services
+            .retain(|existing| !uuids_equal(&existing.uuid, &service.uuid));
+        // A published service starts without subscribers, even if it replaces one.
+        for characteristic in &service.characteristics {
+            let key = expand_uuid(&characteristic.uuid);
+            state.subscribers.remove(&key);
+            state.muted.remove(&key);
+        }
+        state.local_services.push(service);
+        state.forget_unpublished();
+    }
+
+    pub(crate) fn remove_service(&self, service_uuid: &str) -> Result<()> {
+        let mut state = self.state();
+        let count = state.local_services.len();
+        state
+            .local_services
+            .retain(|existing| !uuids_equal(&existing.uuid, service_uuid));
+        if state.local_services.len() == count {
+            return Err(Error::InvalidInput("Service not found".to_string()));
+        }
+        state.forget_unpublished();
+        Ok(())
+    }
+
+    pub(crate) fn remove_all_services(&self) {
+        let mut state = self.state();
+        state.local_services.clear();
+        state.forget_unpublished();
+    }
+
+    pub(crate) fn respond_to_request(&self, response: RequestResponse) -> Result<()> {
+        let mut state = self.state();
+        if !state.pending_requests.remove(&response.request_id) {
+            return Err(Error::InvalidInput("Request not found".to_string()));
+        }
+        state.responses.insert(response.request_id.clone(), response);
+        Ok(())
+    }
+
+    /// Stores the value of a published characteristic and notifies the subscribed
+    /// centrals, only those in `central_uuids` if given, unless its notifications
+    /// are turned off.
+    pub(crate) fn update_characteristic_value(
+        &self,
+        characteristic_uuid: &str,
+        value: Vec<u8>,
+        central_uuids: Option<Vec<String>>,
+    ) -> Result<()> {
+        let mut state = self.state();
+        let characteristic = state.local_characteristic(characteristic_uuid)?;
+        characteristic.value = Some(value.clone());
+        let characteristic_uuid = characteristic.uuid.clone();
+
+        let key = expand_uuid(&characteristic_uuid);
+        if state.muted.contains(&key) {
+            return Ok(());
+        }
+        let mut recipients: Vec<String> = state
+            .subscribers
+            .get(&key)
+            .into_iter()
+            .flatten()
+            .filter(|central| {
+                central_uuids
+                    .as_ref()
+                    .map_or(true, |listed| listed.iter().any(|uuid| uuid.eq_ignore_ascii_case(central)))
+            })
+            .cloned()
+            .collect();
+        recipients.sort();
+        for central in recipients {
+            state
+                .notifications
+                .entry(central)
+                .or_default()
+                .push((characteristic_uuid.clone(), value.clone()));
+        }
+        Ok(())
+    }
+
+    /// Turns the notifications of a published characteristic on or off. They are
+    /// on until turned off.
+    pub(crate) fn set_notify_value(&self, characteristic_uuid: &str, enabled: bool) -> Result<()> {
+        let mut state = self.state();
+        let characteristic = state.local_characteristic(characteristic_uuid)?;
+        if !characteristic.properties.notify && !characteristic.properties.indicate {
+            return Err(Error::OperationFailed(
+                "Characteristic does not support notifications".to_string(),
+            ));
+        }
+        let key = expand_uuid(&characteristic.uuid);
+        if enabled {
+            state.muted.remove(&key);
+        } else {
+            state.muted.insert(key);
+        }
+        Ok(())
+    }
+}
+
+fn max_write_length(peripheral: &VirtualPeripheral, write_type: &WriteType) -> usize {
+    match write_type {
+        WriteType::WithResponse => MAX_ATTRIBUTE_LENGTH,
+        WriteType::WithoutResponse => peripheral.mtu.saturating_sub(ATT_HEADER_LENGTH),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Subscription;
+
+    use super::*;
+
+    const HEART_RATE: &str = "180D";
+    const MEASUREMENT: &str = "2A37";
+    const CONTROL_POINT: &str = "2A39";
+
+    type Recorded = Arc<Mutex<Vec<BluetoothEventPayload>>>;
+
+    fn simulator() -> (Simulator, Subscription, Recorded) {
+        let events = Arc::new(EventBus::default());
+        let recorded = Recorded::default();
+        let sink = recorded.clone();
+        let subscription = events.listen(Arc::new(move |event: &BluetoothEvent| {
+            sink.lock().unwrap().push(event.payload.clone());
+        }));
+        (Simulator::new(events), subscription, recorded)
+    }
+
+    fn take(recorded: &Recorded) -> Vec<BluetoothEventPayload> {
+        std::mem::take(&mut *recorded.lock().unwrap())
+    }
+
+    fn characteristic(uuid: &str, properties: CharacteristicProperties) -> PeripheralCharacteristic {
+        PeripheralCharacteristic {
+            uuid: uuid.to_string(),
+            properties,
+            permissions: CharacteristicPermissions {
+                readable: true,
+                writeable: true,
+                ..Default::default()
+            },
+            value: None,
+            descriptors: vec![],
+        }
+    }
+
+    fn heart_rate_monitor(uuid: &str) -> VirtualPeripheral {
+        let advertising = AdvertisingData {
+            local_name: Some("Polar H10".to_string()),
+            service_uuids: vec![HEART_RATE.to_string()],
+            manufacturer_data: None,
+            service_data: None,
+            tx_power_level: None,
+            is_connectable: true,
+        };
+        VirtualPeripheral::new(uuid, advertising)
+            .with_mtu(23)
+            .with_service(PeripheralService {
+                uuid: HEART_RATE.to_string(),
+                is_primary: true,
+                characteristics: vec![
+                    characteristic(
+                        MEASUREMENT,
+                        CharacteristicProperties {
+                            read: true,
+                            notify: true,
+                            ..Default::default()
+                        },
+                    ),
+                    characteristic(
+                        CONTROL_POINT,
+                        CharacteristicProperties {
+                            write: true,
+                            write_without_response: true,
+                            ..Default::default()
+                        },
+                    ),
+                ],
+            })
+    }
+
+    fn scan_for(services: &[&str]) -> ScanOptions {
+        ScanOptions {
+            service_uuids: services.iter().map(|uuid| uuid.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn discovered(events: &[BluetoothEventPayload]) -> Vec<String> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                BluetoothEventPayload::PeripheralDiscovered(peripheral) => Some(peripheral.uuid.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// A simulator with a heart rate monitor that has been discovered and connected.
+    fn connected() -> (Simulator, Subscription, Recorded) {
+        let (simulator, subscription, recorded) = simulator();
+        simulator.add_peripheral(heart_rate_monitor("A"));
+        simulator.start_central_scan(scan_for(&[])).unwrap();
+        simulator.stop_central_scan().unwrap();
+        simulator.connect_peripheral("A").unwrap();
+        take(&recorded);
+        (simulator, subscription, recorded)
+    }
+
+    #[test]
+    fn scans_report_matching_peripherals_once() {
+        let (simulator, _subscription, recorded) = simulator();
+        simulator.add_peripheral(heart_rate_monitor("A"));
+        simulator.start_central_scan(scan_for(&["180F"])).unwrap();
+        assert!(discovered(&take(&recorded)).is_empty());
+        simulator.stop_central_scan().unwrap();
+
+        simulator.start_central_scan(scan_for(&[HEART_RATE])).unwrap();
+        simulator.add_peripheral(heart_rate_monitor("B"));
+        simulator.set_rssi("A", -40).unwrap();
+        assert_eq!(discovered(&take(&recorded)), ["A", "B"]);
+
+        simulator.set_in_range("B", false).unwrap();
+        simulator.stop_central_scan().unwrap();
+        simulator.start_central_scan(scan_for(&[])).unwrap();
+        assert_eq!(discovered(&take(&recorded)), ["A"]);
+        assert!(matches!(simulator.stop_central_scan(), Ok(())));
+        assert!(simulator.stop_central_scan().is_err());
+    }
+
+    #[test]
+    fn only_discovered_peripherals_connect() {
+        let (simulator, _subscription, recorded) = simulator();
+        simulator.add_peripheral(heart_rate_monitor("A"));
+        assert!(simulator.connect_peripheral("A").is_err());
+
+        simulator.start_central_scan(scan_for(&[])).unwrap();
+        simulator.connect_peripheral("A").unwrap();
+        assert!(take(&recorded)
+            .iter()
+            .any(|event| matches!(event, BluetoothEventPayload::PeripheralConnected { .. })));
+        assert_eq!(simulator.connected_peripherals().len(), 1);
+    }
+
+    #[test]
+    fn held_connections_complete_once_released_unless_cancelled() {
+        let (simulator, _subscription, recorded) = simulator();
+        simulator.add_peripheral(heart_rate_monitor("A"));
+        simulator.start_central_scan(scan_for(&[])).unwrap();
+        simulator.hold_connections("A", true).unwrap();
+        take(&recorded);
+
+        simulator.connect_peripheral("A").unwrap();
+        assert!(take(&recorded).is_empty());
+        assert!(simulator.connected_peripherals().is_empty());
+        simulator.disconnect_peripheral("A").unwrap();
+        simulator.hold_connections("A", false).unwrap();
+        assert!(take(&recorded).is_empty());
+
+        simulator.hold_connections("A", true).unwrap();
+        simulator.connect_peripheral("A").unwrap();
+        simulator.hold_connections("A", false).unwrap();
+        assert!(matches!(take(&recorded)[..], [BluetoothEventPayload::PeripheralConnected { .. }]));
+        assert_eq!(simulator.connected_peripherals().len(), 1);
+    }
+
+    #[test]
+    fn reads_writes_and_notifications_reach_the_gatt_database() {
+        let (simulator, _subscription, recorded) = connected();
+        simulator.notify("A", MEASUREMENT, vec![0x00, 72]).unwrap();
+        assert_eq!(simulator.read_characteristic("A", "2a37").unwrap(), [0x00, 72]);
+        assert!(take(&recorded).is_empty(), "nobody subscribed to notifications yet");
+
+        simulator.set_characteristic_notifications("A", MEASUREMENT, true).unwrap();
+        simulator.notify("A", MEASUREMENT, vec![0x00, 80]).unwrap();
+        let events = take(&recorded);
+        assert!(matches!(
+            events.last(),
+            Some(BluetoothEventPayload::CharacteristicValueUpdated { value, .. }) if value == &[0x00, 80]
+        ));
+
+        simulator
+            .write_characteristic("A", CONTROL_POINT, vec![1], WriteOptions { with_response: true })
+            .unwrap();
+        assert_eq!(simulator.characteristic_value("A", CONTROL_POINT).unwrap(), Some(vec![1]));
+        assert!(matches!(
+            simulator.read_characteristic("A", CONTROL_POINT),
+            Err(Error::OperationFailed(message)) if message == READ_NOT_PERMITTED
+        ));
+        assert!(matches!(
+            simulator.read_characteristic("A", "2A19"),
+            Err(Error::InvalidInput(message)) if message == CHARACTERISTIC_NOT_FOUND
+        ));
+    }
+
+    #[test]
+    fn writes_without_response_fit_in_the_mtu() {
+        let (simulator, _subscription, _recorded) = connected();
+        let without_response = WriteOptions { with_response: false };
+        assert_eq!(
+            simulator.maximum_write_length("A", &WriteType::WithoutResponse).unwrap(),
+            20
+        );
+        simulator
+            .write_characteristic("A", CONTROL_POINT, vec![0; 20], without_response.clone())
+            .unwrap();
+        assert!(matches!(
+            simulator.write_characteristic("A", CONTROL_POINT, vec![0; 21], without_response),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn scripted_disconnects_fail_the_next_operation() {
+        let (simulator, _subscription, recorded) = connected();
+        simulator.script_disconnect("A", 1, Some("Link lost".to_string())).unwrap();
+        simulator.read_characteristic("A", MEASUREMENT).unwrap();
+        assert!(matches!(
+            simulator.read_characteristic("A", MEASUREMENT),
+            Err(Error::OperationFailed(message)) if message == "Peripheral disconnected"
+        ));
+        assert!(matches!(
+            take(&recorded).as_slice(),
+            [BluetoothEventPayload::PeripheralDisconnected { error: Some(error), .. }] if error == "Link lost"
+        ));
+        assert!(simulator.connected_peripherals().is_empty());
+    }
+
+    #[test]
+    fn turning_bluetooth_off_drops_connections_and_scans() {
+        let (simulator, _subscription, recorded) = connected();
+        simulator.start_central_scan(scan_for(&[])).unwrap();
+        take(&recorded);
+
+        simulator.set_bluetooth_state(BluetoothState::PoweredOff);
+        let events = take(&recorded);
+        assert!(matches!(events[0], BluetoothEventPayload::PeripheralDisconnected { .. }));
+        assert!(matches!(
+            events[1],
+            BluetoothEventPayload::StateChanged {
+                state: BluetoothState::PoweredOff
+            }
+        ));
+        assert!(simulator.stop_central_scan().is_err());
+        assert!(simulator.start_central_scan(scan_for(&[])).is_err());
+        assert!(!simulator.is_bluetooth_enabled());
+    }
+
+    #[test]
+    fn central_requests_are_answered_by_the_app() {
+        let (simulator, _subscription, recorded) = simulator();
+        simulator.add_service(heart_rate_monitor("local").services.remove(0));
+        let request_id = simulator.central_read("central", MEASUREMENT, 0).unwrap();
+        assert!(matches!(
+            take(&recorded).as_slice(),
+            [BluetoothEventPayload::ReadRequestReceived(request)] if request.request_id == request_id
+        ));
+        assert!(simulator.take_response(&request_id).is_none());
+
+        simulator
+            .respond_to_request(RequestResponse {
+                request_id: request_id.clone(),
+                result: RequestResult::Success,
+                value: Some(vec![72]),
+            })
+            .unwrap();
+        assert_eq!(simulator.take_response(&request_id).unwrap().value, Some(vec![72]));
+        assert!(simulator
+            .respond_to_request(RequestResponse {
+                request_id,
+                result: RequestResult::Success,
+                value: None,
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn updates_notify_subscribed_and_listed_centrals() {
+        let (simulator, _subscription, recorded) = simulator();
+        simulator.add_service(heart_rate_monitor("local").services.remove(0));
+        assert!(simulator.central_set_subscribed("a", CONTROL_POINT, true).is_err());
+        simulator.central_set_subscribed("a", MEASUREMENT, true).unwrap();
+        simulator.central_set_subscribed("B", MEASUREMENT, true).unwrap();
+        assert_eq!(take(&recorded).len(), 2);
+
+        simulator.update_characteristic_value(MEASUREMENT, vec![1], None).unwrap();
+        simulator
+            .update_characteristic_value(MEASUREMENT, vec![2], Some(vec!["b".to_string(), "C".to_string()]))
+            .unwrap();
+        simulator.update_characteristic_value(MEASUREMENT, vec![3], Some(vec![])).unwrap();
+        assert_eq!(simulator.take_notifications("A"), [(MEASUREMENT.to_string(), vec![1])]);
+        assert_eq!(
+            simulator.take_notifications("b"),
+            [(MEASUREMENT.to_string(), vec![1]), (MEASUREMENT.to_string(), vec![2])]
+        );
+        assert!(simulator.take_notifications("C").is_empty());
+        assert_eq!(simulator.published_services()[0].characteristics[0].value, Some(vec![3]));
+
+        // Turned off, updates only change the value.
+        simulator.set_notify_value(MEASUREMENT, false).unwrap();
+        simulator.update_characteristic_value(MEASUREMENT, vec![4], None).unwrap();
+        assert!(simulator.take_notifications("A").is_empty());
+        simulator.set_notify_value("00002a37-0000-1000-8000-00805f9b34fb", true).unwrap();
+        simulator.central_set_subscribed("B", MEASUREMENT, false).unwrap();
+        simulator.update_characteristic_value(MEASUREMENT, vec![5], None).unwrap();
+        assert_eq!(simulator.take_notifications("A"), [(MEASUREMENT.to_string(), vec![5])]);
+        assert!(simulator.take_notifications("B").is_empty());
+        assert!(simulator.set_notify_value(CONTROL_POINT, false).is_err());
+
+        // Republishing the service starts without subscribers.
+        simulator.add_service(heart_rate_monitor("local").services.remove(0));
+        simulator.update_characteristic_value(MEASUREMENT, vec![6], None).unwrap();
+        assert!(simulator.take_notifications("A").is_empty());
+    }
+}
@@ -0,0 +1,415 @@
+//! Chunked characteristic writes for payloads larger than a single ATT write.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::Runtime;
+
+use crate::{Error, Result, WriteOptions, WriteType};
+
+/// How a chunked transfer is written.
+///
+/// Writes without response aren't paced here: each one returns once
+/// CoreBluetooth has room for it in its transmit queue, so the link sets the
+/// rate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TransferOptions {
+    pub write_type: WriteType,
+    /// Upper bound for each chunk. The negotiated maximum write length is used
+    /// when unset or smaller.
+    pub chunk_size: Option<usize>,
+    pub framing: Framing,
+    /// Byte offset into the framed payload to start from, as reported by a
+    /// previous interrupted or cancelled transfer.
+    pub resume_from: usize,
+    /// How many times a failed chunk is retried before the transfer is aborted.
+    /// A write that timed out may still have reached the peripheral, so a
+    /// retried chunk can be delivered twice.
+    pub max_retries: u32,
+}
+
+/// Optional envelope wrapped around the payload: a little-endian length prefix
+/// before it and a little-endian checksum of everything preceding the checksum.
+///
+/// Chunks carry no sequence numbers, so a chunk retried after a timeout can
+/// reach the receiver twice. The length prefix and checksum let it reject the
+/// frame when that happens.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Framing {
+    pub length_prefix: Option<LengthPrefix>,
+    pub checksum: Option<Checksum>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LengthPrefix {
+    U16,
+    U32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Checksum {
+    /// CRC-16/CCITT-FALSE (polynomial 0x1021, initial value 0xFFFF).
+    Crc16Ccitt,
+    /// CRC-32 as used by zlib and Ethernet.
+    Crc32,
+}
+
+impl LengthPrefix {
+    fn len(self) -> usize {
+        match self {
+            LengthPrefix::U16 => 2,
+            LengthPrefix::U32 => 4,
+        }
+    }
+}
+
+impl Checksum {
+    fn len(self) -> usize {
+        match self {
+            Checksum::Crc16Ccitt => 2,
+            Checksum::Crc32 => 4,
+        }
+    }
+
+    fn compute(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Checksum::Crc16Ccitt => crc16_ccitt(data).to_le_bytes().to_vec(),
+            Checksum::Crc32 => crc32(data).to_le_bytes().to_vec(),
+        }
+    }
+}
+
+impl Framing {
+    /// Wraps `payload` in this framing.
+    pub fn encode(&self, payload: &[u8]) -> Result<Vec<u8>> {
+        let mut frame = Vec::with_capacity(payload.len() + 8);
+        match self.length_prefix {
+            Some(LengthPrefix::U16) => {
+                let len = u16::try_from(payload.len()).map_err(|_| {
+                    Error::InvalidInput(format!("Payload of {} bytes does not fit a 16-bit length prefix", payload.len()))
+                })?;
+                frame.extend_from_slice(&len.to_le_bytes());
+            }
+            Some(LengthPrefix::U32) => {
+                let len = u32::try_from(payload.len()).map_err(|_| {
+                    Error::InvalidInput(format!("Payload of {} bytes does not fit a 32-bit length prefix", payload.len()))
+                })?;
+                frame.extend_from_slice(&len.to_le_bytes());
+            }
+            None => {}
+        }
+        frame.extend_from_slice(payload);
+        if let Some(checksum) = self.checksum {
+            let crc = checksum.compute(&frame);
+            frame.extend_from_slice(&crc);
+        }
+        Ok(frame)
+    }
+
+    /// Validates a complete frame and returns the payload inside it.
+    pub fn decode(&self, frame: &[u8]) -> Result<Vec<u8>> {
+        let body = match self.checksum {
+            Some(checksum) => {
+                let split = frame
+                    .len()
+                    .checked_sub(checksum.len())
+                    .ok_or_else(|| Error::MalformedValue("frame is shorter than its checksum".into()))?;
+                let (body, crc) = frame.split_at(split);
+                if checksum.compute(body) != crc {
+                    return Err(Error::MalformedValue("frame checksum mismatch".into()));
+                }
+                body
+            }
+            None => frame,
+        };
+
+        let Some(prefix) = self.length_prefix else {
+            return Ok(body.to_vec());
+        };
+        if body.len() < prefix.len() {
+            return Err(Error::MalformedValue("frame is shorter than its length prefix".into()));
+        }
+        let (len, payload) = body.split_at(prefix.len());
+        let expected = match prefix {
+            LengthPrefix::U16 => u16::from_le_bytes([len[0], len[1]]) as usize,
+            LengthPrefix::U32 => u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize,
+        };
+        if expected != payload.len() {
+            return Err(Error::MalformedValue(format!(
+                "frame announces {expected} bytes but carries {}",
+                payload.len()
+            )));
+        }
+        Ok(payload.to_vec())
+    }
+}
+
+/// CRC-16/CCITT-FALSE.
+pub fn crc16_ccitt(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, &byte| {
+        (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// CRC-32 (IEEE 802.3, reflected).
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(0xFFFF_FFFF, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// Cancels a running transfer from another thread. Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Reported after every chunk that was written.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferProgress {
+    /// Bytes of the framed payload written so far, including `resume_from`.
+    pub bytes_sent: usize,
+    pub total_bytes: usize,
+    pub chunks_sent: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferReport {
+    pub bytes_sent: usize,
+    pub total_bytes: usize,
+    /// `false` when the transfer was cancelled; pass `bytes_sent` as
+    /// `resume_from` to continue it.
+    pub completed: bool,
+}
+
+/// Cancel tokens of transfers started from the frontend, keyed by the id JS chose.
+#[derive(Default)]
+pub(crate) struct JsTransfers(Mutex<HashMap<String, CancelToken>>);
+
+impl JsTransfers {
+    pub(crate) fn insert(&self, id: String) -> Result<CancelToken> {
+        let mut transfers = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if transfers.contains_key(&id) {
+            return Err(Error::InvalidInput(format!("Transfer {id} is already running")));
+        }
+        let token = CancelToken::new();
+        transfers.insert(id, token.clone());
+        Ok(token)
+    }
+
+    pub(crate) fn remove(&self, id: &str) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).remove(id);
+    }
+
+    pub(crate) fn cancel(&self, id: &str) -> Result<()> {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+            .map(CancelToken::cancel)
+            .ok_or_else(|| Error::InvalidInput(format!("Transfer {id} not found")))
+    }
+}
+
+impl<R: Runtime> crate::Bluetooth<R> {
+    /// Writes `payload` to a characteristic in chunks no larger than the
+    /// negotiated maximum write length, calling `on_progress` after each chunk.
+    ///
+    /// Blocks until the transfer finishes, fails or `cancel` is triggered. A
+    /// failed chunk is retried `max_retries` times before the transfer fails with
+    /// [`Error::TransferInterrupted`], which carries the offset to resume from.
+    pub fn write_chunked<F>(
+        &self,
+        peripheral_uuid: String,
+        characteristic_uuid: String,
+        payload: &[u8],
+        options: TransferOptions,
+        cancel: &CancelToken,
+        mut on_progress: F,
+    ) -> Result<TransferReport>
+    where
+        F: FnMut(TransferProgress),
+    {
+        let frame = options.framing.encode(payload)?;
+        let total_bytes = frame.len();
+        if options.resume_from > total_bytes {
+            return Err(Error::InvalidInput(format!(
+                "Cannot resume at byte {} of a {total_bytes}-byte transfer",
+                options.resume_from
+            )));
+        }
+
+        let max_length = self.get_maximum_write_length(peripheral_uuid.clone(), options.write_type.clone())?;
+        let chunk_size = options.chunk_size.map_or(max_length, |size| size.min(max_length));
+        if chunk_size == 0 {
+            return Err(Error::InvalidInput("Chunk size must be greater than zero".into()));
+        }
+
+        let with_response = options.write_type == WriteType::WithResponse;
+        let mut bytes_sent = options.resume_from;
+
+        for (index, chunk) in frame[bytes_sent..].chunks(chunk_size).enumerate() {
+            if cancel.is_cancelled() {
+                return Ok(TransferReport {
+                    bytes_sent,
+                    total_bytes,
+                    completed: false,
+                });
+            }
+
+            let mut attempt = 0;
+            loop {
+                match self.write_characteristic(
+                    peripheral_uuid.clone(),
+                    characteristic_uuid.clone(),
+                    chunk.to_vec(),
+                    WriteOptions { with_response },
+                ) {
+                    Ok(()) => break,
+                    Err(_) if attempt < options.max_retries => attempt += 1,
+                    Err(e) => {
+                        return Err(Error::TransferInterrupted {
+                            bytes_sent,
+                            reason: e.to_string(),
+                        })
+                    }
+                }
+            }
+
+            bytes_sent += chunk.len();
+            on_progress(TransferProgress {
+                bytes_sent,
+                total_bytes,
+                chunks_sent: index + 1,
+            });
+        }
+
+        Ok(TransferReport {
+            bytes_sent,
+            total_bytes,
+            completed: true,
+        })
+    }
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        Self {
+            write_type: WriteType::WithResponse,
+            chunk_size: None,
+            framing: Framing::default(),
+            resume_from: 0,
+            max_retries: 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_match_their_check_values() {
+        assert_eq!(crc16_ccitt(b"123456789"), 0x29B1);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc16_ccitt(b""), 0xFFFF);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn framing_round_trips() {
+        let payload = b"123456789";
+        for length_prefix in [None, Some(LengthPrefix::U16), Some(LengthPrefix::U32)] {
+            for checksum in [None, Some(Checksum::Crc16Ccitt), Some(Checksum::Crc32)] {
+                let framing = Framing {
+                    length_prefix,
+                    checksum,
+                };
+                let frame = framing.encode(payload).unwrap();
+                let overhead = length_prefix.map_or(0, LengthPrefix::len) + checksum.map_or(0, Checksum::len);
+                assert_eq!(frame.len(), payload.len() + overhead);
+                assert_eq!(framing.decode(&frame).unwrap(), payload);
+            }
+        }
+    }
+
+    #[test]
+    fn framing_layout_is_little_endian() {
+        let framing = Framing {
+            length_prefix: Some(LengthPrefix::U16),
+            checksum: Some(Checksum::Crc16Ccitt),
+        };
+        let frame = framing.encode(b"ab").unwrap();
+        let crc = crc16_ccitt(&[0x02, 0x00, b'a', b'b']).to_le_bytes();
+        assert_eq!(frame, [0x02, 0x00, b'a', b'b', crc[0], crc[1]]);
+    }
+
+    #[test]
+    fn framing_rejects_corrupt_frames() {
+        let framing = Framing {
+            length_prefix: Some(LengthPrefix::U32),
+            checksum: Some(Checksum::Crc32),
+        };
+        let mut frame = framing.encode(b"payload").unwrap();
+        frame[5] ^= 0x01;
+        assert!(matches!(framing.decode(&frame), Err(Error::MalformedValue(_))));
+        assert!(matches!(framing.decode(&[0x00, 0x01]), Err(Error::MalformedValue(_))));
+
+        // A chunk delivered twice makes the frame longer than announced.
+        let framing = Framing {
+            length_prefix: Some(LengthPrefix::U16),
+            checksum: None,
+        };
+        let mut frame = framing.encode(b"abcd").unwrap();
+        frame.extend_from_slice(b"cd");
+        assert!(matches!(framing.decode(&frame), Err(Error::MalformedValue(_))));
+    }
+
+    #[test]
+    fn oversized_payloads_are_rejected() {
+        let framing = Framing {
+            length_prefix: Some(LengthPrefix::U16),
+            checksum: None,
+        };
+        assert!(matches!(
+            framing.encode(&vec![0; usize::from(u16::MAX) + 1]),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+}
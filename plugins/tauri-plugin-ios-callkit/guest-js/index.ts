@@ -1,5 +1,23 @@
 import { invoke } from '@tauri-apps/api/core'
 
+// Errors
+/** Stable machine-readable code of a rejected command */
+export type ErrorCode =
+  | 'notAvailable'
+  | 'permissionDenied'
+  | 'invalidInput'
+  | 'operationFailed'
+  | 'io'
+  | 'pluginInvoke'
+  | 'internal'
+
+/** Shape of the error every command rejects with */
+export interface PluginError {
+  code: ErrorCode
+  message: string
+  details?: Record<string, unknown> | null
+}
+
 // Enums
 export enum HandleType {
   Generic = 'generic',
@@ -1,6 +1,49 @@
-const COMMANDS: &[&str] = &["ping"];
+const COMMANDS: &[&str] = &[
+    "pick_file",
+    "pick_multiple_files",
+    "pick_folder",
+    "save_file",
+    "open_in_files",
+    "import_from_files",
+    "export_to_files",
+    "list_documents",
+    "read_file",
+    "write_file",
+    "delete_file",
+    "move_file",
+    "copy_file",
+    "create_folder",
+    "get_file_info",
+    "share_file",
+    "preview_file",
+    "get_cloud_status",
+    "download_from_cloud",
+    "cancel_cloud_download",
+    "evict_from_local",
+    "evict_all_from_local",
+    "list_not_downloaded",
+    "start_monitoring",
+    "stop_monitoring",
+    "read_file_range",
+    "read_file_chunked",
+    "cancel_read",
+    "append_file",
+    "open_file_writer",
+    "write_file_chunk",
+    "finish_file_writer",
+    "abort_file_writer",
+    "hash_file",
+    "get_space_info",
+    "get_file_permissions",
+    "create_bookmark",
+    "resolve_bookmark",
+    "start_accessing_resource",
+    "stop_accessing_resource",
+];
 
 fn main() {
+  tauri_ios_plugin_shared::build::verify_commands(COMMANDS);
+
   tauri_plugin::Builder::new(COMMANDS)
     .android_path("android")
     .ios_path("ios")
@@ -1,4 +1,22 @@
-import { invoke } from '@tauri-apps/api/core'
+import { Channel, invoke } from '@tauri-apps/api/core'
+
+// Errors
+/** Stable machine-readable code of a rejected command */
+export type ErrorCode =
+  | 'notAvailable'
+  | 'permissionDenied'
+  | 'invalidInput'
+  | 'operationFailed'
+  | 'io'
+  | 'pluginInvoke'
+  | 'internal'
+
+/** Shape of the error every command rejects with */
+export interface PluginError {
+  code: ErrorCode
+  message: string
+  details?: Record<string, unknown> | null
+}
 
 // Enums
 export enum CloudStatus {
@@ -139,12 +157,51 @@ export interface CloudDownloadProgress {
   downloadedBytes: number
   totalBytes: number
   status: CloudDownloadStatus
+  /** Why the item failed to download */
+  error?: string | null
+}
+
+/** Progress of a download of several items, reported whenever one of them changes */
+export interface CloudBatchProgress {
+  /** The item that changed */
+  item: CloudDownloadProgress
+  /** From 0 to 1; finished items count as done, whether they completed or not */
+  progress: number
+  downloadedBytes: number
+  totalBytes: number
+  completedItems: number
+  failedItems: number
+  totalItems: number
+  status: CloudDownloadStatus
+}
+
+/** Handle to a running iCloud download */
+export interface CloudDownload {
+  id: number
+  /** Stops the items that are still downloading and removes what was downloaded of them */
+  cancel: () => Promise<void>
+}
+
+/** An iCloud item that has no up-to-date local copy */
+export interface CloudItem {
+  fileUrl: string
+  name: string
+  size?: number | null
+  cloudStatus: CloudStatus
+}
+
+export interface CloudItemResult {
+  fileUrl: string
+  error?: string | null
 }
 
 export interface MonitoringOptions {
   directoryUrls: string[]
   recursive?: boolean
+  /** Kinds of change to report; every kind when omitted or empty */
   events?: MonitoringEvent[]
+  /** How long changes are collected before they are reported, in milliseconds (default 100) */
+  debounceMs?: number
 }
 
 export interface FileChange {
@@ -154,11 +211,50 @@ export interface FileChange {
   timestamp: string
 }
 
+/** Part of a file; `data` is base64 */
+export interface FileChunk {
+  offset: number
+  data: string
+  totalSize: number
+  eof: boolean
+}
+
+export interface ChunkedReadOptions {
+  offset?: number
+  /** Bytes to read; the rest of the file when omitted */
+  length?: number
+  chunkSize?: number
+  onChunk?: (chunk: FileChunk) => void
+  signal?: AbortSignal
+}
+
+export interface ReadReport {
+  bytesRead: number
+  totalSize: number
+  /** `false` when the read was aborted; continue at `offset + bytesRead` */
+  completed: boolean
+}
+
+export interface FileWriterOptions {
+  /** Fail instead of replacing an existing file */
+  createNew?: boolean
+}
+
+/** Stops a directory monitor */
+export type UnlistenFn = () => Promise<void>
+
 export interface SecurityScopedResource {
   url: string
   bookmarkData: string
 }
 
+export interface ResolvedBookmark {
+  url: string
+  /** The item moved; store `bookmarkData` in place of the old bookmark */
+  isStale: boolean
+  bookmarkData: string
+}
+
 export interface FilePermissions {
   readable: boolean
   writable: boolean
@@ -215,6 +311,93 @@ export async function writeFile(url: string, data: FileData): Promise<void> {
   return await invoke('plugin:ios-files-v2|write_file', { url, data })
 }
 
+/** Reads at most `length` bytes starting at `offset` */
+export async function readFileRange(url: string, offset: number, length: number): Promise<FileChunk> {
+  return await invoke('plugin:ios-files-v2|read_file_range', { url, offset, length })
+}
+
+let nextReadId = 0
+
+/** Streams a file to `onChunk` without loading it whole */
+export async function readFileChunked(url: string, options: ChunkedReadOptions = {}): Promise<ReadReport> {
+  const { onChunk, signal, ...readOptions } = options
+  signal?.throwIfAborted()
+  const readId = `read-${nextReadId++}`
+  const channel = new Channel<FileChunk>()
+  if (onChunk) channel.onmessage = onChunk
+
+  const cancel = () => {
+    void invoke('plugin:ios-files-v2|cancel_read', { readId }).catch(() => {})
+  }
+  signal?.addEventListener('abort', cancel, { once: true })
+  try {
+    return await invoke<ReadReport>('plugin:ios-files-v2|read_file_chunked', {
+      url,
+      options: readOptions,
+      readId,
+      onChunk: channel,
+    })
+  } finally {
+    signal?.removeEventListener('abort', cancel)
+  }
+}
+
+/** Appends to a file, creating it if needed, and resolves to its new size */
+export async function appendFile(url: string, data: FileData): Promise<number> {
+  return await invoke('plugin:ios-files-v2|append_file', { url, data })
+}
+
+/** Opens a writer whose data replaces the file only once it is finished */
+export async function openFileWriter(url: string, options: FileWriterOptions = {}): Promise<number> {
+  return await invoke('plugin:ios-files-v2|open_file_writer', { url, options })
+}
+
+/** Appends a chunk and resolves to the number of bytes written so far */
+export async function writeFileChunk(id: number, data: FileData): Promise<number> {
+  return await invoke('plugin:ios-files-v2|write_file_chunk', { id, data })
+}
+
+/** Moves the written data into place and resolves to the file size */
+export async function finishFileWriter(id: number): Promise<number> {
+  return await invoke('plugin:ios-files-v2|finish_file_writer', { id })
+}
+
+export async function abortFileWriter(id: number): Promise<void> {
+  return await invoke('plugin:ios-files-v2|abort_file_writer', { id })
+}
+
+/** SHA-256 of a file as lowercase hex, computed without sending the file to the webview */
+export async function hashFile(url: string): Promise<string> {
+  return await invoke('plugin:ios-files-v2|hash_file', { url })
+}
+
+/** Capacity of the volume holding `url`, or of the documents folder */
+export async function getSpaceInfo(url?: string): Promise<SpaceInfo> {
+  return await invoke('plugin:ios-files-v2|get_space_info', { url })
+}
+
+export async function getFilePermissions(url: string): Promise<FilePermissions> {
+  return await invoke('plugin:ios-files-v2|get_file_permissions', { url })
+}
+
+/** Creates a bookmark that reopens a picked file or folder in later launches */
+export async function createBookmark(url: string): Promise<SecurityScopedResource> {
+  return await invoke('plugin:ios-files-v2|create_bookmark', { url })
+}
+
+export async function resolveBookmark(bookmarkData: string): Promise<ResolvedBookmark> {
+  return await invoke('plugin:ios-files-v2|resolve_bookmark', { bookmarkData })
+}
+
+/** Starts accessing a picked or bookmarked item; balance each granted call with `stopAccessingResource` */
+export async function startAccessingResource(url: string): Promise<boolean> {
+  return await invoke('plugin:ios-files-v2|start_accessing_resource', { url })
+}
+
+export async function stopAccessingResource(url: string): Promise<void> {
+  return await invoke('plugin:ios-files-v2|stop_accessing_resource', { url })
+}
+
 export async function deleteFile(url: string): Promise<void> {
   return await invoke('plugin:ios-files-v2|delete_file', { url })
 }
@@ -247,18 +430,64 @@ export async function getCloudStatus(url: string): Promise<CloudStatus> {
   return await invoke('plugin:ios-files-v2|get_cloud_status', { url })
 }
 
-export async function downloadFromCloud(url: string): Promise<void> {
-  return await invoke('plugin:ios-files-v2|download_from_cloud', { url })
+/** Downloads an iCloud item, reporting its progress until it completed, failed or was cancelled */
+export async function downloadFromCloud(
+  url: string,
+  onProgress?: (progress: CloudDownloadProgress) => void
+): Promise<CloudDownload> {
+  return await downloadAllFromCloud([url], (progress) => onProgress?.(progress.item))
+}
+
+/** Downloads several iCloud items, reporting each change together with the progress of the whole batch */
+export async function downloadAllFromCloud(
+  urls: string[],
+  onProgress?: (progress: CloudBatchProgress) => void
+): Promise<CloudDownload> {
+  let finished = false
+  const channel = new Channel<CloudBatchProgress>()
+  channel.onmessage = (progress) => {
+    finished = [CloudDownloadStatus.Completed, CloudDownloadStatus.Failed, CloudDownloadStatus.Cancelled].includes(progress.status)
+    onProgress?.(progress)
+  }
+  const id = await invoke<number>('plugin:ios-files-v2|download_from_cloud', { urls, onProgress: channel })
+  return {
+    id,
+    cancel: async () => {
+      if (!finished) {
+        await cancelCloudDownload(id)
+      }
+    }
+  }
+}
+
+export async function cancelCloudDownload(id: number): Promise<void> {
+  return await invoke('plugin:ios-files-v2|cancel_cloud_download', { id })
 }
 
 export async function evictFromLocal(url: string): Promise<void> {
   return await invoke('plugin:ios-files-v2|evict_from_local', { url })
 }
 
-export async function startMonitoring(options: MonitoringOptions): Promise<void> {
-  return await invoke('plugin:ios-files-v2|start_monitoring', { options })
+/** Removes the local copies of several iCloud items; every item is tried */
+export async function evictAllFromLocal(urls: string[]): Promise<CloudItemResult[]> {
+  return await invoke('plugin:ios-files-v2|evict_all_from_local', { urls })
+}
+
+/** Lists the iCloud items in a folder that are not downloaded or still downloading */
+export async function listNotDownloaded(directoryUrl: string, recursive = false): Promise<CloudItem[]> {
+  return await invoke('plugin:ios-files-v2|list_not_downloaded', { directoryUrl, recursive })
+}
+
+/** Watches folders until the returned function is called; several monitors may run at once */
+export async function startMonitoring(options: MonitoringOptions, handler: (change: FileChange) => void): Promise<UnlistenFn> {
+  const onChange = new Channel<FileChange>()
+  onChange.onmessage = handler
+  const id = await invoke<number>('plugin:ios-files-v2|start_monitoring', { options, onChange })
+  return async () => {
+    await stopMonitoring(id)
+  }
 }
 
-export async function stopMonitoring(): Promise<void> {
-  return await invoke('plugin:ios-files-v2|stop_monitoring')
+export async function stopMonitoring(id: number): Promise<void> {
+  return await invoke('plugin:ios-files-v2|stop_monitoring', { id })
 }
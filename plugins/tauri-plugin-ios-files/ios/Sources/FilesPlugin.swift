@@ -3,6 +3,7 @@ import UIKit
 import UniformTypeIdentifiers
 import QuickLook
 import MobileCoreServices
+import CryptoKit
 
 // Request structures
 struct FilePickerOptionsData: Decodable {
@@ -68,9 +69,16 @@ struct PreviewOptionsData: Decodable {
 }
 
 struct MonitoringOptionsData: Decodable {
+    let id: UInt32
     let directoryUrls: [String]
     let recursive: Bool
-    let events: [String]
+    let handler: Channel
+}
+
+struct CloudDownloadArgs: Decodable {
+    let id: UInt32
+    let urls: [String]
+    let handler: Channel
 }
 
 class FilesPlugin: Plugin, UIDocumentPickerDelegate, UIDocumentInteractionControllerDelegate, QLPreviewControllerDataSource, QLPreviewControllerDelegate {
@@ -78,7 +86,18 @@ class FilesPlugin: Plugin, UIDocumentPickerDelegate, UIDocumentInteractionContro
     private var documentInteractionController: UIDocumentInteractionController?
     private var previewController: QLPreviewController?
     private var previewUrl: URL?
-    private var fileMonitor: FileMonitor?
+    private var fileMonitors: [UInt32: FileMonitor] = [:]
+    private let monitorQueue = DispatchQueue(label: "tauri.files.monitors")
+    /// Files opened for chunked reads, keyed by the reader id from Rust.
+    private var readers: [UInt32: FileHandle] = [:]
+    private let readerQueue = DispatchQueue(label: "tauri.files.readers")
+    private var cloudDownloads: [UInt32: CloudDownload] = [:]
+    private let downloadQueue = DispatchQueue(label: "tauri.files.downloads")
+    /// Security-scoped URLs from pickers and resolved bookmarks, keyed by their
+    /// string form. A URL loses its access rights when it is turned into a
+    /// string, so the originals are kept here.
+    private var scopedUrls: [String: URL] = [:]
+    private var accessCounts: [String: Int] = [:]
     
     @objc public func pickFile(_ invoke: Invoke) throws {
         let args = try invoke.parseArgs(FilePickerOptionsData.self)
@@ -461,6 +480,219 @@ class FilesPlugin: Plugin, UIDocumentPickerDelegate, UIDocumentInteractionContro
         }
     }
     
+    @objc public func readFileRange(_ invoke: Invoke) throws {
+        struct RangeArgs: Decodable {
+            let url: String
+            let offset: UInt64
+            let length: Int
+        }
+        
+        let args = try invoke.parseArgs(RangeArgs.self)
+        
+        guard let url = URL(string: args.url) else {
+            invoke.reject("Invalid URL")
+            return
+        }
+        
+        do {
+            let handle = try FileHandle(forReadingFrom: url)
+            defer { handle.closeFile() }
+            invoke.resolve(readRange(of: handle, offset: args.offset, length: args.length))
+        } catch {
+            invoke.reject("Failed to read file: \(error.localizedDescription)")
+        }
+    }
+    
+    private func readRange(of handle: FileHandle, offset: UInt64, length: Int) -> [String: Any] {
+        let totalSize = handle.seekToEndOfFile()
+        var data = Data()
+        if offset < totalSize {
+            handle.seek(toFileOffset: offset)
+            data = handle.readData(ofLength: length)
+        }
+        return [
+            "offset": Int(offset),
+            "data": data.base64EncodedString(),
+            "totalSize": Int(totalSize),
+            "eof": offset + UInt64(data.count) >= totalSize
+        ]
+    }
+    
+    @objc public func openReader(_ invoke: Invoke) throws {
+        struct OpenArgs: Decodable {
+            let id: UInt32
+            let url: String
+        }
+        
+        let args = try invoke.parseArgs(OpenArgs.self)
+        
+        guard let url = URL(string: args.url) else {
+            invoke.reject("Invalid URL")
+            return
+        }
+        
+        do {
+            let handle = try FileHandle(forReadingFrom: url)
+            readerQueue.sync {
+                readers[args.id] = handle
+            }
+            invoke.resolve()
+        } catch {
+            invoke.reject("Failed to open file: \(error.localizedDescription)")
+        }
+    }
+    
+    @objc public func readFromReader(_ invoke: Invoke) throws {
+        struct ReadArgs: Decodable {
+            let id: UInt32
+            let offset: UInt64
+            let length: Int
+        }
+        
+        let args = try invoke.parseArgs(ReadArgs.self)
+        
+        let response: [String: Any]? = readerQueue.sync {
+            guard let handle = readers[args.id] else { return nil }
+            return readRange(of: handle, offset: args.offset, length: args.length)
+        }
+        if let response = response {
+            invoke.resolve(response)
+        } else {
+            invoke.reject("No reader with id \(args.id)")
+        }
+    }
+    
+    @objc public func closeReader(_ invoke: Invoke) throws {
+        struct CloseArgs: Decodable {
+            let id: UInt32
+        }
+        
+        let args = try invoke.parseArgs(CloseArgs.self)
+        
+        let handle = readerQueue.sync {
+            readers.removeValue(forKey: args.id)
+        }
+        handle?.closeFile()
+        invoke.resolve()
+    }
+    
+    @objc public func appendToFile(_ invoke: Invoke) throws {
+        struct AppendArgs: Decodable {
+            let url: String
+            let data: String
+        }
+        
+        let args = try invoke.parseArgs(AppendArgs.self)
+        
+        guard let url = URL(string: args.url), let data = Data(base64Encoded: args.data) else {
+            invoke.reject("Invalid URL or data")
+            return
+        }
+        
+        do {
+            if !FileManager.default.fileExists(atPath: url.path) {
+                try Data().write(to: url)
+            }
+            let handle = try FileHandle(forWritingTo: url)
+            defer { handle.closeFile() }
+            let size = handle.seekToEndOfFile() + UInt64(data.count)
+            handle.write(data)
+            invoke.resolve(["size": Int(size)])
+        } catch {
+            invoke.reject("Failed to append to file: \(error.localizedDescription)")
+        }
+    }
+    
+    @objc public func beginWrite(_ invoke: Invoke) throws {
+        struct BeginArgs: Decodable {
+            let id: UInt32
+            let url: String
+            let createNew: Bool
+        }
+        
+        let args = try invoke.parseArgs(BeginArgs.self)
+        
+        guard let url = URL(string: args.url) else {
+            invoke.reject("Invalid URL")
+            return
+        }
+        
+        if args.createNew && FileManager.default.fileExists(atPath: url.path) {
+            invoke.reject("\(args.url) already exists")
+            return
+        }
+        
+        let tempUrl = url.deletingLastPathComponent()
+            .appendingPathComponent(".\(url.lastPathComponent).\(args.id).part")
+        do {
+            try Data().write(to: tempUrl)
+            invoke.resolve(["tempUrl": tempUrl.absoluteString])
+        } catch {
+            invoke.reject("Failed to create file: \(error.localizedDescription)")
+        }
+    }
+    
+    @objc public func commitWrite(_ invoke: Invoke) throws {
+        struct CommitArgs: Decodable {
+            let tempUrl: String
+            let url: String
+            let createNew: Bool
+        }
+        
+        let args = try invoke.parseArgs(CommitArgs.self)
+        
+        guard let tempUrl = URL(string: args.tempUrl), let url = URL(string: args.url) else {
+            invoke.reject("Invalid URLs")
+            return
+        }
+        
+        do {
+            if FileManager.default.fileExists(atPath: url.path) {
+                if args.createNew {
+                    invoke.reject("\(args.url) already exists")
+                    return
+                }
+                _ = try FileManager.default.replaceItemAt(url, withItemAt: tempUrl)
+            } else {
+                try FileManager.default.moveItem(at: tempUrl, to: url)
+            }
+            let attributes = try FileManager.default.attributesOfItem(atPath: url.path)
+            invoke.resolve(["size": (attributes[.size] as? NSNumber)?.intValue ?? 0])
+        } catch {
+            invoke.reject("Failed to save file: \(error.localizedDescription)")
+        }
+    }
+    
+    @objc public func hashFile(_ invoke: Invoke) throws {
+        struct HashArgs: Decodable {
+            let url: String
+        }
+        
+        let args = try invoke.parseArgs(HashArgs.self)
+        
+        guard let url = URL(string: args.url) else {
+            invoke.reject("Invalid URL")
+            return
+        }
+        
+        DispatchQueue.global(qos: .userInitiated).async {
+            do {
+                let handle = try FileHandle(forReadingFrom: url)
+                defer { handle.closeFile() }
+                var hasher = SHA256()
+                while true {
+                    let chunk = autoreleasepool { handle.readData(ofLength: 1024 * 1024) }
+                    if chunk.isEmpty { break }
+                    hasher.update(data: chunk)
+                }
+                let hash = hasher.finalize().map { String(format: "%02x", $0) }.joined()
+                invoke.resolve(["hash": hash])
+            } catch {
+                invoke.reject("Failed to hash file: \(error.localizedDescription)")
+            }
+        }
+    }
+    
     @objc public func deleteFile(_ invoke: Invoke) throws {
         struct DeleteArgs: Decodable {
             let url: String
@@ -701,24 +933,45 @@ class FilesPlugin: Plugin, UIDocumentPickerDelegate, UIDocumentInteractionContro
         }
     }
     
-    @objc public func downloadFromCloud(_ invoke: Invoke) throws {
-        struct DownloadArgs: Decodable {
-            let url: String
+    @objc public func startCloudDownload(_ invoke: Invoke) throws {
+        let args = try invoke.parseArgs(CloudDownloadArgs.self)
+        
+        var urls: [URL] = []
+        for urlString in args.urls {
+            guard let url = scopedUrl(urlString), url.isFileURL else {
+                invoke.reject("Invalid URL: \(urlString)")
+                return
+            }
+            urls.append(url)
         }
         
-        let args = try invoke.parseArgs(DownloadArgs.self)
+        let handler = args.handler
+        let download = CloudDownload(urls: urls, progressHandler: { progress in
+            handler.send(.dictionary(progress))
+        }, completion: { [weak self] in
+            self?.downloadQueue.sync {
+                _ = self?.cloudDownloads.removeValue(forKey: args.id)
+            }
+        })
         
-        guard let url = URL(string: args.url) else {
-            invoke.reject("Invalid URL")
-            return
+        downloadQueue.sync {
+            cloudDownloads[args.id] = download
         }
+        download.start()
+        invoke.resolve()
+    }
+    
+    @objc public func cancelCloudDownload(_ invoke: Invoke) throws {
+        struct CancelArgs: Decodable {
+            let id: UInt32
+        }
+        let args = try invoke.parseArgs(CancelArgs.self)
         
-        do {
-            try FileManager.default.startDownloadingUbiquitousItem(at: url)
-            invoke.resolve()
-        } catch {
-            invoke.reject("Failed to start download: \(error.localizedDescription)")
+        let download = downloadQueue.sync {
+            cloudDownloads.removeValue(forKey: args.id)
         }
+        download?.cancel()
+        invoke.resolve()
     }
     
     @objc public func evictFromLocal(_ invoke: Invoke) throws {
@@ -741,50 +994,298 @@ class FilesPlugin: Plugin, UIDocumentPickerDelegate, UIDocumentInteractionContro
         }
     }
     
-    @objc public func startMonitoring(_ invoke: Invoke) throws {
-        let args = try invoke.parseArgs(MonitoringOptionsData.self)
+    @objc public func listNotDownloaded(_ invoke: Invoke) throws {
+        struct ListArgs: Decodable {
+            let directoryUrl: String
+            let recursive: Bool
+        }
+        let args = try invoke.parseArgs(ListArgs.self)
+        
+        guard let directory = scopedUrl(args.directoryUrl), directory.isFileURL else {
+            invoke.reject("Invalid URL")
+            return
+        }
         
-        guard fileMonitor == nil else {
-            invoke.reject("Monitoring already active")
+        let keys: [URLResourceKey] = [
+            .isUbiquitousItemKey, .ubiquitousItemDownloadingStatusKey, .ubiquitousItemIsDownloadingKey,
+            .fileSizeKey, .isHiddenKey
+        ]
+        let items: [URL]
+        do {
+            if args.recursive {
+                guard let enumerator = FileManager.default.enumerator(at: directory, includingPropertiesForKeys: keys) else {
+                    invoke.reject("Failed to list \(args.directoryUrl)")
+                    return
+                }
+                items = enumerator.compactMap { $0 as? URL }
+            } else {
+                items = try FileManager.default.contentsOfDirectory(at: directory, includingPropertiesForKeys: keys)
+            }
+        } catch {
+            invoke.reject("Failed to list \(args.directoryUrl): \(error.localizedDescription)")
             return
         }
         
+        var found: [JSObject] = []
+        for item in items {
+            // Items that were never downloaded may be represented by a hidden
+            // ".<name>.icloud" placeholder.
+            let name = item.lastPathComponent
+            if name.hasPrefix("."), name.hasSuffix(".icloud"), name.count > ".icloud".count + 1 {
+                let realName = String(name.dropFirst().dropLast(".icloud".count))
+                let realUrl = item.deletingLastPathComponent().appendingPathComponent(realName)
+                found.append([
+                    "fileUrl": realUrl.absoluteString,
+                    "name": realName,
+                    "cloudStatus": "notDownloaded"
+                ])
+                continue
+            }
+            
+            guard let values = try? item.resourceValues(forKeys: Set(keys)),
+                  values.isUbiquitousItem == true,
+                  values.isHidden != true else {
+                continue
+            }
+            let status: String
+            if values.ubiquitousItemIsDownloading == true {
+                status = "downloading"
+            } else if values.ubiquitousItemDownloadingStatus == .notDownloaded {
+                status = "notDownloaded"
+            } else {
+                continue
+            }
+            var entry: JSObject = [
+                "fileUrl": item.absoluteString,
+                "name": name,
+                "cloudStatus": status
+            ]
+            if let size = values.fileSize {
+                entry["size"] = size
+            }
+            found.append(entry)
+        }
+        
+        invoke.resolve(["items": found])
+    }
+    
+    @objc public func startMonitoring(_ invoke: Invoke) throws {
+        let args = try invoke.parseArgs(MonitoringOptionsData.self)
+        
         var urls: [URL] = []
         for urlString in args.directoryUrls {
-            if let url = URL(string: urlString) {
-                urls.append(url)
+            guard let url = URL(string: urlString), url.isFileURL else {
+                invoke.reject("Invalid URL: \(urlString)")
+                return
+            }
+            var isDirectory: ObjCBool = false
+            guard FileManager.default.fileExists(atPath: url.path, isDirectory: &isDirectory), isDirectory.boolValue else {
+                invoke.reject("\(urlString) is not a folder")
+                return
             }
+            urls.append(url)
         }
         
-        guard !urls.isEmpty else {
-            invoke.reject("No valid URLs to monitor")
+        let handler = args.handler
+        let monitor = FileMonitor(urls: urls, recursive: args.recursive) { change in
+            var data: JSObject = [
+                "fileUrl": change.fileUrl.absoluteString,
+                "eventType": change.eventType
+            ]
+            if let oldUrl = change.oldUrl {
+                data["oldUrl"] = oldUrl.absoluteString
+            }
+            handler.send(.dictionary(data))
+        }
+        
+        monitorQueue.sync {
+            fileMonitors[args.id] = monitor
+        }
+        monitor.startMonitoring()
+        invoke.resolve()
+    }
+    
+    @objc public func stopMonitoring(_ invoke: Invoke) throws {
+        struct StopArgs: Decodable {
+            let id: UInt32
+        }
+        let args = try invoke.parseArgs(StopArgs.self)
+        
+        let monitor = monitorQueue.sync {
+            fileMonitors.removeValue(forKey: args.id)
+        }
+        monitor?.stopMonitoring()
+        invoke.resolve()
+    }
+    
+    @objc public func getSpaceInfo(_ invoke: Invoke) throws {
+        struct SpaceArgs: Decodable {
+            let url: String?
+        }
+        
+        let args = try invoke.parseArgs(SpaceArgs.self)
+        
+        guard let url = args.url.flatMap({ scopedUrl($0) })
+            ?? FileManager.default.urls(for: .documentDirectory, in: .userDomainMask).first else {
+            invoke.reject("Invalid URL")
             return
         }
         
-        fileMonitor = FileMonitor(urls: urls, recursive: args.recursive) { [weak self] change in
-            self?.trigger("fileChanged", data: [
-                "fileUrl": change.fileUrl.absoluteString,
-                "eventType": change.eventType,
-                "oldUrl": change.oldUrl?.absoluteString ?? NSNull(),
-                "timestamp": ISO8601DateFormatter().string(from: change.timestamp)
+        do {
+            let values = try url.resourceValues(forKeys: [
+                .volumeTotalCapacityKey,
+                .volumeAvailableCapacityKey,
+                .volumeAvailableCapacityForImportantUsageKey,
+                .volumeAvailableCapacityForOpportunisticUsageKey
+            ])
+            let total = values.volumeTotalCapacity ?? 0
+            let available = values.volumeAvailableCapacity ?? 0
+            invoke.resolve([
+                "totalSpace": total,
+                "availableSpace": available,
+                "usedSpace": max(total - available, 0),
+                "importantSpace": Int(values.volumeAvailableCapacityForImportantUsage ?? 0),
+                "opportunisticSpace": Int(values.volumeAvailableCapacityForOpportunisticUsage ?? 0)
             ])
+        } catch {
+            invoke.reject("Failed to get space info: \(error.localizedDescription)")
+        }
+    }
+    
+    @objc public func getFilePermissions(_ invoke: Invoke) throws {
+        struct PermissionsArgs: Decodable {
+            let url: String
         }
         
-        fileMonitor?.startMonitoring()
-        invoke.resolve()
+        let args = try invoke.parseArgs(PermissionsArgs.self)
+        
+        guard let url = scopedUrl(args.url) else {
+            invoke.reject("Invalid URL")
+            return
+        }
+        
+        let fileManager = FileManager.default
+        guard fileManager.fileExists(atPath: url.path) else {
+            invoke.reject("File not found")
+            return
+        }
+        invoke.resolve([
+            "readable": fileManager.isReadableFile(atPath: url.path),
+            "writable": fileManager.isWritableFile(atPath: url.path),
+            "deletable": fileManager.isDeletableFile(atPath: url.path),
+            "executable": fileManager.isExecutableFile(atPath: url.path)
+        ])
     }
     
-    @objc public func stopMonitoring(_ invoke: Invoke) throws {
-        guard fileMonitor != nil else {
-            invoke.reject("Monitoring not active")
+    @objc public func createBookmark(_ invoke: Invoke) throws {
+        struct BookmarkArgs: Decodable {
+            let url: String
+        }
+        
+        let args = try invoke.parseArgs(BookmarkArgs.self)
+        
+        guard let url = scopedUrl(args.url) else {
+            invoke.reject("Invalid URL")
+            return
+        }
+        
+        let accessing = url.startAccessingSecurityScopedResource()
+        defer {
+            if accessing { url.stopAccessingSecurityScopedResource() }
+        }
+        
+        do {
+            let data = try url.bookmarkData(options: [], includingResourceValuesForKeys: nil, relativeTo: nil)
+            invoke.resolve([
+                "url": url.absoluteString,
+                "bookmarkData": data.base64EncodedString()
+            ])
+        } catch {
+            invoke.reject("Failed to create bookmark: \(error.localizedDescription)")
+        }
+    }
+    
+    @objc public func resolveBookmark(_ invoke: Invoke) throws {
+        struct ResolveArgs: Decodable {
+            let bookmarkData: String
+        }
+        
+        let args = try invoke.parseArgs(ResolveArgs.self)
+        
+        guard let data = Data(base64Encoded: args.bookmarkData) else {
+            invoke.reject("Invalid bookmark data")
+            return
+        }
+        
+        do {
+            var isStale = false
+            let url = try URL(resolvingBookmarkData: data, options: [], relativeTo: nil, bookmarkDataIsStale: &isStale)
+            scopedUrls[url.absoluteString] = url
+            
+            var bookmarkData = args.bookmarkData
+            if isStale {
+                let accessing = url.startAccessingSecurityScopedResource()
+                defer {
+                    if accessing { url.stopAccessingSecurityScopedResource() }
+                }
+                bookmarkData = try url.bookmarkData(options: [], includingResourceValuesForKeys: nil, relativeTo: nil)
+                    .base64EncodedString()
+            }
+            
+            invoke.resolve([
+                "url": url.absoluteString,
+                "isStale": isStale,
+                "bookmarkData": bookmarkData
+            ])
+        } catch {
+            invoke.reject("Failed to resolve bookmark: \(error.localizedDescription)")
+        }
+    }
+    
+    @objc public func startAccessingResource(_ invoke: Invoke) throws {
+        struct AccessArgs: Decodable {
+            let url: String
+        }
+        
+        let args = try invoke.parseArgs(AccessArgs.self)
+        
+        guard let url = scopedUrl(args.url) else {
+            invoke.reject("Invalid URL")
+            return
+        }
+        
+        let granted = url.startAccessingSecurityScopedResource()
+        if granted {
+            accessCounts[args.url, default: 0] += 1
+        }
+        invoke.resolve(["granted": granted])
+    }
+    
+    @objc public func stopAccessingResource(_ invoke: Invoke) throws {
+        struct AccessArgs: Decodable {
+            let url: String
+        }
+        
+        let args = try invoke.parseArgs(AccessArgs.self)
+        
+        guard let url = scopedUrl(args.url) else {
+            invoke.reject("Invalid URL")
             return
         }
         
-        fileMonitor?.stopMonitoring()
-        fileMonitor = nil
+        // Unbalanced calls would revoke access another caller still relies on.
+        if let count = accessCounts[args.url], count > 0 {
+            url.stopAccessingSecurityScopedResource()
+            accessCounts[args.url] = count > 1 ? count - 1 : nil
+        }
         invoke.resolve()
     }
     
+    /// The security-scoped URL for `string` if a picker or bookmark produced one.
+    private func scopedUrl(_ string: String) -> URL? {
+        return scopedUrls[string] ?? URL(string: string)
+    }
+    
     // MARK: - UIDocumentPickerDelegate
     
     func documentPicker(_ controller: UIDocumentPickerViewController, didPickDocumentsAt urls: [URL]) {
@@ -794,6 +1295,7 @@ class FilesPlugin: Plugin, UIDocumentPickerDelegate, UIDocumentInteractionContro
         var pickedFiles: [[String: Any]] = []
         
         for url in urls {
+            scopedUrls[url.absoluteString] = url
             _ = url.startAccessingSecurityScopedResource()
             defer { url.stopAccessingSecurityScopedResource() }
             
@@ -933,17 +1435,44 @@ class FilesPlugin: Plugin, UIDocumentPickerDelegate, UIDocumentInteractionContro
 
 // MARK: - File Monitor
 
+/// Watches folders and the files in them with dispatch sources. A folder's source
+/// fires when entries are added, removed or renamed in it, a file's source when it
+/// is written to or its attributes change. Only what fired is examined again, and
+/// everything that fired within a short interval is compared together, so that a
+/// move between two folders is still recognized as a rename by the file system
+/// identifier of the item.
 class FileMonitor {
+    private static let coalescingInterval: DispatchTimeInterval = .milliseconds(50)
+    private static let resourceKeys: [URLResourceKey] = [
+        .fileResourceIdentifierKey, .isDirectoryKey, .contentModificationDateKey,
+        .fileSizeKey, .isHiddenKey, .isUserImmutableKey, .creationDateKey
+    ]
+    
     private let urls: [URL]
     private let recursive: Bool
     private let changeHandler: (FileChangeEvent) -> Void
-    private var sources: [DispatchSourceFileSystemObject] = []
+    private let queue = DispatchQueue(label: "tauri.files.monitor")
+    private var folderSources: [String: DispatchSourceFileSystemObject] = [:]
+    private var fileSources: [String: (identifier: UInt64, source: DispatchSourceFileSystemObject)] = [:]
+    private var snapshot: [String: Entry] = [:]
+    private var changedFolders: Set<String> = []
+    private var changedItems: Set<String> = []
+    private var rescanScheduled = false
+    private var running = false
     
     struct FileChangeEvent {
         let fileUrl: URL
         let eventType: String
         let oldUrl: URL?
-        let timestamp: Date
+    }
+    
+    private struct Entry: Equatable {
+        let url: URL
+        let identifier: UInt64
+        let isDirectory: Bool
+        let modified: Date?
+        let size: Int?
+        let attributes: [String]
     }
     
     init(urls: [URL], recursive: Bool, changeHandler: @escaping (FileChangeEvent) -> Void) {
@@ -953,63 +1482,375 @@ class FileMonitor {
     }
     
     func startMonitoring() {
-        for url in urls {
-            monitorDirectory(at: url)
+        queue.sync {
+            running = true
+            snapshot = [:]
+            for root in urls {
+                snapshot.merge(scan(root, recursive: recursive)) { _, new in new }
+            }
+            updateSources()
         }
     }
     
     func stopMonitoring() {
-        for source in sources {
-            source.cancel()
+        queue.sync {
+            running = false
+            for source in folderSources.values {
+                source.cancel()
+            }
+            for watched in fileSources.values {
+                watched.source.cancel()
+            }
+            folderSources.removeAll()
+            fileSources.removeAll()
         }
-        sources.removeAll()
     }
     
-    private func monitorDirectory(at url: URL) {
-        let fileDescriptor = open(url.path, O_EVTONLY)
-        guard fileDescriptor >= 0 else { return }
+    private func scan(_ folder: URL, recursive: Bool) -> [String: Entry] {
+        let items: [URL]
+        if recursive {
+            let enumerator = FileManager.default.enumerator(at: folder, includingPropertiesForKeys: Self.resourceKeys)
+            items = enumerator?.compactMap { $0 as? URL } ?? []
+        } else {
+            items = (try? FileManager.default.contentsOfDirectory(
+                at: folder,
+                includingPropertiesForKeys: Self.resourceKeys
+            )) ?? []
+        }
         
-        let source = DispatchSource.makeFileSystemObjectSource(
-            fileDescriptor: fileDescriptor,
-            eventMask: [.write, .delete, .rename],
-            queue: DispatchQueue.global()
+        var entries: [String: Entry] = [:]
+        for item in items {
+            if let entry = entry(for: item) {
+                entries[entry.url.path] = entry
+            }
+        }
+        return entries
+    }
+    
+    private func entry(for item: URL) -> Entry? {
+        guard let values = try? item.resourceValues(forKeys: Set(Self.resourceKeys)) else { return nil }
+        var identifier: UInt64 = 0
+        if let attributes = try? FileManager.default.attributesOfItem(atPath: item.path),
+           let number = attributes[.systemFileNumber] as? NSNumber {
+            identifier = number.uint64Value
+        }
+        return Entry(
+            url: item.standardizedFileURL,
+            identifier: identifier,
+            isDirectory: values.isDirectory ?? false,
+            modified: values.contentModificationDate,
+            size: values.fileSize,
+            attributes: [
+                "\(values.isHidden ?? false)",
+                "\(values.isUserImmutable ?? false)",
+                "\(values.creationDate?.timeIntervalSince1970 ?? 0)"
+            ]
         )
+    }
+    
+    /// Watches the monitored folders and, when recursive, every folder below them,
+    /// plus every file in a watched folder. A file's source is tied to the item, so
+    /// it is replaced when another item takes the file's place.
+    private func updateSources() {
+        var folders = Set(urls.map { $0.standardizedFileURL.path })
+        if recursive {
+            for entry in snapshot.values where entry.isDirectory {
+                folders.insert(entry.url.path)
+            }
+        }
         
-        source.setEventHandler { [weak self] in
-            let event = FileChangeEvent(
-                fileUrl: url,
-                eventType: "modified",
-                oldUrl: nil,
-                timestamp: Date()
-            )
-            self?.changeHandler(event)
+        for (path, source) in folderSources where !folders.contains(path) {
+            source.cancel()
+            folderSources.removeValue(forKey: path)
+        }
+        for path in folders where folderSources[path] == nil {
+            folderSources[path] = makeSource(path, eventMask: [.write, .delete, .rename, .attrib, .link]) { [weak self] in
+                self?.folderChanged(path)
+            }
         }
         
+        let files = snapshot.filter { !$0.value.isDirectory }
+        for (path, watched) in fileSources where files[path]?.identifier != watched.identifier {
+            watched.source.cancel()
+            fileSources.removeValue(forKey: path)
+        }
+        for (path, entry) in files where fileSources[path] == nil {
+            let source = makeSource(path, eventMask: [.write, .extend, .attrib]) { [weak self] in
+                self?.itemChanged(path)
+            }
+            if let source = source {
+                fileSources[path] = (entry.identifier, source)
+            }
+        }
+    }
+    
+    private func makeSource(
+        _ path: String,
+        eventMask: DispatchSource.FileSystemEvent,
+        handler: @escaping () -> Void
+    ) -> DispatchSourceFileSystemObject? {
+        let fileDescriptor = open(path, O_EVTONLY)
+        guard fileDescriptor >= 0 else { return nil }
+        
+        let source = DispatchSource.makeFileSystemObjectSource(
+            fileDescriptor: fileDescriptor,
+            eventMask: eventMask,
+            queue: queue
+        )
+        source.setEventHandler(handler: handler)
         source.setCancelHandler {
             close(fileDescriptor)
         }
-        
         source.resume()
-        sources.append(source)
+        return source
+    }
+    
+    private func folderChanged(_ path: String) {
+        changedFolders.insert(path)
+        // The folder's own attributes are part of its parent's listing.
+        changedItems.insert(path)
+        scheduleRescan()
+    }
+    
+    private func itemChanged(_ path: String) {
+        changedItems.insert(path)
+        scheduleRescan()
+    }
+    
+    private func scheduleRescan() {
+        guard running, !rescanScheduled else { return }
+        rescanScheduled = true
+        queue.asyncAfter(deadline: .now() + Self.coalescingInterval) { [weak self] in
+            self?.rescan()
+        }
+    }
+    
+    /// Lists the folders that fired again and looks up the files that fired, then
+    /// compares them with what the snapshot held for them.
+    private func rescan() {
+        rescanScheduled = false
+        guard running else { return }
+        let folders = changedFolders
+        let items = changedItems
+        changedFolders.removeAll()
+        changedItems.removeAll()
+        
+        var previous: [String: Entry] = [:]
+        var current: [String: Entry] = [:]
+        for folder in folders {
+            // A folder that is gone shows up in the listing of its parent.
+            var isDirectory: ObjCBool = false
+            guard FileManager.default.fileExists(atPath: folder, isDirectory: &isDirectory), isDirectory.boolValue else {
+                continue
+            }
+            previous.merge(snapshot.filter { ($0.key as NSString).deletingLastPathComponent == folder }) { old, _ in old }
+            current.merge(scan(URL(fileURLWithPath: folder, isDirectory: true), recursive: false)) { _, new in new }
+        }
         
-        // Monitor subdirectories if recursive
         if recursive {
-            do {
-                let contents = try FileManager.default.contentsOfDirectory(at: url, includingPropertiesForKeys: [.isDirectoryKey])
-                for item in contents {
-                    if let isDirectory = try? item.resourceValues(forKeys: [.isDirectoryKey]).isDirectory,
-                       isDirectory == true {
-                        monitorDirectory(at: item)
-                    }
+            // Folders that appeared, disappeared or were replaced bring everything in them along.
+            for (path, entry) in current where entry.isDirectory && previous[path]?.identifier != entry.identifier {
+                current.merge(scan(entry.url, recursive: true)) { _, new in new }
+            }
+            for (path, entry) in previous where entry.isDirectory && current[path]?.identifier != entry.identifier {
+                previous.merge(snapshot.filter { $0.key.hasPrefix(path + "/") }) { old, _ in old }
+            }
+        }
+        
+        for path in items where previous[path] == nil && current[path] == nil {
+            guard let old = snapshot[path] else { continue }
+            previous[path] = old
+            if let entry = entry(for: URL(fileURLWithPath: path)) {
+                current[path] = entry
+            }
+        }
+        
+        for path in previous.keys {
+            snapshot.removeValue(forKey: path)
+        }
+        snapshot.merge(current) { _, new in new }
+        updateSources()
+        report(previous: previous, current: current)
+    }
+    
+    private func report(previous: [String: Entry], current: [String: Entry]) {
+        var removed = previous.filter { current[$0.key] == nil }
+        for (path, entry) in current.sorted(by: { $0.key < $1.key }) {
+            guard let old = previous[path] else {
+                // A new location holding a known item was renamed.
+                if let match = removed.first(where: { $0.value.identifier != 0 && $0.value.identifier == entry.identifier }) {
+                    removed.removeValue(forKey: match.key)
+                    changeHandler(FileChangeEvent(fileUrl: entry.url, eventType: "renamed", oldUrl: match.value.url))
+                } else {
+                    changeHandler(FileChangeEvent(fileUrl: entry.url, eventType: "created", oldUrl: nil))
                 }
-            } catch {
-                // Ignore errors
+                continue
+            }
+            if old.identifier != entry.identifier || old.modified != entry.modified || old.size != entry.size {
+                changeHandler(FileChangeEvent(fileUrl: entry.url, eventType: "modified", oldUrl: nil))
+            } else if old.attributes != entry.attributes {
+                changeHandler(FileChangeEvent(fileUrl: entry.url, eventType: "attributesChanged", oldUrl: nil))
             }
         }
+        for entry in removed.values {
+            changeHandler(FileChangeEvent(fileUrl: entry.url, eventType: "deleted", oldUrl: nil))
+        }
     }
 }
 
 @_cdecl("init_plugin_ios_files")
 func initPlugin() -> Plugin {
     return FilesPlugin()
-}
\ No newline at end of file
+}
+// MARK: - Cloud Download
+
+/// Follows the download of iCloud items and reports the progress of each. The
+/// percentage comes from a metadata query, which only sees items in the app's
+/// own iCloud containers, so the download status of every item is also polled.
+class CloudDownload: NSObject {
+    private let urls: [URL]
+    private let progressHandler: (JSObject) -> Void
+    private let completion: () -> Void
+    private var query: NSMetadataQuery?
+    private var timer: Timer?
+    private var percents: [String: (percent: Double, size: Int)] = [:]
+    private var reported: [String: String] = [:]
+    private var finished: Set<String> = []
+    private var stopped = false
+    
+    init(urls: [URL], progressHandler: @escaping (JSObject) -> Void, completion: @escaping () -> Void) {
+        self.urls = urls
+        self.progressHandler = progressHandler
+        self.completion = completion
+    }
+    
+    func start() {
+        DispatchQueue.main.async {
+            for url in self.urls {
+                do {
+                    try FileManager.default.startDownloadingUbiquitousItem(at: url)
+                } catch {
+                    self.report(url, status: "failed", error: error.localizedDescription)
+                }
+            }
+            guard !self.stopped else { return }
+            
+            let query = NSMetadataQuery()
+            query.searchScopes = [NSMetadataQueryUbiquitousDocumentsScope, NSMetadataQueryUbiquitousDataScope]
+            query.predicate = NSPredicate(format: "%K IN %@", NSMetadataItemPathKey, self.urls.map { $0.path })
+            NotificationCenter.default.addObserver(
+                self, selector: #selector(self.queryUpdated), name: .NSMetadataQueryDidFinishGathering, object: query
+            )
+            NotificationCenter.default.addObserver(
+                self, selector: #selector(self.queryUpdated), name: .NSMetadataQueryDidUpdate, object: query
+            )
+            query.start()
+            self.query = query
+            
+            self.timer = Timer.scheduledTimer(withTimeInterval: 0.5, repeats: true) { [weak self] _ in
+                self?.poll()
+            }
+            self.poll()
+        }
+    }
+    
+    /// Stops following the items and removes the partial downloads of those
+    /// that have not finished.
+    func cancel() {
+        DispatchQueue.main.async {
+            guard !self.stopped else { return }
+            for url in self.urls where !self.finished.contains(url.path) {
+                try? FileManager.default.evictUbiquitousItem(at: url)
+            }
+            self.stop()
+        }
+    }
+    
+    private func stop() {
+        stopped = true
+        timer?.invalidate()
+        timer = nil
+        if let query = query {
+            query.stop()
+            NotificationCenter.default.removeObserver(self, name: nil, object: query)
+        }
+        query = nil
+    }
+    
+    @objc private func queryUpdated(_ notification: Notification) {
+        guard let query = query else { return }
+        query.disableUpdates()
+        for index in 0..<query.resultCount {
+            guard let item = query.result(at: index) as? NSMetadataItem,
+                  let path = item.value(forAttribute: NSMetadataItemPathKey) as? String else {
+                continue
+            }
+            let percent = (item.value(forAttribute: NSMetadataUbiquitousItemPercentDownloadedKey) as? NSNumber)?.doubleValue ?? 0
+            let size = (item.value(forAttribute: NSMetadataItemFSSizeKey) as? NSNumber)?.intValue ?? 0
+            percents[path] = (percent, size)
+        }
+        query.enableUpdates()
+        poll()
+    }
+    
+    private func poll() {
+        guard !stopped else { return }
+        let keys: Set<URLResourceKey> = [
+            .isUbiquitousItemKey, .ubiquitousItemDownloadingStatusKey, .ubiquitousItemIsDownloadingKey,
+            .ubiquitousItemDownloadingErrorKey, .fileSizeKey
+        ]
+        for url in urls where !finished.contains(url.path) {
+            // Refetch instead of using cached values.
+            var url = url
+            url.removeAllCachedResourceValues()
+            guard let values = try? url.resourceValues(forKeys: keys) else {
+                continue
+            }
+            let known = percents[url.path]
+            let size = known?.size ?? values.fileSize ?? 0
+            
+            if let error = values.ubiquitousItemDownloadingError {
+                report(url, status: "failed", size: size, error: error.localizedDescription)
+            } else if values.isUbiquitousItem != true {
+                // A local file is already complete.
+                report(url, status: "completed", size: values.fileSize ?? 0)
+            } else if values.ubiquitousItemDownloadingStatus == .current
+                        || (values.ubiquitousItemDownloadingStatus == .downloaded && values.ubiquitousItemIsDownloading != true) {
+                report(url, status: "completed", size: size)
+            } else if let percent = known?.percent, percent > 0 || values.ubiquitousItemIsDownloading == true {
+                report(url, status: "downloading", percent: percent, size: size)
+            } else {
+                report(url, status: values.ubiquitousItemIsDownloading == true ? "downloading" : "starting", size: size)
+            }
+            guard !stopped else { return }
+        }
+    }
+    
+    private func report(_ url: URL, status: String, percent: Double = 0, size: Int = 0, error: String? = nil) {
+        let progress = min(max(percent / 100, 0), 1)
+        var data: JSObject = [
+            "fileUrl": url.absoluteString,
+            "progress": progress,
+            "downloadedBytes": Int(Double(size) * progress),
+            "totalBytes": size,
+            "status": status
+        ]
+        if let error = error {
+            data["error"] = error
+        }
+        
+        // Only changes are sent, as the status is polled.
+        let key = "\(status):\(Int(progress * 1000)):\(size)"
+        guard reported[url.path] != key else { return }
+        reported[url.path] = key
+        progressHandler(data)
+        
+        if status == "completed" || status == "failed" {
+            finished.insert(url.path)
+            if finished.count == Set(urls.map { $0.path }).count {
+                stop()
+                completion()
+            }
+        }
+    }
+}
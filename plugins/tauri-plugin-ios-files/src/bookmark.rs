@@ -0,0 +1,219 @@
+//! Bookmarks for the desktop backend. Like the bookmarks iOS creates, they
+//! remember the identity of an item as well as its location, so an item that was
+//! moved or renamed inside the root can still be found.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::{scope::Scope, Error, Result};
+
+const HEADER: &str = "tauri-files-bookmark:1";
+
+/// Identifies a file for as long as it exists, wherever it is moved to on the
+/// same volume. Not available on every platform.
+fn file_id(metadata: &fs::Metadata) -> Option<String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(format!("{}:{}", metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+fn invalid() -> Error {
+    Error::InvalidInput("Invalid bookmark data".to_string())
+}
+
+/// Creates the bookmark data of `path`, which must exist.
+pub(crate) fn create(scope: &Scope, path: &Path) -> Result<String> {
+    let metadata = fs::symlink_metadata(path)?;
+    let relative = path.strip_prefix(scope.root()).map_err(|_| Error::PermissionDenied)?;
+    let relative = relative
+        .to_str()
+        .ok_or_else(|| Error::InvalidInput(format!("{} is not valid UTF-8", path.display())))?;
+    let id = file_id(&metadata).unwrap_or_default();
+    Ok(STANDARD.encode(format!("{HEADER}\n{id}\n{relative}")))
+}
+
+/// Finds the item a bookmark points to, and whether it had to be looked up by
+/// its identity because it moved or its location now holds another item.
+pub(crate) fn resolve(scope: &Scope, data: &str) -> Result<(PathBuf, bool)> {
+    let decoded = STANDARD.decode(data.trim()).map_err(|_| invalid())?;
+    let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+    let mut lines = decoded.splitn(3, '\n');
+    let (Some(HEADER), Some(id), Some(relative)) = (lines.next(), lines.next(), lines.next()) else {
+        return Err(invalid());
+    };
+
+    let path = scope.resolve(relative)?;
+    if let Ok(metadata) = fs::symlink_metadata(&path) {
+        if id.is_empty() || file_id(&metadata).as_deref() == Some(id) {
+            return Ok((path, false));
+        }
+    }
+    if !id.is_empty() {
+        if let Some(found) = find(scope.root(), id) {
+            return Ok((found, true));
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::NotFound, "The bookmarked item no longer exists").into())
+}
+
+/// Looks for the item with `id` below `directory` without following links.
+/// Entries that can't be read or vanish during the search are skipped.
+fn find(directory: &Path, id: &str) -> Option<PathBuf> {
+    for entry in fs::read_dir(directory).ok()?.flatten() {
+        let path = entry.path();
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if file_id(&metadata).as_deref() == Some(id) {
+            return Some(path);
+        }
+        if metadata.is_dir() {
+            if let Some(found) = find(&path, id) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    /// A scope over an empty folder of its own.
+    fn scratch(name: &str) -> Scope {
+        let dir = std::env::temp_dir().join(format!("tauri-plugin-ios-files-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Scope::new(dir).unwrap()
+    }
+
+    fn bookmark(scope: &Scope, relative: &str) -> String {
+        create(scope, &scope.resolve(relative).unwrap()).unwrap()
+    }
+
+    fn assert_invalid(scope: &Scope, data: &str) {
+        let result = resolve(scope, data);
+        assert!(matches!(result, Err(Error::InvalidInput(_))), "{data}: {result:?}");
+    }
+
+    fn assert_not_found(scope: &Scope, data: &str) {
+        let result = resolve(scope, data);
+        assert!(matches!(&result, Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound), "{result:?}");
+    }
+
+    #[test]
+    fn unmoved_items_resolve_in_place() {
+        let scope = scratch("bookmark-in-place");
+        let root = scope.root().to_path_buf();
+        fs::create_dir(root.join("folder")).unwrap();
+        fs::write(root.join("folder/notes.txt"), "notes").unwrap();
+
+        let data = bookmark(&scope, "folder/notes.txt");
+        assert_eq!(resolve(&scope, &data).unwrap(), (root.join("folder/notes.txt"), false));
+        let data = bookmark(&scope, "folder");
+        assert_eq!(resolve(&scope, &data).unwrap(), (root.join("folder"), false));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn moved_and_renamed_items_are_found_by_identity() {
+        let scope = scratch("bookmark-moved");
+        let root = scope.root().to_path_buf();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::write(root.join("notes.txt"), "notes").unwrap();
+        let data = bookmark(&scope, "notes.txt");
+
+        fs::rename(root.join("notes.txt"), root.join("renamed.txt")).unwrap();
+        assert_eq!(resolve(&scope, &data).unwrap(), (root.join("renamed.txt"), true));
+        fs::rename(root.join("renamed.txt"), root.join("a/b/moved.txt")).unwrap();
+        assert_eq!(resolve(&scope, &data).unwrap(), (root.join("a/b/moved.txt"), true));
+
+        // Another item at the old location doesn't take the bookmark over.
+        fs::write(root.join("notes.txt"), "impostor").unwrap();
+        assert_eq!(resolve(&scope, &data).unwrap(), (root.join("a/b/moved.txt"), true));
+
+        // A bookmark of the new location isn't stale.
+        let renewed = create(&scope, &root.join("a/b/moved.txt")).unwrap();
+        assert_ne!(renewed, data);
+        assert_eq!(resolve(&scope, &renewed).unwrap(), (root.join("a/b/moved.txt"), false));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn deleted_items_are_not_found() {
+        let scope = scratch("bookmark-deleted");
+        let root = scope.root().to_path_buf();
+        fs::write(root.join("notes.txt"), "notes").unwrap();
+        let data = bookmark(&scope, "notes.txt");
+
+        fs::remove_file(root.join("notes.txt")).unwrap();
+        assert_not_found(&scope, &data);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn unreadable_folders_are_skipped_while_searching() {
+        let scope = scratch("bookmark-unreadable");
+        let root = scope.root().to_path_buf();
+        for folder in ["a", "b", "c"] {
+            fs::create_dir(root.join(folder)).unwrap();
+        }
+        fs::write(root.join("notes.txt"), "notes").unwrap();
+        let data = bookmark(&scope, "notes.txt");
+        fs::rename(root.join("notes.txt"), root.join("b/notes.txt")).unwrap();
+
+        // The search has to pass over folders it can't read. Running as root, it
+        // can read them and nothing is skipped.
+        for folder in ["a", "c"] {
+            fs::set_permissions(root.join(folder), fs::Permissions::from_mode(0o000)).unwrap();
+        }
+        let resolved = resolve(&scope, &data);
+        fs::remove_file(root.join("b/notes.txt")).unwrap();
+        let missing = resolve(&scope, &data);
+        for folder in ["a", "c"] {
+            fs::set_permissions(root.join(folder), fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        assert_eq!(resolved.unwrap(), (root.join("b/notes.txt"), true));
+        assert!(matches!(&missing, Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound), "{missing:?}");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn malformed_and_foreign_data_is_rejected() {
+        let scope = scratch("bookmark-malformed");
+        let root = scope.root().to_path_buf();
+        for data in [
+            "",
+            "not base64!",
+            &STANDARD.encode([0xff, 0xfe, 0x00]),
+            &STANDARD.encode("tauri-files-bookmark:1"),
+            &STANDARD.encode("tauri-files-bookmark:1\n1:2"),
+            &STANDARD.encode("tauri-files-bookmark:2\n1:2\nnotes.txt"),
+            // Bookmark data created by iOS.
+            &STANDARD.encode(b"book\x00\x02\x00\x00\x00\x00\x04\x10"),
+        ] {
+            assert_invalid(&scope, data);
+        }
+
+        // Well-formed data may still point outside the root.
+        let outside = STANDARD.encode("tauri-files-bookmark:1\n\n../outside.txt");
+        assert!(matches!(resolve(&scope, &outside), Err(Error::PermissionDenied)));
+        let unknown = STANDARD.encode("tauri-files-bookmark:1\n0:0\nmissing.txt");
+        assert_not_found(&scope, &unknown);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,415 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use tauri::Runtime;
+
+use crate::{
+    monitor::Stop, CloudBatchProgress, CloudDownloadProgress, CloudDownloadStatus, CloudItemResult, Error, Result,
+};
+
+type Handler = Box<dyn Fn(&CloudBatchProgress) + Send + Sync>;
+
+static NEXT_DOWNLOAD_ID: AtomicU32 = AtomicU32::new(1);
+
+impl CloudDownloadStatus {
+    /// Whether nothing more happens to the item.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// The progress of every item of one download, which turns the updates of single
+/// items into the progress of the whole batch.
+struct Tracker {
+    items: Mutex<Vec<CloudDownloadProgress>>,
+    finished: AtomicBool,
+    handler: Handler,
+}
+
+impl Tracker {
+    #[cfg_attr(desktop, allow(dead_code))]
+    fn update(&self, mut update: CloudDownloadProgress) {
+        let mut items = self.items.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(index) = items.iter().position(|item| item.file_url == update.file_url) else {
+            return;
+        };
+        // Late updates from the native side after cancelling or failing.
+        if items[index].status.is_finished() {
+            return;
+        }
+
+        update.progress = update.progress.clamp(0.0, 1.0);
+        if update.status == CloudDownloadStatus::Completed {
+            update.progress = 1.0;
+            update.downloaded_bytes = update.total_bytes;
+        }
+        items[index] = update;
+        self.report(&items, index);
+    }
+
+    /// Marks every item that is still downloading as cancelled.
+    fn cancel(&self) {
+        let mut items = self.items.lock().unwrap_or_else(|e| e.into_inner());
+        for index in 0..items.len() {
+            if !items[index].status.is_finished() {
+                items[index].status = CloudDownloadStatus::Cancelled;
+                items[index].error = None;
+                self.report(&items, index);
+            }
+        }
+    }
+
+    fn report(&self, items: &[CloudDownloadProgress], changed: usize) {
+        let count = |status: CloudDownloadStatus| items.iter().filter(|item| item.status == status).count();
+        let completed_items = count(CloudDownloadStatus::Completed);
+        let failed_items = count(CloudDownloadStatus::Failed);
+        let cancelled_items = count(CloudDownloadStatus::Cancelled);
+
+        let status = if completed_items + failed_items + cancelled_items < items.len() {
+            if items.iter().all(|item| item.status == CloudDownloadStatus::Starting) {
+                CloudDownloadStatus::Starting
+            } else {
+                CloudDownloadStatus::Downloading
+            }
+        } else if cancelled_items > 0 {
+            CloudDownloadStatus::Cancelled
+        } else if failed_items > 0 {
+            CloudDownloadStatus::Failed
+        } else {
+            CloudDownloadStatus::Completed
+        };
+        if status.is_finished() {
+            self.finished.store(true, Ordering::SeqCst);
+        }
+
+        // Sizes may be unknown until an item starts downloading, so every item
+        // weighs the same.
+        let progress = items
+            .iter()
+            .map(|item| if item.status.is_finished() { 1.0 } else { item.progress })
+            .sum::<f64>()
+            / items.len() as f64;
+
+        (self.handler)(&CloudBatchProgress {
+            item: items[changed].clone(),
+            progress,
+            downloaded_bytes: items.iter().map(|item| item.downloaded_bytes).sum(),
+            total_bytes: items.iter().map(|item| item.total_bytes).sum(),
+            completed_items,
+            failed_items,
+            total_items: items.len(),
+            status,
+        });
+    }
+}
+
+/// Where the native side reports the progress of the items of one download.
+/// There is no iCloud on desktop, so nothing reports there.
+#[derive(Clone)]
+#[cfg_attr(desktop, allow(dead_code))]
+pub(crate) struct ProgressSender(Arc<Tracker>);
+
+impl ProgressSender {
+    #[cfg_attr(desktop, allow(dead_code))]
+    pub(crate) fn send(&self, progress: CloudDownloadProgress) {
+        self.0.update(progress);
+    }
+}
+
+/// Handle to a running iCloud download.
+///
+/// Dropping it before the download has finished cancels the items that are
+/// still downloading, which removes what was downloaded of them so far.
+pub struct CloudDownload {
+    id: u32,
+    tracker: Arc<Tracker>,
+    cancel: Option<Stop>,
+}
+
+impl CloudDownload {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Whether every item has completed, failed or been cancelled.
+    pub fn is_finished(&self) -> bool {
+        self.tracker.finished.load(Ordering::SeqCst)
+    }
+
+    /// Cancels the download. Equivalent to dropping the handle.
+    pub fn cancel(self) {}
+}
+
+impl Drop for CloudDownload {
+    fn drop(&mut self) {
+        if self.is_finished() {
+            return;
+        }
+        if let Some(cancel) = self.cancel.take() {
+            cancel();
+        }
+        self.tracker.cancel();
+    }
+}
+
+/// Downloads started from the frontend, keyed by the id handed back to JS.
+#[derive(Default)]
+pub(crate) struct JsDownloads(Mutex<HashMap<u32, CloudDownload>>);
+
+impl JsDownloads {
+    pub(crate) fn insert(&self, download: CloudDownload) -> u32 {
+        let id = download.id();
+        let mut downloads = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        downloads.retain(|_, download| !download.is_finished());
+        if !download.is_finished() {
+            downloads.insert(id, download);
+        }
+        id
+    }
+
+    pub(crate) fn cancel(&self, id: u32) -> Result<()> {
+        let download = self.0.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+        match download {
+            Some(download) => {
+                download.cancel();
+                Ok(())
+            }
+            None => Err(Error::InvalidInput(format!("No running download with id {id}"))),
+        }
+    }
+}
+
+impl<R: Runtime> crate::Files<R> {
+    /// Downloads an iCloud item and reports its progress to `handler` until it
+    /// has completed, failed or been cancelled.
+    pub fn download_from_cloud<F>(&self, url: String, handler: F) -> Result<CloudDownload>
+    where
+        F: Fn(&CloudDownloadProgress) + Send + Sync + 'static,
+    {
+        self.download_all_from_cloud(vec![url], move |progress| handler(&progress.item))
+    }
+
+    /// Downloads several iCloud items at once. `handler` receives every change
+    /// of an item together with the progress of the whole batch, and the last
+    /// update has a finished status.
+    pub fn download_all_from_cloud<F>(&self, urls: Vec<String>, handler: F) -> Result<CloudDownload>
+    where
+        F: Fn(&CloudBatchProgress) + Send + Sync + 'static,
+    {
+        if urls.is_empty() {
+            return Err(Error::InvalidInput("No items to download".to_string()));
+        }
+        let mut unique = Vec::with_capacity(urls.len());
+        for url in urls {
+            if !unique.contains(&url) {
+                unique.push(url);
+            }
+        }
+
+        let id = NEXT_DOWNLOAD_ID.fetch_add(1, Ordering::Relaxed);
+        let tracker = Arc::new(Tracker {
+            items: Mutex::new(
+                unique
+                    .iter()
+                    .map(|url| CloudDownloadProgress {
+                        file_url: url.clone(),
+                        progress: 0.0,
+                        downloaded_bytes: 0,
+                        total_bytes: 0,
+                        status: CloudDownloadStatus::Starting,
+                        error: None,
+                    })
+                    .collect(),
+            ),
+            finished: AtomicBool::new(false),
+            handler: Box::new(handler),
+        });
+        let cancel = self.start_download(id, &unique, ProgressSender(tracker.clone()))?;
+        Ok(CloudDownload {
+            id,
+            tracker,
+            cancel: Some(cancel),
+        })
+    }
+
+    /// Removes the local copies of several iCloud items. Every item is tried,
+    /// and the result of each is returned in the order of `urls`.
+    pub fn evict_all_from_local(&self, urls: Vec<String>) -> Vec<CloudItemResult> {
+        urls.into_iter()
+            .map(|url| {
+                let error = self.evict_from_local(url.clone()).err().map(|e| e.to_string());
+                CloudItemResult { file_url: url, error }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use CloudDownloadStatus::*;
+
+    /// A tracker of `urls` and the batch progress it reports.
+    fn tracker(urls: &[&str]) -> (Arc<Tracker>, Arc<Mutex<Vec<CloudBatchProgress>>>) {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let received = reports.clone();
+        let tracker = Tracker {
+            items: Mutex::new(urls.iter().map(|url| item(url, Starting, 0.0, 0)).collect()),
+            finished: AtomicBool::new(false),
+            handler: Box::new(move |progress: &CloudBatchProgress| received.lock().unwrap().push(progress.clone())),
+        };
+        (Arc::new(tracker), reports)
+    }
+
+    fn item(url: &str, status: CloudDownloadStatus, progress: f64, downloaded_bytes: u64) -> CloudDownloadProgress {
+        CloudDownloadProgress {
+            file_url: url.to_string(),
+            progress,
+            downloaded_bytes,
+            total_bytes: 100,
+            error: (status == Failed).then(|| "offline".to_string()),
+            status,
+        }
+    }
+
+    fn last(reports: &Mutex<Vec<CloudBatchProgress>>) -> CloudBatchProgress {
+        reports.lock().unwrap().last().unwrap().clone()
+    }
+
+    #[test]
+    fn the_batch_goes_from_starting_to_completed() {
+        let (tracker, reports) = tracker(&["a", "b"]);
+
+        tracker.update(item("a", Starting, 0.0, 0));
+        assert_eq!(last(&reports).status, Starting);
+        tracker.update(item("a", Downloading, 0.5, 50));
+        let report = last(&reports);
+        assert_eq!((report.status, report.progress, report.downloaded_bytes), (Downloading, 0.25, 50));
+        assert_eq!((report.item.file_url.as_str(), report.total_bytes, report.total_items), ("a", 200, 2));
+
+        // A completed item counts in full, whatever it last reported.
+        tracker.update(item("a", Completed, 0.9, 90));
+        let report = last(&reports);
+        assert_eq!((report.status, report.progress, report.completed_items), (Downloading, 0.5, 1));
+        assert_eq!((report.item.progress, report.item.downloaded_bytes), (1.0, 100));
+        assert!(!tracker.finished.load(Ordering::SeqCst));
+
+        tracker.update(item("b", Completed, 1.0, 100));
+        let report = last(&reports);
+        assert_eq!((report.status, report.progress, report.completed_items, report.failed_items), (Completed, 1.0, 2, 0));
+        assert_eq!(report.downloaded_bytes, 200);
+        assert!(tracker.finished.load(Ordering::SeqCst));
+        assert_eq!(reports.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn unfinished_items_keep_the_batch_going() {
+        let (tracker, reports) = tracker(&["a", "b", "c"]);
+
+        tracker.update(item("a", Failed, 0.2, 20));
+        let report = last(&reports);
+        assert_eq!((report.status, report.failed_items), (Downloading, 1));
+        assert!((report.progress - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(report.item.error.as_deref(), Some("offline"));
+
+        tracker.update(item("b", Completed, 1.0, 100));
+        tracker.update(item("c", Failed, 0.0, 0));
+        let report = last(&reports);
+        assert_eq!((report.status, report.progress, report.completed_items, report.failed_items), (Failed, 1.0, 1, 2));
+    }
+
+    #[test]
+    fn cancelling_outranks_failing() {
+        let (tracker, reports) = tracker(&["a", "b", "c"]);
+        tracker.update(item("a", Failed, 0.0, 0));
+        tracker.update(item("b", Cancelled, 0.0, 0));
+        tracker.update(item("c", Completed, 1.0, 100));
+        let report = last(&reports);
+        assert_eq!((report.status, report.completed_items, report.failed_items), (Cancelled, 1, 1));
+    }
+
+    #[test]
+    fn cancelling_only_marks_unfinished_items() {
+        let (tracker, reports) = tracker(&["a", "b", "c", "d"]);
+        tracker.update(item("a", Completed, 1.0, 100));
+        tracker.update(item("b", Failed, 0.3, 30));
+        tracker.update(item("c", Downloading, 0.5, 50));
+        reports.lock().unwrap().clear();
+
+        tracker.cancel();
+        let reports = reports.lock().unwrap().clone();
+        // One report for each of the items that were still running.
+        let cancelled: Vec<_> = reports.iter().map(|report| report.item.file_url.as_str()).collect();
+        assert_eq!(cancelled, ["c", "d"]);
+        assert!(reports.iter().all(|report| report.item.status == Cancelled && report.item.error.is_none()));
+        assert_eq!(reports[0].status, Downloading);
+        let report = reports.last().unwrap().clone();
+        assert_eq!((report.status, report.progress, report.completed_items, report.failed_items), (Cancelled, 1.0, 1, 1));
+        assert!(tracker.finished.load(Ordering::SeqCst));
+
+        let items = tracker.items.lock().unwrap();
+        let statuses: Vec<_> = items.iter().map(|item| item.status.clone()).collect();
+        assert_eq!(statuses, [Completed, Failed, Cancelled, Cancelled]);
+        // What was downloaded of a cancelled item stays counted.
+        assert_eq!(items[2].downloaded_bytes, 50);
+    }
+
+    #[test]
+    fn late_and_unknown_updates_are_ignored() {
+        let (tracker, reports) = tracker(&["a", "b"]);
+        tracker.update(item("a", Failed, 0.0, 0));
+        tracker.update(item("b", Completed, 1.0, 100));
+        let count = reports.lock().unwrap().len();
+
+        tracker.update(item("a", Downloading, 0.5, 50));
+        tracker.update(item("b", Failed, 0.0, 0));
+        tracker.update(item("elsewhere", Downloading, 0.5, 50));
+        tracker.cancel();
+        assert_eq!(reports.lock().unwrap().len(), count);
+        assert_eq!(last(&reports).status, Failed);
+    }
+
+    #[test]
+    fn progress_is_clamped() {
+        let (tracker, reports) = tracker(&["a"]);
+        tracker.update(item("a", Downloading, 1.7, 100));
+        assert_eq!((last(&reports).item.progress, last(&reports).progress), (1.0, 1.0));
+        tracker.update(item("a", Downloading, -0.5, 0));
+        assert_eq!((last(&reports).item.progress, last(&reports).progress), (0.0, 0.0));
+        assert_eq!(last(&reports).status, Downloading);
+    }
+
+    #[test]
+    fn dropping_a_download_cancels_it_unless_finished() {
+        let stopped = Arc::new(AtomicU32::new(0));
+        let download = |tracker: &Arc<Tracker>| {
+            let stopped = stopped.clone();
+            CloudDownload {
+                id: 1,
+                tracker: tracker.clone(),
+                cancel: Some(Box::new(move || {
+                    stopped.fetch_add(1, Ordering::SeqCst);
+                })),
+            }
+        };
+
+        let (running, reports) = tracker(&["a"]);
+        download(&running).cancel();
+        assert_eq!(stopped.load(Ordering::SeqCst), 1);
+        assert_eq!(last(&reports).status, Cancelled);
+
+        let (finished, reports) = tracker(&["a"]);
+        finished.update(item("a", Completed, 1.0, 100));
+        let handle = download(&finished);
+        assert!(handle.is_finished());
+        drop(handle);
+        assert_eq!(stopped.load(Ordering::SeqCst), 1);
+        assert_eq!(last(&reports).status, Completed);
+    }
+}
@@ -1,6 +1,13 @@
-use tauri::{command, AppHandle, Runtime};
+use tauri::{command, ipc::Channel, AppHandle, Manager, Runtime};
 
-use crate::{FilesExt, FilePickerOptions, SaveFileOptions, ImportOptions, ExportOptions, ListOptions, FileData, FileOperation, ShareOptions, PreviewOptions, MonitoringOptions, Result};
+use crate::{
+    cloud::JsDownloads,
+    monitor::JsMonitors,
+    stream::{JsReads, JsWriters},
+    ChunkedReadOptions, CloudBatchProgress, CloudItem, CloudItemResult, ExportOptions, FileChange, FileChunk, FileData, FileOperation, FilePickerOptions, FileWriter,
+    FileWriterOptions, FilesExt, ImportOptions, ListOptions, MonitoringOptions, PreviewOptions, ReadReport, Result,
+    SaveFileOptions, ShareOptions,
+};
 
 #[command]
 pub(crate) async fn pick_file<R: Runtime>(
@@ -150,9 +157,21 @@ pub(crate) async fn get_cloud_status<R: Runtime>(
 #[command]
 pub(crate) async fn download_from_cloud<R: Runtime>(
     app: AppHandle<R>,
-    url: String,
+    urls: Vec<String>,
+    on_progress: Channel<CloudBatchProgress>,
+) -> Result<u32> {
+    let download = app.files().download_all_from_cloud(urls, move |progress| {
+        let _ = on_progress.send(progress.clone());
+    })?;
+    Ok(app.state::<JsDownloads>().insert(download))
+}
+
+#[command]
+pub(crate) async fn cancel_cloud_download<R: Runtime>(
+    app: AppHandle<R>,
+    id: u32,
 ) -> Result<()> {
-    app.files().download_from_cloud(url)
+    app.state::<JsDownloads>().cancel(id)
 }
 
 #[command]
@@ -163,17 +182,189 @@ pub(crate) async fn evict_from_local<R: Runtime>(
     app.files().evict_from_local(url)
 }
 
+#[command]
+pub(crate) async fn evict_all_from_local<R: Runtime>(
+    app: AppHandle<R>,
+    urls: Vec<String>,
+) -> Result<Vec<CloudItemResult>> {
+    Ok(app.files().evict_all_from_local(urls))
+}
+
+#[command]
+pub(crate) async fn list_not_downloaded<R: Runtime>(
+    app: AppHandle<R>,
+    directory_url: String,
+    recursive: Option<bool>,
+) -> Result<Vec<CloudItem>> {
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        handle.files().list_not_downloaded(directory_url, recursive.unwrap_or(false))
+    })
+    .await?
+}
+
 #[command]
 pub(crate) async fn start_monitoring<R: Runtime>(
     app: AppHandle<R>,
     options: MonitoringOptions,
-) -> Result<()> {
-    app.files().start_monitoring(options)
+    on_change: Channel<FileChange>,
+) -> Result<u32> {
+    let monitor = app.files().start_monitoring(options, move |change| {
+        let _ = on_change.send(change.clone());
+    })?;
+    Ok(app.state::<JsMonitors>().insert(monitor))
 }
 
 #[command]
 pub(crate) async fn stop_monitoring<R: Runtime>(
     app: AppHandle<R>,
+    id: u32,
+) -> Result<()> {
+    app.state::<JsMonitors>().remove(id)
+}
+
+#[command]
+pub(crate) async fn read_file_range<R: Runtime>(
+    app: AppHandle<R>,
+    url: String,
+    offset: u64,
+    length: usize,
+) -> Result<FileChunk> {
+    app.files().read_file_range(url, offset, length)
+}
+
+#[command]
+pub(crate) async fn read_file_chunked<R: Runtime>(
+    app: AppHandle<R>,
+    url: String,
+    options: Option<ChunkedReadOptions>,
+    read_id: String,
+    on_chunk: Channel<FileChunk>,
+) -> Result<ReadReport> {
+    let cancel = app.state::<JsReads>().insert(read_id.clone())?;
+    // The read blocks between chunks, so keep it off the async workers.
+    let handle = app.clone();
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        handle.files().read_file_chunked(url, options.unwrap_or_default(), &cancel, |chunk| {
+            let _ = on_chunk.send(chunk);
+        })
+    })
+    .await;
+    app.state::<JsReads>().remove(&read_id);
+    report?
+}
+
+#[command]
+pub(crate) async fn cancel_read<R: Runtime>(
+    app: AppHandle<R>,
+    read_id: String,
+) -> Result<()> {
+    app.state::<JsReads>().cancel(&read_id)
+}
+
+#[command]
+pub(crate) async fn append_file<R: Runtime>(
+    app: AppHandle<R>,
+    url: String,
+    data: FileData,
+) -> Result<u64> {
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || handle.files().append_file(url, data)).await?
+}
+
+#[command]
+pub(crate) async fn open_file_writer<R: Runtime>(
+    app: AppHandle<R>,
+    url: String,
+    options: Option<FileWriterOptions>,
+) -> Result<u32> {
+    let writer = app.files().open_file_writer(url, options.unwrap_or_default())?;
+    Ok(app.state::<JsWriters<R>>().insert(writer))
+}
+
+#[command]
+pub(crate) async fn write_file_chunk<R: Runtime>(
+    app: AppHandle<R>,
+    id: u32,
+    data: FileData,
+) -> Result<u64> {
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        handle.state::<JsWriters<R>>().with(id, false, |writer| writer.write_data(data))?
+    })
+    .await?
+}
+
+#[command]
+pub(crate) async fn finish_file_writer<R: Runtime>(
+    app: AppHandle<R>,
+    id: u32,
+) -> Result<u64> {
+    app.state::<JsWriters<R>>().with(id, true, FileWriter::commit)?
+}
+
+#[command]
+pub(crate) async fn abort_file_writer<R: Runtime>(
+    app: AppHandle<R>,
+    id: u32,
 ) -> Result<()> {
-    app.files().stop_monitoring()
-}
\ No newline at end of file
+    // Dropping the writer discards its data.
+    app.state::<JsWriters<R>>().with(id, true, |_| ())
+}
+
+#[command]
+pub(crate) async fn hash_file<R: Runtime>(
+    app: AppHandle<R>,
+    url: String,
+) -> Result<String> {
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || handle.files().hash_file(url)).await?
+}
+
+#[command]
+pub(crate) async fn get_space_info<R: Runtime>(
+    app: AppHandle<R>,
+    url: Option<String>,
+) -> Result<crate::SpaceInfo> {
+    app.files().get_space_info(url)
+}
+
+#[command]
+pub(crate) async fn get_file_permissions<R: Runtime>(
+    app: AppHandle<R>,
+    url: String,
+) -> Result<crate::FilePermissions> {
+    app.files().get_file_permissions(url)
+}
+
+#[command]
+pub(crate) async fn create_bookmark<R: Runtime>(
+    app: AppHandle<R>,
+    url: String,
+) -> Result<crate::SecurityScopedResource> {
+    app.files().create_bookmark(url)
+}
+
+#[command]
+pub(crate) async fn resolve_bookmark<R: Runtime>(
+    app: AppHandle<R>,
+    bookmark_data: String,
+) -> Result<crate::ResolvedBookmark> {
+    app.files().resolve_bookmark(bookmark_data)
+}
+
+#[command]
+pub(crate) async fn start_accessing_resource<R: Runtime>(
+    app: AppHandle<R>,
+    url: String,
+) -> Result<bool> {
+    app.files().start_accessing_resource(url)
+}
+
+#[command]
+pub(crate) async fn stop_accessing_resource<R: Runtime>(
+    app: AppHandle<R>,
+    url: String,
+) -> Result<()> {
+    app.files().stop_accessing_resource(url)
+}
@@ -1,104 +1,1287 @@
-use serde::de::DeserializeOwned;
-use tauri::{plugin::PluginApi, AppHandle, Runtime};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering as AtomicOrdering},
+};
 
-use crate::{models::*, Result, Error};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use notify::{
+    event::{ModifyKind, RenameMode},
+    EventKind, RecursiveMode, Watcher,
+};
+use sha2::{Digest, Sha256};
+use tauri::{plugin::PluginApi, AppHandle, Manager, Runtime};
 
-pub fn init<R: Runtime, C: DeserializeOwned>(
+use crate::{
+    bookmark,
+    cloud::ProgressSender,
+    file_types::{is_package, type_info},
+    models::*,
+    monitor::{ChangeSender, RawChange, Stop},
+    scope::{to_url, Scope},
+    stream::FileChunk,
+    Error, Result,
+};
+
+/// Where the files live when the config doesn't name a root, relative to the app
+/// data directory. It plays the part of the documents folder of the iOS app
+/// container.
+const DEFAULT_ROOT: &str = "Documents";
+
+pub fn init<R: Runtime>(
     app: &AppHandle<R>,
-    _api: PluginApi<R, C>,
+    api: PluginApi<R, Option<Config>>,
 ) -> Result<Files<R>> {
-    Ok(Files(app.clone()))
+    let root = api
+        .config()
+        .as_ref()
+        .and_then(|config| config.root.clone())
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_ROOT));
+
+    Files::new(app.clone(), app.path().app_data_dir()?.join(root))
+}
+
+/// A file opened by [`Files::open_reader`]. Every range comes from the file that
+/// was opened, even if another one takes its place in the meantime.
+pub(crate) struct RangeReader(fs::File);
+
+impl RangeReader {
+    pub(crate) fn read(&mut self, offset: u64, length: usize) -> Result<FileChunk> {
+        let total_size = self.0.metadata()?.len();
+        let mut data = Vec::with_capacity(length.min(total_size.saturating_sub(offset) as usize));
+        self.0.seek(SeekFrom::Start(offset))?;
+        (&mut self.0).take(length as u64).read_to_end(&mut data)?;
+        Ok(FileChunk {
+            offset,
+            eof: offset + data.len() as u64 >= total_size,
+            data,
+            total_size,
+        })
+    }
+}
+
+/// Access to the Files APIs on desktop, backed by the local filesystem.
+///
+/// Every location must be inside the configured root folder. Pickers, sharing,
+/// previews and iCloud are not available.
+pub struct Files<R: Runtime> {
+    app: AppHandle<R>,
+    scope: Scope,
+}
+
+#[derive(Clone, Copy)]
+enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+/// Whether the current user may access `path` in the given way.
+#[cfg(unix)]
+fn accessible(path: &Path, access: Access) -> bool {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mode = match access {
+        Access::Read => libc::R_OK,
+        Access::Write => libc::W_OK,
+        Access::Execute => libc::X_OK,
+    };
+    // SAFETY: `path` is a valid NUL-terminated string.
+    unsafe { libc::access(path.as_ptr(), mode) == 0 }
+}
+
+#[cfg(not(unix))]
+fn accessible(path: &Path, access: Access) -> bool {
+    let Ok(metadata) = fs::metadata(path) else {
+        return false;
+    };
+    match access {
+        Access::Read => true,
+        Access::Write => !metadata.permissions().readonly(),
+        Access::Execute => {
+            metadata.is_dir()
+                || path.extension().and_then(|extension| extension.to_str()).is_some_and(|extension| {
+                    ["exe", "bat", "cmd", "com"].contains(&extension.to_ascii_lowercase().as_str())
+                })
+        }
+    }
+}
+
+#[cfg(unix)]
+fn space_info(path: &Path) -> Result<SpaceInfo> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| Error::InvalidInput(format!("Invalid path: {}", path.display())))?;
+    // SAFETY: `statvfs` is plain data, and `path` is a valid NUL-terminated string.
+    let stats = unsafe {
+        let mut stats: libc::statvfs = std::mem::zeroed();
+        if libc::statvfs(path.as_ptr(), &mut stats) != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        stats
+    };
+
+    let block_size = stats.f_frsize as u64;
+    let total_space = stats.f_blocks as u64 * block_size;
+    let free_space = stats.f_bfree as u64 * block_size;
+    let available_space = stats.f_bavail as u64 * block_size;
+    Ok(SpaceInfo {
+        total_space,
+        available_space,
+        used_space: total_space.saturating_sub(free_space),
+        important_space: available_space,
+        opportunistic_space: available_space,
+    })
+}
+
+#[cfg(not(unix))]
+fn space_info(_path: &Path) -> Result<SpaceInfo> {
+    Err(Error::NotAvailable)
+}
+
+fn is_hidden(path: &Path, metadata: &fs::Metadata) -> bool {
+    let dotfile = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        dotfile || metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = metadata;
+        dotfile
+    }
+}
+
+fn document_info(path: &Path) -> Result<DocumentInfo> {
+    let link = fs::symlink_metadata(path)?;
+    let is_alias = link.file_type().is_symlink();
+    // A link describes the item it points to, unless that is gone.
+    let metadata = if is_alias {
+        fs::metadata(path).unwrap_or(link)
+    } else {
+        link
+    };
+
+    let is_directory = metadata.is_dir();
+    let modified_date: DateTime<Utc> = metadata.modified()?.into();
+    let info = type_info(path, is_directory);
+
+    let mut attributes = HashMap::new();
+    attributes.insert("readOnly".to_string(), metadata.permissions().readonly().to_string());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        attributes.insert(
+            "posixPermissions".to_string(),
+            format!("{:o}", metadata.permissions().mode() & 0o7777),
+        );
+    }
+
+    Ok(DocumentInfo {
+        url: to_url(path),
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        size: if is_directory { 0 } else { metadata.len() },
+        // Not every filesystem records when a file was created.
+        created_date: metadata.created().map(Into::into).unwrap_or(modified_date),
+        modified_date,
+        accessed_date: metadata.accessed().ok().map(Into::into),
+        mime_type: info.mime_type.map(str::to_string),
+        uti_type: info.uti_type.to_string(),
+        is_directory,
+        is_package: is_directory && is_package(path),
+        is_hidden: is_hidden(path, &metadata),
+        is_alias,
+        cloud_status: CloudStatus::NotInCloud,
+        tags: Vec::new(),
+        attributes,
+    })
+}
+
+/// Matches `*` and `?` wildcards, ignoring case.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                // Let the last star swallow one more character.
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Types and sizes only describe files, so folders never match a filter on them.
+fn filter_matches(filter: &FileFilter, path: &Path, document: &DocumentInfo) -> bool {
+    let file_only = filter.types.as_ref().is_some_and(|types| !types.is_empty())
+        || filter.min_size.is_some()
+        || filter.max_size.is_some();
+    if file_only && document.is_directory {
+        return false;
+    }
+
+    let info = type_info(path, document.is_directory);
+    filter
+        .types
+        .as_ref()
+        .map_or(true, |types| types.is_empty() || types.iter().any(|file_type| info.matches(path, file_type)))
+        && filter
+            .name_pattern
+            .as_ref()
+            .map_or(true, |pattern| glob_matches(pattern, &document.name))
+        && filter.min_size.map_or(true, |min| document.size >= min)
+        && filter.max_size.map_or(true, |max| document.size <= max)
+        && filter
+            .modified_after
+            .map_or(true, |after| document.modified_date >= after)
+        && filter
+            .modified_before
+            .map_or(true, |before| document.modified_date <= before)
+}
+
+/// Names ascending, the newest and largest first, types grouped by identifier.
+fn compare(sort_by: &SortOption, a: &DocumentInfo, b: &DocumentInfo) -> Ordering {
+    let by_name = || a.name.to_lowercase().cmp(&b.name.to_lowercase()).then_with(|| a.name.cmp(&b.name));
+    match sort_by {
+        SortOption::Name => by_name(),
+        SortOption::Date => b.modified_date.cmp(&a.modified_date).then_with(by_name),
+        SortOption::Size => b.size.cmp(&a.size).then_with(by_name),
+        SortOption::Type => a.uti_type.cmp(&b.uti_type).then_with(by_name),
+    }
+}
+
+/// Copies files and folders. Links are recreated rather than followed, so a copy
+/// can't pull in files from outside the root.
+fn copy_recursive(source: &Path, destination: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(source)?;
+    if metadata.file_type().is_symlink() {
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(fs::read_link(source)?, destination)?;
+        return Ok(());
+    }
+    if metadata.is_dir() {
+        fs::create_dir(destination)?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &destination.join(entry.file_name()))?;
+        }
+        return Ok(());
+    }
+    fs::copy(source, destination).map(|_| ())
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// A sibling of `path` to write to before renaming it into place. Unique within
+/// the process, so concurrent writes to the same file don't share one.
+fn temp_path(path: &Path) -> PathBuf {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let unique = NEXT.fetch_add(1, AtomicOrdering::Relaxed);
+    path.with_file_name(format!(".{name}.{}.{unique}.tmp", std::process::id()))
+}
+
+/// Whether a rename failed because the paths are on different volumes.
+fn crosses_devices(error: &io::Error) -> bool {
+    #[cfg(unix)]
+    let code = libc::EXDEV;
+    // ERROR_NOT_SAME_DEVICE
+    #[cfg(not(unix))]
+    let code = 17;
+    error.raw_os_error() == Some(code)
 }
 
-/// Access to the Files APIs on desktop (returns errors as not available).
-pub struct Files<R: Runtime>(AppHandle<R>);
+/// Renames `temp` to `destination`, replacing whatever is there. A folder
+/// involved on either side can't be renamed over, so the old destination is
+/// moved aside first and put back if the rename fails.
+fn replace(temp: &Path, destination: &Path) -> io::Result<()> {
+    let folders = fs::symlink_metadata(temp)?.is_dir()
+        || fs::symlink_metadata(destination).is_ok_and(|metadata| metadata.is_dir());
+    if !folders || fs::symlink_metadata(destination).is_err() {
+        return fs::rename(temp, destination);
+    }
+
+    let old = temp_path(destination);
+    fs::rename(destination, &old)?;
+    if let Err(e) = fs::rename(temp, destination) {
+        let _ = fs::rename(&old, destination);
+        return Err(e);
+    }
+    // The replacement is in place; a leftover only wastes space.
+    let _ = remove(&old);
+    Ok(())
+}
 
 impl<R: Runtime> Files<R> {
+    /// Confines the files to `root`, which is created if needed.
+    pub(crate) fn new(app: AppHandle<R>, root: PathBuf) -> Result<Self> {
+        Ok(Self {
+            app,
+            scope: Scope::new(root)?,
+        })
+    }
+
+    /// The folder every location is confined to.
+    pub fn root(&self) -> &Path {
+        self.scope.root()
+    }
+
+    /// Resolves the source and destination of a move or copy, and checks the
+    /// destination may be overwritten if it exists.
+    fn prepare_transfer(&self, operation: &FileOperation) -> Result<(PathBuf, PathBuf)> {
+        let source = self.scope.resolve_entry(&operation.source_url)?;
+        let destination = self.scope.resolve_entry(&operation.destination_url)?;
+        let metadata = fs::symlink_metadata(&source)?;
+
+        if destination == source {
+            return Err(Error::InvalidInput("The source and destination are the same".to_string()));
+        }
+        if metadata.is_dir() && destination.starts_with(&source) {
+            return Err(Error::InvalidInput("A folder can't be moved or copied into itself".to_string()));
+        }
+        if !operation.overwrite && fs::symlink_metadata(&destination).is_ok() {
+            return Err(Error::OperationFailed(format!(
+                "{} already exists",
+                to_url(&destination)
+            )));
+        }
+        Ok((source, destination))
+    }
+
+    fn collect(
+        &self,
+        directory: &Path,
+        options: &ListOptions,
+        documents: &mut Vec<DocumentInfo>,
+    ) -> Result<()> {
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            let document = match document_info(&path) {
+                Ok(document) => document,
+                // Removed while listing.
+                Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            if document.is_hidden && !options.include_hidden {
+                continue;
+            }
+            if document.is_package && !options.include_packages {
+                continue;
+            }
+
+            // Links are listed but not followed, they may lead out of the root.
+            let descend = document.is_directory && !document.is_package && !document.is_alias;
+            if options
+                .filter
+                .as_ref()
+                .map_or(true, |filter| filter_matches(filter, &path, &document))
+            {
+                documents.push(document);
+            }
+            if descend {
+                self.collect(&path, options, documents)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn pick_file(&self, _options: FilePickerOptions) -> Result<PickedFile> {
         Err(Error::NotAvailable)
     }
-    
+
     pub fn pick_multiple_files(&self, _options: FilePickerOptions) -> Result<Vec<PickedFile>> {
         Err(Error::NotAvailable)
     }
-    
+
     pub fn pick_folder(&self) -> Result<PickedFile> {
         Err(Error::NotAvailable)
     }
-    
+
     pub fn save_file(&self, _options: SaveFileOptions) -> Result<String> {
         Err(Error::NotAvailable)
     }
-    
+
     pub fn open_in_files(&self, _url: String) -> Result<()> {
         Err(Error::NotAvailable)
     }
-    
+
     pub fn import_from_files(&self, _options: ImportOptions) -> Result<Vec<PickedFile>> {
         Err(Error::NotAvailable)
     }
-    
+
     pub fn export_to_files(&self, _options: ExportOptions) -> Result<()> {
         Err(Error::NotAvailable)
     }
-    
-    pub fn list_documents(&self, _options: ListOptions) -> Result<Vec<DocumentInfo>> {
-        Err(Error::NotAvailable)
+
+    /// Lists the contents of a folder and its subfolders, like the iOS
+    /// implementation. Defaults to the root folder.
+    pub fn list_documents(&self, options: ListOptions) -> Result<Vec<DocumentInfo>> {
+        let directory = match &options.directory_url {
+            Some(url) => self.scope.resolve(url)?,
+            None => self.scope.root().to_path_buf(),
+        };
+        if !directory.is_dir() {
+            return Err(Error::InvalidInput(format!("{} is not a folder", to_url(&directory))));
+        }
+
+        let mut documents = Vec::new();
+        self.collect(&directory, &options, &mut documents)?;
+        documents.sort_by(|a, b| compare(&options.sort_by, a, b));
+        Ok(documents)
     }
-    
-    pub fn read_file(&self, _url: String) -> Result<FileData> {
-        Err(Error::NotAvailable)
+
+    /// Returns text for UTF-8 files and base64 for anything else.
+    pub fn read_file(&self, url: String) -> Result<FileData> {
+        let path = self.scope.resolve(&url)?;
+        if path.is_dir() {
+            return Err(Error::InvalidInput(format!("{url} is a folder")));
+        }
+        Ok(match String::from_utf8(fs::read(&path)?) {
+            Ok(text) => FileData::Text(text),
+            Err(e) => FileData::Base64(STANDARD.encode(e.into_bytes())),
+        })
     }
-    
-    pub fn write_file(&self, _url: String, _data: FileData) -> Result<()> {
-        Err(Error::NotAvailable)
+
+    /// Replaces the file atomically. `FileData::Url` copies another file inside
+    /// the root.
+    pub fn write_file(&self, url: String, data: FileData) -> Result<()> {
+        let path = self.scope.resolve_entry(&url)?;
+        if path.is_dir() {
+            return Err(Error::InvalidInput(format!("{url} is a folder")));
+        }
+
+        let temp = temp_path(&path);
+        let written = match data {
+            FileData::Base64(encoded) => {
+                let bytes = STANDARD
+                    .decode(encoded.trim())
+                    .map_err(|e| Error::InvalidInput(format!("Invalid base64 data: {e}")))?;
+                fs::write(&temp, bytes)
+            }
+            FileData::Text(text) => fs::write(&temp, text),
+            FileData::Url(source) => {
+                let source = self.scope.resolve(&source)?;
+                if source.is_dir() {
+                    return Err(Error::InvalidInput(format!("{} is a folder", to_url(&source))));
+                }
+                fs::copy(source, &temp).map(|_| ())
+            }
+        };
+
+        if let Err(e) = written.and_then(|_| fs::rename(&temp, &path)) {
+            let _ = fs::remove_file(&temp);
+            return Err(e.into());
+        }
+        Ok(())
     }
-    
-    pub fn delete_file(&self, _url: String) -> Result<()> {
-        Err(Error::NotAvailable)
+
+    /// Deletes a file, or a folder with everything in it.
+    pub fn delete_file(&self, url: String) -> Result<()> {
+        remove(&self.scope.resolve_entry(&url)?).map_err(Into::into)
     }
-    
-    pub fn move_file(&self, _operation: FileOperation) -> Result<String> {
-        Err(Error::NotAvailable)
+
+    /// Moves the source next to the destination and then renames it over it, so
+    /// an overwritten destination is only lost once the source has arrived.
+    pub fn move_file(&self, operation: FileOperation) -> Result<String> {
+        let (source, destination) = self.prepare_transfer(&operation)?;
+        let temp = temp_path(&destination);
+        // Renaming fails across volumes, where the data has to be copied.
+        let copied = match fs::rename(&source, &temp) {
+            Ok(()) => false,
+            Err(e) if crosses_devices(&e) => {
+                if let Err(e) = copy_recursive(&source, &temp) {
+                    let _ = remove(&temp);
+                    return Err(e.into());
+                }
+                true
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if let Err(e) = replace(&temp, &destination) {
+            let _ = if copied { remove(&temp) } else { fs::rename(&temp, &source) };
+            return Err(e.into());
+        }
+        if copied {
+            remove(&source)?;
+        }
+        Ok(to_url(&destination))
     }
-    
-    pub fn copy_file(&self, _operation: FileOperation) -> Result<String> {
-        Err(Error::NotAvailable)
+
+    /// Copies to a sibling of the destination and renames it over it, so a
+    /// failed copy leaves an overwritten destination untouched.
+    pub fn copy_file(&self, operation: FileOperation) -> Result<String> {
+        let (source, destination) = self.prepare_transfer(&operation)?;
+        let temp = temp_path(&destination);
+        if let Err(e) = copy_recursive(&source, &temp).and_then(|_| replace(&temp, &destination)) {
+            let _ = remove(&temp);
+            return Err(e.into());
+        }
+        Ok(to_url(&destination))
     }
-    
-    pub fn create_folder(&self, _url: String, _name: String) -> Result<String> {
-        Err(Error::NotAvailable)
+
+    /// Creates the folder `name` in the folder at `url`, along with any missing
+    /// parents. An existing folder is not an error.
+    pub fn create_folder(&self, url: String, name: String) -> Result<String> {
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            return Err(Error::InvalidInput(format!("Invalid folder name: {name:?}")));
+        }
+        let folder = self.scope.resolve(&url)?.join(name);
+        fs::create_dir_all(&folder)?;
+        Ok(to_url(&folder))
     }
-    
-    pub fn get_file_info(&self, _url: String) -> Result<DocumentInfo> {
-        Err(Error::NotAvailable)
+
+    pub fn get_file_info(&self, url: String) -> Result<DocumentInfo> {
+        document_info(&self.scope.resolve(&url)?)
     }
-    
+
     pub fn share_file(&self, _options: ShareOptions) -> Result<()> {
         Err(Error::NotAvailable)
     }
-    
+
     pub fn preview_file(&self, _options: PreviewOptions) -> Result<()> {
         Err(Error::NotAvailable)
     }
-    
+
     pub fn get_cloud_status(&self, _url: String) -> Result<CloudStatus> {
         Err(Error::NotAvailable)
     }
-    
-    pub fn download_from_cloud(&self, _url: String) -> Result<()> {
-        Err(Error::NotAvailable)
-    }
-    
+
     pub fn evict_from_local(&self, _url: String) -> Result<()> {
         Err(Error::NotAvailable)
     }
-    
-    pub fn start_monitoring(&self, _options: MonitoringOptions) -> Result<()> {
+
+    pub fn list_not_downloaded(&self, _directory_url: String, _recursive: bool) -> Result<Vec<CloudItem>> {
         Err(Error::NotAvailable)
     }
-    
-    pub fn stop_monitoring(&self) -> Result<()> {
+
+    pub fn hash_file(&self, url: String) -> Result<String> {
+        let path = self.scope.resolve(&url)?;
+        if path.is_dir() {
+            return Err(Error::InvalidInput(format!("{url} is a folder")));
+        }
+        let mut hasher = Sha256::new();
+        io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect())
+    }
+
+    /// Capacity of the volume holding `url`, or the root when unset. The
+    /// important and opportunistic capacity are what is available, as desktop
+    /// systems don't purge anything to make room.
+    pub fn get_space_info(&self, url: Option<String>) -> Result<SpaceInfo> {
+        let path = match url {
+            Some(url) => self.scope.resolve(&url)?,
+            None => self.root().to_path_buf(),
+        };
+        space_info(&path)
+    }
+
+    pub fn get_file_permissions(&self, url: String) -> Result<FilePermissions> {
+        let path = self.scope.resolve(&url)?;
+        fs::symlink_metadata(&path)?;
+        // Removing an item takes write access to its folder.
+        let deletable = path != self.root() && path.parent().is_some_and(|parent| accessible(parent, Access::Write));
+        Ok(FilePermissions {
+            readable: accessible(&path, Access::Read),
+            writable: accessible(&path, Access::Write),
+            deletable,
+            executable: accessible(&path, Access::Execute),
+        })
+    }
+
+    /// Creates a bookmark that finds the item again after it is moved or renamed
+    /// inside the root.
+    pub fn create_bookmark(&self, url: String) -> Result<SecurityScopedResource> {
+        let path = self.scope.resolve(&url)?;
+        Ok(SecurityScopedResource {
+            bookmark_data: bookmark::create(&self.scope, &path)?,
+            url: to_url(&path),
+        })
+    }
+
+    pub fn resolve_bookmark(&self, bookmark_data: String) -> Result<ResolvedBookmark> {
+        let (path, is_stale) = bookmark::resolve(&self.scope, &bookmark_data)?;
+        Ok(ResolvedBookmark {
+            bookmark_data: if is_stale {
+                bookmark::create(&self.scope, &path)?
+            } else {
+                bookmark_data
+            },
+            url: to_url(&path),
+            is_stale,
+        })
+    }
+
+    /// Everything inside the root is always accessible, so this only checks that
+    /// `url` is inside it.
+    pub fn start_accessing_resource(&self, url: String) -> Result<bool> {
+        Ok(fs::symlink_metadata(self.scope.resolve(&url)?).is_ok())
+    }
+
+    pub fn stop_accessing_resource(&self, url: String) -> Result<()> {
+        self.scope.resolve(&url).map(|_| ())
+    }
+
+    pub(crate) fn app(&self) -> &AppHandle<R> {
+        &self.app
+    }
+
+    pub(crate) fn read_range(&self, url: &str, offset: u64, length: usize) -> Result<FileChunk> {
+        self.open_reader(url)?.read(offset, length)
+    }
+
+    /// Opens a file for reading several ranges of it.
+    pub(crate) fn open_reader(&self, url: &str) -> Result<RangeReader> {
+        let path = self.scope.resolve(url)?;
+        if path.is_dir() {
+            return Err(Error::InvalidInput(format!("{url} is a folder")));
+        }
+        Ok(RangeReader(fs::File::open(path)?))
+    }
+
+    /// Appends to a file, creating it if needed, and returns its new size.
+    pub(crate) fn append_bytes(&self, url: &str, bytes: &[u8]) -> Result<u64> {
+        let path = self.scope.resolve_entry(url)?;
+        if path.is_dir() {
+            return Err(Error::InvalidInput(format!("{url} is a folder")));
+        }
+        let mut file = fs::OpenOptions::new().append(true).create(true).open(path)?;
+        file.write_all(bytes)?;
+        Ok(file.metadata()?.len())
+    }
+
+    /// Creates the empty temporary file of a writer and returns its URL.
+    pub(crate) fn begin_write(&self, id: u32, url: &str, create_new: bool) -> Result<String> {
+        let path = self.scope.resolve_entry(url)?;
+        if path.is_dir() {
+            return Err(Error::InvalidInput(format!("{url} is a folder")));
+        }
+        if create_new && fs::symlink_metadata(&path).is_ok() {
+            return Err(Error::OperationFailed(format!("{} already exists", to_url(&path))));
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let temp = path.with_file_name(format!(".{name}.{}-{id}.part", std::process::id()));
+        fs::File::create(&temp)?;
+        Ok(to_url(&temp))
+    }
+
+    /// Moves the temporary file of a writer into place and returns its size.
+    pub(crate) fn commit_write(&self, temp_url: &str, url: &str, create_new: bool) -> Result<u64> {
+        let temp = self.scope.resolve_entry(temp_url)?;
+        let path = self.scope.resolve_entry(url)?;
+        if path.is_dir() {
+            return Err(Error::InvalidInput(format!("{url} is a folder")));
+        }
+        // The destination may have appeared since the writer was opened.
+        if create_new && fs::symlink_metadata(&path).is_ok() {
+            return Err(Error::OperationFailed(format!("{} already exists", to_url(&path))));
+        }
+        fs::rename(&temp, &path)?;
+        Ok(fs::metadata(&path)?.len())
+    }
+
+    pub(crate) fn discard_write(&self, temp_url: &str) {
+        if let Ok(temp) = self.scope.resolve_entry(temp_url) {
+            let _ = fs::remove_file(temp);
+        }
+    }
+
+    pub(crate) fn start_download(&self, _id: u32, _urls: &[String], _progress: ProgressSender) -> Result<Stop> {
         Err(Error::NotAvailable)
     }
-}
\ No newline at end of file
+
+    /// Starts the native watcher of a monitor. Dropping the watcher stops it.
+    pub(crate) fn watch(&self, _id: u32, options: &MonitoringOptions, changes: ChangeSender) -> Result<Stop> {
+        let mode = if options.recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        let directories = options
+            .directory_urls
+            .iter()
+            .map(|url| {
+                let path = self.scope.resolve(url)?;
+                if !path.is_dir() {
+                    return Err(Error::InvalidInput(format!("{url} is not a folder")));
+                }
+                Ok(path)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if let Some(change) = event.ok().and_then(raw_change) {
+                changes.send(change);
+            }
+        })
+        .map_err(watch_error)?;
+        for directory in &directories {
+            watcher.watch(directory, mode).map_err(watch_error)?;
+        }
+        Ok(Box::new(move || drop(watcher)))
+    }
+}
+
+fn watch_error(error: notify::Error) -> Error {
+    match error.kind {
+        notify::ErrorKind::Io(e) => Error::Io(e),
+        notify::ErrorKind::PathNotFound => Error::Io(io::ErrorKind::NotFound.into()),
+        _ => Error::OperationFailed(error.to_string()),
+    }
+}
+
+/// Translates a native watcher event into the change a monitor reports.
+fn raw_change(event: notify::Event) -> Option<RawChange> {
+    let cookie = event.attrs.tracker();
+    let mut paths = event.paths.into_iter();
+    let path = paths.next()?;
+
+    let url = to_url(&path);
+    let change = match event.kind {
+        EventKind::Create(_) => RawChange::Created(url),
+        EventKind::Remove(_) => RawChange::Deleted(url),
+        EventKind::Modify(ModifyKind::Metadata(_)) => RawChange::AttributesChanged(url),
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => RawChange::Renamed {
+            from: url,
+            to: to_url(&paths.next()?),
+        },
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => RawChange::RenamedFrom { url, cookie },
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => RawChange::RenamedTo { url, cookie },
+        // The watcher can't tell which half of the rename this is.
+        EventKind::Modify(ModifyKind::Name(_)) => {
+            if fs::symlink_metadata(&path).is_ok() {
+                RawChange::RenamedTo { url, cookie }
+            } else {
+                RawChange::RenamedFrom { url, cookie }
+            }
+        }
+        EventKind::Modify(_) => RawChange::Modified(url),
+        EventKind::Access(_) | EventKind::Any | EventKind::Other => return None,
+    };
+    Some(change)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty folder of its own for each test.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tauri-plugin-ios-files-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn folder(path: &Path, file: &str, contents: &str) {
+        fs::create_dir_all(path).unwrap();
+        fs::write(path.join(file), contents).unwrap();
+    }
+
+    #[test]
+    fn temp_paths_are_unique_hidden_siblings() {
+        let path = Path::new("/documents/report.txt");
+        let (first, second) = (temp_path(path), temp_path(path));
+        assert_ne!(first, second);
+        assert_eq!(first.parent(), path.parent());
+        assert!(first.file_name().unwrap().to_string_lossy().starts_with(".report.txt."));
+    }
+
+    #[test]
+    fn only_cross_device_renames_are_recognized() {
+        assert!(crosses_devices(&io::Error::from_raw_os_error(libc::EXDEV)));
+        assert!(!crosses_devices(&io::Error::from_raw_os_error(libc::EACCES)));
+        assert!(!crosses_devices(&io::Error::from(io::ErrorKind::NotFound)));
+    }
+
+    #[test]
+    fn replace_puts_files_and_folders_in_place() {
+        let dir = scratch("replace");
+        let destination = dir.join("destination");
+
+        fs::write(dir.join("a"), "a").unwrap();
+        replace(&dir.join("a"), &destination).unwrap();
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "a");
+
+        fs::write(dir.join("b"), "b").unwrap();
+        replace(&dir.join("b"), &destination).unwrap();
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "b");
+
+        folder(&dir.join("c"), "inner", "c");
+        replace(&dir.join("c"), &destination).unwrap();
+        assert_eq!(fs::read_to_string(destination.join("inner")).unwrap(), "c");
+
+        folder(&dir.join("d"), "other", "d");
+        replace(&dir.join("d"), &destination).unwrap();
+        assert_eq!(fs::read_to_string(destination.join("other")).unwrap(), "d");
+        assert!(!destination.join("inner").exists());
+
+        fs::write(dir.join("e"), "e").unwrap();
+        replace(&dir.join("e"), &destination).unwrap();
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "e");
+
+        // Nothing is left behind next to the destination.
+        let names: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(names, ["destination"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_failed_replace_keeps_the_destination() {
+        let dir = scratch("failed-replace");
+        let destination = dir.join("destination");
+        folder(&destination, "inner", "kept");
+
+        assert!(replace(&dir.join("missing"), &destination).is_err());
+        assert_eq!(fs::read_to_string(destination.join("inner")).unwrap(), "kept");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_reader_keeps_reading_the_file_it_opened() {
+        let dir = scratch("reader");
+        let path = dir.join("file");
+        fs::write(&path, "first file").unwrap();
+        let mut reader = RangeReader(fs::File::open(&path).unwrap());
+
+        let chunk = reader.read(0, 5).unwrap();
+        assert_eq!((chunk.data.as_slice(), chunk.total_size, chunk.eof), (&b"first"[..], 10, false));
+
+        fs::write(dir.join("other"), "second").unwrap();
+        fs::rename(dir.join("other"), &path).unwrap();
+        let chunk = reader.read(5, 100).unwrap();
+        assert_eq!((chunk.data.as_slice(), chunk.offset, chunk.eof), (&b" file"[..], 5, true));
+        assert!(reader.read(10, 100).unwrap().data.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn files(dir: &Path) -> Files<tauri::test::MockRuntime> {
+        Files::new(tauri::test::mock_app().handle().clone(), dir.to_path_buf()).unwrap()
+    }
+
+    fn document(name: &str, size: u64, modified: i64, is_directory: bool) -> DocumentInfo {
+        let path = Path::new(name);
+        let modified_date = DateTime::from_timestamp(modified, 0).unwrap();
+        DocumentInfo {
+            url: format!("file:///{name}"),
+            name: name.to_string(),
+            size,
+            created_date: modified_date,
+            modified_date,
+            accessed_date: None,
+            mime_type: None,
+            uti_type: type_info(path, is_directory).uti_type.to_string(),
+            is_directory,
+            is_package: false,
+            is_hidden: false,
+            is_alias: false,
+            cloud_status: CloudStatus::NotInCloud,
+            tags: Vec::new(),
+            attributes: HashMap::new(),
+        }
+    }
+
+    fn filter() -> FileFilter {
+        FileFilter {
+            types: None,
+            name_pattern: None,
+            min_size: None,
+            max_size: None,
+            modified_after: None,
+            modified_before: None,
+        }
+    }
+
+    fn list(files: &Files<tauri::test::MockRuntime>, include_hidden: bool, include_packages: bool) -> Vec<String> {
+        let documents = files
+            .list_documents(ListOptions {
+                directory_url: None,
+                include_hidden,
+                include_packages,
+                sort_by: SortOption::Name,
+                filter: None,
+            })
+            .unwrap();
+        let root = to_url(files.root());
+        documents
+            .iter()
+            .map(|document| document.url.strip_prefix(&root).unwrap().trim_start_matches('/').to_string())
+            .collect()
+    }
+
+    fn transfer(source: &str, destination: &str, overwrite: bool) -> FileOperation {
+        FileOperation {
+            source_url: source.to_string(),
+            destination_url: destination.to_string(),
+            overwrite,
+        }
+    }
+
+    #[test]
+    fn globs_match_whole_names_ignoring_case() {
+        assert!(glob_matches("*.txt", "Notes.TXT"));
+        assert!(glob_matches("report-?.pdf", "report-3.pdf"));
+        assert!(!glob_matches("report-?.pdf", "report-12.pdf"));
+        assert!(glob_matches("*a*b", "xxaxxbxxb"));
+        assert!(!glob_matches("*a*b", "xxaxxbxx"));
+        assert!(glob_matches("a**c", "abbbc"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("", ""));
+        assert!(!glob_matches("", "a"));
+        assert!(!glob_matches("a*", ""));
+        assert!(!glob_matches("notes", "notes.txt"));
+        assert!(glob_matches("ÉTÉ*", "été.jpg"));
+    }
+
+    #[test]
+    fn filters_match_every_condition_and_skip_folders_for_file_conditions() {
+        let notes = document("notes.txt", 100, 1_000, false);
+        let photo = document("photo.png", 5_000, 2_000, false);
+        let folder = document("notes", 0, 1_500, true);
+        let matches = |filter: &FileFilter, document: &DocumentInfo| filter_matches(filter, Path::new(&document.name), document);
+
+        assert!([&notes, &photo, &folder].iter().all(|document| matches(&filter(), document)));
+
+        let text = FileFilter {
+            types: Some(vec![FileType::Text]),
+            ..filter()
+        };
+        assert!(matches(&text, &notes) && !matches(&text, &photo) && !matches(&text, &folder));
+        let custom = FileFilter {
+            types: Some(vec![FileType::Custom(vec![".PNG".into()])]),
+            ..filter()
+        };
+        assert!(!matches(&custom, &notes) && matches(&custom, &photo));
+        // An empty list of types doesn't filter anything out.
+        assert!(matches(&FileFilter { types: Some(Vec::new()), ..filter() }, &folder));
+
+        let sized = FileFilter {
+            min_size: Some(100),
+            max_size: Some(4_999),
+            ..filter()
+        };
+        assert!(matches(&sized, &notes) && !matches(&sized, &photo) && !matches(&sized, &folder));
+        let small = FileFilter {
+            max_size: Some(10_000),
+            ..filter()
+        };
+        assert!(!matches(&small, &folder));
+
+        let named = FileFilter {
+            name_pattern: Some("NOTES*".into()),
+            ..filter()
+        };
+        assert!(matches(&named, &notes) && !matches(&named, &photo) && matches(&named, &folder));
+
+        let dated = FileFilter {
+            modified_after: DateTime::from_timestamp(1_000, 0),
+            modified_before: DateTime::from_timestamp(1_500, 0),
+            ..filter()
+        };
+        assert!(matches(&dated, &notes) && !matches(&dated, &photo) && matches(&dated, &folder));
+        let both = FileFilter {
+            name_pattern: Some("*.txt".into()),
+            modified_after: DateTime::from_timestamp(1_001, 0),
+            ..filter()
+        };
+        assert!(!matches(&both, &notes));
+    }
+
+    #[test]
+    fn documents_sort_by_name_date_size_and_type() {
+        let documents = [
+            document("b.txt", 10, 3_000, false),
+            document("A.png", 30, 1_000, false),
+            document("a.png", 30, 2_000, false),
+            document("c.md", 20, 3_000, false),
+        ];
+        let sorted = |sort_by: SortOption| {
+            let mut sorted = documents.to_vec();
+            sorted.sort_by(|a, b| compare(&sort_by, a, b));
+            sorted.into_iter().map(|document| document.name).collect::<Vec<_>>()
+        };
+
+        assert_eq!(sorted(SortOption::Name), ["A.png", "a.png", "b.txt", "c.md"]);
+        assert_eq!(sorted(SortOption::Date), ["b.txt", "c.md", "a.png", "A.png"]);
+        assert_eq!(sorted(SortOption::Size), ["A.png", "a.png", "c.md", "b.txt"]);
+        // net.daringfireball.markdown, public.plain-text, public.png
+        assert_eq!(sorted(SortOption::Type), ["c.md", "b.txt", "A.png", "a.png"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn listings_skip_hidden_items_and_packages_and_dont_follow_links() {
+        let dir = scratch("list");
+        let root = dir.join("root");
+        folder(&root, "a.txt", "a");
+        fs::write(root.join(".hidden.txt"), "hidden").unwrap();
+        folder(&root.join("sub"), "b.md", "b");
+        folder(&root.join(".git"), "config", "c");
+        folder(&root.join("App.bundle"), "inner.txt", "inner");
+        folder(&dir.join("outside"), "secret.txt", "secret");
+        std::os::unix::fs::symlink(root.join("sub"), root.join("link")).unwrap();
+        std::os::unix::fs::symlink(dir.join("outside"), root.join("outside")).unwrap();
+        let files = files(&root);
+
+        // Sorted by name, wherever in the tree the items are.
+        assert_eq!(list(&files, false, false), ["a.txt", "sub/b.md", "link", "outside", "sub"]);
+        assert_eq!(
+            list(&files, true, false),
+            [".git", ".hidden.txt", "a.txt", "sub/b.md", ".git/config", "link", "outside", "sub"]
+        );
+        // Packages are listed as one item, without their contents.
+        assert_eq!(list(&files, false, true), ["a.txt", "App.bundle", "sub/b.md", "link", "outside", "sub"]);
+
+        let documents = files
+            .list_documents(ListOptions {
+                directory_url: Some("sub".into()),
+                include_hidden: false,
+                include_packages: false,
+                sort_by: SortOption::Name,
+                filter: None,
+            })
+            .unwrap();
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].name, "b.md");
+
+        let all = files
+            .list_documents(ListOptions {
+                directory_url: None,
+                include_hidden: false,
+                include_packages: true,
+                sort_by: SortOption::Name,
+                filter: None,
+            })
+            .unwrap();
+        let link = all.iter().find(|document| document.name == "link").unwrap();
+        assert!(link.is_alias && link.is_directory);
+        assert!(all.iter().find(|document| document.name == "App.bundle").unwrap().is_package);
+
+        let result = files.list_documents(ListOptions {
+            directory_url: Some("a.txt".into()),
+            include_hidden: false,
+            include_packages: false,
+            sort_by: SortOption::Name,
+            filter: None,
+        });
+        assert!(matches!(result, Err(Error::InvalidInput(_))), "{result:?}");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn existing_destinations_are_only_replaced_when_overwriting() {
+        let dir = scratch("overwrite");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        let files = files(&dir);
+
+        let result = files.copy_file(transfer("a.txt", "b.txt", false));
+        assert!(matches!(result, Err(Error::OperationFailed(_))), "{result:?}");
+        let result = files.move_file(transfer("a.txt", "b.txt", false));
+        assert!(matches!(result, Err(Error::OperationFailed(_))), "{result:?}");
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "b");
+
+        files.copy_file(transfer("a.txt", "b.txt", true)).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "a");
+        fs::write(dir.join("a.txt"), "moved").unwrap();
+        let url = files.move_file(transfer("a.txt", "b.txt", true)).unwrap();
+        assert_eq!(url, to_url(&files.root().join("b.txt")));
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "moved");
+        assert!(!dir.join("a.txt").exists());
+
+        let result = files.copy_file(transfer("b.txt", "./b.txt", true));
+        assert!(matches!(result, Err(Error::InvalidInput(_))), "{result:?}");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn folders_cant_be_moved_or_copied_into_themselves() {
+        let dir = scratch("into-itself");
+        folder(&dir.join("folder"), "file.txt", "contents");
+        let files = files(&dir);
+
+        for destination in ["folder/inner", "folder/inner/deeper"] {
+            let result = files.move_file(transfer("folder", destination, false));
+            assert!(matches!(result, Err(Error::InvalidInput(_))), "{result:?}");
+            let result = files.copy_file(transfer("folder", destination, false));
+            assert!(matches!(result, Err(Error::InvalidInput(_))), "{result:?}");
+        }
+        assert_eq!(fs::read_to_string(dir.join("folder/file.txt")).unwrap(), "contents");
+
+        // A sibling that only shares the name as a prefix is fine.
+        files.copy_file(transfer("folder", "folder-copy", false)).unwrap();
+        files.move_file(transfer("folder", "folder-copy/folder", false)).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("folder-copy/folder/file.txt")).unwrap(), "contents");
+        assert!(!dir.join("folder").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn files_are_written_from_text_base64_and_other_files() {
+        let dir = scratch("write");
+        fs::create_dir(dir.join("folder")).unwrap();
+        let files = files(&dir);
+
+        files.write_file("text.txt".into(), FileData::Text("héllo".into())).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("text.txt")).unwrap(), "héllo");
+        files
+            .write_file("data.bin".into(), FileData::Base64(STANDARD.encode([0, 159, 146, 150])))
+            .unwrap();
+        assert_eq!(fs::read(dir.join("data.bin")).unwrap(), [0, 159, 146, 150]);
+        files.write_file("copy.txt".into(), FileData::Url("text.txt".into())).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("copy.txt")).unwrap(), "héllo");
+        files.write_file("copy.txt".into(), FileData::Text("replaced".into())).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("copy.txt")).unwrap(), "replaced");
+
+        // Reading gives back the form the data was written in.
+        assert!(matches!(files.read_file("text.txt".into()).unwrap(), FileData::Text(text) if text == "héllo"));
+        assert!(matches!(files.read_file("data.bin".into()).unwrap(), FileData::Base64(data) if data == "AJ+Slg=="));
+
+        let result = files.write_file("data.bin".into(), FileData::Base64("not base64!".into()));
+        assert!(matches!(result, Err(Error::InvalidInput(_))), "{result:?}");
+        assert_eq!(fs::read(dir.join("data.bin")).unwrap(), [0, 159, 146, 150]);
+        let result = files.write_file("copy.txt".into(), FileData::Url("folder".into()));
+        assert!(matches!(result, Err(Error::InvalidInput(_))), "{result:?}");
+        let result = files.write_file("copy.txt".into(), FileData::Url("/etc/hosts".into()));
+        assert!(matches!(result, Err(Error::PermissionDenied)), "{result:?}");
+        let result = files.write_file("copy.txt".into(), FileData::Url("missing.txt".into()));
+        assert!(matches!(result, Err(Error::Io(_))), "{result:?}");
+        assert_eq!(fs::read_to_string(dir.join("copy.txt")).unwrap(), "replaced");
+        let result = files.write_file("folder".into(), FileData::Text("text".into()));
+        assert!(matches!(result, Err(Error::InvalidInput(_))), "{result:?}");
+
+        // No temporary files are left behind by either outcome.
+        let mut names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["copy.txt", "data.bin", "folder", "text.txt"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stale_bookmarks_are_renewed() {
+        let dir = scratch("bookmarks");
+        fs::write(dir.join("notes.txt"), "notes").unwrap();
+        let files = files(&dir);
+
+        let bookmark = files.create_bookmark("notes.txt".into()).unwrap();
+        assert_eq!(bookmark.url, to_url(&files.root().join("notes.txt")));
+        let resolved = files.resolve_bookmark(bookmark.bookmark_data.clone()).unwrap();
+        assert!(!resolved.is_stale);
+        assert_eq!((resolved.url, resolved.bookmark_data), (bookmark.url, bookmark.bookmark_data.clone()));
+
+        fs::create_dir(dir.join("archive")).unwrap();
+        fs::rename(dir.join("notes.txt"), dir.join("archive/old-notes.txt")).unwrap();
+        let resolved = files.resolve_bookmark(bookmark.bookmark_data.clone()).unwrap();
+        assert!(resolved.is_stale);
+        assert_eq!(resolved.url, to_url(&files.root().join("archive/old-notes.txt")));
+        assert_ne!(resolved.bookmark_data, bookmark.bookmark_data);
+        let renewed = files.resolve_bookmark(resolved.bookmark_data).unwrap();
+        assert!(!renewed.is_stale);
+        assert_eq!(renewed.url, resolved.url);
+
+        fs::remove_file(dir.join("archive/old-notes.txt")).unwrap();
+        assert!(matches!(files.resolve_bookmark(bookmark.bookmark_data), Err(Error::Io(_))));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn permissions_reflect_the_mode_and_the_folder() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch("permissions");
+        folder(&dir.join("locked"), "file.txt", "locked");
+        fs::write(dir.join("script.sh"), "#!/bin/sh").unwrap();
+        fs::write(dir.join("notes.txt"), "notes").unwrap();
+        fs::set_permissions(dir.join("script.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(dir.join("notes.txt"), fs::Permissions::from_mode(0o644)).unwrap();
+        let files = files(&dir);
+        let permissions = |url: &str| files.get_file_permissions(url.into()).unwrap();
+
+        let notes = permissions("notes.txt");
+        assert!(notes.readable && notes.writable && notes.deletable && !notes.executable);
+        assert!(permissions("script.sh").executable);
+        let root = permissions("");
+        assert!(root.readable && root.writable && !root.deletable && root.executable);
+        assert!(matches!(files.get_file_permissions("missing.txt".into()), Err(Error::Io(_))));
+        assert!(matches!(files.get_file_permissions("../".into()), Err(Error::PermissionDenied)));
+
+        // Root may write anything, so read-only items only show up for other users.
+        // SAFETY: `geteuid` has no preconditions.
+        if unsafe { libc::geteuid() } != 0 {
+            fs::set_permissions(dir.join("notes.txt"), fs::Permissions::from_mode(0o444)).unwrap();
+            fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o555)).unwrap();
+            let notes = permissions("notes.txt");
+            assert!(notes.readable && !notes.writable && notes.deletable);
+            let locked = permissions("locked/file.txt");
+            assert!(locked.writable && !locked.deletable);
+            fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn space_is_reported_for_the_volume_of_the_root() {
+        let dir = scratch("space");
+        fs::write(dir.join("notes.txt"), "notes").unwrap();
+        let files = files(&dir);
+
+        let space = files.get_space_info(None).unwrap();
+        assert!(space.total_space > 0);
+        assert!(space.available_space <= space.total_space);
+        assert!(space.used_space <= space.total_space);
+        assert_eq!(space.important_space, space.available_space);
+        assert_eq!(space.opportunistic_space, space.available_space);
+        assert_eq!(files.get_space_info(Some("notes.txt".into())).unwrap().total_space, space.total_space);
+        assert!(matches!(files.get_space_info(Some("missing".into())), Err(Error::Io(_))));
+        assert!(matches!(files.get_space_info(Some("/".into())), Err(Error::PermissionDenied)));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{ser::Serializer, Serialize};
+use tauri_ios_plugin_shared::{ErrorCode, IntoPluginError, PluginError};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -7,6 +8,9 @@ pub enum Error {
     #[error(transparent)]
     Tauri(#[from] tauri::Error),
     
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    
     #[error("Files is not available on this device")]
     NotAvailable,
     
@@ -24,11 +28,39 @@ pub enum Error {
     PluginInvoke(#[from] tauri::plugin::mobile::PluginInvokeError),
 }
 
+impl Error {
+    /// Stable machine-readable code sent to the frontend alongside the message.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::Tauri(_) => ErrorCode::Internal,
+            Error::Io(_) => ErrorCode::Io,
+            Error::NotAvailable => ErrorCode::NotAvailable,
+            Error::PermissionDenied => ErrorCode::PermissionDenied,
+            Error::InvalidInput(_) => ErrorCode::InvalidInput,
+            Error::OperationFailed(_) => ErrorCode::OperationFailed,
+            #[cfg(mobile)]
+            Error::PluginInvoke(_) => ErrorCode::PluginInvoke,
+        }
+    }
+}
+
+impl IntoPluginError for &Error {
+    fn into_plugin_error(self) -> PluginError {
+        #[cfg(mobile)]
+        if let Error::PluginInvoke(tauri::plugin::mobile::PluginInvokeError::InvokeRejected(response)) = self {
+            let message = response.message.clone().unwrap_or_else(|| self.to_string());
+            return PluginError::from_native(response.code.as_deref(), message);
+        }
+
+        PluginError::new(self.code(), self.to_string())
+    }
+}
+
 impl Serialize for Error {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_str(self.to_string().as_ref())
+        self.into_plugin_error().serialize(serializer)
     }
 }
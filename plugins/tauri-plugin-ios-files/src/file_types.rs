@@ -0,0 +1,140 @@
+use std::path::Path;
+
+use crate::FileType;
+
+/// What the desktop backend reports for a file extension: its MIME type, the
+/// uniform type identifier iOS would report and the picker category it falls in.
+pub(crate) struct TypeInfo {
+    pub mime_type: Option<&'static str>,
+    pub uti_type: &'static str,
+    category: Option<Category>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Category {
+    Image,
+    Video,
+    Audio,
+    Pdf,
+    Text,
+    Spreadsheet,
+    Presentation,
+    Archive,
+}
+
+const FOLDER_UTI: &str = "public.folder";
+const DATA_UTI: &str = "public.data";
+
+/// Directory extensions macOS treats as a single document.
+const PACKAGE_EXTENSIONS: &[&str] = &[
+    "app", "bundle", "framework", "plugin", "kext", "pages", "numbers", "key", "rtfd", "xcodeproj",
+    "xcworkspace", "playground", "photoslibrary",
+];
+
+#[rustfmt::skip]
+const TYPES: &[(&str, &str, &str, Category)] = &[
+    ("png", "image/png", "public.png", Category::Image),
+    ("jpg", "image/jpeg", "public.jpeg", Category::Image),
+    ("jpeg", "image/jpeg", "public.jpeg", Category::Image),
+    ("gif", "image/gif", "com.compuserve.gif", Category::Image),
+    ("heic", "image/heic", "public.heic", Category::Image),
+    ("webp", "image/webp", "org.webmproject.webp", Category::Image),
+    ("tif", "image/tiff", "public.tiff", Category::Image),
+    ("tiff", "image/tiff", "public.tiff", Category::Image),
+    ("bmp", "image/bmp", "com.microsoft.bmp", Category::Image),
+    ("svg", "image/svg+xml", "public.svg-image", Category::Image),
+    ("mp4", "video/mp4", "public.mpeg-4", Category::Video),
+    ("m4v", "video/x-m4v", "com.apple.m4v-video", Category::Video),
+    ("mov", "video/quicktime", "com.apple.quicktime-movie", Category::Video),
+    ("avi", "video/x-msvideo", "public.avi", Category::Video),
+    ("mp3", "audio/mpeg", "public.mp3", Category::Audio),
+    ("m4a", "audio/mp4", "com.apple.m4a-audio", Category::Audio),
+    ("aac", "audio/aac", "public.aac-audio", Category::Audio),
+    ("wav", "audio/wav", "com.microsoft.waveform-audio", Category::Audio),
+    ("flac", "audio/flac", "org.xiph.flac", Category::Audio),
+    ("pdf", "application/pdf", "com.adobe.pdf", Category::Pdf),
+    ("txt", "text/plain", "public.plain-text", Category::Text),
+    ("md", "text/markdown", "net.daringfireball.markdown", Category::Text),
+    ("rtf", "application/rtf", "public.rtf", Category::Text),
+    ("html", "text/html", "public.html", Category::Text),
+    ("htm", "text/html", "public.html", Category::Text),
+    ("xml", "application/xml", "public.xml", Category::Text),
+    ("json", "application/json", "public.json", Category::Text),
+    ("csv", "text/csv", "public.comma-separated-values-text", Category::Spreadsheet),
+    ("doc", "application/msword", "com.microsoft.word.doc", Category::Text),
+    ("docx", "application/vnd.openxmlformats-officedocument.wordprocessingml.document", "org.openxmlformats.wordprocessingml.document", Category::Text),
+    ("pages", "application/vnd.apple.pages", "com.apple.iwork.pages.pages", Category::Text),
+    ("xls", "application/vnd.ms-excel", "com.microsoft.excel.xls", Category::Spreadsheet),
+    ("xlsx", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", "org.openxmlformats.spreadsheetml.sheet", Category::Spreadsheet),
+    ("numbers", "application/vnd.apple.numbers", "com.apple.iwork.numbers.numbers", Category::Spreadsheet),
+    ("ppt", "application/vnd.ms-powerpoint", "com.microsoft.powerpoint.ppt", Category::Presentation),
+    ("pptx", "application/vnd.openxmlformats-officedocument.presentationml.presentation", "org.openxmlformats.presentationml.presentation", Category::Presentation),
+    ("key", "application/vnd.apple.keynote", "com.apple.iwork.keynote.key", Category::Presentation),
+    ("zip", "application/zip", "public.zip-archive", Category::Archive),
+    ("gz", "application/gzip", "org.gnu.gnu-zip-archive", Category::Archive),
+    ("tar", "application/x-tar", "public.tar-archive", Category::Archive),
+    ("7z", "application/x-7z-compressed", "org.7-zip.7-zip-archive", Category::Archive),
+    ("rar", "application/vnd.rar", "com.rarlab.rar-archive", Category::Archive),
+];
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+pub(crate) fn type_info(path: &Path, is_directory: bool) -> TypeInfo {
+    if is_directory {
+        return TypeInfo {
+            mime_type: None,
+            uti_type: FOLDER_UTI,
+            category: None,
+        };
+    }
+    let extension = extension(path);
+    TYPES
+        .iter()
+        .find(|(known, ..)| Some(*known) == extension.as_deref())
+        .map(|&(_, mime_type, uti_type, category)| TypeInfo {
+            mime_type: Some(mime_type),
+            uti_type,
+            category: Some(category),
+        })
+        .unwrap_or(TypeInfo {
+            mime_type: None,
+            uti_type: DATA_UTI,
+            category: None,
+        })
+}
+
+/// Whether a directory is a package, a folder presented as one document.
+pub(crate) fn is_package(path: &Path) -> bool {
+    extension(path).is_some_and(|extension| PACKAGE_EXTENSIONS.contains(&extension.as_str()))
+}
+
+impl TypeInfo {
+    /// Whether the file is one of `file_type`. Custom types match a uniform type
+    /// identifier or a file extension.
+    pub fn matches(&self, path: &Path, file_type: &FileType) -> bool {
+        let category = match file_type {
+            FileType::Image => Category::Image,
+            FileType::Video => Category::Video,
+            FileType::Audio => Category::Audio,
+            FileType::Pdf => Category::Pdf,
+            FileType::Text => Category::Text,
+            FileType::Spreadsheet => Category::Spreadsheet,
+            FileType::Presentation => Category::Presentation,
+            FileType::Archive => Category::Archive,
+            FileType::Custom(types) => {
+                let extension = extension(path);
+                return types.iter().any(|custom| {
+                    custom.eq_ignore_ascii_case(self.uti_type)
+                        || extension
+                            .as_deref()
+                            .is_some_and(|extension| custom.trim_start_matches('.').eq_ignore_ascii_case(extension))
+                });
+            }
+        };
+        self.category == Some(category)
+    }
+}
@@ -10,16 +10,31 @@ use tauri::{
 
 pub use models::*;
 
+#[cfg(desktop)]
+mod bookmark;
 #[cfg(desktop)]
 mod desktop;
+#[cfg(desktop)]
+mod file_types;
 #[cfg(mobile)]
 mod mobile;
+#[cfg(desktop)]
+mod scope;
 
+mod cloud;
 mod commands;
 mod error;
 mod models;
+mod monitor;
+mod stream;
 
+pub use cloud::CloudDownload;
 pub use error::{Error, Result};
+pub use monitor::Monitor;
+pub use stream::{
+    CancelToken, ChunkedReadOptions, FileChunk, FileWriter, FileWriterOptions, ReadReport, DEFAULT_CHUNK_SIZE,
+    MAX_CHUNK_SIZE,
+};
 
 #[cfg(desktop)]
 use desktop::Files;
@@ -36,8 +51,8 @@ impl<R: Runtime, T: Manager<R>> FilesExt<R> for T {
     }
 }
 
-pub fn init<R: Runtime>() -> TauriPlugin<R> {
-    Builder::new("ios-files")
+pub fn init<R: Runtime>() -> TauriPlugin<R, Option<Config>> {
+    Builder::<R, Option<Config>>::new("ios-files")
         .invoke_handler(tauri::generate_handler![
             commands::pick_file,
             commands::pick_multiple_files,
@@ -58,9 +73,27 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             commands::preview_file,
             commands::get_cloud_status,
             commands::download_from_cloud,
+            commands::cancel_cloud_download,
             commands::evict_from_local,
+            commands::evict_all_from_local,
+            commands::list_not_downloaded,
             commands::start_monitoring,
             commands::stop_monitoring,
+            commands::read_file_range,
+            commands::read_file_chunked,
+            commands::cancel_read,
+            commands::append_file,
+            commands::open_file_writer,
+            commands::write_file_chunk,
+            commands::finish_file_writer,
+            commands::abort_file_writer,
+            commands::hash_file,
+            commands::get_space_info,
+            commands::get_file_permissions,
+            commands::create_bookmark,
+            commands::resolve_bookmark,
+            commands::start_accessing_resource,
+            commands::stop_accessing_resource,
         ])
         .setup(|app, api| {
             #[cfg(mobile)]
@@ -69,6 +102,10 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             let files = desktop::init(app, api)?;
             
             app.manage(files);
+            app.manage(cloud::JsDownloads::default());
+            app.manage(monitor::JsMonitors::default());
+            app.manage(stream::JsReads::default());
+            app.manage(stream::JsWriters::<R>::default());
             Ok(())
         })
         .build()
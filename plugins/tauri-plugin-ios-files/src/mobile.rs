@@ -1,10 +1,20 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
 use serde::de::DeserializeOwned;
 use tauri::{
+    ipc::{Channel, InvokeResponseBody},
     plugin::{PluginApi, PluginHandle},
     AppHandle, Runtime,
 };
 
-use crate::{models::*, Result};
+use crate::{
+    cloud::ProgressSender,
+    models::*,
+    monitor::{ChangeSender, RawChange, Stop},
+    stream::FileChunk,
+    Result,
+};
 
 #[cfg(target_os = "ios")]
 tauri::ios_plugin_binding!(init_plugin_ios_files);
@@ -21,9 +31,45 @@ pub fn init<R: Runtime, C: DeserializeOwned>(
     Ok(Files(handle))
 }
 
+static NEXT_READER_ID: AtomicU32 = AtomicU32::new(1);
+
 /// Access to the Files APIs on mobile.
 pub struct Files<R: Runtime>(PluginHandle<R>);
 
+/// A file opened by [`Files::open_reader`]. The native side keeps the file
+/// handle until the reader is dropped, so every range comes from the file that
+/// was opened, even if another one takes its place in the meantime.
+pub(crate) struct RangeReader<R: Runtime> {
+    id: u32,
+    handle: PluginHandle<R>,
+}
+
+impl<R: Runtime> RangeReader<R> {
+    pub(crate) fn read(&mut self, offset: u64, length: usize) -> Result<FileChunk> {
+        #[derive(serde::Serialize)]
+        struct Args {
+            id: u32,
+            offset: u64,
+            length: usize,
+        }
+        
+        self.handle
+            .run_mobile_plugin("readFromReader", Args { id: self.id, offset, length })
+            .map_err(Into::into)
+    }
+}
+
+impl<R: Runtime> Drop for RangeReader<R> {
+    fn drop(&mut self) {
+        #[derive(serde::Serialize)]
+        struct Args {
+            id: u32,
+        }
+        
+        let _ = self.handle.run_mobile_plugin::<()>("closeReader", Args { id: self.id });
+    }
+}
+
 impl<R: Runtime> Files<R> {
     pub fn pick_file(&self, options: FilePickerOptions) -> Result<PickedFile> {
         self.0
@@ -170,37 +216,311 @@ impl<R: Runtime> Files<R> {
             .map_err(Into::into)
     }
     
-    pub fn download_from_cloud(&self, url: String) -> Result<()> {
+    pub fn evict_from_local(&self, url: String) -> Result<()> {
         #[derive(serde::Serialize)]
         struct Args {
             url: String,
         }
         
         self.0
-            .run_mobile_plugin("downloadFromCloud", Args { url })
+            .run_mobile_plugin("evictFromLocal", Args { url })
             .map_err(Into::into)
     }
     
-    pub fn evict_from_local(&self, url: String) -> Result<()> {
+    pub fn list_not_downloaded(&self, directory_url: String, recursive: bool) -> Result<Vec<CloudItem>> {
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Args {
+            directory_url: String,
+            recursive: bool,
+        }
+        
+        #[derive(serde::Deserialize)]
+        struct Items {
+            items: Vec<CloudItem>,
+        }
+        
+        self.0
+            .run_mobile_plugin::<Items>("listNotDownloaded", Args { directory_url, recursive })
+            .map(|result| result.items)
+            .map_err(Into::into)
+    }
+    
+    pub fn hash_file(&self, url: String) -> Result<String> {
         #[derive(serde::Serialize)]
         struct Args {
             url: String,
         }
         
+        #[derive(serde::Deserialize)]
+        struct Response {
+            hash: String,
+        }
+        
         self.0
-            .run_mobile_plugin("evictFromLocal", Args { url })
+            .run_mobile_plugin::<Response>("hashFile", Args { url })
+            .map(|response| response.hash)
             .map_err(Into::into)
     }
     
-    pub fn start_monitoring(&self, options: MonitoringOptions) -> Result<()> {
+    pub fn get_space_info(&self, url: Option<String>) -> Result<SpaceInfo> {
+        #[derive(serde::Serialize)]
+        struct Args {
+            url: Option<String>,
+        }
+        
         self.0
-            .run_mobile_plugin("startMonitoring", options)
+            .run_mobile_plugin("getSpaceInfo", Args { url })
             .map_err(Into::into)
     }
     
-    pub fn stop_monitoring(&self) -> Result<()> {
+    pub fn get_file_permissions(&self, url: String) -> Result<FilePermissions> {
+        #[derive(serde::Serialize)]
+        struct Args {
+            url: String,
+        }
+        
         self.0
-            .run_mobile_plugin("stopMonitoring", ())
+            .run_mobile_plugin("getFilePermissions", Args { url })
             .map_err(Into::into)
     }
-}
\ No newline at end of file
+    
+    /// Creates a bookmark that keeps access to a picked file or folder across
+    /// launches.
+    pub fn create_bookmark(&self, url: String) -> Result<SecurityScopedResource> {
+        #[derive(serde::Serialize)]
+        struct Args {
+            url: String,
+        }
+        
+        self.0
+            .run_mobile_plugin("createBookmark", Args { url })
+            .map_err(Into::into)
+    }
+    
+    pub fn resolve_bookmark(&self, bookmark_data: String) -> Result<ResolvedBookmark> {
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Args {
+            bookmark_data: String,
+        }
+        
+        self.0
+            .run_mobile_plugin("resolveBookmark", Args { bookmark_data })
+            .map_err(Into::into)
+    }
+    
+    /// Starts accessing a security-scoped URL, returned by a picker or a
+    /// resolved bookmark. Every successful call must be balanced by
+    /// [`Files::stop_accessing_resource`].
+    pub fn start_accessing_resource(&self, url: String) -> Result<bool> {
+        #[derive(serde::Serialize)]
+        struct Args {
+            url: String,
+        }
+        
+        #[derive(serde::Deserialize)]
+        struct Response {
+            granted: bool,
+        }
+        
+        self.0
+            .run_mobile_plugin::<Response>("startAccessingResource", Args { url })
+            .map(|response| response.granted)
+            .map_err(Into::into)
+    }
+    
+    pub fn stop_accessing_resource(&self, url: String) -> Result<()> {
+        #[derive(serde::Serialize)]
+        struct Args {
+            url: String,
+        }
+        
+        self.0
+            .run_mobile_plugin("stopAccessingResource", Args { url })
+            .map_err(Into::into)
+    }
+    
+    pub(crate) fn app(&self) -> &AppHandle<R> {
+        self.0.app()
+    }
+    
+    pub(crate) fn read_range(&self, url: &str, offset: u64, length: usize) -> Result<FileChunk> {
+        #[derive(serde::Serialize)]
+        struct Args<'a> {
+            url: &'a str,
+            offset: u64,
+            length: usize,
+        }
+        
+        self.0
+            .run_mobile_plugin("readFileRange", Args { url, offset, length })
+            .map_err(Into::into)
+    }
+    
+    /// Opens a file for reading several ranges of it.
+    pub(crate) fn open_reader(&self, url: &str) -> Result<RangeReader<R>> {
+        #[derive(serde::Serialize)]
+        struct Args<'a> {
+            id: u32,
+            url: &'a str,
+        }
+        
+        let id = NEXT_READER_ID.fetch_add(1, Ordering::Relaxed);
+        self.0.run_mobile_plugin::<()>("openReader", Args { id, url })?;
+        Ok(RangeReader {
+            id,
+            handle: self.0.clone(),
+        })
+    }
+    
+    /// Appends to a file, creating it if needed, and returns its new size.
+    pub(crate) fn append_bytes(&self, url: &str, bytes: &[u8]) -> Result<u64> {
+        #[derive(serde::Serialize)]
+        struct Args<'a> {
+            url: &'a str,
+            data: String,
+        }
+        
+        #[derive(serde::Deserialize)]
+        struct Response {
+            size: u64,
+        }
+        
+        self.0
+            .run_mobile_plugin::<Response>("appendToFile", Args { url, data: STANDARD.encode(bytes) })
+            .map(|response| response.size)
+            .map_err(Into::into)
+    }
+    
+    /// Creates the empty temporary file of a writer and returns its URL.
+    pub(crate) fn begin_write(&self, id: u32, url: &str, create_new: bool) -> Result<String> {
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Args<'a> {
+            id: u32,
+            url: &'a str,
+            create_new: bool,
+        }
+        
+        #[derive(serde::Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Response {
+            temp_url: String,
+        }
+        
+        self.0
+            .run_mobile_plugin::<Response>("beginWrite", Args { id, url, create_new })
+            .map(|response| response.temp_url)
+            .map_err(Into::into)
+    }
+    
+    /// Moves the temporary file of a writer into place and returns its size.
+    pub(crate) fn commit_write(&self, temp_url: &str, url: &str, create_new: bool) -> Result<u64> {
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Args<'a> {
+            temp_url: &'a str,
+            url: &'a str,
+            create_new: bool,
+        }
+        
+        #[derive(serde::Deserialize)]
+        struct Response {
+            size: u64,
+        }
+        
+        self.0
+            .run_mobile_plugin::<Response>("commitWrite", Args { temp_url, url, create_new })
+            .map(|response| response.size)
+            .map_err(Into::into)
+    }
+    
+    pub(crate) fn discard_write(&self, temp_url: &str) {
+        let _ = self.delete_file(temp_url.to_string());
+    }
+    
+    pub(crate) fn start_download(&self, id: u32, urls: &[String], progress: ProgressSender) -> Result<Stop> {
+        let handler = Channel::new(move |body: InvokeResponseBody| {
+            progress.send(body.deserialize::<CloudDownloadProgress>()?);
+            Ok(())
+        });
+
+        #[derive(serde::Serialize)]
+        struct Args<'a> {
+            id: u32,
+            urls: &'a [String],
+            handler: Channel,
+        }
+
+        self.0
+            .run_mobile_plugin::<()>("startCloudDownload", Args { id, urls, handler })?;
+
+        #[derive(serde::Serialize)]
+        struct CancelArgs {
+            id: u32,
+        }
+
+        let handle = self.0.clone();
+        Ok(Box::new(move || {
+            let _ = handle.run_mobile_plugin::<()>("cancelCloudDownload", CancelArgs { id });
+        }))
+    }
+    
+    /// Starts the native watcher of a monitor, which sends each change it
+    /// detects through a channel. Renames arrive paired already.
+    pub(crate) fn watch(&self, id: u32, options: &MonitoringOptions, changes: ChangeSender) -> Result<Stop> {
+        #[derive(serde::Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct NativeChange {
+            file_url: String,
+            event_type: MonitoringEvent,
+            old_url: Option<String>,
+        }
+
+        let handler = Channel::new(move |body: InvokeResponseBody| {
+            let change = body.deserialize::<NativeChange>()?;
+            let url = change.file_url;
+            changes.send(match change.event_type {
+                MonitoringEvent::Created => RawChange::Created(url),
+                MonitoringEvent::Modified => RawChange::Modified(url),
+                MonitoringEvent::AttributesChanged => RawChange::AttributesChanged(url),
+                MonitoringEvent::Deleted => RawChange::Deleted(url),
+                MonitoringEvent::Renamed => match change.old_url {
+                    Some(from) => RawChange::Renamed { from, to: url },
+                    None => RawChange::Created(url),
+                },
+            });
+            Ok(())
+        });
+
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Args<'a> {
+            id: u32,
+            directory_urls: &'a [String],
+            recursive: bool,
+            handler: Channel,
+        }
+
+        self.0.run_mobile_plugin::<()>(
+            "startMonitoring",
+            Args {
+                id,
+                directory_urls: &options.directory_urls,
+                recursive: options.recursive,
+                handler,
+            },
+        )?;
+
+        #[derive(serde::Serialize)]
+        struct StopArgs {
+            id: u32,
+        }
+
+        let handle = self.0.clone();
+        Ok(Box::new(move || {
+            let _ = handle.run_mobile_plugin::<()>("stopMonitoring", StopArgs { id });
+        }))
+    }
+}
@@ -1,6 +1,15 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf};
+
+/// Plugin configuration, under `plugins > ios-files` in `tauri.conf.json`.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    /// The folder the desktop implementation is confined to, relative to the app
+    /// data directory unless absolute. Defaults to `Documents`.
+    pub root: Option<PathBuf>,
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -156,6 +165,9 @@ pub struct CloudDownloadProgress {
     pub downloaded_bytes: u64,
     pub total_bytes: u64,
     pub status: CloudDownloadStatus,
+    /// Why the item failed to download.
+    #[serde(default)]
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -168,12 +180,58 @@ pub enum CloudDownloadStatus {
     Cancelled,
 }
 
+/// The progress of a download of several items, reported whenever one of them
+/// changes.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudBatchProgress {
+    /// The item that changed.
+    pub item: CloudDownloadProgress,
+    /// From 0 to 1. Finished items count as done, whether they completed or not.
+    pub progress: f64,
+    pub downloaded_bytes: u64,
+    pub total_bytes: u64,
+    pub completed_items: usize,
+    pub failed_items: usize,
+    pub total_items: usize,
+    /// `Completed` once every item completed. When every item is finished but
+    /// some did not complete, `Cancelled` if any was cancelled and `Failed`
+    /// otherwise.
+    pub status: CloudDownloadStatus,
+}
+
+/// An iCloud item that has no up-to-date local copy.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudItem {
+    pub file_url: String,
+    pub name: String,
+    /// The size in iCloud, when known.
+    pub size: Option<u64>,
+    pub cloud_status: CloudStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudItemResult {
+    pub file_url: String,
+    /// Why the operation failed for this item.
+    pub error: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct MonitoringOptions {
     pub directory_urls: Vec<String>,
+    #[serde(default)]
     pub recursive: bool,
+    /// The kinds of change to report. Every kind is reported when empty.
+    #[serde(default)]
     pub events: Vec<MonitoringEvent>,
+    /// How long changes are collected before they are reported, in
+    /// milliseconds. Defaults to 100.
+    #[serde(default)]
+    pub debounce_ms: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -202,6 +260,16 @@ pub struct SecurityScopedResource {
     pub bookmark_data: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedBookmark {
+    pub url: String,
+    /// Whether the item moved or the bookmark was created on an older system.
+    /// `bookmark_data` is then a fresh bookmark to store in place of the old one.
+    pub is_stale: bool,
+    pub bookmark_data: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct FilePermissions {
@@ -217,7 +285,10 @@ pub struct SpaceInfo {
     pub total_space: u64,
     pub available_space: u64,
     pub used_space: u64,
+    /// Space available for data the user asked for, counting what the system
+    /// would purge to make room.
     pub important_space: u64,
+    /// Space available for data that can be recreated, such as caches.
     pub opportunistic_space: u64,
 }
 
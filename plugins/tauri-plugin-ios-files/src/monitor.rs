@@ -0,0 +1,597 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use tauri::Runtime;
+
+use crate::{Error, FileChange, MonitoringEvent, MonitoringOptions, Result};
+
+pub(crate) type Stop = Box<dyn FnOnce() + Send>;
+type Handler = Arc<dyn Fn(&FileChange) + Send + Sync>;
+
+/// How long changes are collected when the options don't say.
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// A busy directory is still reported after this many debounce periods, even if
+/// it never goes quiet.
+const MAX_DEBOUNCE_PERIODS: u32 = 10;
+
+static NEXT_MONITOR_ID: AtomicU32 = AtomicU32::new(1);
+
+/// A change as the native watcher reports it, before debouncing.
+#[derive(Debug, Clone)]
+pub(crate) enum RawChange {
+    Created(String),
+    Modified(String),
+    AttributesChanged(String),
+    Deleted(String),
+    Renamed { from: String, to: String },
+    /// The first half of a rename. Halves are paired by `cookie` when the
+    /// watcher provides one, and otherwise by order.
+    RenamedFrom { url: String, cookie: Option<usize> },
+    RenamedTo { url: String, cookie: Option<usize> },
+}
+
+enum Message {
+    Change(RawChange, DateTime<Utc>),
+    Stop,
+}
+
+/// Where a native watcher sends the changes of one monitor.
+#[derive(Clone)]
+pub(crate) struct ChangeSender(Sender<Message>);
+
+impl ChangeSender {
+    pub(crate) fn send(&self, change: RawChange) {
+        let _ = self.0.send(Message::Change(change, Utc::now()));
+    }
+}
+
+struct Pending {
+    url: String,
+    event: MonitoringEvent,
+    old_url: Option<String>,
+    timestamp: DateTime<Utc>,
+}
+
+/// The changes collected during one debounce period, coalesced per location so
+/// a file that is written several times is reported once.
+#[derive(Default)]
+struct Batch {
+    pending: Vec<Pending>,
+    moved_out: Vec<(Option<usize>, String, DateTime<Utc>)>,
+    renames: Vec<(String, String)>,
+}
+
+impl Batch {
+    fn position(&self, url: &str) -> Option<usize> {
+        self.pending.iter().position(|pending| pending.url == url)
+    }
+
+    fn take(&mut self, url: &str) -> Option<Pending> {
+        self.position(url).map(|index| self.pending.remove(index))
+    }
+
+    fn push(&mut self, url: String, event: MonitoringEvent, old_url: Option<String>, timestamp: DateTime<Utc>) {
+        self.pending.push(Pending {
+            url,
+            event,
+            old_url,
+            timestamp,
+        });
+    }
+
+    fn apply(&mut self, change: RawChange, timestamp: DateTime<Utc>) {
+        match change {
+            RawChange::Created(url) => match self.position(&url) {
+                // Deleted and created again: the file was replaced.
+                Some(index) if self.pending[index].event == MonitoringEvent::Deleted => {
+                    self.pending[index].event = MonitoringEvent::Modified;
+                }
+                Some(_) => {}
+                None => self.push(url, MonitoringEvent::Created, None, timestamp),
+            },
+            RawChange::Modified(url) => match self.position(&url) {
+                Some(index) if self.pending[index].event == MonitoringEvent::AttributesChanged => {
+                    self.pending[index].event = MonitoringEvent::Modified;
+                }
+                Some(_) => {}
+                None => self.push(url, MonitoringEvent::Modified, None, timestamp),
+            },
+            RawChange::AttributesChanged(url) => {
+                if self.position(&url).is_none() {
+                    self.push(url, MonitoringEvent::AttributesChanged, None, timestamp);
+                }
+            }
+            RawChange::Deleted(url) => match self.take(&url) {
+                // Never seen by the handler, so there is nothing to report.
+                Some(pending) if pending.event == MonitoringEvent::Created => {}
+                Some(Pending {
+                    event: MonitoringEvent::Renamed,
+                    old_url: Some(old_url),
+                    timestamp,
+                    ..
+                }) => self.push(old_url, MonitoringEvent::Deleted, None, timestamp),
+                Some(pending) => self.push(url, MonitoringEvent::Deleted, None, pending.timestamp),
+                None => self.push(url, MonitoringEvent::Deleted, None, timestamp),
+            },
+            RawChange::Renamed { from, to } => self.rename(from, to, timestamp),
+            RawChange::RenamedFrom { url, cookie } => self.moved_out.push((cookie, url, timestamp)),
+            RawChange::RenamedTo { url, cookie } => {
+                let source = match cookie {
+                    Some(_) => self.moved_out.iter().position(|(other, ..)| *other == cookie),
+                    None => self.moved_out.iter().rposition(|(other, ..)| other.is_none()),
+                };
+                match source {
+                    Some(index) => {
+                        let (_, from, timestamp) = self.moved_out.remove(index);
+                        self.rename(from, url, timestamp);
+                    }
+                    // Moved in from outside the watched folders.
+                    None => self.apply(RawChange::Created(url), timestamp),
+                }
+            }
+        }
+    }
+
+    fn rename(&mut self, from: String, to: String, timestamp: DateTime<Utc>) {
+        // Some watchers report both halves and the pair.
+        let rename = (from, to);
+        if rename.0 == rename.1 || self.renames.contains(&rename) {
+            return;
+        }
+        self.renames.push(rename.clone());
+        let (from, to) = rename;
+
+        match (is_hidden(&from), is_hidden(&to)) {
+            (false, false) => {}
+            // Saving through a hidden temporary file replaces the content.
+            (true, false) => {
+                self.take(&from);
+                return self.apply(RawChange::Modified(to), timestamp);
+            }
+            (false, true) => return self.apply(RawChange::Deleted(from), timestamp),
+            (true, true) => return,
+        }
+        // Whatever was at the destination has been replaced.
+        self.take(&to);
+
+        match self.take(&from) {
+            Some(pending) if pending.event == MonitoringEvent::Created => {
+                self.push(to, MonitoringEvent::Created, None, pending.timestamp);
+            }
+            Some(Pending {
+                event: MonitoringEvent::Renamed,
+                old_url: Some(old_url),
+                timestamp,
+                ..
+            }) => {
+                // Renamed back to where it started.
+                if old_url != to {
+                    self.push(to, MonitoringEvent::Renamed, Some(old_url), timestamp);
+                }
+            }
+            pending => {
+                let timestamp = pending.map_or(timestamp, |pending| pending.timestamp);
+                self.push(to, MonitoringEvent::Renamed, Some(from), timestamp);
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pending.is_empty() && self.moved_out.is_empty()
+    }
+
+    fn drain(&mut self) -> Vec<FileChange> {
+        self.renames.clear();
+        // Unpaired halves left the watched folders.
+        for (_, url, timestamp) in std::mem::take(&mut self.moved_out) {
+            self.apply(RawChange::Deleted(url), timestamp);
+        }
+        self.pending
+            .drain(..)
+            .filter(|pending| !is_hidden(&pending.url))
+            .map(|pending| FileChange {
+                file_url: pending.url,
+                event_type: pending.event,
+                old_url: pending.old_url,
+                timestamp: pending.timestamp,
+            })
+            .collect()
+    }
+}
+
+/// Whether `url` names a hidden file. Monitors don't report those, like listings
+/// leave them out.
+fn is_hidden(url: &str) -> bool {
+    url.trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+/// Collects changes until the monitored folders have been quiet for `debounce`,
+/// then hands the coalesced changes to `handler`. Stops at the first
+/// [`Message::Stop`] without delivering what is still pending.
+fn debounce(receiver: Receiver<Message>, debounce: Duration, events: Vec<MonitoringEvent>, handler: Handler) {
+    let mut batch = Batch::default();
+    let mut deadline: Option<Instant> = None;
+
+    loop {
+        let message = match deadline {
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            Some(deadline) => {
+                let now = Instant::now();
+                let wait = debounce.min(deadline.saturating_duration_since(now));
+                if wait.is_zero() {
+                    Err(RecvTimeoutError::Timeout)
+                } else {
+                    receiver.recv_timeout(wait)
+                }
+            }
+        };
+
+        match message {
+            Ok(Message::Change(change, timestamp)) => {
+                batch.apply(change, timestamp);
+                if deadline.is_none() {
+                    deadline = Some(Instant::now() + debounce * MAX_DEBOUNCE_PERIODS);
+                }
+                continue;
+            }
+            Ok(Message::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        deadline = None;
+        if batch.is_empty() {
+            continue;
+        }
+        for change in batch.drain() {
+            if events.is_empty() || events.contains(&change.event_type) {
+                handler(&change);
+            }
+        }
+    }
+}
+
+/// Handle to a running directory monitor.
+///
+/// Dropping it stops the monitor. Changes that are still being debounced at that
+/// point are discarded.
+pub struct Monitor {
+    id: u32,
+    sender: Sender<Message>,
+    stop: Option<Stop>,
+}
+
+impl Monitor {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Stops the monitor. Equivalent to dropping the handle.
+    pub fn stop(self) {}
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            stop();
+        }
+        let _ = self.sender.send(Message::Stop);
+    }
+}
+
+/// Monitors started from the frontend, keyed by the id handed back to JS.
+#[derive(Default)]
+pub(crate) struct JsMonitors(Mutex<HashMap<u32, Monitor>>);
+
+impl JsMonitors {
+    pub(crate) fn insert(&self, monitor: Monitor) -> u32 {
+        let id = monitor.id();
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).insert(id, monitor);
+        id
+    }
+
+    pub(crate) fn remove(&self, id: u32) -> Result<()> {
+        let monitor = self.0.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+        match monitor {
+            Some(monitor) => {
+                monitor.stop();
+                Ok(())
+            }
+            None => Err(Error::InvalidInput(format!("No monitor with id {id}"))),
+        }
+    }
+}
+
+impl<R: Runtime> crate::Files<R> {
+    /// Watches `options.directory_urls` and delivers their changes to `handler`
+    /// until the returned monitor is dropped. Any number of monitors may run at
+    /// the same time.
+    ///
+    /// Changes are debounced: a file written several times in a row is reported
+    /// once, and a rename is reported as one `Renamed` change carrying the
+    /// previous location in `old_url`. Only the kinds in `options.events` are
+    /// delivered, or every kind when it is empty.
+    pub fn start_monitoring<F>(&self, options: MonitoringOptions, handler: F) -> Result<Monitor>
+    where
+        F: Fn(&FileChange) + Send + Sync + 'static,
+    {
+        if options.directory_urls.is_empty() {
+            return Err(Error::InvalidInput("No folders to monitor".to_string()));
+        }
+
+        let id = NEXT_MONITOR_ID.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = mpsc::channel();
+        let period = options.debounce_ms.map_or(DEFAULT_DEBOUNCE, Duration::from_millis);
+        let events = options.events.clone();
+        let handler: Handler = Arc::new(handler);
+        thread::Builder::new()
+            .name(format!("files-monitor-{id}"))
+            .spawn(move || debounce(receiver, period, events, handler))?;
+
+        // On failure the dropped sender ends the thread.
+        let stop = self.watch(id, &options, ChangeSender(sender.clone()))?;
+        Ok(Monitor {
+            id,
+            sender,
+            stop: Some(stop),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coalesce(changes: Vec<RawChange>) -> Vec<(MonitoringEvent, String, Option<String>)> {
+        let mut batch = Batch::default();
+        for change in changes {
+            batch.apply(change, Utc::now());
+        }
+        batch
+            .drain()
+            .into_iter()
+            .map(|change| (change.event_type, change.file_url, change.old_url))
+            .collect()
+    }
+
+    fn url(name: &str) -> String {
+        format!("/documents/{name}")
+    }
+
+    #[test]
+    fn repeated_writes_are_reported_once() {
+        let changes = coalesce(vec![
+            RawChange::Modified(url("a")),
+            RawChange::Modified(url("a")),
+            RawChange::Modified(url("a")),
+        ]);
+        assert_eq!(changes, vec![(MonitoringEvent::Modified, url("a"), None)]);
+    }
+
+    #[test]
+    fn created_then_deleted_is_not_reported() {
+        let changes = coalesce(vec![
+            RawChange::Created(url("a")),
+            RawChange::Modified(url("a")),
+            RawChange::Deleted(url("a")),
+        ]);
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn deleted_then_created_is_a_modification() {
+        let changes = coalesce(vec![RawChange::Deleted(url("a")), RawChange::Created(url("a"))]);
+        assert_eq!(changes, vec![(MonitoringEvent::Modified, url("a"), None)]);
+    }
+
+    #[test]
+    fn content_changes_outrank_attribute_changes() {
+        let changes = coalesce(vec![
+            RawChange::AttributesChanged(url("a")),
+            RawChange::Modified(url("a")),
+            RawChange::AttributesChanged(url("b")),
+        ]);
+        assert_eq!(
+            changes,
+            vec![
+                (MonitoringEvent::Modified, url("a"), None),
+                (MonitoringEvent::AttributesChanged, url("b"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn rename_halves_are_paired_by_cookie_and_by_order() {
+        let changes = coalesce(vec![
+            RawChange::RenamedFrom {
+                url: url("a"),
+                cookie: Some(1),
+            },
+            RawChange::RenamedFrom {
+                url: url("b"),
+                cookie: Some(2),
+            },
+            RawChange::RenamedTo {
+                url: url("d"),
+                cookie: Some(2),
+            },
+            RawChange::RenamedTo {
+                url: url("c"),
+                cookie: Some(1),
+            },
+            RawChange::RenamedFrom {
+                url: url("e"),
+                cookie: None,
+            },
+            RawChange::RenamedTo {
+                url: url("f"),
+                cookie: None,
+            },
+        ]);
+        assert_eq!(
+            changes,
+            vec![
+                (MonitoringEvent::Renamed, url("d"), Some(url("b"))),
+                (MonitoringEvent::Renamed, url("c"), Some(url("a"))),
+                (MonitoringEvent::Renamed, url("f"), Some(url("e"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn unpaired_rename_halves_leave_or_enter_the_folders() {
+        let changes = coalesce(vec![
+            RawChange::RenamedFrom {
+                url: url("a"),
+                cookie: Some(1),
+            },
+            RawChange::RenamedTo {
+                url: url("b"),
+                cookie: Some(2),
+            },
+        ]);
+        assert_eq!(
+            changes,
+            vec![
+                (MonitoringEvent::Created, url("b"), None),
+                (MonitoringEvent::Deleted, url("a"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn saving_through_a_hidden_file_is_a_modification() {
+        let changes = coalesce(vec![
+            RawChange::Created(url(".a.tmp")),
+            RawChange::Modified(url(".a.tmp")),
+            RawChange::Renamed {
+                from: url(".a.tmp"),
+                to: url("a"),
+            },
+        ]);
+        assert_eq!(changes, vec![(MonitoringEvent::Modified, url("a"), None)]);
+
+        let changes = coalesce(vec![RawChange::Renamed {
+            from: url("a"),
+            to: url(".a"),
+        }]);
+        assert_eq!(changes, vec![(MonitoringEvent::Deleted, url("a"), None)]);
+    }
+
+    #[test]
+    fn renames_are_chained_and_undone() {
+        let changes = coalesce(vec![
+            RawChange::Renamed {
+                from: url("a"),
+                to: url("b"),
+            },
+            RawChange::Renamed {
+                from: url("b"),
+                to: url("c"),
+            },
+        ]);
+        assert_eq!(changes, vec![(MonitoringEvent::Renamed, url("c"), Some(url("a")))]);
+
+        let changes = coalesce(vec![
+            RawChange::Renamed {
+                from: url("a"),
+                to: url("b"),
+            },
+            RawChange::Renamed {
+                from: url("b"),
+                to: url("a"),
+            },
+        ]);
+        assert!(changes.is_empty());
+
+        let changes = coalesce(vec![
+            RawChange::Created(url("a")),
+            RawChange::Renamed {
+                from: url("a"),
+                to: url("b"),
+            },
+        ]);
+        assert_eq!(changes, vec![(MonitoringEvent::Created, url("b"), None)]);
+    }
+
+    const PERIOD: Duration = Duration::from_millis(50);
+
+    /// Runs [`debounce`] on a thread of its own and returns where its deliveries
+    /// arrive.
+    fn start(events: Vec<MonitoringEvent>) -> (Sender<Message>, Receiver<FileChange>, thread::JoinHandle<()>) {
+        let (sender, receiver) = mpsc::channel();
+        let (delivered, deliveries) = mpsc::channel();
+        let delivered = Mutex::new(delivered);
+        let handler: Handler = Arc::new(move |change: &FileChange| {
+            let _ = delivered.lock().unwrap().send(change.clone());
+        });
+        let thread = thread::spawn(move || debounce(receiver, PERIOD, events, handler));
+        (sender, deliveries, thread)
+    }
+
+    fn send(sender: &Sender<Message>, change: RawChange) {
+        sender.send(Message::Change(change, Utc::now())).unwrap();
+    }
+
+    #[test]
+    fn a_burst_is_delivered_once_the_folders_are_quiet() {
+        let (sender, deliveries, _) = start(Vec::new());
+        for _ in 0..5 {
+            send(&sender, RawChange::Modified(url("a")));
+            thread::sleep(PERIOD / 5);
+        }
+
+        let change = deliveries.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(change.event_type, MonitoringEvent::Modified);
+        assert_eq!(change.file_url, url("a"));
+        assert!(deliveries.recv_timeout(PERIOD * 3).is_err());
+    }
+
+    #[test]
+    fn busy_folders_are_delivered_after_the_longest_wait() {
+        let (sender, deliveries, _) = start(Vec::new());
+        let started = Instant::now();
+        let mut written = 0;
+        while deliveries.try_recv().is_err() {
+            assert!(started.elapsed() < PERIOD * MAX_DEBOUNCE_PERIODS * 4, "never delivered");
+            send(&sender, RawChange::Modified(url(&written.to_string())));
+            written += 1;
+            thread::sleep(PERIOD / 5);
+        }
+        assert!(started.elapsed() >= PERIOD * MAX_DEBOUNCE_PERIODS);
+    }
+
+    #[test]
+    fn only_requested_events_are_delivered() {
+        let (sender, deliveries, _) = start(vec![MonitoringEvent::Deleted]);
+        send(&sender, RawChange::Created(url("a")));
+        send(&sender, RawChange::Deleted(url("b")));
+
+        let change = deliveries.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(change.event_type, MonitoringEvent::Deleted);
+        assert_eq!(change.file_url, url("b"));
+        assert!(deliveries.recv_timeout(PERIOD * 3).is_err());
+    }
+
+    #[test]
+    fn stopping_discards_pending_changes() {
+        let (sender, deliveries, thread) = start(Vec::new());
+        send(&sender, RawChange::Modified(url("a")));
+        sender.send(Message::Stop).unwrap();
+
+        thread.join().unwrap();
+        assert!(deliveries.try_recv().is_err());
+    }
+}
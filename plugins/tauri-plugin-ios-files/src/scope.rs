@@ -0,0 +1,244 @@
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use tauri::Url;
+
+use crate::{Error, Result};
+
+/// Confines the desktop backend to one directory, the way the app container
+/// confines the iOS app.
+///
+/// Locations are `file://` URLs, absolute paths or paths relative to the root.
+/// Symbolic links are resolved before the check, so a link can't be used to reach
+/// outside the root either.
+pub(crate) struct Scope {
+    root: PathBuf,
+}
+
+impl Scope {
+    /// Creates the root if it doesn't exist yet.
+    pub fn new(root: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&root)?;
+        Ok(Self {
+            root: root.canonicalize()?,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolves `location` to a path inside the root, which doesn't need to
+    /// exist yet.
+    pub fn resolve(&self, location: &str) -> Result<PathBuf> {
+        let path = if location.starts_with("file:") {
+            Url::parse(location)
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+                .ok_or_else(|| Error::InvalidInput(format!("Invalid file URL: {location}")))?
+        } else if location.contains("://") {
+            return Err(Error::InvalidInput(format!("Only file URLs are supported: {location}")));
+        } else {
+            PathBuf::from(location)
+        };
+
+        // The last component is kept as is, so operations on a link apply to the
+        // link rather than its target.
+        let path = normalize(&self.root.join(path));
+        let path = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => canonicalize_existing(parent)?.join(name),
+            _ => path,
+        };
+        if !path.starts_with(&self.root) {
+            return Err(Error::PermissionDenied);
+        }
+        if path.symlink_metadata().is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+            match path.canonicalize() {
+                Ok(target) if target.starts_with(&self.root) => {}
+                _ => return Err(Error::PermissionDenied),
+            }
+        }
+        Ok(path)
+    }
+
+    /// Like [`Scope::resolve`], but the location must not be the root itself.
+    pub fn resolve_entry(&self, location: &str) -> Result<PathBuf> {
+        let path = self.resolve(location)?;
+        if path == self.root {
+            return Err(Error::InvalidInput("The operation is not allowed on the root folder".to_string()));
+        }
+        Ok(path)
+    }
+}
+
+/// The `file://` URL of `path`, the form locations are returned in on every
+/// platform.
+pub(crate) fn to_url(path: &Path) -> String {
+    Url::from_file_path(path)
+        .map(String::from)
+        .unwrap_or_else(|_| path.display().to_string())
+}
+
+/// Removes `.` and `..` components without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Canonicalizes the longest existing prefix of `path` and appends the rest.
+fn canonicalize_existing(path: &Path) -> Result<PathBuf> {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        match existing.canonicalize() {
+            Ok(canonical) => {
+                return Ok(rest.iter().rev().fold(canonical, |path: PathBuf, name| path.join(name)));
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                // A dangling link would be followed when the file is created.
+                if existing.symlink_metadata().is_ok() {
+                    return Err(Error::PermissionDenied);
+                }
+                let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+                    return Err(e.into());
+                };
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{fs, os::unix::fs::symlink};
+
+    use super::*;
+
+    /// A scope over an empty folder of its own, next to an `outside` folder.
+    fn scratch(name: &str) -> (Scope, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("tauri-plugin-ios-files-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("outside")).unwrap();
+        (Scope::new(dir.join("root")).unwrap(), dir.canonicalize().unwrap())
+    }
+
+    fn assert_denied(result: Result<PathBuf>) {
+        assert!(matches!(result, Err(Error::PermissionDenied)), "{result:?}");
+    }
+
+    #[test]
+    fn locations_resolve_inside_the_root() {
+        let (scope, dir) = scratch("scope-inside");
+        let root = scope.root().to_path_buf();
+        assert_eq!(root, dir.join("root"));
+
+        assert_eq!(scope.resolve("notes/today.txt").unwrap(), root.join("notes/today.txt"));
+        assert_eq!(scope.resolve("./a/../b").unwrap(), root.join("b"));
+        assert_eq!(scope.resolve(&to_url(&root.join("a b.txt"))).unwrap(), root.join("a b.txt"));
+        assert_eq!(scope.resolve(root.join("c").to_str().unwrap()).unwrap(), root.join("c"));
+        assert_eq!(scope.resolve("").unwrap(), root);
+        assert_eq!(scope.resolve(".").unwrap(), root);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn parent_components_cant_escape_the_root() {
+        let (scope, dir) = scratch("scope-parent");
+        let root = scope.root().to_path_buf();
+        assert_denied(scope.resolve(".."));
+        assert_denied(scope.resolve("../outside/file.txt"));
+        assert_denied(scope.resolve("a/../../outside"));
+        assert_denied(scope.resolve(&to_url(&root.join("../outside"))));
+        // Climbing above the filesystem root stays at it.
+        assert_denied(scope.resolve("../../../../../../../../../../.."));
+        assert_eq!(scope.resolve("a/b/../../c").unwrap(), root.join("c"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn absolute_paths_outside_the_root_are_denied() {
+        let (scope, dir) = scratch("scope-absolute");
+        assert_denied(scope.resolve(dir.join("outside").to_str().unwrap()));
+        assert_denied(scope.resolve("/etc/passwd"));
+        assert_denied(scope.resolve("file:///etc/passwd"));
+        // A sibling that shares the root's name as a prefix is outside too.
+        fs::create_dir(dir.join("rootless")).unwrap();
+        assert_denied(scope.resolve(dir.join("rootless/file").to_str().unwrap()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn only_file_urls_are_accepted() {
+        let (scope, dir) = scratch("scope-urls");
+        for location in ["https://example.com/file.txt", "icloud://container/file", "ftp://host/file"] {
+            let result = scope.resolve(location);
+            assert!(matches!(result, Err(Error::InvalidInput(_))), "{location}: {result:?}");
+        }
+        let result = scope.resolve("file://remote-host/file.txt");
+        assert!(matches!(result, Err(Error::InvalidInput(_))), "{result:?}");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn links_are_followed_only_within_the_root() {
+        let (scope, dir) = scratch("scope-links");
+        let root = scope.root().to_path_buf();
+        fs::create_dir(root.join("inside")).unwrap();
+        fs::write(dir.join("outside/secret.txt"), "secret").unwrap();
+        symlink(root.join("inside"), root.join("to-inside")).unwrap();
+        symlink(dir.join("outside"), root.join("to-outside")).unwrap();
+        symlink(dir.join("outside/secret.txt"), root.join("secret.txt")).unwrap();
+
+        // Links are returned as themselves, so operations apply to the link.
+        assert_eq!(scope.resolve("to-inside").unwrap(), root.join("to-inside"));
+        assert_eq!(scope.resolve("to-inside/new.txt").unwrap(), root.join("inside/new.txt"));
+        assert_denied(scope.resolve("to-outside"));
+        assert_denied(scope.resolve("to-outside/secret.txt"));
+        assert_denied(scope.resolve("to-outside/new/deeper.txt"));
+        assert_denied(scope.resolve("secret.txt"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn dangling_links_cant_be_created_through() {
+        let (scope, dir) = scratch("scope-dangling");
+        let root = scope.root().to_path_buf();
+        symlink(dir.join("outside/missing.txt"), root.join("dangling.txt")).unwrap();
+        symlink(dir.join("outside/missing"), root.join("dangling")).unwrap();
+        symlink(root.join("missing.txt"), root.join("dangling-inside.txt")).unwrap();
+
+        // Writing to any of these would create a file at the link's target.
+        assert_denied(scope.resolve("dangling.txt"));
+        assert_denied(scope.resolve("dangling-inside.txt"));
+        assert_denied(scope.resolve("dangling/new.txt"));
+        assert_denied(scope.resolve("dangling/new/deeper.txt"));
+        assert_eq!(scope.resolve("missing/new.txt").unwrap(), root.join("missing/new.txt"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn entries_exclude_the_root() {
+        let (scope, dir) = scratch("scope-entry");
+        let root = scope.root().to_path_buf();
+        for location in ["", ".", "a/..", root.to_str().unwrap(), &to_url(&root)] {
+            let result = scope.resolve_entry(location);
+            assert!(matches!(result, Err(Error::InvalidInput(_))), "{location}: {result:?}");
+        }
+        assert_eq!(scope.resolve_entry("a").unwrap(), root.join("a"));
+        assert_denied(scope.resolve_entry(".."));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
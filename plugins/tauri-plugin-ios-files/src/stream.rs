@@ -0,0 +1,645 @@
+//! Range reads, chunked reads and writes, and hashing, so large files never have
+//! to be held in memory or sent over IPC in one piece.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Runtime};
+
+use crate::{Error, FileData, FilesExt, Result};
+
+/// Chunk size of chunked reads when the options don't name one.
+pub const DEFAULT_CHUNK_SIZE: usize = 512 * 1024;
+
+/// Upper bound for a range read or a chunk, to keep each IPC message small.
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+static NEXT_WRITER_ID: AtomicU32 = AtomicU32::new(1);
+
+/// Part of a file. `data` is sent to the frontend as base64.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileChunk {
+    pub offset: u64,
+    #[serde(with = "base64_bytes")]
+    pub data: Vec<u8>,
+    /// Size of the whole file when the chunk was read.
+    pub total_size: u64,
+    /// Whether the chunk reaches the end of the file.
+    pub eof: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChunkedReadOptions {
+    /// Byte offset to start reading at.
+    pub offset: u64,
+    /// Bytes to read, or everything up to the end of the file when unset.
+    pub length: Option<u64>,
+    pub chunk_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadReport {
+    pub bytes_read: u64,
+    pub total_size: u64,
+    /// `false` when the read was cancelled; pass `offset + bytes_read` as
+    /// `offset` to continue it.
+    pub completed: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FileWriterOptions {
+    /// Fail instead of replacing a file that already exists.
+    pub create_new: bool,
+}
+
+mod base64_bytes {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded.trim()).map_err(D::Error::custom)
+    }
+}
+
+/// The bytes of inline file data. `FileData::Url` has to be streamed instead.
+fn inline_bytes(data: FileData) -> Result<Vec<u8>> {
+    match data {
+        FileData::Base64(encoded) => STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| Error::InvalidInput(format!("Invalid base64 data: {e}"))),
+        FileData::Text(text) => Ok(text.into_bytes()),
+        FileData::Url(_) => Err(Error::InvalidInput("Expected base64 or text data".to_string())),
+    }
+}
+
+fn check_chunk_size(size: usize) -> Result<()> {
+    if size == 0 || size > MAX_CHUNK_SIZE {
+        return Err(Error::InvalidInput(format!(
+            "Chunk size must be between 1 and {MAX_CHUNK_SIZE} bytes"
+        )));
+    }
+    Ok(())
+}
+
+/// Cancels a running read from another thread. Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Writes a file in chunks. The data goes to a hidden file next to the
+/// destination, which only replaces the destination once the writer is
+/// finished, so readers never see a partly written file.
+///
+/// Dropping the writer without finishing it discards the data.
+pub struct FileWriter<R: Runtime> {
+    id: u32,
+    app: AppHandle<R>,
+    url: String,
+    temp_url: String,
+    create_new: bool,
+    bytes_written: u64,
+    done: bool,
+}
+
+impl<R: Runtime> FileWriter<R> {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// The file the data is written to once the writer is finished.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Appends `bytes` and returns the number of bytes written so far.
+    pub fn write(&mut self, bytes: &[u8]) -> Result<u64> {
+        if self.done {
+            return Err(Error::InvalidInput("The writer is already finished".to_string()));
+        }
+        self.bytes_written = self.app.files().append_bytes(&self.temp_url, bytes)?;
+        Ok(self.bytes_written)
+    }
+
+    /// Appends `data`. `FileData::Url` copies another file in chunks.
+    pub fn write_data(&mut self, data: FileData) -> Result<u64> {
+        let FileData::Url(source) = data else {
+            return self.write(&inline_bytes(data)?);
+        };
+        let app = self.app.clone();
+        let mut failure = None;
+        app.files()
+            .read_file_chunked(source, ChunkedReadOptions::default(), &CancelToken::new(), |chunk| {
+                if failure.is_none() {
+                    failure = self.write(&chunk.data).err();
+                }
+            })?;
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(self.bytes_written),
+        }
+    }
+
+    /// Moves the written data into place and returns its size.
+    pub fn finish(mut self) -> Result<u64> {
+        self.commit()
+    }
+
+    /// Discards the written data. Equivalent to dropping the writer.
+    pub fn abort(self) {}
+
+    pub(crate) fn commit(&mut self) -> Result<u64> {
+        if self.done {
+            return Err(Error::InvalidInput("The writer is already finished".to_string()));
+        }
+        self.done = true;
+        let files = self.app.files();
+        let committed = files.commit_write(&self.temp_url, &self.url, self.create_new);
+        if committed.is_err() {
+            files.discard_write(&self.temp_url);
+        }
+        committed
+    }
+}
+
+impl<R: Runtime> Drop for FileWriter<R> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.app.files().discard_write(&self.temp_url);
+        }
+    }
+}
+
+/// Cancel tokens of chunked reads started from the frontend, keyed by the id JS
+/// chose.
+#[derive(Default)]
+pub(crate) struct JsReads(Mutex<HashMap<String, CancelToken>>);
+
+impl JsReads {
+    pub(crate) fn insert(&self, id: String) -> Result<CancelToken> {
+        let mut reads = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if reads.contains_key(&id) {
+            return Err(Error::InvalidInput(format!("Read {id} is already running")));
+        }
+        let token = CancelToken::new();
+        reads.insert(id, token.clone());
+        Ok(token)
+    }
+
+    pub(crate) fn remove(&self, id: &str) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).remove(id);
+    }
+
+    pub(crate) fn cancel(&self, id: &str) -> Result<()> {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+            .map(CancelToken::cancel)
+            .ok_or_else(|| Error::InvalidInput(format!("Read {id} not found")))
+    }
+}
+
+type SharedWriter<R> = Arc<Mutex<FileWriter<R>>>;
+
+/// Writers opened from the frontend, keyed by the id handed back to JS. Each
+/// writer has its own lock so writers don't wait on each other.
+pub(crate) struct JsWriters<R: Runtime>(Mutex<HashMap<u32, SharedWriter<R>>>);
+
+impl<R: Runtime> Default for JsWriters<R> {
+    fn default() -> Self {
+        Self(Mutex::default())
+    }
+}
+
+impl<R: Runtime> JsWriters<R> {
+    fn writers(&self) -> MutexGuard<'_, HashMap<u32, SharedWriter<R>>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn insert(&self, writer: FileWriter<R>) -> u32 {
+        let id = writer.id();
+        self.writers().insert(id, Arc::new(Mutex::new(writer)));
+        id
+    }
+
+    /// Runs `f` on the writer `id`. With `remove`, the writer is forgotten first.
+    pub(crate) fn with<T>(&self, id: u32, remove: bool, f: impl FnOnce(&mut FileWriter<R>) -> T) -> Result<T> {
+        let writer = if remove {
+            self.writers().remove(&id)
+        } else {
+            self.writers().get(&id).cloned()
+        };
+        let writer = writer.ok_or_else(|| Error::InvalidInput(format!("No writer with id {id}")))?;
+        let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(f(&mut writer))
+    }
+}
+
+impl<R: Runtime> crate::Files<R> {
+    /// Reads at most `length` bytes starting at `offset`. Reading past the end
+    /// of the file returns fewer bytes, or none.
+    pub fn read_file_range(&self, url: String, offset: u64, length: usize) -> Result<FileChunk> {
+        if length > MAX_CHUNK_SIZE {
+            return Err(Error::InvalidInput(format!(
+                "A range read is limited to {MAX_CHUNK_SIZE} bytes"
+            )));
+        }
+        self.read_range(&url, offset, length)
+    }
+
+    /// Reads a file in chunks and hands each one to `on_chunk`.
+    ///
+    /// Blocks until the read reaches the end of the requested range, fails or
+    /// `cancel` is triggered. The file is opened once, so replacing it during
+    /// the read doesn't mix the contents of two files.
+    pub fn read_file_chunked<F>(
+        &self,
+        url: String,
+        options: ChunkedReadOptions,
+        cancel: &CancelToken,
+        mut on_chunk: F,
+    ) -> Result<ReadReport>
+    where
+        F: FnMut(FileChunk),
+    {
+        let chunk_size = options.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+        check_chunk_size(chunk_size)?;
+
+        let mut reader = self.open_reader(&url)?;
+        let end = options.length.map(|length| options.offset.saturating_add(length));
+        let mut offset = options.offset;
+        loop {
+            if cancel.is_cancelled() {
+                let total_size = reader.read(offset, 0)?.total_size;
+                return Ok(ReadReport {
+                    bytes_read: offset - options.offset,
+                    total_size,
+                    completed: false,
+                });
+            }
+
+            let length = end.map_or(chunk_size, |end| (end - offset).min(chunk_size as u64) as usize);
+            let chunk = reader.read(offset, length)?;
+            offset += chunk.data.len() as u64;
+            let total_size = chunk.total_size;
+            let done = chunk.eof || end == Some(offset) || chunk.data.is_empty();
+            if !chunk.data.is_empty() {
+                on_chunk(chunk);
+            }
+            if done {
+                return Ok(ReadReport {
+                    bytes_read: offset - options.offset,
+                    total_size,
+                    completed: true,
+                });
+            }
+        }
+    }
+
+    /// Appends `data` to a file, creating it if needed, and returns the new
+    /// size. `FileData::Url` appends another file in chunks, as much of it as
+    /// there was when appending started, so a file can be appended to itself.
+    pub fn append_file(&self, url: String, data: FileData) -> Result<u64> {
+        let FileData::Url(source) = data else {
+            return self.append_bytes(&url, &inline_bytes(data)?);
+        };
+        let options = ChunkedReadOptions {
+            length: Some(self.read_range(&source, 0, 0)?.total_size),
+            ..Default::default()
+        };
+        let mut size = None;
+        let mut failure = None;
+        self.read_file_chunked(source, options, &CancelToken::new(), |chunk| {
+            if failure.is_none() {
+                match self.append_bytes(&url, &chunk.data) {
+                    Ok(new_size) => size = Some(new_size),
+                    Err(e) => failure = Some(e),
+                }
+            }
+        })?;
+        match (failure, size) {
+            (Some(e), _) => Err(e),
+            (None, Some(size)) => Ok(size),
+            // The source is empty, but the file must exist afterwards.
+            (None, None) => self.append_bytes(&url, &[]),
+        }
+    }
+
+    /// Opens a writer that replaces the file at `url` once it is finished.
+    pub fn open_file_writer(&self, url: String, options: FileWriterOptions) -> Result<FileWriter<R>> {
+        let id = NEXT_WRITER_ID.fetch_add(1, Ordering::Relaxed);
+        let temp_url = self.begin_write(id, &url, options.create_new)?;
+        Ok(FileWriter {
+            id,
+            app: self.app().clone(),
+            url,
+            temp_url,
+            create_new: options.create_new,
+            bytes_written: 0,
+            done: false,
+        })
+    }
+}
+
+#[cfg(all(test, desktop))]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use tauri::{
+        ipc::{Channel, InvokeResponseBody},
+        test::MockRuntime,
+        App, Manager,
+    };
+
+    use super::*;
+    use crate::commands;
+
+    /// An app whose files live in an empty folder of their own.
+    fn scratch(name: &str) -> (App<MockRuntime>, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("tauri-plugin-ios-files-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let app = tauri::test::mock_app();
+        app.manage(crate::Files::new(app.handle().clone(), dir.clone()).unwrap());
+        app.manage(JsReads::default());
+        (app, dir.canonicalize().unwrap())
+    }
+
+    /// The names in `dir`, sorted.
+    fn names(dir: &PathBuf) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Runs the `read_file_chunked` command and collects the chunks sent over its
+    /// channel. `on_chunk` runs as each chunk arrives.
+    fn read_over_channel(
+        app: &App<MockRuntime>,
+        read_id: &str,
+        options: ChunkedReadOptions,
+        on_chunk: impl Fn(&FileChunk) + Send + Sync + 'static,
+    ) -> (Result<ReadReport>, Vec<FileChunk>) {
+        let chunks = Arc::new(Mutex::new(Vec::new()));
+        let received = chunks.clone();
+        let channel = Channel::new(move |body| {
+            let InvokeResponseBody::Json(json) = body else {
+                panic!("chunks are sent as JSON");
+            };
+            let chunk: FileChunk = serde_json::from_str(&json).unwrap();
+            on_chunk(&chunk);
+            received.lock().unwrap().push(chunk);
+            Ok(())
+        });
+        let report = tauri::async_runtime::block_on(commands::read_file_chunked(
+            app.handle().clone(),
+            "file.bin".into(),
+            Some(options),
+            read_id.into(),
+            channel,
+        ));
+        let chunks = chunks.lock().unwrap().clone();
+        (report, chunks)
+    }
+
+    #[test]
+    fn range_reads_stop_at_the_end_of_the_file() {
+        let (app, dir) = scratch("stream-range");
+        fs::write(dir.join("file.txt"), "0123456789").unwrap();
+        let files = app.files();
+        let read = |offset, length| files.read_file_range("file.txt".into(), offset, length).unwrap();
+
+        let chunk = read(0, 4);
+        assert_eq!((chunk.data.as_slice(), chunk.total_size, chunk.eof), (&b"0123"[..], 10, false));
+        let chunk = read(6, 10);
+        assert_eq!((chunk.data.as_slice(), chunk.offset, chunk.eof), (&b"6789"[..], 6, true));
+        let chunk = read(10, 5);
+        assert!(chunk.data.is_empty() && chunk.eof);
+        let chunk = read(25, 5);
+        assert_eq!((chunk.data.len(), chunk.offset, chunk.total_size, chunk.eof), (0, 25, 10, true));
+        assert!(!read(0, 0).eof);
+
+        let result = files.read_file_range("file.txt".into(), 0, MAX_CHUNK_SIZE + 1);
+        assert!(matches!(result, Err(Error::InvalidInput(_))), "{result:?}");
+        let result = files.read_file_range(".".into(), 0, 1);
+        assert!(matches!(result, Err(Error::InvalidInput(_))), "{result:?}");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn chunked_reads_stream_over_the_channel() {
+        let (app, dir) = scratch("stream-chunked");
+        fs::write(dir.join("file.bin"), b"0123456789").unwrap();
+        let options = |offset, length, chunk_size| ChunkedReadOptions {
+            offset,
+            length,
+            chunk_size: Some(chunk_size),
+        };
+
+        let (report, chunks) = read_over_channel(&app, "all", options(0, None, 4), |_| {});
+        let report = report.unwrap();
+        assert_eq!((report.bytes_read, report.total_size, report.completed), (10, 10, true));
+        let parts: Vec<_> = chunks.iter().map(|chunk| (chunk.offset, chunk.data.as_slice(), chunk.eof)).collect();
+        assert_eq!(parts, [(0, &b"0123"[..], false), (4, b"4567", false), (8, b"89", true)]);
+
+        let (report, chunks) = read_over_channel(&app, "range", options(3, Some(5), 4), |_| {});
+        assert_eq!(report.unwrap().bytes_read, 5);
+        let parts: Vec<_> = chunks.iter().map(|chunk| (chunk.offset, chunk.data.as_slice())).collect();
+        assert_eq!(parts, [(3, &b"3456"[..]), (7, b"7")]);
+
+        // Nothing is sent for a range past the end.
+        let (report, chunks) = read_over_channel(&app, "past", options(20, Some(5), 4), |_| {});
+        assert_eq!((report.unwrap().bytes_read, chunks.len()), (0, 0));
+
+        // A failed read releases its id.
+        let (report, _) = read_over_channel(&app, "again", options(0, None, 0), |_| {});
+        assert!(matches!(report, Err(Error::InvalidInput(_))), "{report:?}");
+        let (report, _) = read_over_channel(&app, "again", options(0, None, MAX_CHUNK_SIZE), |_| {});
+        assert_eq!(report.unwrap().bytes_read, 10);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn chunked_reads_stop_when_cancelled() {
+        let (app, dir) = scratch("stream-cancel");
+        fs::write(dir.join("file.bin"), b"0123456789").unwrap();
+        let options = ChunkedReadOptions {
+            chunk_size: Some(3),
+            ..Default::default()
+        };
+
+        let handle = app.handle().clone();
+        let (report, chunks) = read_over_channel(&app, "cancelled", options.clone(), move |_| {
+            handle.state::<JsReads>().cancel("cancelled").unwrap();
+        });
+        let report = report.unwrap();
+        assert_eq!((report.bytes_read, report.total_size, report.completed), (3, 10, false));
+        assert_eq!(chunks.len(), 1);
+        assert!(matches!(app.state::<JsReads>().cancel("cancelled"), Err(Error::InvalidInput(_))));
+
+        // Ids are unique among the running reads.
+        let _running = app.state::<JsReads>().insert("running".into()).unwrap();
+        let (report, chunks) = read_over_channel(&app, "running", options, |_| {});
+        assert!(matches!(report, Err(Error::InvalidInput(_))), "{report:?}");
+        assert!(chunks.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn writers_replace_the_file_once_finished() {
+        let (app, dir) = scratch("stream-writer");
+        fs::write(dir.join("out.txt"), "old").unwrap();
+        fs::write(dir.join("source.txt"), "from a file").unwrap();
+        let files = app.files();
+
+        let mut writer = files.open_file_writer("out.txt".into(), FileWriterOptions::default()).unwrap();
+        assert_eq!(writer.write(b"hello ").unwrap(), 6);
+        assert_eq!(writer.write_data(FileData::Base64(STANDARD.encode("world, "))).unwrap(), 13);
+        assert_eq!(writer.write_data(FileData::Url("source.txt".into())).unwrap(), 24);
+        assert_eq!(writer.bytes_written(), 24);
+        // Until then the data goes to a hidden file next to it.
+        assert_eq!(fs::read_to_string(dir.join("out.txt")).unwrap(), "old");
+        let temp = names(&dir).into_iter().find(|name| name.starts_with(".out.txt.")).unwrap();
+        assert!(temp.ends_with(".part"));
+
+        assert_eq!(writer.finish().unwrap(), 24);
+        assert_eq!(fs::read_to_string(dir.join("out.txt")).unwrap(), "hello world, from a file");
+        assert_eq!(names(&dir), ["out.txt", "source.txt"]);
+
+        let mut writer = files.open_file_writer("out.txt".into(), FileWriterOptions::default()).unwrap();
+        writer.commit().unwrap();
+        assert_eq!(fs::read_to_string(dir.join("out.txt")).unwrap(), "");
+        assert!(matches!(writer.write(b"late"), Err(Error::InvalidInput(_))));
+        assert!(matches!(writer.commit(), Err(Error::InvalidInput(_))));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn new_files_are_not_replaced() {
+        let (app, dir) = scratch("stream-create-new");
+        fs::write(dir.join("existing.txt"), "kept").unwrap();
+        let files = app.files();
+        let create_new = || FileWriterOptions { create_new: true };
+
+        let result = files.open_file_writer("existing.txt".into(), create_new());
+        assert!(matches!(result, Err(Error::OperationFailed(_))), "{:?}", result.err());
+
+        let mut writer = files.open_file_writer("new.txt".into(), create_new()).unwrap();
+        writer.write(b"mine").unwrap();
+        // Another file took the name while the writer was open.
+        fs::write(dir.join("new.txt"), "theirs").unwrap();
+        let result = writer.finish();
+        assert!(matches!(result, Err(Error::OperationFailed(_))), "{result:?}");
+        assert_eq!(fs::read_to_string(dir.join("new.txt")).unwrap(), "theirs");
+        assert_eq!(names(&dir), ["existing.txt", "new.txt"]);
+
+        let mut writer = files.open_file_writer("other.txt".into(), create_new()).unwrap();
+        writer.write(b"mine").unwrap();
+        assert_eq!(writer.finish().unwrap(), 4);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn aborted_writers_leave_nothing_behind() {
+        let (app, dir) = scratch("stream-abort");
+        fs::write(dir.join("out.txt"), "old").unwrap();
+        let files = app.files();
+
+        let mut writer = files.open_file_writer("out.txt".into(), FileWriterOptions::default()).unwrap();
+        writer.write(b"discarded").unwrap();
+        writer.abort();
+        let mut writer = files.open_file_writer("new.txt".into(), FileWriterOptions::default()).unwrap();
+        writer.write(b"dropped").unwrap();
+        drop(writer);
+        assert_eq!(names(&dir), ["out.txt"]);
+        assert_eq!(fs::read_to_string(dir.join("out.txt")).unwrap(), "old");
+
+        // Writers opened from the frontend are discarded when they are removed.
+        let writers = JsWriters::<MockRuntime>::default();
+        let id = writers.insert(files.open_file_writer("js.txt".into(), FileWriterOptions::default()).unwrap());
+        writers.with(id, false, |writer| writer.write(b"data")).unwrap().unwrap();
+        assert_eq!(names(&dir).len(), 2);
+        writers.with(id, true, |_| ()).unwrap();
+        assert!(matches!(writers.with(id, false, |_| ()), Err(Error::InvalidInput(_))));
+        assert_eq!(names(&dir), ["out.txt"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn appending_a_file_to_itself_copies_it_once() {
+        let (app, dir) = scratch("stream-append");
+        let files = app.files();
+
+        assert_eq!(files.append_file("log.txt".into(), FileData::Text("abc".into())).unwrap(), 3);
+        assert_eq!(files.append_file("log.txt".into(), FileData::Base64(STANDARD.encode("de"))).unwrap(), 5);
+        assert_eq!(files.append_file("log.txt".into(), FileData::Url("log.txt".into())).unwrap(), 10);
+        assert_eq!(fs::read_to_string(dir.join("log.txt")).unwrap(), "abcdeabcde");
+
+        // An empty source still creates the file.
+        fs::write(dir.join("empty.txt"), "").unwrap();
+        assert_eq!(files.append_file("created.txt".into(), FileData::Url("empty.txt".into())).unwrap(), 0);
+        assert!(dir.join("created.txt").exists());
+
+        let result = files.append_file("log.txt".into(), FileData::Url("missing.txt".into()));
+        assert!(matches!(result, Err(Error::Io(_))), "{result:?}");
+        let result = files.append_file("log.txt".into(), FileData::Base64("!!".into()));
+        assert!(matches!(result, Err(Error::InvalidInput(_))), "{result:?}");
+        assert_eq!(fs::read_to_string(dir.join("log.txt")).unwrap(), "abcdeabcde");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hashes_are_hex_sha256_digests() {
+        let (app, dir) = scratch("stream-hash");
+        fs::write(dir.join("abc.txt"), "abc").unwrap();
+        fs::write(dir.join("empty.txt"), "").unwrap();
+        let files = app.files();
+
+        assert_eq!(
+            files.hash_file("abc.txt".into()).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            files.hash_file("empty.txt".into()).unwrap(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert!(matches!(files.hash_file(".".into()), Err(Error::InvalidInput(_))));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
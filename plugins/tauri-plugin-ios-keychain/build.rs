@@ -1,6 +1,38 @@
-const COMMANDS: &[&str] = &["ping"];
+const COMMANDS: &[&str] = &[
+    "set_item",
+    "get_item",
+    "delete_item",
+    "has_item",
+    "update_item",
+    "get_all_keys",
+    "delete_all",
+    "find_items",
+    "delete_items",
+    "get_status",
+    "set_access_group",
+    "get_access_group",
+    "set_secure_item",
+    "get_secure_item",
+    "generate_password",
+    "generate_passphrase",
+    "estimate_password_strength",
+    "validate_password",
+    "check_authentication",
+    "set_internet_password",
+    "get_internet_password",
+    "migrate_items",
+    "export_items",
+    "import_items",
+    "parse_otp_uri",
+    "add_otp_account",
+    "list_otp_accounts",
+    "generate_otp_code",
+    "delete_otp_account",
+];
 
 fn main() {
+  tauri_ios_plugin_shared::build::verify_commands(COMMANDS);
+
   tauri_plugin::Builder::new(COMMANDS)
     .android_path("android")
     .ios_path("ios")
@@ -1,5 +1,23 @@
 import { invoke } from '@tauri-apps/api/core'
 
+// Errors
+/** Stable machine-readable code of a rejected command */
+export type ErrorCode =
+  | 'notAvailable'
+  | 'permissionDenied'
+  | 'invalidInput'
+  | 'operationFailed'
+  | 'io'
+  | 'pluginInvoke'
+  | 'internal'
+
+/** Shape of the error every command rejects with */
+export interface PluginError {
+  code: ErrorCode
+  message: string
+  details?: Record<string, unknown> | null
+}
+
 // Enums
 export enum Accessible {
   WhenUnlocked = 'whenUnlocked',
@@ -34,7 +52,8 @@ export enum AuthenticationType {
 export enum BiometryType {
   None = 'none',
   TouchId = 'touchId',
-  FaceId = 'faceId'
+  FaceId = 'faceId',
+  OpticId = 'opticId'
 }
 
 // Types
@@ -72,6 +91,107 @@ export interface KeychainUpdate {
   comment?: string
 }
 
+/** Selects keychain items by their attributes; unset attributes match any value */
+export interface KeychainItemFilter {
+  service?: string
+  account?: string
+  accessGroup?: string
+  label?: string
+  accessible?: Accessible
+  synchronizable?: boolean
+}
+
+/** The attributes of a keychain item, without its value */
+export interface KeychainItemMetadata {
+  service: string
+  account: string
+  accessGroup?: string
+  accessible: Accessible
+  synchronizable: boolean
+  label?: string
+  comment?: string
+  /** Whether the item was stored with `setSecureItem` and reading it requires authentication */
+  secure: boolean
+}
+
+export interface KeychainMigration {
+  fromService: string
+  /** By default items of every access group are migrated */
+  fromAccessGroup?: string
+  toService: string
+  /** By default items keep their access group */
+  toAccessGroup?: string
+  /** By default items keep their accessibility */
+  accessible?: Accessible
+  mode?: 'copy' | 'move'
+  /** Replace items that already exist at the destination instead of skipping them */
+  overwrite?: boolean
+}
+
+export interface ImportOptions {
+  service?: string
+  accessGroup?: string
+  accessible?: Accessible
+  overwrite?: boolean
+}
+
+export interface SkippedItem {
+  service: string
+  account: string
+  reason: string
+}
+
+export interface MigrationReport {
+  migrated: number
+  skipped: SkippedItem[]
+}
+
+export interface KeychainExport {
+  /** The encrypted items, base64 encoded */
+  data: string
+  exported: number
+  skipped: SkippedItem[]
+}
+
+export type OtpType = 'totp' | 'hotp'
+
+export type OtpAlgorithm = 'sha1' | 'sha256' | 'sha512'
+
+export interface OtpAccount {
+  type: OtpType
+  /** The shared secret, base32 encoded */
+  secret: string
+  issuer?: string
+  accountName: string
+  algorithm?: OtpAlgorithm
+  digits?: number
+  /** Seconds each TOTP code is valid for */
+  period?: number
+  /** The counter the next HOTP code is generated with */
+  counter?: number
+}
+
+/** A stored OTP account, without its secret */
+export interface OtpAccountInfo {
+  key: string
+  type: OtpType
+  issuer?: string
+  accountName: string
+  algorithm: OtpAlgorithm
+  digits: number
+  period: number
+  counter: number
+}
+
+export interface OtpCode {
+  code: string
+  counter: number
+  /** Seconds until a TOTP code expires */
+  remaining?: number
+  /** Unix time in seconds at which a TOTP code expires */
+  expiresAt?: number
+}
+
 export interface AuthenticationPolicy {
   biometryAny: boolean
   biometryCurrentSet: boolean
@@ -126,10 +246,120 @@ export interface PasswordOptions {
   customCharacters?: string
 }
 
+export interface PassphraseOptions {
+  wordCount?: number
+  separator?: string
+  capitalize?: boolean
+  /** Appends a random digit to one of the words */
+  includeNumber?: boolean
+}
+
+export type PatternKind =
+  | 'commonPassword'
+  | 'dictionaryWord'
+  | 'userInput'
+  | 'spatial'
+  | 'repeat'
+  | 'sequence'
+  | 'date'
+  | 'bruteforce'
+
+export interface PasswordPattern {
+  kind: PatternKind
+  token: string
+  start: number
+  end: number
+  guessesLog10: number
+  reversed: boolean
+  l33t: boolean
+}
+
+/** Estimated seconds to crack a password under different attacks */
+export interface CrackTimes {
+  onlineThrottled: number
+  onlineUnthrottled: number
+  offlineSlowHash: number
+  offlineFastHash: number
+}
+
+export interface PasswordStrength {
+  /** From 0 (too guessable) to 4 (very unguessable) */
+  score: 0 | 1 | 2 | 3 | 4
+  guessesLog10: number
+  entropyBits: number
+  crackTimes: CrackTimes
+  feedback: {
+    warning: string | null
+    suggestions: string[]
+  }
+  patterns: PasswordPattern[]
+}
+
+export interface PasswordPolicy {
+  minLength?: number
+  maxLength?: number
+  requireUppercase?: boolean
+  requireLowercase?: boolean
+  requireNumbers?: boolean
+  requireSymbols?: boolean
+  maxRepeatedCharacters?: number
+  minScore?: 0 | 1 | 2 | 3 | 4
+  rejectCommon?: boolean
+  /** Values the password must not contain, like the user's name or email */
+  userInputs?: string[]
+}
+
+export type PolicyRule =
+  | 'minLength'
+  | 'maxLength'
+  | 'uppercase'
+  | 'lowercase'
+  | 'number'
+  | 'symbol'
+  | 'repeatedCharacters'
+  | 'minScore'
+  | 'common'
+  | 'userInput'
+
+export interface PolicyValidation {
+  valid: boolean
+  violations: { rule: PolicyRule; message: string }[]
+  strength: PasswordStrength
+}
+
+export interface AuthenticationOptions {
+  /** Biometry only when the policy allows neither the passcode nor user presence */
+  policy?: AuthenticationPolicy
+  /** Title of the button shown after a failed biometric attempt; an empty title hides it */
+  fallbackTitle?: string
+  cancelTitle?: string
+  /** Seconds a successful authentication is reused instead of prompting again, at most 300 */
+  reuseDuration?: number
+  /** Trust the currently enrolled biometry; until then a change fails `biometryCurrentSet` authentications */
+  acceptBiometryChange?: boolean
+}
+
+export type AuthenticationFailure =
+  | 'userCancel'
+  | 'userFallback'
+  | 'systemCancel'
+  | 'appCancel'
+  | 'authenticationFailed'
+  | 'biometryLockout'
+  | 'biometryNotAvailable'
+  | 'biometryNotEnrolled'
+  | 'biometryChanged'
+  | 'passcodeNotSet'
+  | 'notInteractive'
+  | 'other'
+
 export interface AuthenticationResult {
   success: boolean
   biometryType?: BiometryType
   error?: string
+  failure?: AuthenticationFailure
+  /** Whether an earlier authentication was reused without prompting */
+  reused: boolean
 }
 
 export interface KeychainStatus {
@@ -169,7 +399,21 @@ export async function deleteAll(service?: string): Promise<void> {
   return await invoke('plugin:ios-keychain-v2|delete_all', { service })
 }
 
-export async function setAccessGroup(group: string): Promise<void> {
+export async function findItems(filter: KeychainItemFilter = {}): Promise<KeychainItemMetadata[]> {
+  return await invoke('plugin:ios-keychain-v2|find_items', { filter })
+}
+
+/** Deletes the items matching `filter` and resolves with how many were deleted */
+export async function deleteItems(filter: KeychainItemFilter): Promise<number> {
+  return await invoke('plugin:ios-keychain-v2|delete_items', { filter })
+}
+
+export async function getStatus(): Promise<KeychainStatus> {
+  return await invoke('plugin:ios-keychain-v2|get_status')
+}
+
+/** Sets the access group used by item operations that don't name one; `null` clears it */
+export async function setAccessGroup(group: string | null): Promise<void> {
   return await invoke('plugin:ios-keychain-v2|set_access_group', { group })
 }
 
@@ -189,8 +433,20 @@ export async function generatePassword(options?: PasswordOptions): Promise<strin
   return await invoke('plugin:ios-keychain-v2|generate_password', { options })
 }
 
-export async function checkAuthentication(reason: string): Promise<AuthenticationResult> {
-  return await invoke('plugin:ios-keychain-v2|check_authentication', { reason })
+export async function generatePassphrase(options?: PassphraseOptions): Promise<string> {
+  return await invoke('plugin:ios-keychain-v2|generate_passphrase', { options })
+}
+
+export async function estimatePasswordStrength(password: string, userInputs?: string[]): Promise<PasswordStrength> {
+  return await invoke('plugin:ios-keychain-v2|estimate_password_strength', { password, userInputs })
+}
+
+export async function validatePassword(password: string, policy: PasswordPolicy = {}): Promise<PolicyValidation> {
+  return await invoke('plugin:ios-keychain-v2|validate_password', { password, policy })
+}
+
+export async function checkAuthentication(reason: string, options: AuthenticationOptions = {}): Promise<AuthenticationResult> {
+  return await invoke('plugin:ios-keychain-v2|check_authentication', { reason, options })
 }
 
 export async function setInternetPassword(item: InternetPasswordItem): Promise<void> {
@@ -200,3 +456,47 @@ export async function setInternetPassword(item: InternetPasswordItem): Promise<v
 export async function getInternetPassword(query: InternetPasswordQuery): Promise<InternetPasswordItem> {
   return await invoke('plugin:ios-keychain-v2|get_internet_password', { query })
 }
+
+/** Copies or moves items between services and access groups */
+export async function migrateItems(migration: KeychainMigration): Promise<MigrationReport> {
+  return await invoke('plugin:ios-keychain-v2|migrate_items', { migration })
+}
+
+/** Exports the items matching `filter`, encrypted with `passphrase` */
+export async function exportItems(passphrase: string, filter: KeychainItemFilter = {}): Promise<KeychainExport> {
+  return await invoke('plugin:ios-keychain-v2|export_items', { filter, passphrase })
+}
+
+export async function importItems(
+  data: string,
+  passphrase: string,
+  options?: ImportOptions
+): Promise<MigrationReport> {
+  return await invoke('plugin:ios-keychain-v2|import_items', { data, passphrase, options })
+}
+
+/** Parses an `otpauth://` URI as found in authenticator QR codes */
+export async function parseOtpUri(uri: string): Promise<OtpAccount> {
+  return await invoke('plugin:ios-keychain-v2|parse_otp_uri', { uri })
+}
+
+/** Stores an OTP account under `key`, given as an account or an `otpauth://` URI */
+export async function addOtpAccount(key: string, account: OtpAccount | string): Promise<void> {
+  if (typeof account === 'string') {
+    account = await parseOtpUri(account)
+  }
+  return await invoke('plugin:ios-keychain-v2|add_otp_account', { key, account })
+}
+
+export async function listOtpAccounts(): Promise<OtpAccountInfo[]> {
+  return await invoke('plugin:ios-keychain-v2|list_otp_accounts')
+}
+
+/** Generates the current code of an account; HOTP accounts advance their counter */
+export async function generateOtpCode(key: string): Promise<OtpCode> {
+  return await invoke('plugin:ios-keychain-v2|generate_otp_code', { key })
+}
+
+export async function deleteOtpAccount(key: string): Promise<void> {
+  return await invoke('plugin:ios-keychain-v2|delete_otp_account', { key })
+}
@@ -35,6 +35,15 @@ struct UpdateItemArgs: Decodable {
     let updates: KeychainUpdateData
 }
 
+struct KeychainItemFilterData: Decodable {
+    let service: String?
+    let account: String?
+    let accessGroup: String?
+    let label: String?
+    let accessible: String?
+    let synchronizable: Bool?
+}
+
 struct SecureKeychainItemData: Decodable {
     let key: String
     let value: SecureValueData
@@ -84,21 +93,14 @@ struct InternetPasswordQueryData: Decodable {
     let `protocol`: String?
 }
 
-struct PasswordOptionsData: Decodable {
-    let length: Int
-    let includeUppercase: Bool
-    let includeLowercase: Bool
-    let includeNumbers: Bool
-    let includeSymbols: Bool
-    let excludeAmbiguous: Bool
-    let customCharacters: String?
-}
-
 class KeychainPlugin: Plugin {
     private var defaultService: String {
         return Bundle.main.bundleIdentifier ?? "com.tauri.keychain"
     }
     
+    /// Where the enrolled biometry of the last successful authentication is kept
+    private let biometryDomainStateKey = "tauri.keychain.biometryDomainState"
+    
     @objc public func setItem(_ invoke: Invoke) throws {
         let args = try invoke.parseArgs(KeychainItemData.self)
         
@@ -166,6 +168,7 @@ class KeychainPlugin: Plugin {
             kSecClass as String: kSecClassGenericPassword,
             kSecAttrService as String: service,
             kSecAttrAccount as String: account,
+            kSecAttrSynchronizable as String: kSecAttrSynchronizableAny,
             kSecReturnData as String: true,
             kSecReturnAttributes as String: true,
             kSecMatchLimit as String: kSecMatchLimitOne
@@ -210,7 +213,8 @@ class KeychainPlugin: Plugin {
         var query: [String: Any] = [
             kSecClass as String: kSecClassGenericPassword,
             kSecAttrService as String: service,
-            kSecAttrAccount as String: account
+            kSecAttrAccount as String: account,
+            kSecAttrSynchronizable as String: kSecAttrSynchronizableAny
         ]
         
         if let accessGroup = args.accessGroup {
@@ -236,6 +240,7 @@ class KeychainPlugin: Plugin {
             kSecClass as String: kSecClassGenericPassword,
             kSecAttrService as String: service,
             kSecAttrAccount as String: account,
+            kSecAttrSynchronizable as String: kSecAttrSynchronizableAny,
             kSecMatchLimit as String: kSecMatchLimitOne
         ]
         
@@ -256,7 +261,8 @@ class KeychainPlugin: Plugin {
         var query: [String: Any] = [
             kSecClass as String: kSecClassGenericPassword,
             kSecAttrService as String: service,
-            kSecAttrAccount as String: account
+            kSecAttrAccount as String: account,
+            kSecAttrSynchronizable as String: kSecAttrSynchronizableAny
         ]
         
         if let accessGroup = args.query.accessGroup {
@@ -347,6 +353,58 @@ class KeychainPlugin: Plugin {
         }
     }
     
+    @objc public func findItems(_ invoke: Invoke) throws {
+        let args = try invoke.parseArgs(KeychainItemFilterData.self)
+        
+        var query = filterQuery(args)
+        query[kSecReturnAttributes as String] = true
+        query[kSecMatchLimit as String] = kSecMatchLimitAll
+        
+        var result: AnyObject?
+        let status = SecItemCopyMatching(query as CFDictionary, &result)
+        
+        if status == errSecSuccess,
+           let items = result as? [[String: Any]] {
+            invoke.resolve(["items": items.map(itemMetadata)])
+        } else if status == errSecItemNotFound {
+            invoke.resolve(["items": [[String: Any]]()])
+        } else {
+            invoke.reject("Failed to find items: \(status)")
+        }
+    }
+    
+    @objc public func deleteItems(_ invoke: Invoke) throws {
+        let args = try invoke.parseArgs(KeychainItemFilterData.self)
+        
+        let query = filterQuery(args)
+        
+        // SecItemDelete doesn't report how many items it removed, so count them first
+        var countQuery = query
+        countQuery[kSecReturnAttributes as String] = true
+        countQuery[kSecMatchLimit as String] = kSecMatchLimitAll
+        
+        var result: AnyObject?
+        let countStatus = SecItemCopyMatching(countQuery as CFDictionary, &result)
+        
+        if countStatus == errSecItemNotFound {
+            invoke.resolve(["deleted": 0])
+            return
+        }
+        guard countStatus == errSecSuccess else {
+            invoke.reject("Failed to delete items: \(countStatus)")
+            return
+        }
+        let count = (result as? [[String: Any]])?.count ?? 0
+        
+        let status = SecItemDelete(query as CFDictionary)
+        
+        if status == errSecSuccess || status == errSecItemNotFound {
+            invoke.resolve(["deleted": count])
+        } else {
+            invoke.reject("Failed to delete items: \(status)")
+        }
+    }
+    
     @objc public func setSecureItem(_ invoke: Invoke) throws {
         let args = try invoke.parseArgs(SecureKeychainItemData.self)
         
@@ -585,107 +643,230 @@ class KeychainPlugin: Plugin {
         }
     }
     
-    @objc public func generatePassword(_ invoke: Invoke) throws {
-        let args = try invoke.parseArgs(PasswordOptionsData.self)
+    @objc public func checkAuthentication(_ invoke: Invoke) throws {
+        struct CheckAuthArgs: Decodable {
+            let reason: String
+            let policy: AuthenticationPolicyData
+            let fallbackTitle: String?
+            let cancelTitle: String?
+            let acceptBiometryChange: Bool
+        }
         
-        var characters = ""
+        let args = try invoke.parseArgs(CheckAuthArgs.self)
         
-        if args.includeLowercase {
-            characters += "abcdefghijklmnopqrstuvwxyz"
-        }
-        if args.includeUppercase {
-            characters += "ABCDEFGHIJKLMNOPQRSTUVWXYZ"
-        }
-        if args.includeNumbers {
-            characters += "0123456789"
-        }
-        if args.includeSymbols {
-            characters += "!@#$%^&*()_+-=[]{}|;:,.<>?"
-        }
+        let context = LAContext()
+        context.localizedFallbackTitle = args.fallbackTitle
+        context.localizedCancelTitle = args.cancelTitle
         
-        if args.excludeAmbiguous {
-            characters = characters.replacingOccurrences(of: "0", with: "")
-                                 .replacingOccurrences(of: "O", with: "")
-                                 .replacingOccurrences(of: "l", with: "")
-                                 .replacingOccurrences(of: "1", with: "")
-                                 .replacingOccurrences(of: "I", with: "")
-        }
+        // Biometry only, or biometry with the device passcode as fallback
+        let biometryOnly = (args.policy.biometryAny || args.policy.biometryCurrentSet)
+            && !args.policy.devicePasscode && !args.policy.userPresence
+        let policy: LAPolicy = biometryOnly ? .deviceOwnerAuthenticationWithBiometrics : .deviceOwnerAuthentication
         
-        if let custom = args.customCharacters {
-            characters += custom
-        }
+        var error: NSError?
+        let canEvaluate = context.canEvaluatePolicy(policy, error: &error)
+        let biometryType = biometryTypeString(context, canEvaluate: canEvaluate)
         
-        guard !characters.isEmpty else {
-            invoke.reject("No characters available for password generation")
+        guard canEvaluate else {
+            invoke.resolve(authenticationFailure(error, biometryType: biometryType))
             return
         }
         
-        var password = ""
-        for _ in 0..<args.length {
-            let randomIndex = Int.random(in: 0..<characters.count)
-            let index = characters.index(characters.startIndex, offsetBy: randomIndex)
-            password.append(characters[index])
+        context.evaluatePolicy(policy, localizedReason: args.reason) { success, error in
+            guard success else {
+                invoke.resolve(self.authenticationFailure(error, biometryType: biometryType))
+                return
+            }
+            
+            // The domain state only describes the enrolled biometry after a
+            // biometric evaluation. A changed enrollment keeps being reported
+            // until the caller accepts it.
+            if biometryOnly, let domainState = context.evaluatedPolicyDomainState {
+                let stored = UserDefaults.standard.data(forKey: self.biometryDomainStateKey)
+                if stored == nil || args.acceptBiometryChange {
+                    UserDefaults.standard.set(domainState, forKey: self.biometryDomainStateKey)
+                } else if args.policy.biometryCurrentSet, stored != domainState {
+                    invoke.resolve([
+                        "success": false,
+                        "biometryType": biometryType,
+                        "error": "The enrolled biometry changed",
+                        "failure": "biometryChanged"
+                    ])
+                    return
+                }
+            }
+            
+            invoke.resolve([
+                "success": true,
+                "biometryType": biometryType
+            ])
         }
+    }
+    
+    @objc public func getStatus(_ invoke: Invoke) throws {
+        let context = LAContext()
+        var error: NSError?
+        let biometryAvailable = context.canEvaluatePolicy(.deviceOwnerAuthenticationWithBiometrics, error: &error)
+        let biometryType = biometryTypeString(context, canEvaluate: biometryAvailable)
+        let accessGroups = defaultAccessGroup().map { [$0] } ?? []
         
-        invoke.resolve(password)
+        // Protected data is unavailable while the device is locked
+        DispatchQueue.main.async {
+            invoke.resolve([
+                "isAvailable": true,
+                "isLocked": !UIApplication.shared.isProtectedDataAvailable,
+                "biometryAvailable": biometryAvailable,
+                "biometryType": biometryType,
+                "accessGroups": accessGroups
+            ])
+        }
     }
     
-    @objc public func checkAuthentication(_ invoke: Invoke) throws {
-        struct CheckAuthArgs: Decodable {
-            let reason: String
+    // MARK: - Helper Methods
+    
+    private func filterQuery(_ filter: KeychainItemFilterData) -> [String: Any] {
+        var query: [String: Any] = [
+            kSecClass as String: kSecClassGenericPassword,
+            kSecAttrSynchronizable as String: filter.synchronizable.map { $0 as Any } ?? kSecAttrSynchronizableAny
+        ]
+        
+        if let service = filter.service {
+            query[kSecAttrService as String] = service
+        }
+        if let account = filter.account {
+            query[kSecAttrAccount as String] = account
+        }
+        if let accessGroup = filter.accessGroup {
+            query[kSecAttrAccessGroup as String] = accessGroup
+        }
+        if let label = filter.label {
+            query[kSecAttrLabel as String] = label
+        }
+        if let accessible = filter.accessible {
+            query[kSecAttrAccessible as String] = parseAccessible(accessible)
         }
         
-        let args = try invoke.parseArgs(CheckAuthArgs.self)
+        return query
+    }
+    
+    private func itemMetadata(_ item: [String: Any]) -> [String: Any] {
+        var metadata: [String: Any] = [
+            "service": item[kSecAttrService as String] as? String ?? "",
+            "account": item[kSecAttrAccount as String] as? String ?? "",
+            "accessible": accessibleToString(item[kSecAttrAccessible as String] as? String),
+            "synchronizable": item[kSecAttrSynchronizable as String] as? Bool ?? false,
+            "secure": item[kSecAttrAccessControl as String] != nil
+        ]
         
-        let context = LAContext()
-        var error: NSError?
+        if let accessGroup = item[kSecAttrAccessGroup as String] as? String {
+            metadata["accessGroup"] = accessGroup
+        }
+        if let label = item[kSecAttrLabel as String] as? String {
+            metadata["label"] = label
+        }
+        if let comment = item[kSecAttrComment as String] as? String {
+            metadata["comment"] = comment
+        }
         
-        // Check if biometry is available
-        let canEvaluate = context.canEvaluatePolicy(.deviceOwnerAuthenticationWithBiometrics, error: &error)
-        
-        let biometryType: String
-        if #available(iOS 11.0, *) {
-            switch context.biometryType {
-            case .none:
-                biometryType = "none"
-            case .touchID:
-                biometryType = "touchId"
-            case .faceID:
-                biometryType = "faceId"
-            case .opticID:
-                if #available(iOS 17.0, *) {
-                    biometryType = "opticId"
-                } else {
-                    biometryType = "none"
-                }
-            @unknown default:
-                biometryType = "none"
-            }
-        } else {
-            biometryType = canEvaluate ? "touchId" : "none"
+        return metadata
+    }
+    
+    private func accessibleToString(_ accessible: String?) -> String {
+        switch accessible {
+        case kSecAttrAccessibleAfterFirstUnlock as String:
+            return "afterFirstUnlock"
+        case kSecAttrAccessibleWhenUnlockedThisDeviceOnly as String:
+            return "whenUnlockedThisDeviceOnly"
+        case kSecAttrAccessibleAfterFirstUnlockThisDeviceOnly as String:
+            return "afterFirstUnlockThisDeviceOnly"
+        case kSecAttrAccessibleWhenPasscodeSetThisDeviceOnly as String:
+            return "whenPasscodeSetThisDeviceOnly"
+        default:
+            return "whenUnlocked"
+        }
+    }
+    
+    private func authenticationFailure(_ error: Error?, biometryType: String) -> [String: Any] {
+        let failure: String
+        switch (error as? LAError)?.code {
+        case .userCancel:
+            failure = "userCancel"
+        case .userFallback:
+            failure = "userFallback"
+        case .systemCancel:
+            failure = "systemCancel"
+        case .appCancel:
+            failure = "appCancel"
+        case .authenticationFailed:
+            failure = "authenticationFailed"
+        case .biometryLockout:
+            failure = "biometryLockout"
+        case .biometryNotAvailable:
+            failure = "biometryNotAvailable"
+        case .biometryNotEnrolled:
+            failure = "biometryNotEnrolled"
+        case .passcodeNotSet:
+            failure = "passcodeNotSet"
+        case .notInteractive:
+            failure = "notInteractive"
+        default:
+            failure = "other"
         }
         
-        if canEvaluate {
-            context.evaluatePolicy(.deviceOwnerAuthenticationWithBiometrics, localizedReason: args.reason) { success, error in
-                invoke.resolve([
-                    "success": success,
-                    "biometryType": biometryType,
-                    "error": error?.localizedDescription
-                ])
-            }
-        } else {
-            // Fall back to device passcode
-            context.evaluatePolicy(.deviceOwnerAuthentication, localizedReason: args.reason) { success, error in
-                invoke.resolve([
-                    "success": success,
-                    "biometryType": biometryType,
-                    "error": error?.localizedDescription
-                ])
+        return [
+            "success": false,
+            "biometryType": biometryType,
+            "error": error?.localizedDescription ?? "Authentication failed",
+            "failure": failure
+        ]
+    }
+    
+    private func biometryTypeString(_ context: LAContext, canEvaluate: Bool) -> String {
+        guard #available(iOS 11.0, *) else {
+            return canEvaluate ? "touchId" : "none"
+        }
+        switch context.biometryType {
+        case .none:
+            return "none"
+        case .touchID:
+            return "touchId"
+        case .faceID:
+            return "faceId"
+        case .opticID:
+            if #available(iOS 17.0, *) {
+                return "opticId"
             }
+            return "none"
+        @unknown default:
+            return "none"
         }
     }
     
-    // MARK: - Helper Methods
+    /// The access group items are stored in when none is given, read back from a
+    /// temporary item since there is no API to query it.
+    private func defaultAccessGroup() -> String? {
+        let query: [String: Any] = [
+            kSecClass as String: kSecClassGenericPassword,
+            kSecAttrService as String: "\(defaultService).access-group-probe",
+            kSecAttrAccount as String: "access-group-probe",
+            kSecAttrAccessible as String: kSecAttrAccessibleAfterFirstUnlockThisDeviceOnly
+        ]
+        
+        var attributesQuery = query
+        attributesQuery[kSecReturnAttributes as String] = true
+        
+        var result: AnyObject?
+        var status = SecItemCopyMatching(attributesQuery as CFDictionary, &result)
+        if status == errSecItemNotFound {
+            status = SecItemAdd(attributesQuery as CFDictionary, &result)
+        }
+        SecItemDelete(query as CFDictionary)
+        
+        guard status == errSecSuccess, let attributes = result as? [String: Any] else {
+            return nil
+        }
+        return attributes[kSecAttrAccessGroup as String] as? String
+    }
     
     private func parseAccessible(_ accessible: String) -> CFString {
         switch accessible {
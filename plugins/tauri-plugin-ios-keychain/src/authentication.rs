@@ -0,0 +1,349 @@
+//! Scripted authentication for desktop, which has no biometry or device passcode
+//! the plugin could prompt for. Tests and demo builds describe the device and
+//! answer the prompts in advance, and the flows play out like they do on iOS.
+
+use std::collections::VecDeque;
+
+use crate::{AuthenticationFailure, AuthenticationOptions, AuthenticationPolicy, AuthenticationResult, BiometryType};
+
+/// Failed biometric attempts after which biometry locks, like on iOS.
+const MAX_BIOMETRIC_ATTEMPTS: u32 = 5;
+
+/// The answer to one authentication prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockResponse {
+    Approve,
+    Reject(AuthenticationFailure),
+}
+
+/// Describes the simulated device and how its prompts are answered.
+///
+/// By default the device has neither biometry nor a passcode, so every
+/// authentication fails the way it would on such a device.
+#[derive(Debug, Clone)]
+pub struct AuthenticationMock {
+    pub biometry_type: BiometryType,
+    /// Whether biometry is enrolled, when the device has any.
+    pub biometry_enrolled: bool,
+    pub passcode_set: bool,
+    /// Reported by authentications with a `biometry_current_set` policy until one
+    /// sets `accept_biometry_change`, as if the user enrolled a new finger or face.
+    pub biometry_changed: bool,
+    /// Answers to the next prompts, in order. Prompts are approved once they run
+    /// out. A biometric prompt followed by the passcode counts as two prompts.
+    pub responses: VecDeque<MockResponse>,
+}
+
+impl Default for AuthenticationMock {
+    fn default() -> Self {
+        Self {
+            biometry_type: BiometryType::None,
+            biometry_enrolled: false,
+            passcode_set: false,
+            biometry_changed: false,
+            responses: VecDeque::new(),
+        }
+    }
+}
+
+impl AuthenticationMock {
+    /// A device with enrolled biometry and a passcode.
+    pub fn with_biometry(biometry_type: BiometryType) -> Self {
+        Self {
+            biometry_type,
+            biometry_enrolled: true,
+            passcode_set: true,
+            ..Default::default()
+        }
+    }
+
+    /// Queues the answers to the next prompts.
+    pub fn respond(mut self, responses: impl IntoIterator<Item = MockResponse>) -> Self {
+        self.responses.extend(responses);
+        self
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct MockAuthenticator {
+    mock: AuthenticationMock,
+    failed_attempts: u32,
+}
+
+impl MockAuthenticator {
+    pub fn set_mock(&mut self, mock: AuthenticationMock) {
+        self.mock = mock;
+        self.failed_attempts = 0;
+    }
+
+    pub fn biometry_type(&self) -> BiometryType {
+        self.mock.biometry_type
+    }
+
+    /// Whether biometry can be evaluated right now.
+    pub fn biometry_available(&self) -> bool {
+        self.mock.biometry_type != BiometryType::None
+            && self.mock.biometry_enrolled
+            && !self.locked_out()
+    }
+
+    fn locked_out(&self) -> bool {
+        self.failed_attempts >= MAX_BIOMETRIC_ATTEMPTS
+    }
+
+    fn prompt(&mut self) -> MockResponse {
+        self.mock.responses.pop_front().unwrap_or(MockResponse::Approve)
+    }
+
+    fn biometric_prompt(&mut self) -> MockResponse {
+        let response = self.prompt();
+        match response {
+            MockResponse::Approve => self.failed_attempts = 0,
+            MockResponse::Reject(AuthenticationFailure::AuthenticationFailed) => {
+                self.failed_attempts += 1;
+                if self.locked_out() {
+                    return MockResponse::Reject(AuthenticationFailure::BiometryLockout);
+                }
+            }
+            MockResponse::Reject(_) => {}
+        }
+        response
+    }
+
+    fn passcode_prompt(&mut self) -> MockResponse {
+        let response = self.prompt();
+        // Entering the passcode unlocks biometry again.
+        if response == MockResponse::Approve {
+            self.failed_attempts = 0;
+        }
+        response
+    }
+
+    pub fn authenticate(&mut self, options: &AuthenticationOptions) -> AuthenticationResult {
+        let response = self.evaluate(&options.policy, options.accept_biometry_change);
+        let biometry_type = Some(self.mock.biometry_type);
+        match response {
+            MockResponse::Approve => AuthenticationResult {
+                success: true,
+                biometry_type,
+                error: None,
+                failure: None,
+                reused: false,
+            },
+            MockResponse::Reject(failure) => AuthenticationResult {
+                success: false,
+                biometry_type,
+                error: Some(failure_message(failure).to_string()),
+                failure: Some(failure),
+                reused: false,
+            },
+        }
+    }
+
+    fn evaluate(&mut self, policy: &AuthenticationPolicy, accept_biometry_change: bool) -> MockResponse {
+        let has_biometry = self.mock.biometry_type != BiometryType::None;
+
+        if policy.biometry_only() {
+            if !has_biometry {
+                return MockResponse::Reject(AuthenticationFailure::BiometryNotAvailable);
+            }
+            if !self.mock.biometry_enrolled {
+                return MockResponse::Reject(AuthenticationFailure::BiometryNotEnrolled);
+            }
+            if self.locked_out() {
+                return MockResponse::Reject(AuthenticationFailure::BiometryLockout);
+            }
+        } else if !self.mock.passcode_set {
+            return MockResponse::Reject(AuthenticationFailure::PasscodeNotSet);
+        }
+
+        let response = if self.biometry_available() {
+            match self.biometric_prompt() {
+                MockResponse::Reject(AuthenticationFailure::UserFallback | AuthenticationFailure::BiometryLockout)
+                    if !policy.biometry_only() =>
+                {
+                    self.passcode_prompt()
+                }
+                response => response,
+            }
+        } else {
+            self.passcode_prompt()
+        };
+
+        // Like the enrollment iOS reports, the change is only known to policies
+        // bound to the current biometric set.
+        if response == MockResponse::Approve
+            && policy.biometry_only()
+            && policy.biometry_current_set
+            && self.mock.biometry_changed
+        {
+            if !accept_biometry_change {
+                return MockResponse::Reject(AuthenticationFailure::BiometryChanged);
+            }
+            self.mock.biometry_changed = false;
+        }
+        response
+    }
+}
+
+fn failure_message(failure: AuthenticationFailure) -> &'static str {
+    match failure {
+        AuthenticationFailure::UserCancel => "Authentication was cancelled by the user",
+        AuthenticationFailure::UserFallback => "The user chose the fallback",
+        AuthenticationFailure::SystemCancel => "Authentication was cancelled by the system",
+        AuthenticationFailure::AppCancel => "Authentication was cancelled by the app",
+        AuthenticationFailure::AuthenticationFailed => "The user failed to provide valid credentials",
+        AuthenticationFailure::BiometryLockout => "Biometry is locked because there were too many failed attempts",
+        AuthenticationFailure::BiometryNotAvailable => "Biometry is not available on this device",
+        AuthenticationFailure::BiometryNotEnrolled => "No biometric identities are enrolled",
+        AuthenticationFailure::BiometryChanged => "The enrolled biometry changed",
+        AuthenticationFailure::PasscodeNotSet => "A passcode is not set on this device",
+        AuthenticationFailure::NotInteractive => "Authentication requires user interaction",
+        AuthenticationFailure::Other => "Authentication failed",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(policy: AuthenticationPolicy, accept_biometry_change: bool) -> AuthenticationOptions {
+        AuthenticationOptions {
+            policy,
+            accept_biometry_change,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_biometry_change_is_reported_until_accepted() {
+        let mut authenticator = MockAuthenticator::default();
+        authenticator.set_mock(AuthenticationMock {
+            biometry_changed: true,
+            ..AuthenticationMock::with_biometry(BiometryType::FaceId)
+        });
+        let current_set = AuthenticationPolicy {
+            biometry_current_set: true,
+            device_passcode: false,
+            user_presence: false,
+            ..Default::default()
+        };
+        let any = AuthenticationPolicy {
+            biometry_any: true,
+            device_passcode: false,
+            user_presence: false,
+            ..Default::default()
+        };
+        let passcode = AuthenticationPolicy {
+            biometry_current_set: true,
+            device_passcode: true,
+            ..Default::default()
+        };
+
+        for _ in 0..2 {
+            let result = authenticator.authenticate(&options(current_set.clone(), false));
+            assert_eq!(result.failure, Some(AuthenticationFailure::BiometryChanged));
+        }
+        // Policies that don't pin the enrollment, or aren't biometric, neither
+        // report nor accept the change.
+        assert!(authenticator.authenticate(&options(any.clone(), false)).success);
+        assert!(authenticator.authenticate(&options(any, true)).success);
+        assert!(authenticator.authenticate(&options(passcode, true)).success);
+        assert!(!authenticator.authenticate(&options(current_set.clone(), false)).success);
+
+        assert!(authenticator.authenticate(&options(current_set.clone(), true)).success);
+        assert!(authenticator.authenticate(&options(current_set, false)).success);
+    }
+
+    fn biometry_only() -> AuthenticationPolicy {
+        AuthenticationPolicy {
+            biometry_any: true,
+            device_passcode: false,
+            user_presence: false,
+            ..Default::default()
+        }
+    }
+
+    fn failure(authenticator: &mut MockAuthenticator, policy: AuthenticationPolicy) -> Option<AuthenticationFailure> {
+        authenticator.authenticate(&options(policy, false)).failure
+    }
+
+    #[test]
+    fn repeated_failures_lock_biometry_until_the_passcode_is_entered() {
+        let mut authenticator = MockAuthenticator::default();
+        authenticator.set_mock(
+            AuthenticationMock::with_biometry(BiometryType::TouchId)
+                .respond([MockResponse::Reject(AuthenticationFailure::AuthenticationFailed); 5]),
+        );
+
+        for _ in 1..MAX_BIOMETRIC_ATTEMPTS {
+            assert_eq!(
+                failure(&mut authenticator, biometry_only()),
+                Some(AuthenticationFailure::AuthenticationFailed)
+            );
+            assert!(authenticator.biometry_available());
+        }
+        assert_eq!(failure(&mut authenticator, biometry_only()), Some(AuthenticationFailure::BiometryLockout));
+        assert!(!authenticator.biometry_available());
+        // Locked biometry isn't prompted for at all, so the approval left in the
+        // queue doesn't unlock it.
+        assert_eq!(failure(&mut authenticator, biometry_only()), Some(AuthenticationFailure::BiometryLockout));
+
+        assert_eq!(failure(&mut authenticator, AuthenticationPolicy::default()), None);
+        assert!(authenticator.biometry_available());
+        assert_eq!(failure(&mut authenticator, biometry_only()), None);
+    }
+
+    #[test]
+    fn the_passcode_is_asked_for_after_the_fallback_button() {
+        let mut authenticator = MockAuthenticator::default();
+        authenticator.set_mock(AuthenticationMock::with_biometry(BiometryType::FaceId).respond([
+            MockResponse::Reject(AuthenticationFailure::UserFallback),
+            MockResponse::Approve,
+            MockResponse::Reject(AuthenticationFailure::UserFallback),
+            MockResponse::Reject(AuthenticationFailure::UserCancel),
+            MockResponse::Reject(AuthenticationFailure::UserFallback),
+            MockResponse::Reject(AuthenticationFailure::AuthenticationFailed),
+        ]));
+
+        assert_eq!(failure(&mut authenticator, AuthenticationPolicy::default()), None);
+        assert_eq!(
+            failure(&mut authenticator, AuthenticationPolicy::default()),
+            Some(AuthenticationFailure::UserCancel)
+        );
+        // Without the passcode in the policy the fallback ends the authentication.
+        assert_eq!(failure(&mut authenticator, biometry_only()), Some(AuthenticationFailure::UserFallback));
+        assert_eq!(
+            failure(&mut authenticator, biometry_only()),
+            Some(AuthenticationFailure::AuthenticationFailed)
+        );
+        assert!(authenticator.mock.responses.is_empty());
+    }
+
+    #[test]
+    fn devices_without_biometry_or_a_passcode_fail_up_front() {
+        let mut authenticator = MockAuthenticator::default();
+        assert_eq!(
+            failure(&mut authenticator, AuthenticationPolicy::default()),
+            Some(AuthenticationFailure::PasscodeNotSet)
+        );
+        assert_eq!(failure(&mut authenticator, biometry_only()), Some(AuthenticationFailure::BiometryNotAvailable));
+
+        authenticator.set_mock(AuthenticationMock {
+            passcode_set: true,
+            ..Default::default()
+        }
+        .respond([MockResponse::Reject(AuthenticationFailure::UserCancel)]));
+        assert_eq!(
+            failure(&mut authenticator, AuthenticationPolicy::default()),
+            Some(AuthenticationFailure::UserCancel)
+        );
+        assert_eq!(failure(&mut authenticator, AuthenticationPolicy::default()), None);
+
+        authenticator.set_mock(AuthenticationMock {
+            biometry_enrolled: false,
+            ..AuthenticationMock::with_biometry(BiometryType::FaceId)
+        });
+        assert_eq!(failure(&mut authenticator, biometry_only()), Some(AuthenticationFailure::BiometryNotEnrolled));
+    }
+}
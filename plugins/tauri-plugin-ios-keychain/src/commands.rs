@@ -1,6 +1,9 @@
 use tauri::{command, AppHandle, Runtime};
 
-use crate::{KeychainExt, KeychainItem, KeychainQuery, KeychainUpdate, SecureKeychainItem, SecureKeychainQuery, InternetPasswordItem, InternetPasswordQuery, PasswordOptions, AuthenticationResult, Result};
+use crate::{KeychainExt, KeychainItem, KeychainQuery, KeychainUpdate, KeychainItemFilter, KeychainItemMetadata, KeychainStatus, SecureKeychainItem, SecureKeychainQuery, InternetPasswordItem, InternetPasswordQuery, PasswordOptions, AuthenticationOptions, AuthenticationResult, Result};
+use crate::migration::{ImportOptions, KeychainExport, KeychainMigration, MigrationReport};
+use crate::otp::{self, OtpAccount, OtpAccountInfo, OtpCode};
+use crate::password::{self, PassphraseOptions, PasswordPolicy, PasswordStrength, PolicyValidation};
 
 #[command]
 pub(crate) async fn set_item<R: Runtime>(
@@ -59,21 +62,43 @@ pub(crate) async fn delete_all<R: Runtime>(
     app.keychain().delete_all(service)
 }
 
+#[command]
+pub(crate) async fn find_items<R: Runtime>(
+    app: AppHandle<R>,
+    filter: Option<KeychainItemFilter>,
+) -> Result<Vec<KeychainItemMetadata>> {
+    app.keychain().find_items(filter.unwrap_or_default())
+}
+
+#[command]
+pub(crate) async fn delete_items<R: Runtime>(
+    app: AppHandle<R>,
+    filter: KeychainItemFilter,
+) -> Result<u32> {
+    app.keychain().delete_items(filter)
+}
+
+#[command]
+pub(crate) async fn get_status<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<KeychainStatus> {
+    app.keychain().get_status()
+}
+
 #[command]
 pub(crate) async fn set_access_group<R: Runtime>(
-    _app: AppHandle<R>,
-    _group: String,
+    app: AppHandle<R>,
+    group: Option<String>,
 ) -> Result<()> {
-    // This would be implemented in mobile module
+    app.keychain().set_access_group(group);
     Ok(())
 }
 
 #[command]
 pub(crate) async fn get_access_group<R: Runtime>(
-    _app: AppHandle<R>,
+    app: AppHandle<R>,
 ) -> Result<Option<String>> {
-    // This would be implemented in mobile module
-    Ok(None)
+    Ok(app.keychain().access_group())
 }
 
 #[command]
@@ -100,17 +125,41 @@ pub(crate) async fn generate_password<R: Runtime>(
     app.keychain().generate_password(options.unwrap_or_default())
 }
 
+#[command]
+pub(crate) async fn generate_passphrase(
+    options: Option<PassphraseOptions>,
+) -> Result<String> {
+    password::generate_passphrase(&options.unwrap_or_default())
+}
+
+#[command]
+pub(crate) async fn estimate_password_strength(
+    password: String,
+    user_inputs: Option<Vec<String>>,
+) -> Result<PasswordStrength> {
+    Ok(password::estimate_strength(&password, &user_inputs.unwrap_or_default()))
+}
+
+#[command]
+pub(crate) async fn validate_password(
+    password: String,
+    policy: PasswordPolicy,
+) -> Result<PolicyValidation> {
+    password::validate(&password, &policy)
+}
+
 #[command]
 pub(crate) async fn check_authentication<R: Runtime>(
-    _app: AppHandle<R>,
-    _reason: String,
+    app: AppHandle<R>,
+    reason: String,
+    options: Option<AuthenticationOptions>,
 ) -> Result<AuthenticationResult> {
-    // This would be implemented in mobile module
-    Ok(AuthenticationResult {
-        success: false,
-        biometry_type: None,
-        error: Some("Not implemented".to_string()),
+    // The prompt waits for the user, so keep it off the async runtime.
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        handle.keychain().authenticate(&reason, options.unwrap_or_default())
     })
+    .await?
 }
 
 #[command]
@@ -127,4 +176,76 @@ pub(crate) async fn get_internet_password<R: Runtime>(
     query: InternetPasswordQuery,
 ) -> Result<InternetPasswordItem> {
     app.keychain().get_internet_password(query)
-}
\ No newline at end of file
+}
+#[command]
+pub(crate) async fn migrate_items<R: Runtime>(
+    app: AppHandle<R>,
+    migration: KeychainMigration,
+) -> Result<MigrationReport> {
+    app.keychain().migrate_items(migration)
+}
+
+#[command]
+pub(crate) async fn export_items<R: Runtime>(
+    app: AppHandle<R>,
+    filter: Option<KeychainItemFilter>,
+    passphrase: String,
+) -> Result<KeychainExport> {
+    // Deriving the key is deliberately slow, so keep it off the async workers.
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        handle.keychain().export_items(filter.unwrap_or_default(), &passphrase)
+    })
+    .await?
+}
+
+#[command]
+pub(crate) async fn import_items<R: Runtime>(
+    app: AppHandle<R>,
+    data: String,
+    passphrase: String,
+    options: Option<ImportOptions>,
+) -> Result<MigrationReport> {
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        handle.keychain().import_items(&data, &passphrase, options.unwrap_or_default())
+    })
+    .await?
+}
+
+#[command]
+pub(crate) async fn parse_otp_uri(uri: String) -> Result<OtpAccount> {
+    otp::parse_uri(&uri)
+}
+
+#[command]
+pub(crate) async fn add_otp_account<R: Runtime>(
+    app: AppHandle<R>,
+    key: String,
+    account: OtpAccount,
+) -> Result<()> {
+    app.keychain().add_otp_account(&key, account)
+}
+
+#[command]
+pub(crate) async fn list_otp_accounts<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<Vec<OtpAccountInfo>> {
+    app.keychain().otp_accounts()
+}
+
+#[command]
+pub(crate) async fn generate_otp_code<R: Runtime>(
+    app: AppHandle<R>,
+    key: String,
+) -> Result<OtpCode> {
+    app.keychain().generate_otp_code(&key)
+}
+
+#[command]
+pub(crate) async fn delete_otp_account<R: Runtime>(
+    app: AppHandle<R>,
+    key: String,
+) -> Result<()> {
+    app.keychain().delete_otp_account(&key)
+}
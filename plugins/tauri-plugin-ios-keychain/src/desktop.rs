@@ -1,101 +1,676 @@
+use std::{marker::PhantomData, path::PathBuf, sync::Mutex};
+
 use serde::de::DeserializeOwned;
-use tauri::{plugin::PluginApi, AppHandle, Runtime};
+use tauri::{plugin::PluginApi, AppHandle, Manager, Runtime};
 
-use crate::{models::*, Result, Error};
+use crate::{
+    authentication::{AuthenticationMock, MockAuthenticator},
+    models::*,
+    store::{FileStore, GenericEntry, StoredValue},
+    Error, Result,
+};
 
 pub fn init<R: Runtime, C: DeserializeOwned>(
     app: &AppHandle<R>,
     _api: PluginApi<R, C>,
 ) -> Result<Keychain<R>> {
-    Ok(Keychain(app.clone()))
+    Ok(Keychain::new(
+        app.path().app_data_dir()?,
+        app.config().identifier.clone(),
+    ))
 }
 
-/// Access to the keychain APIs on desktop (returns errors as not available).
-pub struct Keychain<R: Runtime>(AppHandle<R>);
+/// Access to the keychain APIs on desktop, backed by an encrypted file in the app
+/// data directory.
+///
+/// Items are scoped by service, account and access group the same way as on iOS.
+/// The service defaults to the app identifier, like the bundle identifier on iOS.
+/// Authentication is answered by an [`AuthenticationMock`].
+pub struct Keychain<R: Runtime> {
+    store: FileStore,
+    default_service: String,
+    authenticator: Mutex<MockAuthenticator>,
+    _runtime: PhantomData<fn() -> R>,
+}
+
+fn not_found() -> Error {
+    Error::OperationFailed("Keychain item not found".to_string())
+}
+
+fn filter_matches(filter: &KeychainItemFilter, entry: &GenericEntry) -> bool {
+    filter.service.as_ref().map_or(true, |service| &entry.service == service)
+        && filter.account.as_ref().map_or(true, |account| &entry.account == account)
+        && filter
+            .access_group
+            .as_ref()
+            .map_or(true, |group| entry.access_group.as_ref() == Some(group))
+        && filter.label.as_ref().map_or(true, |label| entry.label.as_ref() == Some(label))
+        && filter.accessible.map_or(true, |accessible| entry.accessible == accessible)
+        && filter
+            .synchronizable
+            .map_or(true, |synchronizable| entry.synchronizable == synchronizable)
+}
 
 impl<R: Runtime> Keychain<R> {
-    pub fn set_item(&self, _item: KeychainItem) -> Result<()> {
-        Err(Error::NotAvailable)
+    /// Opens the keychain stored in `dir`.
+    pub(crate) fn new(dir: PathBuf, default_service: String) -> Self {
+        Self {
+            store: FileStore::new(dir),
+            default_service,
+            authenticator: Mutex::default(),
+            _runtime: PhantomData,
+        }
+    }
+
+    fn service(&self, service: Option<String>) -> String {
+        service.unwrap_or_else(|| self.default_service.clone())
     }
 
-    pub fn get_item(&self, _query: KeychainQuery) -> Result<KeychainItem> {
-        Err(Error::OperationFailed("Item not found".to_string()))
+    /// Resolves the service and account a query selects; the account defaults to the key.
+    fn scope(&self, query: &KeychainQuery) -> (String, String) {
+        (
+            self.service(query.service.clone()),
+            query.account.clone().unwrap_or_else(|| query.key.clone()),
+        )
     }
 
-    pub fn delete_item(&self, _query: KeychainQuery) -> Result<()> {
-        Err(Error::OperationFailed("Item not found".to_string()))
+    pub fn set_item(&self, item: KeychainItem) -> Result<()> {
+        let service = self.service(item.service);
+        let account = item.account.unwrap_or(item.key);
+
+        self.store.write(|items| {
+            let mut updated = false;
+            for entry in items
+                .generic
+                .iter_mut()
+                .filter(|entry| entry.matches(&service, &account, item.access_group.as_deref()))
+            {
+                entry.value = StoredValue::Plain(item.value.clone());
+                entry.accessible = item.accessible;
+                entry.synchronizable = item.synchronizable;
+                if item.label.is_some() {
+                    entry.label = item.label.clone();
+                }
+                if item.comment.is_some() {
+                    entry.comment = item.comment.clone();
+                }
+                updated = true;
+            }
+
+            if !updated {
+                items.generic.push(GenericEntry {
+                    service,
+                    account,
+                    access_group: item.access_group,
+                    value: StoredValue::Plain(item.value),
+                    accessible: item.accessible,
+                    synchronizable: item.synchronizable,
+                    label: item.label,
+                    comment: item.comment,
+                });
+            }
+            Ok(())
+        })
     }
 
-    pub fn has_item(&self, _query: KeychainQuery) -> Result<bool> {
-        Ok(false)
+    pub fn get_item(&self, query: KeychainQuery) -> Result<KeychainItem> {
+        let (service, account) = self.scope(&query);
+        let access_group = query.access_group.clone();
+        self.read_item(query, |entry| entry.matches(&service, &account, access_group.as_deref()))
     }
 
-    pub fn update_item(&self, _query: KeychainQuery, _updates: KeychainUpdate) -> Result<()> {
-        Err(Error::OperationFailed("Item not found".to_string()))
+    /// Reads the item stored in exactly the query's access group, where `get_item`
+    /// without one returns the item of any group.
+    pub(crate) fn get_stored_item(&self, query: KeychainQuery) -> Result<KeychainItem> {
+        let (service, account) = self.scope(&query);
+        let access_group = query.access_group.clone();
+        self.read_item(query, |entry| {
+            entry.service == service && entry.account == account && entry.access_group == access_group
+        })
     }
 
-    pub fn get_all_keys(&self, _service: Option<String>) -> Result<Vec<String>> {
-        Ok(vec![])
+    fn read_item(&self, query: KeychainQuery, selects: impl Fn(&GenericEntry) -> bool) -> Result<KeychainItem> {
+        let entry = self
+            .store
+            .read(|items| items.generic.iter().find(|entry| selects(entry)).cloned())?
+            .ok_or_else(not_found)?;
+
+        let StoredValue::Plain(value) = entry.value else {
+            return Err(Error::OperationFailed(
+                "Failed to get item: it is protected by an access control, use get_secure_item".to_string(),
+            ));
+        };
+        Ok(KeychainItem {
+            key: query.key,
+            value,
+            service: Some(entry.service),
+            account: Some(entry.account),
+            access_group: entry.access_group,
+            accessible: entry.accessible,
+            synchronizable: entry.synchronizable,
+            label: entry.label,
+            comment: entry.comment,
+        })
     }
 
-    pub fn delete_all(&self, _service: Option<String>) -> Result<()> {
-        Ok(())
+    pub fn delete_item(&self, query: KeychainQuery) -> Result<()> {
+        let (service, account) = self.scope(&query);
+        self.store.write(|items| {
+            items
+                .generic
+                .retain(|entry| !entry.matches(&service, &account, query.access_group.as_deref()));
+            Ok(())
+        })
     }
 
-    pub fn set_secure_item(&self, _item: SecureKeychainItem) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub fn has_item(&self, query: KeychainQuery) -> Result<bool> {
+        let (service, account) = self.scope(&query);
+        self.store.read(|items| {
+            items
+                .generic
+                .iter()
+                .any(|entry| entry.matches(&service, &account, query.access_group.as_deref()))
+        })
     }
 
-    pub fn get_secure_item(&self, _query: SecureKeychainQuery) -> Result<SecureKeychainItem> {
-        Err(Error::OperationFailed("Item not found".to_string()))
+    pub fn update_item(&self, query: KeychainQuery, updates: KeychainUpdate) -> Result<()> {
+        let (service, account) = self.scope(&query);
+        self.store.write(|items| {
+            let mut found = false;
+            for entry in items
+                .generic
+                .iter_mut()
+                .filter(|entry| entry.matches(&service, &account, query.access_group.as_deref()))
+            {
+                if let Some(value) = &updates.value {
+                    match &mut entry.value {
+                        StoredValue::Plain(current) => *current = value.clone(),
+                        StoredValue::Secure { value: current, .. } => *current = SecureValue::Password(value.clone()),
+                    }
+                }
+                if let Some(accessible) = updates.accessible {
+                    entry.accessible = accessible;
+                }
+                if let Some(synchronizable) = updates.synchronizable {
+                    entry.synchronizable = synchronizable;
+                }
+                if updates.label.is_some() {
+                    entry.label = updates.label.clone();
+                }
+                if updates.comment.is_some() {
+                    entry.comment = updates.comment.clone();
+                }
+                found = true;
+            }
+
+            if found {
+                Ok(())
+            } else {
+                Err(not_found())
+            }
+        })
     }
 
-    pub fn set_internet_password(&self, _item: InternetPasswordItem) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub fn get_all_keys(&self, service: Option<String>) -> Result<Vec<String>> {
+        let service = self.service(service);
+        self.store.read(|items| {
+            items
+                .generic
+                .iter()
+                .filter(|entry| entry.service == service)
+                .map(|entry| entry.account.clone())
+                .collect()
+        })
     }
 
-    pub fn get_internet_password(&self, _query: InternetPasswordQuery) -> Result<InternetPasswordItem> {
-        Err(Error::OperationFailed("Item not found".to_string()))
+    pub fn delete_all(&self, service: Option<String>) -> Result<()> {
+        let service = self.service(service);
+        self.store.write(|items| {
+            items.generic.retain(|entry| entry.service != service);
+            Ok(())
+        })
     }
 
-    pub fn generate_password(&self, options: PasswordOptions) -> Result<String> {
-        use rand::{thread_rng, Rng};
-        
-        let mut chars = String::new();
-        
-        if options.include_lowercase {
-            chars.push_str("abcdefghijklmnopqrstuvwxyz");
-        }
-        if options.include_uppercase {
-            chars.push_str("ABCDEFGHIJKLMNOPQRSTUVWXYZ");
-        }
-        if options.include_numbers {
-            chars.push_str("0123456789");
-        }
-        if options.include_symbols {
-            chars.push_str("!@#$%^&*()_+-=[]{}|;:,.<>?");
-        }
-        
-        if options.exclude_ambiguous {
-            chars = chars.replace("0", "").replace("O", "").replace("l", "").replace("1", "").replace("I", "");
+    pub fn find_items(&self, filter: KeychainItemFilter) -> Result<Vec<KeychainItemMetadata>> {
+        self.store.read(|items| {
+            items
+                .generic
+                .iter()
+                .filter(|entry| filter_matches(&filter, entry))
+                .map(|entry| KeychainItemMetadata {
+                    service: entry.service.clone(),
+                    account: entry.account.clone(),
+                    access_group: entry.access_group.clone(),
+                    accessible: entry.accessible,
+                    synchronizable: entry.synchronizable,
+                    label: entry.label.clone(),
+                    comment: entry.comment.clone(),
+                    secure: matches!(entry.value, StoredValue::Secure { .. }),
+                })
+                .collect()
+        })
+    }
+
+    pub fn delete_items(&self, filter: KeychainItemFilter) -> Result<u32> {
+        self.store.write(|items| {
+            let before = items.generic.len();
+            items.generic.retain(|entry| !filter_matches(&filter, entry));
+            Ok((before - items.generic.len()) as u32)
+        })
+    }
+
+    /// The keychain is available whenever its file can be read. Biometry is the
+    /// mocked one, and the access groups are the ones items were stored with.
+    pub fn get_status(&self) -> Result<KeychainStatus> {
+        let (biometry_available, biometry_type) = {
+            let authenticator = self.authenticator.lock().unwrap_or_else(|e| e.into_inner());
+            (authenticator.biometry_available(), authenticator.biometry_type())
+        };
+
+        let access_groups = self.store.read(|items| {
+            let mut groups: Vec<String> = items
+                .generic
+                .iter()
+                .filter_map(|entry| entry.access_group.clone())
+                .collect();
+            groups.sort();
+            groups.dedup();
+            groups
+        });
+
+        Ok(KeychainStatus {
+            is_available: access_groups.is_ok(),
+            is_locked: false,
+            biometry_available,
+            biometry_type,
+            access_groups: access_groups.unwrap_or_default(),
+        })
+    }
+
+    pub fn set_authentication_mock(&self, mock: AuthenticationMock) {
+        self.authenticator
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .set_mock(mock);
+    }
+
+    pub fn authenticate(&self, _reason: &str, options: &AuthenticationOptions) -> Result<AuthenticationResult> {
+        Ok(self
+            .authenticator
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .authenticate(options))
+    }
+
+    pub fn set_secure_item(&self, item: SecureKeychainItem) -> Result<()> {
+        let service = self.service(item.service);
+        self.store.write(|items| {
+            items
+                .generic
+                .retain(|entry| !entry.matches(&service, &item.key, item.access_group.as_deref()));
+            items.generic.push(GenericEntry {
+                service,
+                account: item.key,
+                access_group: item.access_group,
+                value: StoredValue::Secure {
+                    value: item.value,
+                    authentication: item.authentication,
+                    validity_duration: item.validity_duration,
+                },
+                accessible: item.accessible,
+                synchronizable: false,
+                label: None,
+                comment: None,
+            });
+            Ok(())
+        })
+    }
+
+    pub fn get_secure_item(&self, query: SecureKeychainQuery) -> Result<SecureKeychainItem> {
+        let service = self.service(query.service);
+        let entry = self
+            .store
+            .read(|items| {
+                items
+                    .generic
+                    .iter()
+                    .find(|entry| entry.matches(&service, &query.key, query.access_group.as_deref()))
+                    .cloned()
+            })?
+            .ok_or_else(|| Error::OperationFailed("Secure item not found".to_string()))?;
+
+        let (value, authentication, validity_duration) = match entry.value {
+            StoredValue::Secure {
+                value,
+                authentication,
+                validity_duration,
+            } => {
+                self.require_authentication(&authentication)?;
+                (value, authentication, validity_duration)
+            }
+            StoredValue::Plain(value) => (SecureValue::Password(value), AuthenticationPolicy::default(), None),
+        };
+        Ok(SecureKeychainItem {
+            key: query.key,
+            value,
+            service: Some(entry.service),
+            access_group: entry.access_group,
+            authentication,
+            accessible: entry.accessible,
+            validity_duration,
+        })
+    }
+
+    /// Evaluates the policy an item was stored with before its value is released,
+    /// like iOS does for items protected by an access control.
+    fn require_authentication(&self, policy: &AuthenticationPolicy) -> Result<()> {
+        let options = AuthenticationOptions {
+            policy: policy.clone(),
+            ..Default::default()
+        };
+        let result = self
+            .authenticator
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .authenticate(&options);
+        if result.success {
+            return Ok(());
         }
-        
-        if let Some(custom) = options.custom_characters {
-            chars.push_str(&custom);
+        Err(Error::OperationFailed(format!(
+            "Failed to get secure item: {}",
+            result.error.unwrap_or_default()
+        )))
+    }
+
+    pub fn set_internet_password(&self, item: InternetPasswordItem) -> Result<()> {
+        self.store.write(|items| {
+            // Like the iOS implementation, attributes left unset match any value.
+            items.internet.retain(|existing| {
+                !(existing.server == item.server
+                    && existing.account == item.account
+                    && item.port.map_or(true, |port| existing.port == Some(port))
+                    && item.protocol.map_or(true, |protocol| existing.protocol == Some(protocol))
+                    && item
+                        .authentication_type
+                        .map_or(true, |kind| existing.authentication_type == Some(kind))
+                    && item
+                        .security_domain
+                        .as_ref()
+                        .map_or(true, |domain| existing.security_domain.as_ref() == Some(domain)))
+            });
+            items.internet.push(item);
+            Ok(())
+        })
+    }
+
+    pub fn get_internet_password(&self, query: InternetPasswordQuery) -> Result<InternetPasswordItem> {
+        self.store
+            .read(|items| {
+                items
+                    .internet
+                    .iter()
+                    .find(|item| {
+                        item.server == query.server
+                            && query.account.as_ref().map_or(true, |account| &item.account == account)
+                            && query.port.map_or(true, |port| item.port == Some(port))
+                            && query.protocol.map_or(true, |protocol| item.protocol == Some(protocol))
+                    })
+                    .cloned()
+            })?
+            .ok_or_else(|| Error::OperationFailed("Internet password not found".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockResponse;
+
+    fn entry(account: &str, access_group: Option<&str>, value: &str) -> GenericEntry {
+        GenericEntry {
+            service: "app".into(),
+            account: account.into(),
+            access_group: access_group.map(Into::into),
+            value: StoredValue::Plain(value.into()),
+            accessible: Accessible::WhenUnlocked,
+            synchronizable: false,
+            label: None,
+            comment: None,
         }
-        
-        if chars.is_empty() {
-            return Err(Error::OperationFailed("Password generation failed".to_string()));
+    }
+
+    fn query(account: &str, access_group: Option<&str>) -> KeychainQuery {
+        KeychainQuery {
+            key: account.into(),
+            service: None,
+            account: None,
+            access_group: access_group.map(Into::into),
         }
-        
-        let mut rng = thread_rng();
-        let password: String = (0..options.length)
-            .map(|_| {
-                let idx = rng.gen_range(0..chars.len());
-                chars.chars().nth(idx).unwrap()
+    }
+
+    #[test]
+    fn stored_items_are_read_from_exactly_their_access_group() {
+        let (keychain, dir) = crate::scratch_keychain("desktop-stored-item");
+        let keychain = &keychain.inner;
+        keychain
+            .store
+            .write(|items| {
+                items.generic.push(entry("token", Some("team"), "team secret"));
+                items.generic.push(entry("token", None, "own secret"));
+                items.generic.push(entry("other", Some("team"), "other secret"));
+                Ok(())
             })
-            .collect();
-        
-        Ok(password)
+            .unwrap();
+
+        assert_eq!(keychain.get_item(query("token", None)).unwrap().value, "team secret");
+        assert_eq!(keychain.get_stored_item(query("token", None)).unwrap().value, "own secret");
+        assert_eq!(keychain.get_stored_item(query("token", Some("team"))).unwrap().value, "team secret");
+        assert!(keychain.get_stored_item(query("other", None)).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
     }
-}
\ No newline at end of file
+
+    /// Six items that each differ from the first in one attribute.
+    fn seed(keychain: &Keychain<tauri::Wry>) {
+        keychain
+            .store
+            .write(|items| {
+                let base = entry("alice", None, "secret");
+                items.generic.extend([
+                    base.clone(),
+                    GenericEntry {
+                        service: "other".into(),
+                        ..base.clone()
+                    },
+                    entry("bob", None, "secret"),
+                    entry("alice", Some("team"), "secret"),
+                    GenericEntry {
+                        label: Some("Work".into()),
+                        ..base.clone()
+                    },
+                    GenericEntry {
+                        accessible: Accessible::AfterFirstUnlock,
+                        synchronizable: true,
+                        ..base
+                    },
+                ]);
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn filters_select_items_by_each_attribute() {
+        let (keychain, dir) = crate::scratch_keychain("desktop-filters");
+        let keychain = &keychain.inner;
+        seed(keychain);
+
+        let count = |filter: KeychainItemFilter| keychain.find_items(filter).unwrap().len();
+        assert_eq!(count(KeychainItemFilter::default()), 6);
+        assert_eq!(
+            count(KeychainItemFilter {
+                service: Some("other".into()),
+                ..Default::default()
+            }),
+            1
+        );
+        assert_eq!(
+            count(KeychainItemFilter {
+                account: Some("bob".into()),
+                ..Default::default()
+            }),
+            1
+        );
+        assert_eq!(
+            count(KeychainItemFilter {
+                access_group: Some("team".into()),
+                ..Default::default()
+            }),
+            1
+        );
+        assert_eq!(
+            count(KeychainItemFilter {
+                label: Some("Work".into()),
+                ..Default::default()
+            }),
+            1
+        );
+        assert_eq!(
+            count(KeychainItemFilter {
+                accessible: Some(Accessible::AfterFirstUnlock),
+                ..Default::default()
+            }),
+            1
+        );
+        assert_eq!(
+            count(KeychainItemFilter {
+                synchronizable: Some(false),
+                ..Default::default()
+            }),
+            5
+        );
+        // Every set attribute has to match.
+        assert_eq!(
+            count(KeychainItemFilter {
+                service: Some("app".into()),
+                account: Some("alice".into()),
+                synchronizable: Some(false),
+                ..Default::default()
+            }),
+            3
+        );
+        assert_eq!(
+            count(KeychainItemFilter {
+                account: Some("bob".into()),
+                access_group: Some("team".into()),
+                ..Default::default()
+            }),
+            0
+        );
+
+        let found = keychain
+            .find_items(KeychainItemFilter {
+                label: Some("Work".into()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!((found[0].service.as_str(), found[0].account.as_str()), ("app", "alice"));
+        assert!(!found[0].secure);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn deleting_by_filter_returns_the_number_of_items_deleted() {
+        let (keychain, dir) = crate::scratch_keychain("desktop-delete-items");
+        let keychain = &keychain.inner;
+        seed(keychain);
+
+        let alice = KeychainItemFilter {
+            service: Some("app".into()),
+            account: Some("alice".into()),
+            ..Default::default()
+        };
+        assert_eq!(keychain.delete_items(alice.clone()).unwrap(), 4);
+        assert_eq!(keychain.delete_items(alice).unwrap(), 0);
+
+        let remaining = keychain.find_items(KeychainItemFilter::default()).unwrap();
+        let mut remaining: Vec<_> = remaining.iter().map(|item| (item.service.as_str(), item.account.as_str())).collect();
+        remaining.sort();
+        assert_eq!(remaining, [("app", "bob"), ("other", "alice")]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn the_status_lists_each_access_group_once() {
+        let (keychain, dir) = crate::scratch_keychain("desktop-status");
+        let keychain = &keychain.inner;
+        let status = keychain.get_status().unwrap();
+        assert!(status.is_available);
+        assert!(status.access_groups.is_empty());
+
+        keychain
+            .store
+            .write(|items| {
+                items.generic.extend([
+                    entry("a", Some("team"), "secret"),
+                    entry("b", Some("shared"), "secret"),
+                    entry("c", None, "secret"),
+                    entry("d", Some("team"), "secret"),
+                ]);
+                Ok(())
+            })
+            .unwrap();
+        let status = keychain.get_status().unwrap();
+        assert!(status.is_available);
+        assert!(!status.is_locked);
+        assert_eq!(status.access_groups, ["shared", "team"]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn secure_items_are_only_read_once_their_policy_is_satisfied() {
+        let (keychain, dir) = crate::scratch_keychain("desktop-secure-item");
+        let keychain = &keychain.inner;
+        keychain
+            .set_secure_item(SecureKeychainItem {
+                key: "token".into(),
+                value: SecureValue::Password("secret".into()),
+                service: None,
+                access_group: None,
+                authentication: AuthenticationPolicy {
+                    biometry_current_set: true,
+                    device_passcode: false,
+                    user_presence: false,
+                    ..Default::default()
+                },
+                accessible: Accessible::WhenPasscodeSetThisDeviceOnly,
+                validity_duration: None,
+            })
+            .unwrap();
+        let read = || {
+            keychain.get_secure_item(SecureKeychainQuery {
+                key: "token".into(),
+                service: None,
+                access_group: None,
+                authentication_prompt: None,
+            })
+        };
+
+        // The default mock has no biometry to satisfy the policy with.
+        assert!(matches!(read(), Err(Error::OperationFailed(_))));
+        keychain.set_authentication_mock(
+            AuthenticationMock::with_biometry(BiometryType::FaceId)
+                .respond([MockResponse::Reject(AuthenticationFailure::UserCancel)]),
+        );
+        assert!(matches!(read(), Err(Error::OperationFailed(_))));
+        let item = read().unwrap();
+        assert!(matches!(item.value, SecureValue::Password(value) if value == "secret"));
+        assert!(item.authentication.biometry_current_set);
+
+        keychain.set_authentication_mock(AuthenticationMock {
+            biometry_changed: true,
+            ..AuthenticationMock::with_biometry(BiometryType::FaceId)
+        });
+        assert!(matches!(read(), Err(Error::OperationFailed(_))));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
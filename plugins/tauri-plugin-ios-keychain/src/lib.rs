@@ -1,3 +1,8 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
 use tauri::{
     plugin::{Builder, TauriPlugin},
     Manager, Runtime,
@@ -6,12 +11,22 @@ use tauri::{
 pub use models::*;
 
 mod error;
+pub mod migration;
 mod models;
+pub mod otp;
+pub mod password;
 
 pub use error::{Error, Result};
 
+#[cfg(desktop)]
+mod authentication;
 #[cfg(desktop)]
 mod desktop;
+#[cfg(desktop)]
+mod store;
+
+#[cfg(desktop)]
+pub use authentication::{AuthenticationMock, MockResponse};
 #[cfg(mobile)]
 mod mobile;
 
@@ -28,8 +43,26 @@ impl<R: Runtime, T: Manager<R>> crate::KeychainExt<R> for T {
     }
 }
 
+/// Longest time a successful authentication may be reused, the same limit iOS
+/// puts on reusing a device unlock.
+const MAX_AUTHENTICATION_REUSE: u32 = 300;
+
 /// Access to the keychain APIs.
-pub struct Keychain<R: Runtime>(KeychainImpl<R>);
+pub struct Keychain<R: Runtime> {
+    inner: KeychainImpl<R>,
+    access_group: Mutex<Option<String>>,
+    last_authentication: Mutex<Option<Authenticated>>,
+    /// Held while an HOTP counter is read and advanced, so two codes are never
+    /// generated from the same counter.
+    otp_counter: Mutex<()>,
+}
+
+/// A successful authentication that later ones may reuse.
+struct Authenticated {
+    at: Instant,
+    biometry_only: bool,
+    biometry_type: Option<BiometryType>,
+}
 
 #[cfg(desktop)]
 type KeychainImpl<R> = desktop::Keychain<R>;
@@ -37,52 +70,170 @@ type KeychainImpl<R> = desktop::Keychain<R>;
 type KeychainImpl<R> = mobile::Keychain<R>;
 
 impl<R: Runtime> Keychain<R> {
+    fn new(inner: KeychainImpl<R>) -> Self {
+        Self {
+            inner,
+            access_group: Mutex::default(),
+            last_authentication: Mutex::default(),
+            otp_counter: Mutex::default(),
+        }
+    }
+
+    /// Sets the access group used by item operations that don't name one, or
+    /// clears it with `None`. Queries by filter are not affected.
+    pub fn set_access_group(&self, access_group: Option<String>) {
+        *self.access_group.lock().unwrap_or_else(|e| e.into_inner()) = access_group;
+    }
+
+    pub fn access_group(&self) -> Option<String> {
+        self.access_group.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn scoped(&self, query: KeychainQuery) -> KeychainQuery {
+        KeychainQuery {
+            access_group: query.access_group.or_else(|| self.access_group()),
+            ..query
+        }
+    }
+
     pub fn set_item(&self, item: KeychainItem) -> Result<()> {
-        self.0.set_item(item)
+        self.inner.set_item(KeychainItem {
+            access_group: item.access_group.or_else(|| self.access_group()),
+            ..item
+        })
     }
 
     pub fn get_item(&self, query: KeychainQuery) -> Result<KeychainItem> {
-        self.0.get_item(query)
+        self.inner.get_item(self.scoped(query))
     }
 
     pub fn delete_item(&self, query: KeychainQuery) -> Result<()> {
-        self.0.delete_item(query)
+        self.inner.delete_item(self.scoped(query))
     }
 
     pub fn has_item(&self, query: KeychainQuery) -> Result<bool> {
-        self.0.has_item(query)
+        self.inner.has_item(self.scoped(query))
     }
 
     pub fn update_item(&self, query: KeychainQuery, updates: KeychainUpdate) -> Result<()> {
-        self.0.update_item(query, updates)
+        self.inner.update_item(self.scoped(query), updates)
     }
 
     pub fn get_all_keys(&self, service: Option<String>) -> Result<Vec<String>> {
-        self.0.get_all_keys(service)
+        self.inner.get_all_keys(service)
     }
 
     pub fn delete_all(&self, service: Option<String>) -> Result<()> {
-        self.0.delete_all(service)
+        self.inner.delete_all(service)
+    }
+
+    /// Lists the attributes of the generic password items matching `filter`.
+    pub fn find_items(&self, filter: KeychainItemFilter) -> Result<Vec<KeychainItemMetadata>> {
+        self.inner.find_items(filter)
+    }
+
+    /// Deletes the generic password items matching `filter` and returns how many
+    /// were deleted. An empty filter is rejected rather than deleting every item.
+    pub fn delete_items(&self, filter: KeychainItemFilter) -> Result<u32> {
+        if filter.is_empty() {
+            return Err(Error::InvalidInput(
+                "Specify at least one attribute to select the items to delete".to_string(),
+            ));
+        }
+        self.inner.delete_items(filter)
+    }
+
+    pub fn get_status(&self) -> Result<KeychainStatus> {
+        self.inner.get_status()
+    }
+
+    /// Asks the user to authenticate with biometry or the device passcode, as
+    /// `options.policy` allows. A failed or cancelled authentication is not an
+    /// error: the result says why it failed.
+    pub fn authenticate(&self, reason: &str, options: AuthenticationOptions) -> Result<AuthenticationResult> {
+        if reason.trim().is_empty() {
+            return Err(Error::InvalidInput("The authentication reason must not be empty".to_string()));
+        }
+        if !options.policy.requires_authentication() {
+            return Err(Error::InvalidInput(
+                "The authentication policy must allow biometry or the device passcode".to_string(),
+            ));
+        }
+        let reuse_duration = options.reuse_duration.unwrap_or(0);
+        if reuse_duration > MAX_AUTHENTICATION_REUSE {
+            return Err(Error::InvalidInput(format!(
+                "The reuse duration must be at most {MAX_AUTHENTICATION_REUSE} seconds"
+            )));
+        }
+
+        let biometry_only = options.policy.biometry_only();
+        if let Some(reused) = self.reusable_authentication(reuse_duration, biometry_only) {
+            return Ok(reused);
+        }
+
+        let result = self.inner.authenticate(reason, &options)?;
+        if result.success {
+            *self.last_authentication.lock().unwrap_or_else(|e| e.into_inner()) = Some(Authenticated {
+                at: Instant::now(),
+                biometry_only,
+                biometry_type: result.biometry_type,
+            });
+        }
+        Ok(result)
+    }
+
+    fn reusable_authentication(&self, reuse_duration: u32, biometry_only: bool) -> Option<AuthenticationResult> {
+        let last = self.last_authentication.lock().unwrap_or_else(|e| e.into_inner());
+        let previous = last.as_ref()?;
+        // A passcode-backed authentication doesn't stand in for a biometric one.
+        let reusable = previous.at.elapsed() < Duration::from_secs(reuse_duration.into())
+            && (previous.biometry_only || !biometry_only);
+        reusable.then_some(AuthenticationResult {
+            success: true,
+            biometry_type: previous.biometry_type,
+            error: None,
+            failure: None,
+            reused: true,
+        })
+    }
+
+    /// Forgets the last successful authentication, so the next one prompts even
+    /// within its reuse duration.
+    pub fn invalidate_authentication(&self) {
+        *self.last_authentication.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Replaces how authentication prompts are answered on desktop.
+    #[cfg(desktop)]
+    pub fn set_authentication_mock(&self, mock: AuthenticationMock) {
+        self.invalidate_authentication();
+        self.inner.set_authentication_mock(mock);
     }
 
     pub fn set_secure_item(&self, item: SecureKeychainItem) -> Result<()> {
-        self.0.set_secure_item(item)
+        self.inner.set_secure_item(SecureKeychainItem {
+            access_group: item.access_group.or_else(|| self.access_group()),
+            ..item
+        })
     }
 
     pub fn get_secure_item(&self, query: SecureKeychainQuery) -> Result<SecureKeychainItem> {
-        self.0.get_secure_item(query)
+        self.inner.get_secure_item(SecureKeychainQuery {
+            access_group: query.access_group.or_else(|| self.access_group()),
+            ..query
+        })
     }
 
     pub fn set_internet_password(&self, item: InternetPasswordItem) -> Result<()> {
-        self.0.set_internet_password(item)
+        self.inner.set_internet_password(item)
     }
 
     pub fn get_internet_password(&self, query: InternetPasswordQuery) -> Result<InternetPasswordItem> {
-        self.0.get_internet_password(query)
+        self.inner.get_internet_password(query)
     }
 
     pub fn generate_password(&self, options: PasswordOptions) -> Result<String> {
-        self.0.generate_password(options)
+        password::generate(&options)
     }
 }
 
@@ -97,14 +248,28 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             commands::update_item,
             commands::get_all_keys,
             commands::delete_all,
+            commands::find_items,
+            commands::delete_items,
+            commands::get_status,
             commands::set_access_group,
             commands::get_access_group,
             commands::set_secure_item,
             commands::get_secure_item,
             commands::generate_password,
+            commands::generate_passphrase,
+            commands::estimate_password_strength,
+            commands::validate_password,
             commands::check_authentication,
             commands::set_internet_password,
             commands::get_internet_password,
+            commands::migrate_items,
+            commands::export_items,
+            commands::import_items,
+            commands::parse_otp_uri,
+            commands::add_otp_account,
+            commands::list_otp_accounts,
+            commands::generate_otp_code,
+            commands::delete_otp_account,
         ])
         .setup(|app, api| {
             #[cfg(mobile)]
@@ -112,8 +277,112 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             #[cfg(desktop)]
             let keychain = desktop::init(app, api)?;
             
-            app.manage(Keychain(keychain));
+            app.manage(Keychain::new(keychain));
             Ok(())
         })
         .build()
-}
\ No newline at end of file
+}
+
+/// A keychain of its own for each test, stored in an empty temporary folder that
+/// the test removes when it's done.
+#[cfg(all(test, desktop))]
+pub(crate) fn scratch_keychain(name: &str) -> (Keychain<tauri::Wry>, std::path::PathBuf) {
+    let dir = std::env::temp_dir().join(format!("tauri-plugin-ios-keychain-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    (
+        Keychain::new(desktop::Keychain::new(dir.clone(), "app".to_string())),
+        dir,
+    )
+}
+
+#[cfg(all(test, desktop))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deleting_items_requires_a_filter() {
+        let (keychain, dir) = scratch_keychain("delete-items");
+        keychain
+            .set_item(KeychainItem {
+                key: "token".into(),
+                value: "secret".into(),
+                service: None,
+                account: None,
+                access_group: None,
+                accessible: Accessible::WhenUnlocked,
+                synchronizable: false,
+                label: None,
+                comment: None,
+            })
+            .unwrap();
+
+        let result = keychain.delete_items(KeychainItemFilter::default());
+        assert!(matches!(result, Err(Error::InvalidInput(_))), "{result:?}");
+        assert_eq!(keychain.find_items(KeychainItemFilter::default()).unwrap().len(), 1);
+
+        let filter = KeychainItemFilter {
+            account: Some("token".into()),
+            ..Default::default()
+        };
+        assert_eq!(keychain.delete_items(filter).unwrap(), 1);
+        assert!(keychain.find_items(KeychainItemFilter::default()).unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn reusable(seconds: u32, policy: AuthenticationPolicy) -> AuthenticationOptions {
+        AuthenticationOptions {
+            policy,
+            reuse_duration: Some(seconds),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn authentications_are_reused_within_their_duration() {
+        let (keychain, dir) = scratch_keychain("reuse");
+        let cancel = MockResponse::Reject(AuthenticationFailure::UserCancel);
+        keychain.set_authentication_mock(AuthenticationMock::with_biometry(BiometryType::FaceId).respond([
+            MockResponse::Approve,
+            cancel,
+            cancel,
+            cancel,
+        ]));
+        let passcode = AuthenticationPolicy::default;
+        let biometry = || AuthenticationPolicy {
+            biometry_any: true,
+            device_passcode: false,
+            user_presence: false,
+            ..Default::default()
+        };
+
+        let first = keychain.authenticate("Unlock", reusable(60, passcode())).unwrap();
+        assert!(first.success && !first.reused);
+        let reused = keychain.authenticate("Unlock", reusable(60, passcode())).unwrap();
+        assert!(reused.success && reused.reused);
+        assert_eq!(reused.biometry_type, Some(BiometryType::FaceId));
+
+        // Without a reuse duration, and for a biometric policy after a passcode
+        // one, the user is asked again.
+        assert!(!keychain.authenticate("Unlock", reusable(0, passcode())).unwrap().success);
+        assert!(!keychain.authenticate("Unlock", reusable(60, biometry())).unwrap().success);
+
+        // A failed prompt doesn't forget the earlier success, but time does.
+        assert!(keychain.authenticate("Unlock", reusable(60, passcode())).unwrap().reused);
+        keychain
+            .last_authentication
+            .lock()
+            .unwrap()
+            .as_mut()
+            .unwrap()
+            .at -= Duration::from_secs(61);
+        assert!(keychain.authenticate("Unlock", reusable(120, passcode())).unwrap().reused);
+        let expired = keychain.authenticate("Unlock", reusable(60, passcode())).unwrap();
+        assert!(!expired.success && !expired.reused);
+
+        keychain.invalidate_authentication();
+        let result = keychain.authenticate("Unlock", reusable(301, passcode()));
+        assert!(matches!(result, Err(Error::InvalidInput(_))), "{result:?}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
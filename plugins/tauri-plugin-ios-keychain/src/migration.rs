@@ -0,0 +1,541 @@
+//! Moving items between services and access groups, and passphrase-protected
+//! exports for carrying items that don't sync over to another device.
+//!
+//! Items stored with `set_secure_item` can't be read without authentication and
+//! are skipped.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tauri::Runtime;
+
+use crate::{
+    Accessible, Error, KeychainItem, KeychainItemFilter, KeychainItemMetadata, KeychainQuery, Result,
+};
+
+const EXPORT_MAGIC: &[u8; 4] = b"TKX1";
+const EXPORT_VERSION: u32 = 1;
+const KDF_ITERATIONS: u32 = 600_000;
+/// Upper bound on the iterations an export may ask for, so a crafted file can't
+/// stall the import.
+const MAX_KDF_ITERATIONS: u32 = 10_000_000;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const HEADER_LENGTH: usize = EXPORT_MAGIC.len() + 4 + SALT_LENGTH + NONCE_LENGTH;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MigrationMode {
+    /// Leave the original items in place.
+    #[default]
+    Copy,
+    /// Delete the original items once they were written to the destination.
+    Move,
+}
+
+/// Copies or moves the generic password items of one service and access group
+/// to another.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeychainMigration {
+    pub from_service: String,
+    /// Only migrate items of this access group. By default items of every group
+    /// are migrated.
+    pub from_access_group: Option<String>,
+    pub to_service: String,
+    /// By default items keep their access group.
+    pub to_access_group: Option<String>,
+    /// Re-classifies the migrated items. By default they keep their accessibility.
+    pub accessible: Option<Accessible>,
+    #[serde(default)]
+    pub mode: MigrationMode,
+    /// Replace items that already exist at the destination instead of skipping them.
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// Where imported items are written.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ImportOptions {
+    /// By default items are restored to the service they were exported from.
+    pub service: Option<String>,
+    /// By default items are restored to the access group they were exported
+    /// from, or the one set with `set_access_group` if they had none.
+    pub access_group: Option<String>,
+    /// Re-classifies the imported items. By default they keep their accessibility.
+    pub accessible: Option<Accessible>,
+    /// Replace existing items instead of skipping them.
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedItem {
+    pub service: String,
+    pub account: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationReport {
+    pub migrated: u32,
+    pub skipped: Vec<SkippedItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeychainExport {
+    /// The encrypted items, base64 encoded.
+    pub data: String,
+    pub exported: u32,
+    pub skipped: Vec<SkippedItem>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportPayload {
+    version: u32,
+    items: Vec<KeychainItem>,
+}
+
+impl SkippedItem {
+    fn new(item: &KeychainItemMetadata, reason: impl Into<String>) -> Self {
+        Self {
+            service: item.service.clone(),
+            account: item.account.clone(),
+            reason: reason.into(),
+        }
+    }
+}
+
+fn query(service: &str, account: &str, access_group: Option<String>) -> KeychainQuery {
+    KeychainQuery {
+        key: account.to_string(),
+        service: Some(service.to_string()),
+        account: Some(account.to_string()),
+        access_group,
+    }
+}
+
+impl<R: Runtime> crate::Keychain<R> {
+    /// Reads the value of an item found by `find_items`, exactly where it is
+    /// stored regardless of the access group set with `set_access_group`.
+    fn read_value(&self, item: &KeychainItemMetadata) -> std::result::Result<KeychainItem, SkippedItem> {
+        if item.secure {
+            return Err(SkippedItem::new(item, "Reading the value requires authentication"));
+        }
+        self.inner
+            .get_stored_item(query(&item.service, &item.account, item.access_group.clone()))
+            .map_err(|e| SkippedItem::new(item, e.to_string()))
+    }
+
+    /// Copies or moves items between services and access groups. Items that
+    /// can't be migrated are reported rather than failing the whole migration.
+    pub fn migrate_items(&self, migration: KeychainMigration) -> Result<MigrationReport> {
+        let items = self.inner.find_items(KeychainItemFilter {
+            service: Some(migration.from_service.clone()),
+            access_group: migration.from_access_group.clone(),
+            ..Default::default()
+        })?;
+
+        let mut report = MigrationReport::default();
+        for item in items {
+            let stored = match self.read_value(&item) {
+                Ok(stored) => stored,
+                Err(skipped) => {
+                    report.skipped.push(skipped);
+                    continue;
+                }
+            };
+
+            let access_group = migration.to_access_group.clone().or_else(|| item.access_group.clone());
+            let in_place = migration.to_service == item.service && access_group == item.access_group;
+            let destination = query(&migration.to_service, &item.account, access_group.clone());
+            if !in_place && !migration.overwrite && self.inner.has_item(destination)? {
+                report
+                    .skipped
+                    .push(SkippedItem::new(&item, "An item already exists at the destination"));
+                continue;
+            }
+
+            let original = KeychainItem {
+                key: item.account.clone(),
+                value: stored.value,
+                service: Some(item.service.clone()),
+                account: Some(item.account.clone()),
+                access_group: item.access_group.clone(),
+                accessible: item.accessible,
+                synchronizable: item.synchronizable,
+                label: item.label.clone(),
+                comment: item.comment.clone(),
+            };
+            let migrated = KeychainItem {
+                service: Some(migration.to_service.clone()),
+                access_group,
+                accessible: migration.accessible.unwrap_or(item.accessible),
+                ..original.clone()
+            };
+
+            let moving = migration.mode == MigrationMode::Move && !in_place;
+            let original_query = query(&item.service, &item.account, item.access_group.clone());
+            // A query without an access group matches the item in every group, so
+            // an original stored without one that only changes group can't be
+            // deleted apart from its copy and has to go first.
+            if moving && item.access_group.is_none() && migration.to_service == item.service {
+                self.inner.delete_item(original_query)?;
+                if let Err(e) = self.inner.set_item(migrated) {
+                    return match self.inner.set_item(original) {
+                        Ok(()) => Err(e),
+                        Err(restore) => Err(Error::OperationFailed(format!(
+                            "Failed to migrate {}: {e}, and restoring the original failed: {restore}",
+                            item.account
+                        ))),
+                    };
+                }
+            } else {
+                self.inner.set_item(migrated)?;
+                if moving {
+                    self.inner.delete_item(original_query)?;
+                }
+            }
+            report.migrated += 1;
+        }
+        Ok(report)
+    }
+
+    /// Exports the items matching `filter`, encrypted with a key derived from
+    /// `passphrase`.
+    pub fn export_items(&self, filter: KeychainItemFilter, passphrase: &str) -> Result<KeychainExport> {
+        if passphrase.is_empty() {
+            return Err(Error::InvalidInput("The passphrase must not be empty".to_string()));
+        }
+
+        let mut items = Vec::new();
+        let mut skipped = Vec::new();
+        for item in self.inner.find_items(filter)? {
+            match self.read_value(&item) {
+                Ok(stored) => items.push(KeychainItem {
+                    key: item.account.clone(),
+                    service: Some(item.service),
+                    account: Some(item.account),
+                    access_group: item.access_group,
+                    accessible: item.accessible,
+                    synchronizable: item.synchronizable,
+                    label: item.label,
+                    comment: item.comment,
+                    value: stored.value,
+                }),
+                Err(item) => skipped.push(item),
+            }
+        }
+
+        let exported = items.len() as u32;
+        let payload = serde_json::to_vec(&ExportPayload {
+            version: EXPORT_VERSION,
+            items,
+        })
+        .map_err(|e| Error::OperationFailed(format!("Failed to encode export: {e}")))?;
+
+        Ok(KeychainExport {
+            data: STANDARD.encode(seal(&payload, passphrase)?),
+            exported,
+            skipped,
+        })
+    }
+
+    /// Restores items from [`Keychain::export_items`](crate::Keychain::export_items).
+    /// Fails with `InvalidInput` if the passphrase is wrong or the data isn't an export.
+    pub fn import_items(&self, data: &str, passphrase: &str, options: ImportOptions) -> Result<MigrationReport> {
+        let invalid = || Error::InvalidInput("The data is not a keychain export".to_string());
+        let sealed = STANDARD.decode(data.trim()).map_err(|_| invalid())?;
+        let payload: ExportPayload = serde_json::from_slice(&open(&sealed, passphrase)?).map_err(|_| invalid())?;
+        if payload.version != EXPORT_VERSION {
+            return Err(Error::InvalidInput(format!(
+                "Unsupported keychain export version {}",
+                payload.version
+            )));
+        }
+
+        let mut report = MigrationReport::default();
+        for item in payload.items {
+            let service = options.service.clone().or(item.service);
+            let account = item.account.unwrap_or_else(|| item.key.clone());
+            let access_group = options.access_group.clone().or(item.access_group);
+
+            let existing = KeychainQuery {
+                key: account.clone(),
+                service: service.clone(),
+                account: Some(account.clone()),
+                access_group: access_group.clone(),
+            };
+            if !options.overwrite && self.has_item(existing)? {
+                report.skipped.push(SkippedItem {
+                    service: service.unwrap_or_default(),
+                    account,
+                    reason: "An item already exists".to_string(),
+                });
+                continue;
+            }
+
+            self.set_item(KeychainItem {
+                key: item.key,
+                value: item.value,
+                service,
+                account: Some(account),
+                access_group,
+                accessible: options.accessible.unwrap_or(item.accessible),
+                synchronizable: item.synchronizable,
+                label: item.label,
+                comment: item.comment,
+            })?;
+            report.migrated += 1;
+        }
+        Ok(report)
+    }
+}
+
+fn cipher(passphrase: &str, salt: &[u8], iterations: u32) -> ChaCha20Poly1305 {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, &mut key);
+    ChaCha20Poly1305::new(Key::from_slice(&key))
+}
+
+/// Encrypts `payload` as `TKX1 | iterations | salt | nonce | ciphertext`.
+fn seal(payload: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    seal_with(payload, passphrase, KDF_ITERATIONS)
+}
+
+fn seal_with(payload: &[u8], passphrase: &str, iterations: u32) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let ciphertext = cipher(passphrase, &salt, iterations)
+        .encrypt(Nonce::from_slice(&nonce), payload)
+        .map_err(|_| Error::OperationFailed("Failed to encrypt export".to_string()))?;
+
+    let mut sealed = Vec::with_capacity(HEADER_LENGTH + ciphertext.len());
+    sealed.extend_from_slice(EXPORT_MAGIC);
+    sealed.extend_from_slice(&iterations.to_be_bytes());
+    sealed.extend_from_slice(&salt);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+fn open(sealed: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let invalid = || Error::InvalidInput("The data is not a keychain export".to_string());
+    if sealed.len() < HEADER_LENGTH || !sealed.starts_with(EXPORT_MAGIC) {
+        return Err(invalid());
+    }
+    let (header, ciphertext) = sealed.split_at(HEADER_LENGTH);
+    let (iterations, rest) = header[EXPORT_MAGIC.len()..].split_at(4);
+    let (salt, nonce) = rest.split_at(SALT_LENGTH);
+
+    let iterations = u32::from_be_bytes([iterations[0], iterations[1], iterations[2], iterations[3]]);
+    if iterations == 0 || iterations > MAX_KDF_ITERATIONS {
+        return Err(invalid());
+    }
+    cipher(passphrase, salt, iterations)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| Error::InvalidInput("Wrong passphrase or corrupted keychain export".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Far below `KDF_ITERATIONS`, which takes seconds in a debug build.
+    const ITERATIONS: u32 = 1_000;
+
+    fn assert_invalid(result: Result<Vec<u8>>) {
+        assert!(matches!(result, Err(Error::InvalidInput(_))), "{result:?}");
+    }
+
+    #[test]
+    fn sealed_payloads_open_with_the_passphrase() {
+        let payload = br#"{"version":1,"items":[]}"#;
+        let sealed = seal_with(payload, "correct horse", ITERATIONS).unwrap();
+        assert!(sealed.starts_with(EXPORT_MAGIC));
+        assert_eq!(sealed[4..8], ITERATIONS.to_be_bytes());
+        assert_eq!(sealed.len(), HEADER_LENGTH + payload.len() + 16);
+        assert_eq!(open(&sealed, "correct horse").unwrap(), payload);
+
+        // Every export gets its own salt and nonce.
+        assert_ne!(seal_with(payload, "correct horse", ITERATIONS).unwrap(), sealed);
+        assert_eq!(open(&seal_with(b"", "x", 1).unwrap(), "x").unwrap(), b"");
+    }
+
+    #[test]
+    fn a_wrong_passphrase_or_tampering_is_rejected() {
+        let sealed = seal_with(b"secret", "correct horse", ITERATIONS).unwrap();
+        assert_invalid(open(&sealed, "battery staple"));
+        assert_invalid(open(&sealed, ""));
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 0x01;
+        assert_invalid(open(&tampered, "correct horse"));
+
+        let mut salted = sealed.clone();
+        salted[8] ^= 0x01;
+        assert_invalid(open(&salted, "correct horse"));
+    }
+
+    #[test]
+    fn data_that_is_not_an_export_is_rejected() {
+        let sealed = seal_with(b"secret", "passphrase", ITERATIONS).unwrap();
+        assert_invalid(open(&sealed[..HEADER_LENGTH - 1], "passphrase"));
+
+        let mut magic = sealed.clone();
+        magic[..4].copy_from_slice(b"TKX2");
+        assert_invalid(open(&magic, "passphrase"));
+
+        for iterations in [0, MAX_KDF_ITERATIONS + 1] {
+            let mut crafted = sealed.clone();
+            crafted[4..8].copy_from_slice(&iterations.to_be_bytes());
+            assert_invalid(open(&crafted, "passphrase"));
+        }
+    }
+
+    #[cfg(desktop)]
+    mod migrate {
+        use super::*;
+
+        fn store(keychain: &crate::Keychain<tauri::Wry>, service: &str, access_group: Option<&str>, value: &str) {
+            keychain
+                .set_item(KeychainItem {
+                    key: "token".into(),
+                    value: value.into(),
+                    service: Some(service.into()),
+                    account: None,
+                    access_group: access_group.map(Into::into),
+                    accessible: Accessible::WhenUnlocked,
+                    synchronizable: false,
+                    label: None,
+                    comment: None,
+                })
+                .unwrap();
+        }
+
+        /// The items named "token" as (service, access group, value, accessibility).
+        fn stored(keychain: &crate::Keychain<tauri::Wry>) -> Vec<(String, Option<String>, String, Accessible)> {
+            let mut stored: Vec<_> = keychain
+                .find_items(KeychainItemFilter::default())
+                .unwrap()
+                .into_iter()
+                .map(|item| {
+                    let value = keychain.read_value(&item).unwrap().value;
+                    (item.service, item.access_group, value, item.accessible)
+                })
+                .collect();
+            stored.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+            stored
+        }
+
+        fn entry(service: &str, access_group: Option<&str>, value: &str) -> (String, Option<String>, String, Accessible) {
+            (service.into(), access_group.map(Into::into), value.into(), Accessible::WhenUnlocked)
+        }
+
+        fn migration(to_service: &str, mode: MigrationMode) -> KeychainMigration {
+            KeychainMigration {
+                from_service: "app".into(),
+                from_access_group: None,
+                to_service: to_service.into(),
+                to_access_group: None,
+                accessible: None,
+                mode,
+                overwrite: false,
+            }
+        }
+
+        #[test]
+        fn copies_keep_the_original_and_moves_delete_it() {
+            let (keychain, dir) = crate::scratch_keychain("migrate-copy-move");
+            store(&keychain, "app", Some("team"), "secret");
+
+            let report = keychain.migrate_items(migration("copy", MigrationMode::Copy)).unwrap();
+            assert_eq!(report.migrated, 1);
+            assert!(report.skipped.is_empty());
+            let report = keychain.migrate_items(migration("moved", MigrationMode::Move)).unwrap();
+            assert_eq!(report.migrated, 1);
+            assert_eq!(
+                stored(&keychain),
+                [entry("copy", Some("team"), "secret"), entry("moved", Some("team"), "secret")]
+            );
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+
+        #[test]
+        fn existing_items_are_skipped_unless_overwritten() {
+            let (keychain, dir) = crate::scratch_keychain("migrate-overwrite");
+            store(&keychain, "app", None, "new");
+            store(&keychain, "other", None, "old");
+
+            let report = keychain.migrate_items(migration("other", MigrationMode::Move)).unwrap();
+            assert_eq!(report.migrated, 0);
+            assert_eq!(report.skipped.len(), 1);
+            assert_eq!(report.skipped[0].service, "app");
+            assert_eq!(stored(&keychain), [entry("app", None, "new"), entry("other", None, "old")]);
+
+            let report = keychain
+                .migrate_items(KeychainMigration {
+                    overwrite: true,
+                    ..migration("other", MigrationMode::Move)
+                })
+                .unwrap();
+            assert_eq!(report.migrated, 1);
+            assert_eq!(stored(&keychain), [entry("other", None, "new")]);
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+
+        #[test]
+        fn items_are_reclassified_in_place() {
+            let (keychain, dir) = crate::scratch_keychain("migrate-in-place");
+            store(&keychain, "app", Some("team"), "secret");
+
+            // The item is its own destination, so it is neither skipped nor deleted.
+            let report = keychain
+                .migrate_items(KeychainMigration {
+                    accessible: Some(Accessible::AfterFirstUnlockThisDeviceOnly),
+                    ..migration("app", MigrationMode::Move)
+                })
+                .unwrap();
+            assert_eq!(report.migrated, 1);
+            assert!(report.skipped.is_empty());
+            assert_eq!(
+                stored(&keychain),
+                [(
+                    "app".into(),
+                    Some("team".into()),
+                    "secret".into(),
+                    Accessible::AfterFirstUnlockThisDeviceOnly
+                )]
+            );
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+
+        #[test]
+        fn items_without_an_access_group_move_into_one() {
+            let (keychain, dir) = crate::scratch_keychain("migrate-into-group");
+            store(&keychain, "app", None, "secret");
+
+            let report = keychain
+                .migrate_items(KeychainMigration {
+                    to_access_group: Some("team".into()),
+                    ..migration("app", MigrationMode::Move)
+                })
+                .unwrap();
+            assert_eq!(report.migrated, 1);
+            assert_eq!(stored(&keychain), [entry("app", Some("team"), "secret")]);
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+    }
+}
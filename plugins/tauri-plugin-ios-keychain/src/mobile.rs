@@ -37,6 +37,12 @@ impl<R: Runtime> Keychain<R> {
             .map_err(Into::into)
     }
 
+    /// Items listed by `find_items` always carry the access group they are stored
+    /// in on iOS, so reading with it selects exactly that item.
+    pub(crate) fn get_stored_item(&self, query: KeychainQuery) -> Result<KeychainItem> {
+        self.get_item(query)
+    }
+
     pub fn delete_item(&self, query: KeychainQuery) -> Result<()> {
         self.0
             .run_mobile_plugin("deleteItem", query)
@@ -83,50 +89,82 @@ impl<R: Runtime> Keychain<R> {
             .map_err(Into::into)
     }
 
-    pub fn set_secure_item(&self, item: SecureKeychainItem) -> Result<()> {
+    pub fn find_items(&self, filter: KeychainItemFilter) -> Result<Vec<KeychainItemMetadata>> {
+        #[derive(serde::Deserialize)]
+        struct FindItemsResponse {
+            items: Vec<KeychainItemMetadata>,
+        }
+
         self.0
-            .run_mobile_plugin("setSecureItem", item)
+            .run_mobile_plugin::<FindItemsResponse>("findItems", filter)
+            .map(|response| response.items)
             .map_err(Into::into)
     }
 
-    pub fn get_secure_item(&self, query: SecureKeychainQuery) -> Result<SecureKeychainItem> {
+    pub fn delete_items(&self, filter: KeychainItemFilter) -> Result<u32> {
+        #[derive(serde::Deserialize)]
+        struct DeleteItemsResponse {
+            deleted: u32,
+        }
+
         self.0
-            .run_mobile_plugin("getSecureItem", query)
+            .run_mobile_plugin::<DeleteItemsResponse>("deleteItems", filter)
+            .map(|response| response.deleted)
             .map_err(Into::into)
     }
 
-    pub fn set_internet_password(&self, item: InternetPasswordItem) -> Result<()> {
+    pub fn get_status(&self) -> Result<KeychainStatus> {
         self.0
-            .run_mobile_plugin("setInternetPassword", item)
+            .run_mobile_plugin("getStatus", ())
             .map_err(Into::into)
     }
 
-    pub fn get_internet_password(&self, query: InternetPasswordQuery) -> Result<InternetPasswordItem> {
+    pub fn authenticate(&self, reason: &str, options: &AuthenticationOptions) -> Result<AuthenticationResult> {
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct AuthenticateArgs<'a> {
+            reason: &'a str,
+            policy: &'a AuthenticationPolicy,
+            fallback_title: Option<&'a str>,
+            cancel_title: Option<&'a str>,
+            accept_biometry_change: bool,
+        }
+
         self.0
-            .run_mobile_plugin("getInternetPassword", query)
+            .run_mobile_plugin(
+                "checkAuthentication",
+                AuthenticateArgs {
+                    reason,
+                    policy: &options.policy,
+                    fallback_title: options.fallback_title.as_deref(),
+                    cancel_title: options.cancel_title.as_deref(),
+                    accept_biometry_change: options.accept_biometry_change,
+                },
+            )
             .map_err(Into::into)
     }
 
-    pub fn generate_password(&self, options: PasswordOptions) -> Result<String> {
+    pub fn set_secure_item(&self, item: SecureKeychainItem) -> Result<()> {
         self.0
-            .run_mobile_plugin("generatePassword", options)
+            .run_mobile_plugin("setSecureItem", item)
             .map_err(Into::into)
     }
 
-    pub fn get_access_group(&self) -> Result<Vec<String>> {
+    pub fn get_secure_item(&self, query: SecureKeychainQuery) -> Result<SecureKeychainItem> {
         self.0
-            .run_mobile_plugin("getAccessGroup", ())
+            .run_mobile_plugin("getSecureItem", query)
             .map_err(Into::into)
     }
 
-    pub fn set_access_group(&self, access_group: String) -> Result<()> {
-        #[derive(serde::Serialize)]
-        struct SetAccessGroupArgs {
-            access_group: String,
-        }
-        
+    pub fn set_internet_password(&self, item: InternetPasswordItem) -> Result<()> {
+        self.0
+            .run_mobile_plugin("setInternetPassword", item)
+            .map_err(Into::into)
+    }
+
+    pub fn get_internet_password(&self, query: InternetPasswordQuery) -> Result<InternetPasswordItem> {
         self.0
-            .run_mobile_plugin("setAccessGroup", SetAccessGroupArgs { access_group })
+            .run_mobile_plugin("getInternetPassword", query)
             .map_err(Into::into)
     }
 }
\ No newline at end of file
@@ -33,7 +33,47 @@ pub struct KeychainUpdate {
     pub comment: Option<String>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// Selects keychain items by their attributes. Unset attributes match any value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeychainItemFilter {
+    pub service: Option<String>,
+    pub account: Option<String>,
+    pub access_group: Option<String>,
+    pub label: Option<String>,
+    pub accessible: Option<Accessible>,
+    pub synchronizable: Option<bool>,
+}
+
+impl KeychainItemFilter {
+    /// Whether the filter matches every item.
+    pub fn is_empty(&self) -> bool {
+        self.service.is_none()
+            && self.account.is_none()
+            && self.access_group.is_none()
+            && self.label.is_none()
+            && self.accessible.is_none()
+            && self.synchronizable.is_none()
+    }
+}
+
+/// The attributes of a keychain item, without its value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeychainItemMetadata {
+    pub service: String,
+    pub account: String,
+    pub access_group: Option<String>,
+    pub accessible: Accessible,
+    pub synchronizable: bool,
+    pub label: Option<String>,
+    pub comment: Option<String>,
+    /// Whether the item was stored with `set_secure_item` and reading it requires
+    /// authentication.
+    pub secure: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum Accessible {
     WhenUnlocked,
@@ -101,6 +141,19 @@ impl Default for AuthenticationPolicy {
     }
 }
 
+impl AuthenticationPolicy {
+    /// Whether only biometry satisfies the policy, without falling back to the
+    /// device passcode.
+    pub fn biometry_only(&self) -> bool {
+        (self.biometry_any || self.biometry_current_set) && !self.device_passcode && !self.user_presence
+    }
+
+    /// Whether the policy asks for any kind of authentication at all.
+    pub fn requires_authentication(&self) -> bool {
+        self.biometry_any || self.biometry_current_set || self.device_passcode || self.user_presence
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct InternetPasswordItem {
@@ -124,7 +177,7 @@ pub struct InternetPasswordQuery {
     pub protocol: Option<InternetProtocol>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum InternetProtocol {
     Http,
@@ -139,7 +192,7 @@ pub enum InternetProtocol {
     Telnet,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum AuthenticationType {
     Default,
@@ -151,7 +204,7 @@ pub enum AuthenticationType {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct PasswordOptions {
     pub length: u32,
     pub include_uppercase: bool,
@@ -176,20 +229,70 @@ impl Default for PasswordOptions {
     }
 }
 
+/// How the user is asked to authenticate.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AuthenticationOptions {
+    /// Biometry only when the policy doesn't allow the device passcode, otherwise
+    /// biometry with the passcode as fallback.
+    pub policy: AuthenticationPolicy,
+    /// Title of the button shown after a failed biometric attempt. An empty title
+    /// hides the button.
+    pub fallback_title: Option<String>,
+    pub cancel_title: Option<String>,
+    /// Seconds a successful authentication is reused instead of prompting again,
+    /// at most 300.
+    pub reuse_duration: Option<u32>,
+    /// Trust the currently enrolled biometry from now on. Until an
+    /// authentication accepts it, a change of enrollment fails every
+    /// `biometry_current_set` authentication with `BiometryChanged`.
+    pub accept_biometry_change: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AuthenticationResult {
     pub success: bool,
     pub biometry_type: Option<BiometryType>,
     pub error: Option<String>,
+    /// Why the authentication failed.
+    pub failure: Option<AuthenticationFailure>,
+    /// Whether an earlier authentication was reused without prompting.
+    #[serde(default)]
+    pub reused: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuthenticationFailure {
+    /// The user cancelled the prompt.
+    UserCancel,
+    /// The user tapped the fallback button.
+    UserFallback,
+    /// The system cancelled the prompt, for example because the app went to the background.
+    SystemCancel,
+    AppCancel,
+    /// The user failed to provide valid credentials.
+    AuthenticationFailed,
+    /// Biometry is locked after too many failed attempts and needs the passcode.
+    BiometryLockout,
+    BiometryNotAvailable,
+    BiometryNotEnrolled,
+    /// The enrolled biometry changed since the last successful authentication.
+    /// Only reported for policies with `biometry_current_set`.
+    BiometryChanged,
+    PasscodeNotSet,
+    NotInteractive,
+    Other,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum BiometryType {
     None,
     TouchId,
     FaceId,
+    OpticId,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,567 @@
+//! One-time passwords (HOTP, RFC 4226, and TOTP, RFC 6238) backed by the keychain.
+//!
+//! Accounts are stored as generic password items of their own service, with the
+//! secret and its parameters as the value, and codes are computed here so the
+//! secret never has to reach the webview.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use tauri::{Runtime, Url};
+
+use crate::{Accessible, Error, KeychainItem, KeychainItemFilter, KeychainQuery, KeychainUpdate, Result};
+
+/// The service OTP accounts are stored under.
+pub const OTP_SERVICE: &str = "tauri.keychain.otp";
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const MIN_DIGITS: u32 = 6;
+const MAX_DIGITS: u32 = 10;
+const MAX_PERIOD: u32 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OtpType {
+    /// Time-based, a new code every period.
+    Totp,
+    /// Counter-based, a new code every time one is generated.
+    Hotp,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OtpAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl OtpAlgorithm {
+    fn uri_name(self) -> &'static str {
+        match self {
+            Self::Sha1 => "SHA1",
+            Self::Sha256 => "SHA256",
+            Self::Sha512 => "SHA512",
+        }
+    }
+}
+
+fn default_digits() -> u32 {
+    6
+}
+
+fn default_period() -> u32 {
+    30
+}
+
+/// An OTP secret with the parameters codes are generated with, as found in an
+/// `otpauth://` URI.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OtpAccount {
+    #[serde(rename = "type")]
+    pub otp_type: OtpType,
+    /// The shared secret, base32 encoded.
+    pub secret: String,
+    pub issuer: Option<String>,
+    pub account_name: String,
+    #[serde(default)]
+    pub algorithm: OtpAlgorithm,
+    #[serde(default = "default_digits")]
+    pub digits: u32,
+    /// Seconds each TOTP code is valid for.
+    #[serde(default = "default_period")]
+    pub period: u32,
+    /// The counter the next HOTP code is generated with.
+    #[serde(default)]
+    pub counter: u64,
+}
+
+/// A stored OTP account, without its secret.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OtpAccountInfo {
+    pub key: String,
+    #[serde(rename = "type")]
+    pub otp_type: OtpType,
+    pub issuer: Option<String>,
+    pub account_name: String,
+    pub algorithm: OtpAlgorithm,
+    pub digits: u32,
+    pub period: u32,
+    pub counter: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OtpCode {
+    pub code: String,
+    /// The counter the code was generated with; for TOTP the number of periods
+    /// since the Unix epoch.
+    pub counter: u64,
+    /// Seconds until a TOTP code expires.
+    pub remaining: Option<u32>,
+    /// Unix time in seconds at which a TOTP code expires.
+    pub expires_at: Option<u64>,
+}
+
+/// Parses an `otpauth://totp/Issuer:account?secret=...` URI as used in
+/// authenticator QR codes.
+pub fn parse_uri(uri: &str) -> Result<OtpAccount> {
+    let invalid = |reason: &str| Error::InvalidInput(format!("Invalid otpauth URI: {reason}"));
+    let url = Url::parse(uri.trim()).map_err(|_| invalid("not a URI"))?;
+    if url.scheme() != "otpauth" {
+        return Err(invalid("the scheme must be otpauth"));
+    }
+    let otp_type = match url.host_str().map(str::to_ascii_lowercase).as_deref() {
+        Some("totp") => OtpType::Totp,
+        Some("hotp") => OtpType::Hotp,
+        _ => return Err(invalid("the type must be totp or hotp")),
+    };
+
+    let label = percent_decode_str(url.path().trim_start_matches('/'))
+        .decode_utf8()
+        .map_err(|_| invalid("the label is not UTF-8"))?;
+    let (label_issuer, account_name) = match label.split_once(':') {
+        Some((issuer, account)) => (Some(issuer.trim().to_string()), account.trim().to_string()),
+        None => (None, label.trim().to_string()),
+    };
+
+    let mut account = OtpAccount {
+        otp_type,
+        secret: String::new(),
+        issuer: label_issuer,
+        account_name,
+        algorithm: OtpAlgorithm::default(),
+        digits: default_digits(),
+        period: default_period(),
+        counter: 0,
+    };
+    let mut has_counter = false;
+    for (name, value) in url.query_pairs() {
+        match name.to_ascii_lowercase().as_str() {
+            "secret" => account.secret = value.into_owned(),
+            // The parameter wins over the label prefix.
+            "issuer" if !value.is_empty() => account.issuer = Some(value.into_owned()),
+            "algorithm" => {
+                account.algorithm = match value.to_ascii_uppercase().as_str() {
+                    "SHA1" => OtpAlgorithm::Sha1,
+                    "SHA256" => OtpAlgorithm::Sha256,
+                    "SHA512" => OtpAlgorithm::Sha512,
+                    _ => return Err(invalid("unsupported algorithm")),
+                }
+            }
+            "digits" => account.digits = value.parse().map_err(|_| invalid("digits is not a number"))?,
+            "period" => account.period = value.parse().map_err(|_| invalid("period is not a number"))?,
+            "counter" => {
+                account.counter = value.parse().map_err(|_| invalid("counter is not a number"))?;
+                has_counter = true;
+            }
+            _ => {}
+        }
+    }
+
+    if account.secret.is_empty() {
+        return Err(invalid("the secret is missing"));
+    }
+    if otp_type == OtpType::Hotp && !has_counter {
+        return Err(invalid("HOTP requires a counter"));
+    }
+    normalize(account)
+}
+
+/// Formats `account` as an `otpauth://` URI, for example to show it as a QR code.
+pub fn to_uri(account: &OtpAccount) -> String {
+    let encode = |value: &str| utf8_percent_encode(value, NON_ALPHANUMERIC).to_string();
+    let label = match &account.issuer {
+        Some(issuer) => format!("{}:{}", encode(issuer), encode(&account.account_name)),
+        None => encode(&account.account_name),
+    };
+    let mut uri = format!(
+        "otpauth://{}/{label}?secret={}&algorithm={}&digits={}",
+        match account.otp_type {
+            OtpType::Totp => "totp",
+            OtpType::Hotp => "hotp",
+        },
+        account.secret,
+        account.algorithm.uri_name(),
+        account.digits,
+    );
+    match account.otp_type {
+        OtpType::Totp => uri.push_str(&format!("&period={}", account.period)),
+        OtpType::Hotp => uri.push_str(&format!("&counter={}", account.counter)),
+    }
+    if let Some(issuer) = &account.issuer {
+        uri.push_str(&format!("&issuer={}", encode(issuer)));
+    }
+    uri
+}
+
+/// Generates the code of `account`, at `time` (Unix seconds) for TOTP and at its
+/// counter for HOTP.
+pub fn generate_code(account: &OtpAccount, time: u64) -> Result<OtpCode> {
+    let secret = validate(account)?;
+    match account.otp_type {
+        OtpType::Totp => {
+            let period = u64::from(account.period);
+            let counter = time / period;
+            let expires_at = (counter + 1) * period;
+            Ok(OtpCode {
+                code: hotp(&secret, account.algorithm, counter, account.digits),
+                counter,
+                remaining: Some((expires_at - time) as u32),
+                expires_at: Some(expires_at),
+            })
+        }
+        OtpType::Hotp => Ok(OtpCode {
+            code: hotp(&secret, account.algorithm, account.counter, account.digits),
+            counter: account.counter,
+            remaining: None,
+            expires_at: None,
+        }),
+    }
+}
+
+/// Checks the parameters and returns the decoded secret.
+fn validate(account: &OtpAccount) -> Result<Vec<u8>> {
+    if !(MIN_DIGITS..=MAX_DIGITS).contains(&account.digits) {
+        return Err(Error::InvalidInput(format!(
+            "OTP codes must have {MIN_DIGITS} to {MAX_DIGITS} digits"
+        )));
+    }
+    if account.otp_type == OtpType::Totp && !(1..=MAX_PERIOD).contains(&account.period) {
+        return Err(Error::InvalidInput(format!(
+            "The TOTP period must be 1 to {MAX_PERIOD} seconds"
+        )));
+    }
+    let secret = decode_base32(&account.secret)?;
+    if secret.is_empty() {
+        return Err(Error::InvalidInput("The OTP secret must not be empty".to_string()));
+    }
+    Ok(secret)
+}
+
+/// Validates `account` and brings its secret into the canonical form.
+fn normalize(account: OtpAccount) -> Result<OtpAccount> {
+    validate(&account)?;
+    Ok(OtpAccount {
+        secret: account
+            .secret
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-' && *c != '=')
+            .map(|c| c.to_ascii_uppercase())
+            .collect(),
+        ..account
+    })
+}
+
+/// Decodes RFC 4648 base32, ignoring case, padding and the spaces and dashes
+/// secrets are often grouped with.
+fn decode_base32(encoded: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in encoded.chars().filter(|c| !c.is_whitespace() && *c != '-' && *c != '=') {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&letter| char::from(letter) == c.to_ascii_uppercase())
+            .ok_or_else(|| Error::InvalidInput("The OTP secret is not valid base32".to_string()))?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Ok(bytes)
+}
+
+fn hmac<M: Mac + hmac::digest::KeyInit>(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// The HOTP value of `counter` with dynamic truncation (RFC 4226, section 5.3).
+fn hotp(secret: &[u8], algorithm: OtpAlgorithm, counter: u64, digits: u32) -> String {
+    let message = counter.to_be_bytes();
+    let digest = match algorithm {
+        OtpAlgorithm::Sha1 => hmac::<Hmac<Sha1>>(secret, &message),
+        OtpAlgorithm::Sha256 => hmac::<Hmac<Sha256>>(secret, &message),
+        OtpAlgorithm::Sha512 => hmac::<Hmac<Sha512>>(secret, &message),
+    };
+    let offset = usize::from(digest[digest.len() - 1] & 0x0f);
+    let binary = u32::from_be_bytes([digest[offset], digest[offset + 1], digest[offset + 2], digest[offset + 3]])
+        & 0x7fff_ffff;
+    let code = u64::from(binary) % 10u64.pow(digits);
+    format!("{code:0width$}", width = digits as usize)
+}
+
+fn now() -> Result<u64> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .map_err(|_| Error::OperationFailed("The system clock is before 1970".to_string()))
+}
+
+fn query(key: &str, access_group: Option<String>) -> KeychainQuery {
+    KeychainQuery {
+        key: key.to_string(),
+        service: Some(OTP_SERVICE.to_string()),
+        account: Some(key.to_string()),
+        access_group,
+    }
+}
+
+fn decode_account(value: &str) -> Result<OtpAccount> {
+    serde_json::from_str(value)
+        .map_err(|e| Error::OperationFailed(format!("The keychain item is not an OTP account: {e}")))
+}
+
+fn encode_account(account: &OtpAccount) -> Result<String> {
+    serde_json::to_string(account).map_err(|e| Error::OperationFailed(format!("Failed to encode OTP account: {e}")))
+}
+
+impl<R: Runtime> crate::Keychain<R> {
+    /// Stores `account` under `key`, replacing the account stored there before.
+    pub fn add_otp_account(&self, key: &str, account: OtpAccount) -> Result<()> {
+        if key.is_empty() {
+            return Err(Error::InvalidInput("The OTP account key must not be empty".to_string()));
+        }
+        let account = normalize(account)?;
+        self.set_item(KeychainItem {
+            key: key.to_string(),
+            value: encode_account(&account)?,
+            service: Some(OTP_SERVICE.to_string()),
+            account: Some(key.to_string()),
+            access_group: None,
+            accessible: Accessible::WhenUnlockedThisDeviceOnly,
+            synchronizable: false,
+            label: Some(match &account.issuer {
+                Some(issuer) => format!("{issuer} ({})", account.account_name),
+                None => account.account_name.clone(),
+            }),
+            comment: None,
+        })
+    }
+
+    /// Lists the stored OTP accounts of every access group.
+    pub fn otp_accounts(&self) -> Result<Vec<OtpAccountInfo>> {
+        let items = self.inner.find_items(KeychainItemFilter {
+            service: Some(OTP_SERVICE.to_string()),
+            ..Default::default()
+        })?;
+
+        items
+            .into_iter()
+            .map(|item| {
+                let stored = self.inner.get_item(query(&item.account, item.access_group))?;
+                let account = decode_account(&stored.value)?;
+                Ok(OtpAccountInfo {
+                    key: item.account,
+                    otp_type: account.otp_type,
+                    issuer: account.issuer,
+                    account_name: account.account_name,
+                    algorithm: account.algorithm,
+                    digits: account.digits,
+                    period: account.period,
+                    counter: account.counter,
+                })
+            })
+            .collect()
+    }
+
+    /// Generates the current code of the account stored under `key`. For HOTP the
+    /// stored counter is advanced before the code is returned.
+    pub fn generate_otp_code(&self, key: &str) -> Result<OtpCode> {
+        let _guard = self.otp_counter.lock().unwrap_or_else(|e| e.into_inner());
+        let stored = self.get_item(query(key, None))?;
+        let account = decode_account(&stored.value)?;
+        let code = generate_code(&account, now()?)?;
+
+        if account.otp_type == OtpType::Hotp {
+            let next = OtpAccount {
+                counter: account
+                    .counter
+                    .checked_add(1)
+                    .ok_or_else(|| Error::OperationFailed("The HOTP counter is exhausted".to_string()))?,
+                ..account
+            };
+            self.update_item(
+                query(key, stored.access_group),
+                KeychainUpdate {
+                    value: Some(encode_account(&next)?),
+                    accessible: None,
+                    synchronizable: None,
+                    label: None,
+                    comment: None,
+                },
+            )?;
+        }
+        Ok(code)
+    }
+
+    pub fn delete_otp_account(&self, key: &str) -> Result<()> {
+        self.delete_item(query(key, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base32(bytes: &[u8]) -> String {
+        bytes
+            .chunks(5)
+            .flat_map(|chunk| {
+                let mut block = [0u8; 5];
+                block[..chunk.len()].copy_from_slice(chunk);
+                let value = block.iter().fold(0u64, |value, &byte| value << 8 | u64::from(byte));
+                let letters = (chunk.len() * 8).div_ceil(5);
+                (0..letters).map(move |i| char::from(BASE32_ALPHABET[(value >> (35 - 5 * i)) as usize & 0x1f]))
+            })
+            .collect()
+    }
+
+    fn account(otp_type: OtpType, seed: &[u8], algorithm: OtpAlgorithm, digits: u32) -> OtpAccount {
+        OtpAccount {
+            otp_type,
+            secret: base32(seed),
+            issuer: None,
+            account_name: "test".to_string(),
+            algorithm,
+            digits,
+            period: 30,
+            counter: 0,
+        }
+    }
+
+    fn invalid(uri: &str) -> bool {
+        matches!(parse_uri(uri), Err(Error::InvalidInput(_)))
+    }
+
+    #[test]
+    fn hotp_matches_rfc_4226() {
+        let seed = b"12345678901234567890";
+        assert_eq!(base32(seed), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871", "520489",
+        ];
+        for (counter, code) in expected.into_iter().enumerate() {
+            let account = OtpAccount {
+                counter: counter as u64,
+                ..account(OtpType::Hotp, seed, OtpAlgorithm::Sha1, 6)
+            };
+            let generated = generate_code(&account, 0).unwrap();
+            assert_eq!(generated.code, code, "counter {counter}");
+            assert_eq!(generated.counter, counter as u64);
+            assert_eq!(generated.remaining, None);
+        }
+    }
+
+    #[test]
+    fn totp_matches_rfc_6238() {
+        let sha1 = account(OtpType::Totp, b"12345678901234567890", OtpAlgorithm::Sha1, 8);
+        let sha256 = account(
+            OtpType::Totp,
+            b"12345678901234567890123456789012",
+            OtpAlgorithm::Sha256,
+            8,
+        );
+        let sha512 = account(
+            OtpType::Totp,
+            b"1234567890123456789012345678901234567890123456789012345678901234",
+            OtpAlgorithm::Sha512,
+            8,
+        );
+        let expected: [(u64, [&str; 3]); 6] = [
+            (59, ["94287082", "46119246", "90693936"]),
+            (1111111109, ["07081804", "68084774", "25091201"]),
+            (1111111111, ["14050471", "67062674", "99943326"]),
+            (1234567890, ["89005924", "91819424", "93441116"]),
+            (2000000000, ["69279037", "90698825", "38618901"]),
+            (20000000000, ["65353130", "77737706", "47863826"]),
+        ];
+        for (time, codes) in expected {
+            for (account, code) in [&sha1, &sha256, &sha512].into_iter().zip(codes) {
+                assert_eq!(generate_code(account, time).unwrap().code, code, "{:?} at {time}", account.algorithm);
+            }
+        }
+
+        let code = generate_code(&sha1, 59).unwrap();
+        assert_eq!(code.counter, 1);
+        assert_eq!(code.remaining, Some(1));
+        assert_eq!(code.expires_at, Some(60));
+    }
+
+    #[test]
+    fn parses_otpauth_uris() {
+        let account = parse_uri(
+            "otpauth://totp/ACME%20Co:john.doe@email.com?secret=HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ\
+             &issuer=ACME%20Co&algorithm=SHA256&digits=8&period=60",
+        )
+        .unwrap();
+        assert_eq!(account.otp_type, OtpType::Totp);
+        assert_eq!(account.issuer.as_deref(), Some("ACME Co"));
+        assert_eq!(account.account_name, "john.doe@email.com");
+        assert_eq!(account.algorithm, OtpAlgorithm::Sha256);
+        assert_eq!((account.digits, account.period), (8, 60));
+
+        let defaults = parse_uri("otpauth://hotp/alice?secret=JBSWY3DPEHPK3PXP&counter=7").unwrap();
+        assert_eq!(defaults.otp_type, OtpType::Hotp);
+        assert_eq!(defaults.issuer, None);
+        assert_eq!(defaults.algorithm, OtpAlgorithm::Sha1);
+        assert_eq!((defaults.digits, defaults.counter), (6, 7));
+
+        let round_trip = parse_uri(&to_uri(&account)).unwrap();
+        assert_eq!(round_trip.secret, account.secret);
+        assert_eq!(round_trip.issuer, account.issuer);
+        assert_eq!(round_trip.account_name, account.account_name);
+    }
+
+    #[test]
+    fn the_issuer_parameter_wins_over_the_label_prefix() {
+        let prefixed = parse_uri("otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(prefixed.issuer.as_deref(), Some("Example"));
+        assert_eq!(prefixed.account_name, "alice");
+
+        let both = parse_uri("otpauth://totp/Old:alice?secret=JBSWY3DPEHPK3PXP&issuer=New").unwrap();
+        assert_eq!(both.issuer.as_deref(), Some("New"));
+
+        let empty = parse_uri("otpauth://totp/Old:alice?secret=JBSWY3DPEHPK3PXP&issuer=").unwrap();
+        assert_eq!(empty.issuer.as_deref(), Some("Old"));
+    }
+
+    #[test]
+    fn secrets_are_normalized() {
+        let grouped = parse_uri("otpauth://totp/alice?secret=jbsw%20y3dp-ehpk-3pxp").unwrap();
+        assert_eq!(grouped.secret, "JBSWY3DPEHPK3PXP");
+
+        let padded = parse_uri("otpauth://totp/alice?secret=MFRGG%3D%3D%3D").unwrap();
+        assert_eq!(padded.secret, "MFRGG");
+        assert_eq!(decode_base32(&padded.secret).unwrap(), b"abc");
+        assert_eq!(decode_base32("mfrgg===").unwrap(), b"abc");
+    }
+
+    #[test]
+    fn invalid_uris_are_rejected() {
+        assert!(invalid("not a uri"));
+        assert!(invalid("https://totp/alice?secret=JBSWY3DPEHPK3PXP"));
+        assert!(invalid("otpauth://motp/alice?secret=JBSWY3DPEHPK3PXP"));
+        assert!(invalid("otpauth://totp/alice"));
+        assert!(invalid("otpauth://totp/alice?secret="));
+        assert!(invalid("otpauth://totp/alice?secret=JBSWY3DP1HPK3PXP"));
+        assert!(invalid("otpauth://totp/alice?secret=%3D%3D%3D"));
+        assert!(invalid("otpauth://hotp/alice?secret=JBSWY3DPEHPK3PXP"));
+        assert!(invalid("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=MD5"));
+        assert!(invalid("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&digits=5"));
+        assert!(invalid("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&digits=six"));
+        assert!(invalid("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&period=0"));
+    }
+}
@@ -0,0 +1,1259 @@
+//! Password generation, strength estimation and policy validation.
+//!
+//! This runs in Rust on every platform, so a password gets the same score on iOS
+//! and desktop. The estimator follows zxcvbn: it finds the patterns an attacker
+//! would try first (common passwords, dictionary words, keyboard walks, repeats,
+//! sequences and dates), takes the cheapest way to cover the password with them
+//! and converts the resulting number of guesses into a score with feedback.
+
+use std::{
+    collections::HashMap,
+    sync::OnceLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rand::{rngs::OsRng, seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, PasswordOptions, Result};
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const NUMBERS: &str = "0123456789";
+const SYMBOLS: &str = "!@#$%^&*()_+-=[]{}|;:,.<>?";
+/// Left out with `exclude_ambiguous` since they are easily confused when read.
+const AMBIGUOUS: &str = "0O1lI";
+const MAX_PASSWORD_LENGTH: u32 = 1024;
+const MAX_PASSPHRASE_WORDS: u32 = 64;
+
+/// 2048 words, so every word of a passphrase adds 11 bits.
+const WORDLIST: &str = include_str!("wordlists/bip39-english.txt");
+/// Ordered from most to least common.
+const COMMON_PASSWORDS: &str = include_str!("wordlists/common-passwords.txt");
+
+/// Longer passwords are only scored on their beginning, which is plenty to rate them.
+const MAX_ANALYSED_LENGTH: usize = 100;
+const MIN_TOKEN_LENGTH: usize = 3;
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_GUESSES_MULTI_CHAR: f64 = 50.0;
+const MIN_YEAR_SPACE: i32 = 20;
+const MAX_L33T_VARIANTS: usize = 16;
+
+const L33T_TABLE: &[(char, &str)] = &[
+    ('a', "4@"),
+    ('b', "8"),
+    ('c', "({[<"),
+    ('e', "3"),
+    ('g', "69"),
+    ('i', "1!|"),
+    ('l', "1|7"),
+    ('o', "0"),
+    ('s', "$5"),
+    ('t', "+7"),
+    ('x', "%"),
+    ('z', "2"),
+];
+
+const KEYBOARD_ROWS: [(&str, &str); 4] = [
+    ("`1234567890-=", "~!@#$%^&*()_+"),
+    ("qwertyuiop[]\\", "QWERTYUIOP{}|"),
+    ("asdfghjkl;'", "ASDFGHJKL:\""),
+    ("zxcvbnm,./", "ZXCVBNM<>?"),
+];
+/// Neighbours of a key on a staggered keyboard, as (row, column) offsets.
+const KEYBOARD_DIRECTIONS: [(isize, isize); 6] = [(0, -1), (0, 1), (-1, 0), (-1, 1), (1, -1), (1, 0)];
+
+/// Options for [`generate_passphrase`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PassphraseOptions {
+    pub word_count: u32,
+    pub separator: String,
+    pub capitalize: bool,
+    /// Appends a random digit to one of the words.
+    pub include_number: bool,
+}
+
+impl Default for PassphraseOptions {
+    fn default() -> Self {
+        Self {
+            word_count: 6,
+            separator: "-".to_string(),
+            capitalize: false,
+            include_number: false,
+        }
+    }
+}
+
+/// Generates a random password containing at least one character of every
+/// enabled class. `custom_characters` widen the alphabet but aren't required.
+pub fn generate(options: &PasswordOptions) -> Result<String> {
+    let classes: Vec<Vec<char>> = [
+        (options.include_lowercase, LOWERCASE),
+        (options.include_uppercase, UPPERCASE),
+        (options.include_numbers, NUMBERS),
+        (options.include_symbols, SYMBOLS),
+    ]
+    .into_iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, class)| {
+        class
+            .chars()
+            .filter(|c| !(options.exclude_ambiguous && AMBIGUOUS.contains(*c)))
+            .collect()
+    })
+    .collect();
+
+    let mut alphabet: Vec<char> = classes.iter().flatten().copied().collect();
+    for c in options.custom_characters.iter().flat_map(|custom| custom.chars()) {
+        if !alphabet.contains(&c) {
+            alphabet.push(c);
+        }
+    }
+
+    if alphabet.is_empty() {
+        return Err(Error::InvalidInput("No characters to generate a password from".to_string()));
+    }
+    if options.length == 0 || options.length > MAX_PASSWORD_LENGTH {
+        return Err(Error::InvalidInput(format!(
+            "Password length must be between 1 and {MAX_PASSWORD_LENGTH}"
+        )));
+    }
+    let length = options.length as usize;
+    if length < classes.len() {
+        return Err(Error::InvalidInput(format!(
+            "A password of {length} characters can't contain all {} required character classes",
+            classes.len()
+        )));
+    }
+
+    let mut rng = OsRng;
+    let mut password: Vec<char> = classes.iter().filter_map(|class| class.choose(&mut rng).copied()).collect();
+    while password.len() < length {
+        password.push(alphabet[rng.gen_range(0..alphabet.len())]);
+    }
+    password.shuffle(&mut rng);
+    Ok(password.into_iter().collect())
+}
+
+/// Generates a passphrase of random words from the bundled wordlist.
+pub fn generate_passphrase(options: &PassphraseOptions) -> Result<String> {
+    if options.word_count == 0 || options.word_count > MAX_PASSPHRASE_WORDS {
+        return Err(Error::InvalidInput(format!(
+            "A passphrase must have between 1 and {MAX_PASSPHRASE_WORDS} words"
+        )));
+    }
+
+    let words = wordlist();
+    let mut rng = OsRng;
+    let mut chosen: Vec<String> = (0..options.word_count)
+        .map(|_| {
+            let word = words[rng.gen_range(0..words.len())];
+            if options.capitalize {
+                capitalize(word)
+            } else {
+                word.to_string()
+            }
+        })
+        .collect();
+    if options.include_number {
+        let index = rng.gen_range(0..chosen.len());
+        chosen[index].push(char::from(b'0' + rng.gen_range(0..10u8)));
+    }
+    Ok(chosen.join(&options.separator))
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn wordlist() -> &'static [&'static str] {
+    static WORDS: OnceLock<Vec<&str>> = OnceLock::new();
+    WORDS.get_or_init(|| WORDLIST.lines().filter(|word| !word.is_empty()).collect())
+}
+
+fn ranked(list: &'static str) -> HashMap<String, usize> {
+    list.lines()
+        .filter(|word| !word.is_empty())
+        .enumerate()
+        .map(|(index, word)| (word.to_string(), index + 1))
+        .collect()
+}
+
+fn common_passwords() -> &'static HashMap<String, usize> {
+    static COMMON: OnceLock<HashMap<String, usize>> = OnceLock::new();
+    COMMON.get_or_init(|| ranked(COMMON_PASSWORDS))
+}
+
+fn dictionary_words() -> &'static HashMap<String, usize> {
+    // The wordlist is alphabetical rather than ordered by frequency, so every word
+    // counts as the average number of guesses into it.
+    static WORDS: OnceLock<HashMap<String, usize>> = OnceLock::new();
+    WORDS.get_or_init(|| {
+        let rank = wordlist().len() / 2;
+        wordlist().iter().map(|word| (word.to_string(), rank)).collect()
+    })
+}
+
+/// What kind of pattern a part of a password matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PatternKind {
+    CommonPassword,
+    DictionaryWord,
+    /// One of the user inputs passed to the estimator, like a name or email.
+    UserInput,
+    /// A walk over adjacent keyboard keys, like `qwerty` or `zxcvfr`.
+    Spatial,
+    Repeat,
+    Sequence,
+    Date,
+    /// Characters without a known pattern.
+    Bruteforce,
+}
+
+/// A part of a password and how many guesses it takes to find.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordPattern {
+    pub kind: PatternKind,
+    pub token: String,
+    /// Offset of the token in characters.
+    pub start: usize,
+    /// Offset in characters just past the token.
+    pub end: usize,
+    pub guesses_log10: f64,
+    /// Whether a dictionary word is spelled backwards.
+    pub reversed: bool,
+    /// Whether a dictionary word has letters swapped for look-alikes, like `p@ssw0rd`.
+    pub l33t: bool,
+}
+
+/// Estimated seconds to crack a password under different attacks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrackTimes {
+    /// Online attack against a rate-limited service, at 100 guesses per hour.
+    pub online_throttled: f64,
+    /// Online attack without rate limiting, at 10 guesses per second.
+    pub online_unthrottled: f64,
+    /// Offline attack on a slow hash like bcrypt, at 10⁴ guesses per second.
+    pub offline_slow_hash: f64,
+    /// Offline attack on a fast hash, at 10¹⁰ guesses per second.
+    pub offline_fast_hash: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Feedback {
+    /// Why the password is weak, if it is.
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordStrength {
+    /// From 0 (too guessable) to 4 (very unguessable).
+    pub score: u8,
+    /// Estimated number of guesses to find the password, as a base 10 logarithm.
+    pub guesses_log10: f64,
+    /// The same estimate in bits.
+    pub entropy_bits: f64,
+    pub crack_times: CrackTimes,
+    pub feedback: Feedback,
+    /// The patterns the estimate is based on, in password order.
+    pub patterns: Vec<PasswordPattern>,
+}
+
+#[derive(Debug, Clone)]
+struct Match {
+    kind: PatternKind,
+    start: usize,
+    end: usize,
+    guesses_log10: f64,
+    reversed: bool,
+    l33t: bool,
+    /// Feedback details: direction changes of a keyboard walk, the length of a
+    /// repeated unit, or whether a date is only a year.
+    turns: usize,
+    base_length: usize,
+    year_only: bool,
+}
+
+impl Match {
+    fn new(kind: PatternKind, start: usize, end: usize, guesses: f64) -> Self {
+        Self {
+            kind,
+            start,
+            end,
+            guesses_log10: guesses.max(1.0).log10(),
+            reversed: false,
+            l33t: false,
+            turns: 0,
+            base_length: 0,
+            year_only: false,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+/// Estimates how hard `password` is to guess.
+///
+/// `user_inputs` are values an attacker may know about the user, such as their
+/// name or email; passwords built from them score lower.
+pub fn estimate_strength(password: &str, user_inputs: &[String]) -> PasswordStrength {
+    let chars: Vec<char> = password.chars().take(MAX_ANALYSED_LENGTH).collect();
+    let user_inputs = user_dictionary(user_inputs);
+    let (guesses_log10, sequence) = most_guessable(&chars, &user_inputs);
+
+    let score = match guesses_log10 {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    };
+    let seconds = |guesses_per_second: f64| 10f64.powf(guesses_log10 - guesses_per_second.log10());
+
+    PasswordStrength {
+        score,
+        guesses_log10,
+        entropy_bits: guesses_log10 * std::f64::consts::LOG2_10,
+        crack_times: CrackTimes {
+            online_throttled: seconds(100.0 / 3600.0),
+            online_unthrottled: seconds(10.0),
+            offline_slow_hash: seconds(1e4),
+            offline_fast_hash: seconds(1e10),
+        },
+        feedback: feedback(score, &chars, &sequence),
+        patterns: sequence
+            .iter()
+            .map(|m| PasswordPattern {
+                kind: m.kind,
+                token: chars[m.start..m.end].iter().collect(),
+                start: m.start,
+                end: m.end,
+                guesses_log10: m.guesses_log10,
+                reversed: m.reversed,
+                l33t: m.l33t,
+            })
+            .collect(),
+    }
+}
+
+fn user_dictionary(user_inputs: &[String]) -> HashMap<String, usize> {
+    let mut dictionary = HashMap::new();
+    let words = user_inputs.iter().flat_map(|input| {
+        // Also match the parts of inputs like `jane.doe@example.com`.
+        let input = input.trim().to_lowercase();
+        let mut parts: Vec<String> = input
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|part| !part.is_empty())
+            .map(str::to_string)
+            .collect();
+        parts.insert(0, input);
+        parts
+    });
+    for word in words {
+        let rank = dictionary.len() + 1;
+        if word.chars().count() >= MIN_TOKEN_LENGTH {
+            dictionary.entry(word).or_insert(rank);
+        }
+    }
+    dictionary
+}
+
+/// Where the best cover of a prefix starts its last pattern, and which pattern;
+/// `None` for bruteforce.
+type Step<'a> = Option<(usize, Option<&'a Match>)>;
+
+/// Finds the sequence of non-overlapping patterns covering `chars` that takes the
+/// fewest guesses, filling gaps with bruteforce. Returns the guesses as a base 10
+/// logarithm.
+fn most_guessable(chars: &[char], user_inputs: &HashMap<String, usize>) -> (f64, Vec<Match>) {
+    let n = chars.len();
+    if n == 0 {
+        return (0.0, Vec::new());
+    }
+
+    let matches = find_matches(chars, user_inputs);
+    let mut by_end: Vec<Vec<&Match>> = vec![Vec::new(); n + 1];
+    for m in &matches {
+        by_end[m.end].push(m);
+    }
+
+    let floor = |start: usize, end: usize| {
+        if end - start == n {
+            0.0
+        } else if end - start == 1 {
+            MIN_GUESSES_SINGLE_CHAR.log10()
+        } else {
+            MIN_GUESSES_MULTI_CHAR.log10()
+        }
+    };
+
+    // best[k][l]: fewest guesses (log10) covering the first k characters with l
+    // patterns, and the step that got there.
+    let mut best = vec![vec![f64::INFINITY; n + 1]; n + 1];
+    let mut back: Vec<Vec<Step>> = vec![vec![None; n + 1]; n + 1];
+    best[0][0] = 0.0;
+    for end in 1..=n {
+        for start in 0..end {
+            let bruteforce = ((end - start) as f64 * BRUTEFORCE_CARDINALITY.log10()).max(floor(start, end));
+            let steps = std::iter::once((None, bruteforce)).chain(
+                by_end[end]
+                    .iter()
+                    .filter(|m| m.start == start)
+                    .map(|m| (Some(*m), m.guesses_log10.max(floor(start, end)))),
+            );
+            for (m, guesses) in steps {
+                for l in 0..end {
+                    let candidate = best[start][l] + guesses;
+                    if candidate < best[end][l + 1] {
+                        best[end][l + 1] = candidate;
+                        back[end][l + 1] = Some((start, m));
+                    }
+                }
+            }
+        }
+    }
+
+    // As in zxcvbn, an attacker has to try the patterns in every order, hence l!.
+    let mut log_factorial = 0.0;
+    let (mut count, mut guesses_log10) = (0, f64::INFINITY);
+    for (l, guesses) in best[n].iter().enumerate().skip(1) {
+        log_factorial += (l as f64).log10();
+        let total = guesses + log_factorial;
+        if total < guesses_log10 {
+            (count, guesses_log10) = (l, total);
+        }
+    }
+
+    let mut sequence = Vec::with_capacity(count);
+    let mut end = n;
+    while let Some((start, m)) = back[end][count] {
+        sequence.push(match m {
+            Some(m) => m.clone(),
+            None => Match::new(
+                PatternKind::Bruteforce,
+                start,
+                end,
+                BRUTEFORCE_CARDINALITY.powi((end - start) as i32),
+            ),
+        });
+        end = start;
+        count -= 1;
+    }
+    sequence.reverse();
+    (guesses_log10, sequence)
+}
+
+fn find_matches(chars: &[char], user_inputs: &HashMap<String, usize>) -> Vec<Match> {
+    let mut matches = dictionary_matches(chars, user_inputs);
+    matches.extend(spatial_matches(chars));
+    matches.extend(repeat_matches(chars, user_inputs));
+    matches.extend(sequence_matches(chars));
+    matches.extend(date_matches(chars));
+    matches
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    (1..=k.min(n - k)).fold(1.0, |result, i| result * (n + 1 - i) as f64 / i as f64)
+}
+
+/// Guesses spent on trying `changed` of `changed + unchanged` positions in every
+/// combination, as for capitalization or look-alike substitutions.
+fn variations(changed: usize, unchanged: usize) -> f64 {
+    if changed == 0 || unchanged == 0 {
+        return 2.0;
+    }
+    (1..=changed.min(unchanged))
+        .map(|i| binomial(changed + unchanged, i))
+        .sum()
+}
+
+fn uppercase_variations(token: &[char]) -> f64 {
+    let upper = token.iter().filter(|c| c.is_uppercase()).count();
+    let lower = token.iter().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        return 1.0;
+    }
+    let first_only = token.first().is_some_and(|c| c.is_uppercase()) && upper == 1;
+    let last_only = token.last().is_some_and(|c| c.is_uppercase()) && upper == 1;
+    if lower == 0 || first_only || last_only {
+        return 2.0;
+    }
+    variations(upper, lower)
+}
+
+/// Readings of `token` with look-alike characters replaced by letters, along
+/// with the substitutions made.
+fn unleet(token: &[char]) -> Vec<(String, Vec<(char, char)>)> {
+    let mut variants: Vec<(String, Vec<(char, char)>)> = vec![(String::new(), Vec::new())];
+    for &c in token {
+        let letters: Vec<char> = L33T_TABLE
+            .iter()
+            .filter(|(_, subs)| subs.contains(c))
+            .map(|(letter, _)| *letter)
+            .collect();
+        if letters.is_empty() {
+            variants.iter_mut().for_each(|(word, _)| word.push(c));
+            continue;
+        }
+        variants = variants
+            .into_iter()
+            .flat_map(|(word, subs)| {
+                letters.iter().map(move |&letter| {
+                    let mut word = word.clone();
+                    let mut subs = subs.clone();
+                    word.push(letter);
+                    if !subs.contains(&(c, letter)) {
+                        subs.push((c, letter));
+                    }
+                    (word, subs)
+                })
+            })
+            .take(MAX_L33T_VARIANTS)
+            .collect();
+    }
+    variants.retain(|(_, subs)| !subs.is_empty());
+    variants
+}
+
+fn l33t_variations(token: &[char], subs: &[(char, char)]) -> f64 {
+    subs.iter()
+        .map(|&(sub, letter)| {
+            let changed = token.iter().filter(|&&c| c == sub).count();
+            let unchanged = token.iter().filter(|&&c| c == letter).count();
+            variations(changed, unchanged)
+        })
+        .product()
+}
+
+fn dictionary_matches(chars: &[char], user_inputs: &HashMap<String, usize>) -> Vec<Match> {
+    let lower: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    let dictionaries = [
+        (PatternKind::CommonPassword, common_passwords()),
+        (PatternKind::DictionaryWord, dictionary_words()),
+        (PatternKind::UserInput, user_inputs),
+    ];
+    let lookup = |word: &str| {
+        dictionaries
+            .iter()
+            .filter_map(|(kind, dictionary)| dictionary.get(word).map(|rank| (*kind, *rank as f64)))
+            .collect::<Vec<_>>()
+    };
+
+    let longest_word = dictionaries
+        .iter()
+        .flat_map(|(_, dictionary)| dictionary.keys())
+        .map(|word| word.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut matches = Vec::new();
+    for start in 0..lower.len() {
+        for end in start + MIN_TOKEN_LENGTH..=lower.len().min(start + longest_word) {
+            let token = &lower[start..end];
+            let uppercase = uppercase_variations(&chars[start..end]);
+            let forward: String = token.iter().collect();
+            let backward: String = token.iter().rev().collect();
+
+            for (kind, rank) in lookup(&forward) {
+                matches.push(Match::new(kind, start, end, rank * uppercase));
+            }
+            if backward != forward {
+                for (kind, rank) in lookup(&backward) {
+                    matches.push(Match {
+                        reversed: true,
+                        ..Match::new(kind, start, end, rank * uppercase * 2.0)
+                    });
+                }
+            }
+            for (word, subs) in unleet(token) {
+                for (kind, rank) in lookup(&word) {
+                    matches.push(Match {
+                        l33t: true,
+                        ..Match::new(kind, start, end, rank * uppercase * l33t_variations(token, &subs))
+                    });
+                }
+            }
+        }
+    }
+    matches
+}
+
+fn keyboard_position(c: char) -> Option<(usize, usize, bool)> {
+    KEYBOARD_ROWS.iter().enumerate().find_map(|(row, (plain, shifted))| {
+        plain
+            .chars()
+            .position(|key| key == c)
+            .map(|column| (row, column, false))
+            .or_else(|| shifted.chars().position(|key| key == c).map(|column| (row, column, true)))
+    })
+}
+
+fn keyboard_direction(from: (usize, usize), to: (usize, usize)) -> Option<usize> {
+    let offset = (to.0 as isize - from.0 as isize, to.1 as isize - from.1 as isize);
+    KEYBOARD_DIRECTIONS.iter().position(|direction| *direction == offset)
+}
+
+fn spatial_matches(chars: &[char]) -> Vec<Match> {
+    let keys: usize = KEYBOARD_ROWS.iter().map(|(plain, _)| plain.len()).sum();
+    let average_degree: f64 = 4.6;
+
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while start + 1 < chars.len() {
+        let Some(first) = keyboard_position(chars[start]) else {
+            start += 1;
+            continue;
+        };
+        let mut shifted = usize::from(first.2);
+        let (mut turns, mut last_direction, mut position) = (0, None, first);
+        let mut end = start + 1;
+        while let Some(next) = chars.get(end).and_then(|c| keyboard_position(*c)) {
+            let Some(direction) = keyboard_direction((position.0, position.1), (next.0, next.1)) else {
+                break;
+            };
+            if last_direction != Some(direction) {
+                turns += 1;
+                last_direction = Some(direction);
+            }
+            shifted += usize::from(next.2);
+            position = next;
+            end += 1;
+        }
+
+        let length = end - start;
+        if length >= MIN_TOKEN_LENGTH {
+            let mut guesses = 0.0;
+            for i in 2..=length {
+                for j in 1..=turns.min(i - 1) {
+                    guesses += binomial(i - 1, j - 1) * keys as f64 * average_degree.powi(j as i32);
+                }
+            }
+            if shifted > 0 {
+                guesses *= variations(shifted, length - shifted);
+            }
+            matches.push(Match {
+                turns,
+                ..Match::new(PatternKind::Spatial, start, end, guesses)
+            });
+        }
+        start = end.max(start + 1);
+    }
+    matches
+}
+
+fn repeat_matches(chars: &[char], user_inputs: &HashMap<String, usize>) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        // The unit repeated over the most characters, the shortest one on ties.
+        let mut found: Option<(usize, usize)> = None;
+        for base_length in 1..=(chars.len() - start) / 2 {
+            let base = &chars[start..start + base_length];
+            let repeats = chars[start..]
+                .chunks_exact(base_length)
+                .take_while(|chunk| *chunk == base)
+                .count();
+            if repeats >= 2 && found.map_or(true, |(length, count)| base_length * repeats > length * count) {
+                found = Some((base_length, repeats));
+            }
+        }
+
+        let Some((base_length, repeats)) = found else {
+            start += 1;
+            continue;
+        };
+        let end = start + base_length * repeats;
+        let (base_guesses, _) = most_guessable(&chars[start..start + base_length], user_inputs);
+        matches.push(Match {
+            base_length,
+            ..Match::new(
+                PatternKind::Repeat,
+                start,
+                end,
+                10f64.powf(base_guesses) * repeats as f64,
+            )
+        });
+        start = end;
+    }
+    matches
+}
+
+fn sequence_matches(chars: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while start + 1 < chars.len() {
+        let delta = chars[start + 1] as i64 - chars[start] as i64;
+        let mut end = start + 2;
+        while end < chars.len() && chars[end] as i64 - chars[end - 1] as i64 == delta {
+            end += 1;
+        }
+
+        if end - start >= MIN_TOKEN_LENGTH && (1..=5).contains(&delta.abs()) {
+            let first = chars[start];
+            let mut base = if "aAzZ019".contains(first) {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            if delta < 0 {
+                base *= 2.0;
+            }
+            matches.push(Match::new(
+                PatternKind::Sequence,
+                start,
+                end,
+                base * (end - start) as f64,
+            ));
+        }
+        start = end - 1;
+    }
+    matches
+}
+
+fn current_year() -> i32 {
+    const SECONDS_PER_YEAR: u64 = 31_556_952;
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(2024, |elapsed| 1970 + (elapsed.as_secs() / SECONDS_PER_YEAR) as i32)
+}
+
+fn year_guesses(year: i32) -> f64 {
+    (year - current_year()).abs().max(MIN_YEAR_SPACE) as f64
+}
+
+/// Reads a day, month and year from three numbers in any of the common orders.
+fn date_year(parts: [&str; 3]) -> Option<i32> {
+    let number = |part: &str| part.parse::<i32>().ok();
+    let year = |part: &str| {
+        let value = number(part)?;
+        match part.len() {
+            2 => Some(if value > 50 { 1900 + value } else { 2000 + value }),
+            4 if (1000..=2050).contains(&value) => Some(value),
+            _ => None,
+        }
+    };
+    let is_date = |day: &str, month: &str| {
+        number(day).is_some_and(|day| (1..=31).contains(&day))
+            && number(month).is_some_and(|month| (1..=12).contains(&month))
+    };
+
+    let [first, middle, last] = parts;
+    let year_last = (is_date(first, middle) || is_date(middle, first))
+        .then(|| year(last))
+        .flatten();
+    let year_first = (is_date(middle, last) || is_date(last, middle))
+        .then(|| year(first))
+        .flatten();
+    // A four digit year is unambiguous; otherwise prefer day-month-year.
+    match (first.len(), year_first, year_last) {
+        (4, Some(year), _) => Some(year),
+        (_, _, Some(year)) => Some(year),
+        (_, year, None) => year,
+    }
+}
+
+fn date_matches(chars: &[char]) -> Vec<Match> {
+    // Split points for dates written without separators, by length.
+    const SPLITS: [&[(usize, usize)]; 5] = [
+        &[(1, 2), (2, 3)],
+        &[(1, 3), (2, 3)],
+        &[(1, 2), (2, 4), (4, 5)],
+        &[(1, 3), (2, 3), (4, 5), (4, 6)],
+        &[(2, 4), (4, 6)],
+    ];
+    const SEPARATORS: &str = " -/._\\";
+
+    let mut matches = Vec::new();
+    let digits_at = |start: usize, max: usize| {
+        chars[start..]
+            .iter()
+            .take(max)
+            .take_while(|c| c.is_ascii_digit())
+            .count()
+    };
+
+    for start in 0..chars.len() {
+        if start + 4 <= chars.len() && digits_at(start, 4) == 4 {
+            let token: String = chars[start..start + 4].iter().collect();
+            if let Ok(year) = token.parse::<i32>() {
+                if (1900..=2099).contains(&year) {
+                    matches.push(Match {
+                        year_only: true,
+                        ..Match::new(PatternKind::Date, start, start + 4, year_guesses(year))
+                    });
+                }
+            }
+        }
+
+        for length in 4..=8 {
+            let end = start + length;
+            if end > chars.len() || digits_at(start, length) < length {
+                break;
+            }
+            let token: String = chars[start..end].iter().collect();
+            let years = SPLITS[length - 4]
+                .iter()
+                .filter_map(|&(k, l)| date_year([&token[..k], &token[k..l], &token[l..]]));
+            if let Some(guesses) = years.map(|year| 365.0 * year_guesses(year)).reduce(f64::min) {
+                matches.push(Match::new(PatternKind::Date, start, end, guesses));
+            }
+        }
+
+        // Dates with separators, like 1/2/99 or 2024-06-30.
+        let first = digits_at(start, 4);
+        let Some(&separator) = chars.get(start + first) else {
+            continue;
+        };
+        if first == 0 || !SEPARATORS.contains(separator) {
+            continue;
+        }
+        let middle_start = start + first + 1;
+        let middle = digits_at(middle_start.min(chars.len()), 2);
+        if middle == 0 || chars.get(middle_start + middle) != Some(&separator) {
+            continue;
+        }
+        let last_start = middle_start + middle + 1;
+        let last = digits_at(last_start.min(chars.len()), 4);
+        let end = last_start + last;
+        let part = |from: usize, to: usize| chars[from..to].iter().collect::<String>();
+        let parts = [
+            part(start, start + first),
+            part(middle_start, middle_start + middle),
+            part(last_start, end),
+        ];
+        if last > 0 {
+            if let Some(year) = date_year([&parts[0], &parts[1], &parts[2]]) {
+                matches.push(Match::new(PatternKind::Date, start, end, 4.0 * 365.0 * year_guesses(year)));
+            }
+        }
+    }
+    matches
+}
+
+fn feedback(score: u8, chars: &[char], sequence: &[Match]) -> Feedback {
+    if chars.is_empty() {
+        return Feedback {
+            warning: None,
+            suggestions: vec![
+                "Use a few words, avoid common phrases".to_string(),
+                "No need for symbols, digits, or uppercase letters".to_string(),
+            ],
+        };
+    }
+    if score > 2 {
+        return Feedback::default();
+    }
+
+    let mut suggestions = vec!["Add another word or two. Uncommon words are better.".to_string()];
+    let Some(longest) = sequence.iter().max_by_key(|m| m.len()) else {
+        return Feedback {
+            warning: None,
+            suggestions,
+        };
+    };
+
+    let warning = match longest.kind {
+        PatternKind::CommonPassword | PatternKind::DictionaryWord | PatternKind::UserInput => {
+            let token = &chars[longest.start..longest.end];
+            if token.first().is_some_and(|c| c.is_uppercase()) && token.iter().skip(1).all(|c| !c.is_uppercase()) {
+                suggestions.push("Capitalization doesn't help very much".to_string());
+            } else if token.iter().any(|c| c.is_uppercase()) && !token.iter().any(|c| c.is_lowercase()) {
+                suggestions.push("All-uppercase is almost as easy to guess as all-lowercase".to_string());
+            }
+            if longest.reversed {
+                suggestions.push("Reversed words aren't much harder to guess".to_string());
+            }
+            if longest.l33t {
+                suggestions
+                    .push("Predictable substitutions like '@' instead of 'a' don't help very much".to_string());
+            }
+
+            let rank = 10f64.powf(longest.guesses_log10);
+            match longest.kind {
+                PatternKind::CommonPassword if sequence.len() == 1 && rank <= 10.0 => {
+                    Some("This is a top-10 common password")
+                }
+                PatternKind::CommonPassword if sequence.len() == 1 && rank <= 100.0 => {
+                    Some("This is a top-100 common password")
+                }
+                PatternKind::CommonPassword if sequence.len() == 1 => Some("This is a very common password"),
+                PatternKind::CommonPassword => Some("This is similar to a commonly used password"),
+                PatternKind::DictionaryWord if sequence.len() == 1 => Some("A word by itself is easy to guess"),
+                PatternKind::UserInput => Some("Avoid personal information like names or email addresses"),
+                _ => None,
+            }
+        }
+        PatternKind::Spatial => {
+            suggestions.push("Use a longer keyboard pattern with more turns".to_string());
+            Some(if longest.turns == 1 {
+                "Straight rows of keys are easy to guess"
+            } else {
+                "Short keyboard patterns are easy to guess"
+            })
+        }
+        PatternKind::Repeat => {
+            suggestions.push("Avoid repeated words and characters".to_string());
+            Some(if longest.base_length == 1 {
+                "Repeats like \"aaa\" are easy to guess"
+            } else {
+                "Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\""
+            })
+        }
+        PatternKind::Sequence => {
+            suggestions.push("Avoid sequences".to_string());
+            Some("Sequences like abc or 6543 are easy to guess")
+        }
+        PatternKind::Date if longest.year_only => {
+            suggestions.push("Avoid recent years and years that are associated with you".to_string());
+            Some("Recent years are easy to guess")
+        }
+        PatternKind::Date => {
+            suggestions.push("Avoid dates and years that are associated with you".to_string());
+            Some("Dates are often easy to guess")
+        }
+        PatternKind::Bruteforce => None,
+    };
+
+    Feedback {
+        warning: warning.map(str::to_string),
+        suggestions,
+    }
+}
+
+/// Requirements for a password chosen by a user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PasswordPolicy {
+    /// Minimum length in characters.
+    pub min_length: u32,
+    pub max_length: Option<u32>,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_numbers: bool,
+    pub require_symbols: bool,
+    /// Longest run of the same character, like `3` to reject `aaaa`.
+    pub max_repeated_characters: Option<u32>,
+    /// Minimum [`PasswordStrength::score`], from 0 to 4.
+    pub min_score: Option<u8>,
+    /// Rejects passwords from the bundled list of common passwords, in any
+    /// capitalization and with look-alike substitutions.
+    pub reject_common: bool,
+    /// Values the password must not contain, like the user's name or email. They
+    /// also lower the strength score.
+    pub user_inputs: Vec<String>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: None,
+            require_uppercase: false,
+            require_lowercase: false,
+            require_numbers: false,
+            require_symbols: false,
+            max_repeated_characters: None,
+            min_score: None,
+            reject_common: true,
+            user_inputs: Vec::new(),
+        }
+    }
+}
+
+/// The rule of a [`PasswordPolicy`] a password breaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PolicyRule {
+    MinLength,
+    MaxLength,
+    Uppercase,
+    Lowercase,
+    Number,
+    Symbol,
+    RepeatedCharacters,
+    MinScore,
+    Common,
+    UserInput,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyViolation {
+    pub rule: PolicyRule,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyValidation {
+    pub valid: bool,
+    pub violations: Vec<PolicyViolation>,
+    pub strength: PasswordStrength,
+}
+
+/// Checks `password` against every rule of `policy` and reports all that fail.
+///
+/// Fails with `InvalidInput` if the policy itself is inconsistent.
+pub fn validate(password: &str, policy: &PasswordPolicy) -> Result<PolicyValidation> {
+    if policy.min_score.is_some_and(|score| score > 4) {
+        return Err(Error::InvalidInput("The minimum score must be between 0 and 4".to_string()));
+    }
+    if policy.max_length.is_some_and(|max| max < policy.min_length) {
+        return Err(Error::InvalidInput(
+            "The maximum length must not be less than the minimum length".to_string(),
+        ));
+    }
+
+    let chars: Vec<char> = password.chars().collect();
+    let strength = estimate_strength(password, &policy.user_inputs);
+    let mut violations = Vec::new();
+    let mut violation = |rule, message: String| violations.push(PolicyViolation { rule, message });
+
+    let length = chars.len() as u32;
+    if length < policy.min_length {
+        violation(
+            PolicyRule::MinLength,
+            format!("Password must be at least {} characters", policy.min_length),
+        );
+    }
+    if let Some(max) = policy.max_length.filter(|max| length > *max) {
+        violation(PolicyRule::MaxLength, format!("Password must be at most {max} characters"));
+    }
+
+    let classes = [
+        (policy.require_uppercase, PolicyRule::Uppercase, "an uppercase letter", char::is_uppercase as fn(char) -> bool),
+        (policy.require_lowercase, PolicyRule::Lowercase, "a lowercase letter", char::is_lowercase),
+        (policy.require_numbers, PolicyRule::Number, "a number", char::is_numeric),
+        (policy.require_symbols, PolicyRule::Symbol, "a symbol", |c: char| !c.is_alphanumeric()),
+    ];
+    for (required, rule, description, is_class) in classes {
+        if required && !chars.iter().any(|c| is_class(*c)) {
+            violation(rule, format!("Password must contain {description}"));
+        }
+    }
+
+    if let Some(max) = policy.max_repeated_characters {
+        let longest_run = chars
+            .chunk_by(|a, b| a == b)
+            .map(|run| run.len() as u32)
+            .max()
+            .unwrap_or(0);
+        if longest_run > max {
+            violation(
+                PolicyRule::RepeatedCharacters,
+                format!("Password must not repeat a character more than {max} times in a row"),
+            );
+        }
+    }
+
+    if let Some(min_score) = policy.min_score.filter(|min| strength.score < *min) {
+        violation(
+            PolicyRule::MinScore,
+            format!("Password is too easy to guess, its strength is {} of the required {min_score}", strength.score),
+        );
+    }
+
+    if policy.reject_common {
+        let analysed = &chars[..chars.len().min(MAX_ANALYSED_LENGTH)];
+        let common = dictionary_matches(analysed, &HashMap::new()).iter().any(|m| {
+            m.kind == PatternKind::CommonPassword && m.start == 0 && m.end == chars.len() && !m.reversed
+        });
+        if common {
+            violation(PolicyRule::Common, "Password is too common".to_string());
+        }
+    }
+
+    let lowercase = password.to_lowercase();
+    let contains_input = policy.user_inputs.iter().any(|input| {
+        let input = input.trim().to_lowercase();
+        input.chars().count() >= MIN_TOKEN_LENGTH && lowercase.contains(&input)
+    });
+    if contains_input {
+        violation(PolicyRule::UserInput, "Password must not contain personal information".to_string());
+    }
+
+    Ok(PolicyValidation {
+        valid: violations.is_empty(),
+        violations,
+        strength,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn only(lowercase: bool, uppercase: bool, numbers: bool, symbols: bool) -> PasswordOptions {
+        PasswordOptions {
+            length: 12,
+            include_lowercase: lowercase,
+            include_uppercase: uppercase,
+            include_numbers: numbers,
+            include_symbols: symbols,
+            exclude_ambiguous: false,
+            custom_characters: None,
+        }
+    }
+
+    #[test]
+    fn passwords_have_the_requested_length_and_every_class() {
+        for length in [4, 5, 16, 64, MAX_PASSWORD_LENGTH] {
+            for _ in 0..50 {
+                let password = generate(&PasswordOptions {
+                    length,
+                    ..PasswordOptions::default()
+                })
+                .unwrap();
+                assert_eq!(password.chars().count(), length as usize);
+                for class in [LOWERCASE, UPPERCASE, NUMBERS, SYMBOLS] {
+                    assert!(password.chars().any(|c| class.contains(c)), "{password} lacks one of {class}");
+                }
+                assert!(!password.chars().any(|c| AMBIGUOUS.contains(c)), "{password} is ambiguous");
+            }
+        }
+    }
+
+    #[test]
+    fn passwords_only_use_the_enabled_classes() {
+        for _ in 0..50 {
+            let password = generate(&only(false, false, true, false)).unwrap();
+            assert!(password.chars().all(|c| NUMBERS.contains(c)), "{password}");
+
+            let password = generate(&only(true, false, false, true)).unwrap();
+            assert!(password.chars().all(|c| LOWERCASE.contains(c) || SYMBOLS.contains(c)), "{password}");
+            assert!(password.chars().any(|c| LOWERCASE.contains(c)) && password.chars().any(|c| SYMBOLS.contains(c)));
+
+            let password = generate(&PasswordOptions {
+                custom_characters: Some("äö".to_string()),
+                ..only(false, false, false, false)
+            })
+            .unwrap();
+            assert!(password.chars().all(|c| c == 'ä' || c == 'ö'), "{password}");
+        }
+    }
+
+    #[test]
+    fn impossible_passwords_are_rejected() {
+        let invalid = |options: PasswordOptions| matches!(generate(&options), Err(Error::InvalidInput(_)));
+        assert!(invalid(only(false, false, false, false)));
+        assert!(invalid(PasswordOptions {
+            length: 0,
+            ..PasswordOptions::default()
+        }));
+        assert!(invalid(PasswordOptions {
+            length: MAX_PASSWORD_LENGTH + 1,
+            ..PasswordOptions::default()
+        }));
+        assert!(invalid(PasswordOptions {
+            length: 3,
+            ..PasswordOptions::default()
+        }));
+    }
+
+    #[test]
+    fn passphrases_are_made_of_wordlist_words() {
+        assert_eq!(wordlist().len(), 2048);
+        let options = PassphraseOptions {
+            word_count: 5,
+            separator: " ".to_string(),
+            capitalize: true,
+            include_number: true,
+        };
+        for _ in 0..50 {
+            let passphrase = generate_passphrase(&options).unwrap();
+            let words: Vec<&str> = passphrase.split(' ').collect();
+            assert_eq!(words.len(), 5);
+            assert_eq!(passphrase.chars().filter(char::is_ascii_digit).count(), 1);
+            for word in words {
+                assert!(word.starts_with(|c: char| c.is_ascii_uppercase()), "{passphrase}");
+                let word = word.trim_end_matches(|c: char| c.is_ascii_digit()).to_lowercase();
+                assert!(wordlist().contains(&word.as_str()), "{word} is not in the wordlist");
+            }
+        }
+        assert!(generate_passphrase(&PassphraseOptions {
+            word_count: 0,
+            ..PassphraseOptions::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn common_and_patterned_passwords_score_low() {
+        for password in ["password", "123456", "qwerty", "aaaaaaaa", "abcdef", "P@ssw0rd"] {
+            let strength = estimate_strength(password, &[]);
+            assert!(strength.score <= 1, "{password} scored {}", strength.score);
+        }
+        let strength = estimate_strength("password", &[]);
+        assert_eq!(strength.patterns[0].kind, PatternKind::CommonPassword);
+        assert!((strength.entropy_bits - strength.guesses_log10 * std::f64::consts::LOG2_10).abs() < 1e-9);
+
+        assert_eq!(estimate_strength("correct-horse-battery-staple-x7", &[]).score, 4);
+        let with_name = estimate_strength("jonathan1987", &["Jonathan".to_string()]);
+        assert!(with_name.patterns.iter().any(|m| m.kind == PatternKind::UserInput));
+    }
+
+    #[test]
+    fn policies_report_every_violation() {
+        let policy = PasswordPolicy {
+            min_length: 10,
+            max_length: Some(12),
+            require_uppercase: true,
+            require_numbers: true,
+            require_symbols: true,
+            max_repeated_characters: Some(2),
+            user_inputs: vec!["alice".to_string()],
+            ..PasswordPolicy::default()
+        };
+        let rules = |password: &str| -> Vec<PolicyRule> {
+            validate(password, &policy)
+                .unwrap()
+                .violations
+                .into_iter()
+                .map(|violation| violation.rule)
+                .collect()
+        };
+        assert_eq!(
+            rules("aaalice"),
+            [
+                PolicyRule::MinLength,
+                PolicyRule::Uppercase,
+                PolicyRule::Number,
+                PolicyRule::Symbol,
+                PolicyRule::RepeatedCharacters,
+                PolicyRule::UserInput,
+            ]
+        );
+        assert_eq!(rules("Tr0ub4dor&3x9"), [PolicyRule::MaxLength]);
+        assert!(validate("Tr0ub4dor&3", &policy).unwrap().valid);
+        assert_eq!(
+            validate("password", &PasswordPolicy::default()).unwrap().violations[0].rule,
+            PolicyRule::Common
+        );
+
+        let inconsistent = PasswordPolicy {
+            min_length: 10,
+            max_length: Some(8),
+            ..PasswordPolicy::default()
+        };
+        assert!(validate("whatever", &inconsistent).is_err());
+    }
+}
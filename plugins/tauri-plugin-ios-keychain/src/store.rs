@@ -0,0 +1,344 @@
+//! Encrypted file persistence for the desktop keychain.
+//!
+//! Items are kept as JSON encrypted with ChaCha20-Poly1305 in `keychain.dat`. The
+//! key is generated on first use and stored next to it in `keychain.key`, readable
+//! only by the current user. This keeps secrets out of plain-text backups and logs;
+//! it does not protect against someone who can read the user's files.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::{Accessible, AuthenticationPolicy, Error, InternetPasswordItem, Result, SecureValue};
+
+const MAGIC: &[u8; 4] = b"TKC1";
+const KEY_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 12;
+
+/// A generic password, identified by service, account and access group like its
+/// iOS counterpart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GenericEntry {
+    pub(crate) service: String,
+    pub(crate) account: String,
+    pub(crate) access_group: Option<String>,
+    pub(crate) value: StoredValue,
+    pub(crate) accessible: Accessible,
+    pub(crate) synchronizable: bool,
+    pub(crate) label: Option<String>,
+    pub(crate) comment: Option<String>,
+}
+
+impl GenericEntry {
+    /// Whether the entry is selected by a query. A query without an access group
+    /// matches entries of every group.
+    pub(crate) fn matches(&self, service: &str, account: &str, access_group: Option<&str>) -> bool {
+        self.service == service
+            && self.account == account
+            && access_group.map_or(true, |group| self.access_group.as_deref() == Some(group))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum StoredValue {
+    Plain(String),
+    Secure {
+        value: SecureValue,
+        authentication: AuthenticationPolicy,
+        validity_duration: Option<u32>,
+    },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Items {
+    pub(crate) generic: Vec<GenericEntry>,
+    pub(crate) internet: Vec<InternetPasswordItem>,
+}
+
+#[derive(Default)]
+struct Cache {
+    loaded: bool,
+    items: Items,
+}
+
+pub(crate) struct FileStore {
+    dir: PathBuf,
+    cache: Mutex<Cache>,
+}
+
+impl FileStore {
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            cache: Mutex::default(),
+        }
+    }
+
+    fn data_path(&self) -> PathBuf {
+        self.dir.join("keychain.dat")
+    }
+
+    fn key_path(&self) -> PathBuf {
+        self.dir.join("keychain.key")
+    }
+
+    /// Locks the items, loading them from disk on first use.
+    fn cache(&self) -> Result<MutexGuard<'_, Cache>> {
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if !cache.loaded {
+            cache.items = self.load()?;
+            cache.loaded = true;
+        }
+        Ok(cache)
+    }
+
+    pub(crate) fn read<T>(&self, f: impl FnOnce(&Items) -> T) -> Result<T> {
+        Ok(f(&self.cache()?.items))
+    }
+
+    /// Applies `f` and persists the result. Nothing is changed if `f` or saving fails.
+    pub(crate) fn write<T>(&self, f: impl FnOnce(&mut Items) -> Result<T>) -> Result<T> {
+        let mut cache = self.cache()?;
+        let mut updated = cache.items.clone();
+        let result = f(&mut updated)?;
+        self.save(&updated)?;
+        cache.items = updated;
+        Ok(result)
+    }
+
+    fn cipher(&self) -> Result<ChaCha20Poly1305> {
+        let path = self.key_path();
+        let key = match fs::read(&path) {
+            Ok(key) if key.len() == KEY_LENGTH => key,
+            Ok(_) => {
+                return Err(Error::OperationFailed(format!("Invalid keychain key in {}", path.display())));
+            }
+            // Items encrypted with the lost key can't be read back, and a new key would
+            // hide that until the next save overwrote them.
+            Err(e) if e.kind() == io::ErrorKind::NotFound && self.data_path().exists() => {
+                return Err(Error::OperationFailed(format!(
+                    "The keychain key {} is missing, so {} can't be decrypted",
+                    path.display(),
+                    self.data_path().display()
+                )));
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let mut key = vec![0u8; KEY_LENGTH];
+                OsRng.fill_bytes(&mut key);
+                fs::create_dir_all(&self.dir)?;
+                write_private(&path, &key)?;
+                key
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+
+    fn load(&self) -> Result<Items> {
+        let data = match fs::read(self.data_path()) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Items::default()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let corrupted = || Error::OperationFailed("Keychain file is corrupted or was encrypted with another key".into());
+        let body = data.strip_prefix(MAGIC.as_slice()).ok_or_else(corrupted)?;
+        if body.len() < NONCE_LENGTH {
+            return Err(corrupted());
+        }
+        let (nonce, ciphertext) = body.split_at(NONCE_LENGTH);
+        let plaintext = self
+            .cipher()?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| corrupted())?;
+        serde_json::from_slice(&plaintext).map_err(|_| corrupted())
+    }
+
+    fn save(&self, items: &Items) -> Result<()> {
+        let plaintext =
+            serde_json::to_vec(items).map_err(|e| Error::OperationFailed(format!("Failed to encode keychain: {e}")))?;
+        let mut nonce = [0u8; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher()?
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| Error::OperationFailed("Failed to encrypt keychain".into()))?;
+
+        let mut data = Vec::with_capacity(MAGIC.len() + NONCE_LENGTH + ciphertext.len());
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+
+        // Replace the file atomically so a crash can't leave it half written.
+        let temp = self.dir.join("keychain.dat.tmp");
+        write_private(&temp, &data)?;
+        fs::rename(temp, self.data_path())?;
+        Ok(())
+    }
+}
+
+/// Writes a file only the current user can read.
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tauri-plugin-ios-keychain-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn add(store: &FileStore, account: &str) -> Result<()> {
+        store.write(|items| {
+            items.generic.push(GenericEntry {
+                service: "app".into(),
+                account: account.into(),
+                access_group: None,
+                value: StoredValue::Plain(format!("{account}-secret")),
+                accessible: Accessible::default(),
+                synchronizable: false,
+                label: None,
+                comment: None,
+            });
+            Ok(())
+        })
+    }
+
+    fn accounts(store: &FileStore) -> Result<Vec<String>> {
+        store.read(|items| items.generic.iter().map(|entry| entry.account.clone()).collect())
+    }
+
+    fn assert_unreadable(store: &FileStore) {
+        let result = accounts(store);
+        assert!(matches!(result, Err(Error::OperationFailed(_))), "{result:?}");
+    }
+
+    #[test]
+    fn saved_items_load_in_a_new_store() {
+        let dir = scratch("round-trip");
+        let store = FileStore::new(dir.clone());
+        assert!(accounts(&store).unwrap().is_empty());
+        add(&store, "alice").unwrap();
+        add(&store, "bob").unwrap();
+
+        let data = fs::read(dir.join("keychain.dat")).unwrap();
+        assert!(data.starts_with(MAGIC));
+        assert!(!data.windows(12).any(|window| window == b"alice-secret"));
+        assert_eq!(fs::read(dir.join("keychain.key")).unwrap().len(), KEY_LENGTH);
+
+        let reopened = FileStore::new(dir.clone());
+        assert_eq!(accounts(&reopened).unwrap(), ["alice", "bob"]);
+        let value = reopened.read(|items| items.generic[0].value.clone()).unwrap();
+        assert!(matches!(value, StoredValue::Plain(value) if value == "alice-secret"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_failed_write_changes_nothing() {
+        let dir = scratch("failed-write");
+        let store = FileStore::new(dir.clone());
+        add(&store, "alice").unwrap();
+        let result: Result<()> = store.write(|items| {
+            items.generic.clear();
+            Err(Error::InvalidInput("rejected".into()))
+        });
+        assert!(result.is_err());
+        assert_eq!(accounts(&store).unwrap(), ["alice"]);
+        assert_eq!(accounts(&FileStore::new(dir.clone())).unwrap(), ["alice"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn saves_replace_the_file_atomically() {
+        let dir = scratch("atomic");
+        let store = FileStore::new(dir.clone());
+        add(&store, "alice").unwrap();
+        assert!(!dir.join("keychain.dat.tmp").exists());
+
+        // A save that can't write its temporary file leaves the previous items in place.
+        fs::create_dir(dir.join("keychain.dat.tmp")).unwrap();
+        assert!(add(&store, "bob").is_err());
+        assert_eq!(accounts(&store).unwrap(), ["alice"]);
+        assert_eq!(accounts(&FileStore::new(dir.clone())).unwrap(), ["alice"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn truncated_or_corrupted_files_are_rejected() {
+        let dir = scratch("corrupted");
+        add(&FileStore::new(dir.clone()), "alice").unwrap();
+        let data = fs::read(dir.join("keychain.dat")).unwrap();
+
+        for damaged in [
+            data[..data.len() - 1].to_vec(),
+            data[..MAGIC.len() + NONCE_LENGTH - 1].to_vec(),
+            data[..2].to_vec(),
+            [b"TKC2".as_slice(), &data[MAGIC.len()..]].concat(),
+            {
+                let mut flipped = data.clone();
+                flipped[MAGIC.len() + NONCE_LENGTH + 3] ^= 1;
+                flipped
+            },
+        ] {
+            fs::write(dir.join("keychain.dat"), damaged).unwrap();
+            assert_unreadable(&FileStore::new(dir.clone()));
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn items_encrypted_with_another_key_are_rejected() {
+        let dir = scratch("wrong-key");
+        add(&FileStore::new(dir.clone()), "alice").unwrap();
+        fs::write(dir.join("keychain.key"), [7u8; KEY_LENGTH]).unwrap();
+        assert_unreadable(&FileStore::new(dir.clone()));
+
+        fs::write(dir.join("keychain.key"), [7u8; KEY_LENGTH - 1]).unwrap();
+        assert_unreadable(&FileStore::new(dir.clone()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_missing_key_is_not_replaced_while_items_exist() {
+        let dir = scratch("missing-key");
+        add(&FileStore::new(dir.clone()), "alice").unwrap();
+        let data = fs::read(dir.join("keychain.dat")).unwrap();
+        fs::remove_file(dir.join("keychain.key")).unwrap();
+
+        let store = FileStore::new(dir.clone());
+        assert_unreadable(&store);
+        assert!(store.write(|items| {
+            items.generic.clear();
+            Ok(())
+        }).is_err());
+        assert!(!dir.join("keychain.key").exists());
+        assert_eq!(fs::read(dir.join("keychain.dat")).unwrap(), data);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,6 +1,33 @@
-const COMMANDS: &[&str] = &["ping"];
+const COMMANDS: &[&str] = &[
+    "check_permissions",
+    "request_permissions",
+    "get_current_location",
+    "start_location_updates",
+    "stop_location_updates",
+    "start_significant_location_updates",
+    "stop_significant_location_updates",
+    "start_monitoring_region",
+    "stop_monitoring_region",
+    "get_monitored_regions",
+    "get_current_heading",
+    "start_heading_updates",
+    "stop_heading_updates",
+    "geocode_address",
+    "reverse_geocode",
+    "get_distance",
+    "add_geofence",
+    "remove_geofence",
+    "get_geofences",
+    "clear_geofences",
+    "watch_geofences",
+    "unwatch_geofences",
+    "listen",
+    "unlisten",
+];
 
 fn main() {
+  tauri_ios_plugin_shared::build::verify_commands(COMMANDS);
+
   tauri_plugin::Builder::new(COMMANDS)
     .android_path("android")
     .ios_path("ios")
@@ -1,4 +1,22 @@
-import { invoke } from '@tauri-apps/api/core'
+import { Channel, invoke } from '@tauri-apps/api/core'
+
+// Errors
+/** Stable machine-readable code of a rejected command */
+export type ErrorCode =
+  | 'notAvailable'
+  | 'permissionDenied'
+  | 'invalidInput'
+  | 'operationFailed'
+  | 'io'
+  | 'pluginInvoke'
+  | 'internal'
+
+/** Shape of the error every command rejects with */
+export interface PluginError {
+  code: ErrorCode
+  message: string
+  details?: Record<string, unknown> | null
+}
 
 // Enums
 export enum LocationAccuracy {
@@ -17,9 +35,19 @@ export enum LocationEventType {
   RegionEntered = 'regionEntered',
   RegionExited = 'regionExited',
   AuthorizationChanged = 'authorizationChanged',
+  HeadingCalibrationNeeded = 'headingCalibrationNeeded',
   Error = 'error'
 }
 
+export enum HeadingOrientation {
+  Portrait = 'portrait',
+  PortraitUpsideDown = 'portraitUpsideDown',
+  LandscapeLeft = 'landscapeLeft',
+  LandscapeRight = 'landscapeRight',
+  FaceUp = 'faceUp',
+  FaceDown = 'faceDown'
+}
+
 // Interfaces
 export interface LocationPermissions {
   whenInUse: 'granted' | 'denied' | 'prompt'
@@ -66,15 +94,44 @@ export interface Region {
   radius: number
   notifyOnEntry: boolean
   notifyOnExit: boolean
+  /** Corners of a polygon geofence, whose center and radius are the circle around it */
+  vertices?: Coordinates[]
+}
+
+export type GeofenceShape =
+  | { type: 'circle'; center: Coordinates; radius: number }
+  | { type: 'polygon'; vertices: Coordinates[] }
+
+/** A region evaluated by the geofence engine rather than monitored by the OS */
+export interface Geofence {
+  identifier: string
+  shape: GeofenceShape
+  notifyOnEntry: boolean
+  notifyOnExit: boolean
+  /** Milliseconds the device has to stay inside before it counts as entered */
+  dwellTime?: number
+  /** Meters beyond the boundary the device has to go before it counts as exited, 10 by default */
+  hysteresis?: number
 }
 
 export interface Heading {
   magneticHeading: number
+  /** Negative while the location needed to compute it is unknown */
   trueHeading: number
+  /** Maximum deviation in degrees, negative when the heading is unreliable */
   headingAccuracy: number
   timestamp: string
 }
 
+export interface HeadingOptions {
+  /** Smallest change in degrees that is reported */
+  headingFilter?: number
+  orientation?: HeadingOrientation
+  /** Lets the system show its compass calibration screen */
+  showCalibration?: boolean
+  timeout?: number
+}
+
 export interface Placemark {
   name?: string
   thoroughfare?: string
@@ -97,10 +154,17 @@ export interface GeocodingResult {
   placemark: Placemark
 }
 
-export interface LocationEvent {
-  eventType: LocationEventType
-  data: any
-}
+export type LocationEvent =
+  | { eventType: LocationEventType.LocationUpdate; data: LocationData }
+  | { eventType: LocationEventType.HeadingUpdate; data: Heading }
+  | { eventType: LocationEventType.RegionEntered; data: Region }
+  | { eventType: LocationEventType.RegionExited; data: Region }
+  | { eventType: LocationEventType.AuthorizationChanged; data: LocationPermissions }
+  | { eventType: LocationEventType.HeadingCalibrationNeeded }
+  | { eventType: LocationEventType.Error; data: { message: string } }
+
+/** Stops a subscription; the native updates stop once their last listener is gone */
+export type UnlistenFn = () => Promise<void>
 
 export interface DistanceRequest {
   from: Coordinates
@@ -120,40 +184,64 @@ export async function getCurrentLocation(options?: LocationOptions): Promise<Loc
   return await invoke('plugin:ios-location-v2|get_current_location', { options })
 }
 
-export async function startLocationUpdates(options?: LocationOptions): Promise<void> {
-  return await invoke('plugin:ios-location-v2|start_location_updates', { options })
+// Event subscriptions
+async function subscribe(command: string, stopCommand: string, args: Record<string, unknown>, handler: (event: LocationEvent) => void): Promise<UnlistenFn> {
+  const onEvent = new Channel<LocationEvent>()
+  onEvent.onmessage = handler
+  const id = await invoke<number>(`plugin:ios-location-v2|${command}`, { ...args, onEvent })
+  return async () => {
+    await invoke(`plugin:ios-location-v2|${stopCommand}`, { id })
+  }
+}
+
+export async function startLocationUpdates(handler: (event: LocationEvent) => void, options?: LocationOptions): Promise<UnlistenFn> {
+  return await subscribe('start_location_updates', 'stop_location_updates', { options }, handler)
+}
+
+export async function startSignificantLocationUpdates(handler: (event: LocationEvent) => void): Promise<UnlistenFn> {
+  return await subscribe('start_significant_location_updates', 'stop_significant_location_updates', {}, handler)
+}
+
+export async function startMonitoringRegion(region: Region, handler: (event: LocationEvent) => void): Promise<UnlistenFn> {
+  return await subscribe('start_monitoring_region', 'stop_monitoring_region', { region }, handler)
+}
+
+export async function addGeofence(geofence: Geofence): Promise<void> {
+  return await invoke('plugin:ios-location-v2|add_geofence', { geofence })
 }
 
-export async function stopLocationUpdates(): Promise<void> {
-  return await invoke('plugin:ios-location-v2|stop_location_updates')
+export async function removeGeofence(identifier: string): Promise<void> {
+  return await invoke('plugin:ios-location-v2|remove_geofence', { identifier })
 }
 
-export async function startSignificantLocationUpdates(): Promise<void> {
-  return await invoke('plugin:ios-location-v2|start_significant_location_updates')
+export async function getGeofences(): Promise<Geofence[]> {
+  return await invoke('plugin:ios-location-v2|get_geofences')
 }
 
-export async function stopSignificantLocationUpdates(): Promise<void> {
-  return await invoke('plugin:ios-location-v2|stop_significant_location_updates')
+export async function clearGeofences(): Promise<void> {
+  return await invoke('plugin:ios-location-v2|clear_geofences')
 }
 
-export async function startMonitoringRegion(region: Region): Promise<void> {
-  return await invoke('plugin:ios-location-v2|start_monitoring_region', { region })
+/** Runs location updates for the geofence engine and receives its entry and exit events */
+export async function watchGeofences(handler: (event: LocationEvent) => void): Promise<UnlistenFn> {
+  return await subscribe('watch_geofences', 'unwatch_geofences', {}, handler)
 }
 
-export async function stopMonitoringRegion(identifier: string): Promise<void> {
-  return await invoke('plugin:ios-location-v2|stop_monitoring_region', { identifier })
+/** Receives every location event without starting any updates */
+export async function listen(handler: (event: LocationEvent) => void): Promise<UnlistenFn> {
+  return await subscribe('listen', 'unlisten', {}, handler)
 }
 
 export async function getMonitoredRegions(): Promise<Region[]> {
   return await invoke('plugin:ios-location-v2|get_monitored_regions')
 }
 
-export async function startHeadingUpdates(): Promise<void> {
-  return await invoke('plugin:ios-location-v2|start_heading_updates')
+export async function getCurrentHeading(options?: HeadingOptions): Promise<Heading> {
+  return await invoke('plugin:ios-location-v2|get_current_heading', { options })
 }
 
-export async function stopHeadingUpdates(): Promise<void> {
-  return await invoke('plugin:ios-location-v2|stop_heading_updates')
+export async function startHeadingUpdates(handler: (event: LocationEvent) => void, options?: HeadingOptions): Promise<UnlistenFn> {
+  return await subscribe('start_heading_updates', 'stop_heading_updates', { options }, handler)
 }
 
 export async function geocodeAddress(address: string): Promise<GeocodingResult[]> {
@@ -31,9 +31,15 @@ struct CoordinatesData: Decodable {
     let longitude: Double
 }
 
-struct DistanceRequest: Decodable {
-    let from: CoordinatesData
-    let to: CoordinatesData
+struct HeadingOptions: Decodable {
+    let headingFilter: Double?
+    let orientation: String?
+    let showCalibration: Bool?
+    let timeout: Int?
+}
+
+struct EventHandlerArgs: Decodable {
+    let handler: Channel
 }
 
 class LocationPlugin: Plugin {
@@ -43,6 +49,13 @@ class LocationPlugin: Plugin {
     private var locationOptions: LocationOptions?
     private var lastLocation: CLLocation?
     private var monitoredRegions: [String: CLCircularRegion] = [:]
+    private var isUpdatingLocation = false
+    private var isMonitoringSignificantChanges = false
+    private var isUpdatingHeading = false
+    private var showHeadingCalibration = false
+    private var pendingHeadingRequests: [Invoke] = []
+    private var headingRequestTimer: Timer?
+    private var eventChannel: Channel?
     private let geocoder = CLGeocoder()
     
     @objc public override func load(webview: WKWebView) {
@@ -50,6 +63,12 @@ class LocationPlugin: Plugin {
         locationManager.delegate = self
     }
     
+    @objc public func setEventHandler(_ invoke: Invoke) throws {
+        let args = try invoke.parseArgs(EventHandlerArgs.self)
+        eventChannel = args.handler
+        invoke.resolve()
+    }
+    
     @objc public override func checkPermissions(_ invoke: Invoke) {
         let authStatus: CLAuthorizationStatus
         if #available(iOS 14.0, *) {
@@ -141,7 +160,6 @@ class LocationPlugin: Plugin {
             locationUpdateTimer = Timer.scheduledTimer(withTimeInterval: Double(timeout) / 1000.0, repeats: false) { _ in
                 self.pendingLocationRequest?.reject("Location timeout")
                 self.pendingLocationRequest = nil
-                self.locationManager.stopUpdatingLocation()
             }
         }
         
@@ -187,11 +205,43 @@ class LocationPlugin: Plugin {
         }
         
         locationManager.startUpdatingLocation()
+        isUpdatingLocation = true
         invoke.resolve()
     }
     
     @objc public func stopLocationUpdates(_ invoke: Invoke) throws {
         locationManager.stopUpdatingLocation()
+        isUpdatingLocation = false
+        invoke.resolve()
+    }
+    
+    @objc public func startSignificantLocationUpdates(_ invoke: Invoke) throws {
+        guard CLLocationManager.significantLocationChangeMonitoringAvailable() else {
+            invoke.reject("Significant location change monitoring not available")
+            return
+        }
+        
+        let authStatus: CLAuthorizationStatus
+        if #available(iOS 14.0, *) {
+            authStatus = locationManager.authorizationStatus
+        } else {
+            authStatus = CLLocationManager.authorizationStatus()
+        }
+        
+        guard authStatus == .authorizedWhenInUse ||
+              authStatus == .authorizedAlways else {
+            invoke.reject("Location permission denied")
+            return
+        }
+        
+        locationManager.startMonitoringSignificantLocationChanges()
+        isMonitoringSignificantChanges = true
+        invoke.resolve()
+    }
+    
+    @objc public func stopSignificantLocationUpdates(_ invoke: Invoke) throws {
+        locationManager.stopMonitoringSignificantLocationChanges()
+        isMonitoringSignificantChanges = false
         invoke.resolve()
     }
     
@@ -298,57 +348,111 @@ class LocationPlugin: Plugin {
         }
     }
     
-    @objc public func getDistance(_ invoke: Invoke) throws {
-        let args = try invoke.parseArgs(DistanceRequest.self)
+    @objc public func getCurrentHeading(_ invoke: Invoke) throws {
+        let args = try? invoke.parseArgs(HeadingOptions.self)
         
-        let fromLocation = CLLocation(
-            latitude: args.from.latitude,
-            longitude: args.from.longitude
-        )
+        guard CLLocationManager.headingAvailable() else {
+            invoke.reject("Heading not available")
+            return
+        }
         
-        let toLocation = CLLocation(
-            latitude: args.to.latitude,
-            longitude: args.to.longitude
-        )
+        pendingHeadingRequests.append(invoke)
+        
+        if !isUpdatingHeading {
+            configureHeading(args)
+            locationManager.startUpdatingHeading()
+        }
         
-        let distance = fromLocation.distance(from: toLocation)
-        invoke.resolve(distance)
+        if let timeout = args?.timeout, headingRequestTimer == nil {
+            headingRequestTimer = Timer.scheduledTimer(withTimeInterval: Double(timeout) / 1000.0, repeats: false) { _ in
+                self.headingRequestTimer = nil
+                for request in self.pendingHeadingRequests {
+                    request.reject("Heading timeout")
+                }
+                self.pendingHeadingRequests.removeAll()
+                if !self.isUpdatingHeading {
+                    self.locationManager.stopUpdatingHeading()
+                }
+            }
+        }
     }
     
     @objc public func startHeadingUpdates(_ invoke: Invoke) throws {
+        let args = try? invoke.parseArgs(HeadingOptions.self)
+        
         guard CLLocationManager.headingAvailable() else {
             invoke.reject("Heading not available")
             return
         }
         
+        configureHeading(args)
         locationManager.startUpdatingHeading()
+        isUpdatingHeading = true
         invoke.resolve()
     }
     
     @objc public func stopHeadingUpdates(_ invoke: Invoke) throws {
-        locationManager.stopUpdatingHeading()
+        isUpdatingHeading = false
+        if pendingHeadingRequests.isEmpty {
+            locationManager.stopUpdatingHeading()
+        }
         invoke.resolve()
     }
     
     @objc public func getMonitoredRegions(_ invoke: Invoke) throws {
-        let regions = monitoredRegions.values.map { region in
-            [
-                "identifier": region.identifier,
-                "center": [
-                    "latitude": region.center.latitude,
-                    "longitude": region.center.longitude
-                ],
-                "radius": region.radius,
-                "notifyOnEntry": region.notifyOnEntry,
-                "notifyOnExit": region.notifyOnExit
-            ]
-        }
+        let regions = monitoredRegions.values.map { serializeRegion($0) }
         
         invoke.resolve(["regions": regions])
     }
     
     // MARK: - Helper Methods
     
+    /// Sends an event to plugin listeners and, once registered, to the Rust event channel
+    /// as `{ eventType, data }`, leaving out `data` for events without any.
+    private func emit(_ eventType: String, _ data: JSObject? = nil) {
+        trigger(eventType, data: data ?? [:])
+        
+        var event: JSObject = ["eventType": eventType]
+        event["data"] = data
+        eventChannel?.send(.dictionary(event))
+    }
+    
+    private func configureHeading(_ options: HeadingOptions?) {
+        locationManager.headingFilter = options?.headingFilter ?? kCLHeadingFilterNone
+        locationManager.headingOrientation = parseOrientation(options?.orientation ?? "portrait")
+        showHeadingCalibration = options?.showCalibration ?? false
+    }
+    
+    private func parseOrientation(_ orientation: String) -> CLDeviceOrientation {
+        switch orientation.lowercased() {
+        case "portraitupsidedown":
+            return .portraitUpsideDown
+        case "landscapeleft":
+            return .landscapeLeft
+        case "landscaperight":
+            return .landscapeRight
+        case "faceup":
+            return .faceUp
+        case "facedown":
+            return .faceDown
+        default:
+            return .portrait
+        }
+    }
+    
+    private func serializeRegion(_ region: CLCircularRegion) -> [String: Any] {
+        return [
+            "identifier": region.identifier,
+            "center": [
+                "latitude": region.center.latitude,
+                "longitude": region.center.longitude
+            ],
+            "radius": region.radius,
+            "notifyOnEntry": region.notifyOnEntry,
+            "notifyOnExit": region.notifyOnExit
+        ]
+    }
+    
     private func convertToJSObject(_ dict: [String: Any]) -> JSObject {
         var jsObject: JSObject = [:]
         for (key, value) in dict {
@@ -487,10 +591,11 @@ extension LocationPlugin: CLLocationManagerDelegate {
             locationUpdateTimer = nil
             invoke.resolve(serializeLocation(location))
             pendingLocationRequest = nil
-            manager.stopUpdatingLocation()
-        } else {
-            // Handle continuous updates
-            trigger("locationUpdate", data: convertToJSObject(serializeLocation(location)))
+        }
+        
+        // Handle continuous updates
+        if isUpdatingLocation || isMonitoringSignificantChanges {
+            emit("locationUpdate", convertToJSObject(serializeLocation(location)))
         }
     }
     
@@ -501,7 +606,7 @@ extension LocationPlugin: CLLocationManagerDelegate {
             invoke.reject("Location error: \(error.localizedDescription)")
             pendingLocationRequest = nil
         } else {
-            trigger("error", data: ["error": error.localizedDescription] as JSObject)
+            emit("error", ["message": error.localizedDescription] as JSObject)
         }
     }
     
@@ -513,15 +618,37 @@ extension LocationPlugin: CLLocationManagerDelegate {
             "timestamp": ISO8601DateFormatter().string(from: newHeading.timestamp)
         ]
         
-        trigger("headingUpdate", data: convertToJSObject(heading))
+        // Handle one-time heading requests once the reading is reliable
+        if !pendingHeadingRequests.isEmpty && newHeading.headingAccuracy >= 0 {
+            headingRequestTimer?.invalidate()
+            headingRequestTimer = nil
+            for request in pendingHeadingRequests {
+                request.resolve(heading)
+            }
+            pendingHeadingRequests.removeAll()
+            if !isUpdatingHeading {
+                manager.stopUpdatingHeading()
+            }
+        }
+        
+        if isUpdatingHeading {
+            emit("headingUpdate", convertToJSObject(heading))
+        }
+    }
+    
+    func locationManagerShouldDisplayHeadingCalibration(_ manager: CLLocationManager) -> Bool {
+        emit("headingCalibrationNeeded")
+        return showHeadingCalibration
     }
     
     func locationManager(_ manager: CLLocationManager, didEnterRegion region: CLRegion) {
-        trigger("regionEntered", data: ["identifier": region.identifier] as JSObject)
+        guard let region = region as? CLCircularRegion else { return }
+        emit("regionEntered", convertToJSObject(serializeRegion(region)))
     }
     
     func locationManager(_ manager: CLLocationManager, didExitRegion region: CLRegion) {
-        trigger("regionExited", data: ["identifier": region.identifier] as JSObject)
+        guard let region = region as? CLCircularRegion else { return }
+        emit("regionExited", convertToJSObject(serializeRegion(region)))
     }
     
     func locationManagerDidChangeAuthorization(_ manager: CLLocationManager) {
@@ -534,7 +661,7 @@ extension LocationPlugin: CLLocationManagerDelegate {
         let whenInUse = authorizationStatusToString(authStatus, for: .authorizedWhenInUse)
         let always = authorizationStatusToString(authStatus, for: .authorizedAlways)
         
-        trigger("authorizationChanged", data: [
+        emit("authorizationChanged", [
             "whenInUse": whenInUse,
             "always": always
         ] as JSObject)
@@ -1,6 +1,9 @@
-use tauri::{command, AppHandle, Runtime};
+use tauri::{command, ipc::Channel, AppHandle, Manager, Runtime};
 
-use crate::{LocationExt, LocationPermissions, PermissionRequest, LocationOptions, LocationData, Region, Coordinates, GeocodingResult, Placemark, Result};
+use crate::{
+    events::JsSubscriptions, LocationExt, LocationPermissions, PermissionRequest, LocationOptions, LocationData,
+    LocationEvent, Heading, HeadingOptions, Region, Coordinates, GeocodingResult, Placemark, Geofence, Result,
+};
 
 #[command]
 pub(crate) async fn check_permissions<R: Runtime>(
@@ -29,48 +32,62 @@ pub(crate) async fn get_current_location<R: Runtime>(
 pub(crate) async fn start_location_updates<R: Runtime>(
     app: AppHandle<R>,
     options: Option<LocationOptions>,
-) -> Result<()> {
-    app.location().start_location_updates(options.unwrap_or_default())
+    on_event: Channel<LocationEvent>,
+) -> Result<u32> {
+    let subscription = app.location().start_location_updates(options.unwrap_or_default(), move |event| {
+        let _ = on_event.send(event.clone());
+    })?;
+    Ok(app.state::<JsSubscriptions>().insert(subscription))
 }
 
 #[command]
 pub(crate) async fn stop_location_updates<R: Runtime>(
     app: AppHandle<R>,
+    id: u32,
 ) -> Result<()> {
-    app.location().stop_location_updates()
+    app.state::<JsSubscriptions>().remove(id);
+    Ok(())
 }
 
 #[command]
 pub(crate) async fn start_significant_location_updates<R: Runtime>(
     app: AppHandle<R>,
-) -> Result<()> {
-    app.location().start_location_updates(LocationOptions {
-        accuracy: crate::LocationAccuracy::ThreeKilometers,
-        ..Default::default()
-    })
+    on_event: Channel<LocationEvent>,
+) -> Result<u32> {
+    let subscription = app.location().start_significant_location_updates(move |event| {
+        let _ = on_event.send(event.clone());
+    })?;
+    Ok(app.state::<JsSubscriptions>().insert(subscription))
 }
 
 #[command]
 pub(crate) async fn stop_significant_location_updates<R: Runtime>(
     app: AppHandle<R>,
+    id: u32,
 ) -> Result<()> {
-    app.location().stop_location_updates()
+    app.state::<JsSubscriptions>().remove(id);
+    Ok(())
 }
 
 #[command]
 pub(crate) async fn start_monitoring_region<R: Runtime>(
     app: AppHandle<R>,
     region: Region,
-) -> Result<()> {
-    app.location().start_monitoring_region(region)
+    on_event: Channel<LocationEvent>,
+) -> Result<u32> {
+    let subscription = app.location().start_monitoring_region(region, move |event| {
+        let _ = on_event.send(event.clone());
+    })?;
+    Ok(app.state::<JsSubscriptions>().insert(subscription))
 }
 
 #[command]
 pub(crate) async fn stop_monitoring_region<R: Runtime>(
     app: AppHandle<R>,
-    identifier: String,
+    id: u32,
 ) -> Result<()> {
-    app.location().stop_monitoring_region(&identifier)
+    app.state::<JsSubscriptions>().remove(id);
+    Ok(())
 }
 
 #[command]
@@ -81,19 +98,32 @@ pub(crate) async fn get_monitored_regions<R: Runtime>(
     Ok(vec![])
 }
 
+#[command]
+pub(crate) async fn get_current_heading<R: Runtime>(
+    app: AppHandle<R>,
+    options: Option<HeadingOptions>,
+) -> Result<Heading> {
+    app.location().get_current_heading(options.unwrap_or_default())
+}
+
 #[command]
 pub(crate) async fn start_heading_updates<R: Runtime>(
-    _app: AppHandle<R>,
-) -> Result<()> {
-    // This would need to be implemented
-    Ok(())
+    app: AppHandle<R>,
+    options: Option<HeadingOptions>,
+    on_event: Channel<LocationEvent>,
+) -> Result<u32> {
+    let subscription = app.location().start_heading_updates(options.unwrap_or_default(), move |event| {
+        let _ = on_event.send(event.clone());
+    })?;
+    Ok(app.state::<JsSubscriptions>().insert(subscription))
 }
 
 #[command]
 pub(crate) async fn stop_heading_updates<R: Runtime>(
-    _app: AppHandle<R>,
+    app: AppHandle<R>,
+    id: u32,
 ) -> Result<()> {
-    // This would need to be implemented
+    app.state::<JsSubscriptions>().remove(id);
     Ok(())
 }
 
@@ -120,4 +150,74 @@ pub(crate) async fn get_distance<R: Runtime>(
     to: Coordinates,
 ) -> Result<f64> {
     app.location().get_distance(from, to)
+}
+
+#[command]
+pub(crate) async fn listen<R: Runtime>(
+    app: AppHandle<R>,
+    on_event: Channel<LocationEvent>,
+) -> Result<u32> {
+    let subscription = app.location().listen(move |event| {
+        let _ = on_event.send(event.clone());
+    });
+    Ok(app.state::<JsSubscriptions>().insert(subscription))
+}
+
+#[command]
+pub(crate) async fn unlisten<R: Runtime>(
+    app: AppHandle<R>,
+    id: u32,
+) -> Result<()> {
+    app.state::<JsSubscriptions>().remove(id);
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn add_geofence<R: Runtime>(
+    app: AppHandle<R>,
+    geofence: Geofence,
+) -> Result<()> {
+    app.location().geofences().add(geofence)
+}
+
+#[command]
+pub(crate) async fn remove_geofence<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+) -> Result<()> {
+    app.location().geofences().remove(&identifier)
+}
+
+#[command]
+pub(crate) async fn get_geofences<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<Vec<Geofence>> {
+    Ok(app.location().geofences().list())
+}
+
+#[command]
+pub(crate) async fn clear_geofences<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<()> {
+    app.location().geofences().clear()
+}
+
+#[command]
+pub(crate) async fn watch_geofences<R: Runtime>(
+    app: AppHandle<R>,
+    on_event: Channel<LocationEvent>,
+) -> Result<u32> {
+    let subscription = app.location().watch_geofences(move |event| {
+        let _ = on_event.send(event.clone());
+    })?;
+    Ok(app.state::<JsSubscriptions>().insert(subscription))
+}
+
+#[command]
+pub(crate) async fn unwatch_geofences<R: Runtime>(
+    app: AppHandle<R>,
+    id: u32,
+) -> Result<()> {
+    app.state::<JsSubscriptions>().remove(id);
+    Ok(())
 }
\ No newline at end of file
@@ -1,37 +1,95 @@
-use serde::de::DeserializeOwned;
+use std::sync::Arc;
+
 use tauri::{plugin::PluginApi, AppHandle, Runtime};
 
-use crate::{models::*, Result, Error};
+use crate::{
+    events::EventBus,
+    geofence::Geofences,
+    models::*,
+    simulator::{Simulator, Updates},
+    Result, Error,
+};
 
-pub fn init<R: Runtime, C: DeserializeOwned>(
+pub fn init<R: Runtime>(
     app: &AppHandle<R>,
-    _api: PluginApi<R, C>,
+    api: PluginApi<R, Option<Config>>,
 ) -> Result<Location<R>> {
-    Ok(Location(app.clone()))
+    let events = Arc::new(EventBus::default());
+    let simulator = Simulator::new(events.clone());
+    if let Some(config) = api.config() {
+        if let Some(speed) = config.replay_speed {
+            simulator.set_speed(speed)?;
+        }
+        simulator.set_looping(config.loop_track);
+        if let Some(track) = &config.simulated_track {
+            simulator.load_file(track)?;
+        }
+    }
+
+    Ok(Location {
+        app: app.clone(),
+        geofences: Geofences::load(app, events.clone()),
+        events,
+        simulator,
+    })
 }
 
-/// Access to the location APIs on desktop (returns errors as not available).
-pub struct Location<R: Runtime>(AppHandle<R>);
+/// Access to the location APIs on desktop, backed by a simulator that replays
+/// recorded tracks. Geocoding and region monitoring are not available.
+pub struct Location<R: Runtime> {
+    pub(crate) app: AppHandle<R>,
+    pub(crate) events: Arc<EventBus>,
+    pub(crate) geofences: Geofences,
+    simulator: Simulator,
+}
 
 impl<R: Runtime> Location<R> {
+    /// The simulated location, used to load tracks and script permissions.
+    pub fn simulator(&self) -> &Simulator {
+        &self.simulator
+    }
+
     pub fn check_permissions(&self) -> Result<LocationPermissions> {
-        Err(Error::NotAvailable)
+        Ok(self.simulator.permissions())
     }
 
-    pub fn request_permissions(&self, _request: PermissionRequest) -> Result<LocationPermissions> {
-        Err(Error::NotAvailable)
+    pub fn request_permissions(&self, request: PermissionRequest) -> Result<LocationPermissions> {
+        Ok(self.simulator.request_permissions(&request))
     }
 
     pub fn get_current_location(&self, _options: LocationOptions) -> Result<LocationData> {
-        Err(Error::NotAvailable)
+        self.simulator.current_location()
     }
 
-    pub fn start_location_updates(&self, _options: LocationOptions) -> Result<()> {
-        Err(Error::NotAvailable)
+    pub fn get_current_heading(&self, _options: HeadingOptions) -> Result<Heading> {
+        self.simulator.current_heading()
+    }
+
+    pub fn start_heading_updates(&self, options: HeadingOptions) -> Result<()> {
+        self.simulator.start_heading_updates(options)
+    }
+
+    pub fn stop_heading_updates(&self) -> Result<()> {
+        self.simulator.stop_updates(Updates::Heading);
+        Ok(())
+    }
+
+    pub fn start_location_updates(&self, options: LocationOptions) -> Result<()> {
+        self.simulator.start_updates(Updates::Standard, options)
     }
 
     pub fn stop_location_updates(&self) -> Result<()> {
-        Err(Error::NotAvailable)
+        self.simulator.stop_updates(Updates::Standard);
+        Ok(())
+    }
+
+    pub fn start_significant_location_updates(&self) -> Result<()> {
+        self.simulator.start_updates(Updates::SignificantChanges, LocationOptions::default())
+    }
+
+    pub fn stop_significant_location_updates(&self) -> Result<()> {
+        self.simulator.stop_updates(Updates::SignificantChanges);
+        Ok(())
     }
 
     pub fn start_monitoring_region(&self, _region: Region) -> Result<()> {
@@ -49,21 +107,4 @@ impl<R: Runtime> Location<R> {
     pub fn reverse_geocode(&self, _location: Coordinates) -> Result<Vec<Placemark>> {
         Err(Error::OperationFailed("Not available on desktop".to_string()))
     }
-
-    pub fn get_distance(&self, from: Coordinates, to: Coordinates) -> Result<f64> {
-        // Simple haversine distance calculation
-        const EARTH_RADIUS: f64 = 6371000.0; // meters
-        
-        let lat1 = from.latitude.to_radians();
-        let lat2 = to.latitude.to_radians();
-        let delta_lat = (to.latitude - from.latitude).to_radians();
-        let delta_lon = (to.longitude - from.longitude).to_radians();
-        
-        let a = (delta_lat / 2.0).sin().powi(2) +
-                lat1.cos() * lat2.cos() *
-                (delta_lon / 2.0).sin().powi(2);
-        let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
-        
-        Ok(EARTH_RADIUS * c)
-    }
-}
\ No newline at end of file
+}
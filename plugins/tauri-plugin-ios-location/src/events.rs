@@ -0,0 +1,489 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+};
+
+use tauri::Runtime;
+
+use crate::{Error, HeadingOptions, LocationEvent, LocationExt, LocationOptions, Region, Result};
+
+type Handler = Arc<dyn Fn(&LocationEvent) + Send + Sync>;
+type Stop = Box<dyn FnOnce() + Send>;
+
+/// A native operation shared by every listener that needs it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Operation {
+    LocationUpdates,
+    SignificantLocationUpdates,
+    HeadingUpdates,
+    Region { identifier: String },
+    Geofences,
+}
+
+impl Operation {
+    fn matches(&self, event: &LocationEvent) -> bool {
+        match (self, event) {
+            // Authorization changes concern every running operation.
+            (_, LocationEvent::AuthorizationChanged(_)) => true,
+            (
+                Operation::LocationUpdates | Operation::SignificantLocationUpdates,
+                LocationEvent::LocationUpdate(_) | LocationEvent::Error { .. },
+            ) => true,
+            (
+                Operation::HeadingUpdates,
+                LocationEvent::HeadingUpdate(_) | LocationEvent::HeadingCalibrationNeeded | LocationEvent::Error { .. },
+            ) => true,
+            (
+                Operation::Region { identifier },
+                LocationEvent::RegionEntered(region) | LocationEvent::RegionExited(region),
+            ) => region.identifier == *identifier,
+            (
+                Operation::Geofences,
+                LocationEvent::RegionEntered(_) | LocationEvent::RegionExited(_) | LocationEvent::Error { .. },
+            ) => true,
+            _ => false,
+        }
+    }
+}
+
+struct Listener {
+    operation: Option<Operation>,
+    handler: Handler,
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: u32,
+    listeners: HashMap<u32, Listener>,
+    // `None` while the operation is still being started.
+    operations: HashMap<Operation, Option<Stop>>,
+    // Why the operation of a listener that was waiting for it failed to start.
+    failures: HashMap<u32, Error>,
+}
+
+/// Fans native location events out to the registered listeners.
+#[derive(Default)]
+pub(crate) struct EventBus {
+    registry: Mutex<Registry>,
+    // Notified when an operation has finished starting, or failed to.
+    started: Condvar,
+}
+
+/// Copies a start failure for each listener that was waiting on it.
+fn start_failure(error: &Error) -> Error {
+    match error {
+        Error::NotAvailable => Error::NotAvailable,
+        Error::PermissionDenied => Error::PermissionDenied,
+        Error::InvalidInput(message) => Error::InvalidInput(message.clone()),
+        _ => Error::OperationFailed(error.to_string()),
+    }
+}
+
+impl EventBus {
+    fn registry(&self) -> MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Delivers `event` to every listener interested in it.
+    pub(crate) fn dispatch(&self, event: &LocationEvent) {
+        let handlers: Vec<Handler> = self
+            .registry()
+            .listeners
+            .values()
+            .filter(|listener| {
+                listener
+                    .operation
+                    .as_ref()
+                    .map_or(true, |operation| operation.matches(event))
+            })
+            .map(|listener| listener.handler.clone())
+            .collect();
+
+        for handler in handlers {
+            handler(event);
+        }
+    }
+
+    /// Registers a listener for every event.
+    pub(crate) fn listen(self: &Arc<Self>, handler: Handler) -> Subscription {
+        self.add_listener(None, handler).0
+    }
+
+    /// Registers a listener and reports whether its operation still has to be
+    /// started, i.e. no other listener is keeping it alive yet.
+    fn add_listener(
+        self: &Arc<Self>,
+        operation: Option<Operation>,
+        handler: Handler,
+    ) -> (Subscription, bool) {
+        let mut registry = self.registry();
+        let id = registry.next_id;
+        registry.next_id = registry.next_id.wrapping_add(1);
+
+        let needs_start = match &operation {
+            Some(operation) if !registry.operations.contains_key(operation) => {
+                registry.operations.insert(operation.clone(), None);
+                true
+            }
+            _ => false,
+        };
+        registry.listeners.insert(id, Listener { operation, handler });
+
+        let subscription = Subscription {
+            id,
+            bus: self.clone(),
+        };
+        (subscription, needs_start)
+    }
+
+    /// Registers a listener for `operation`, running `start` if it isn't active yet.
+    /// `start` runs without the registry locked so backends may dispatch events
+    /// while starting. Listeners added meanwhile wait for it to finish, and share
+    /// its error if it fails.
+    fn subscribe<S>(self: &Arc<Self>, operation: Operation, handler: Handler, start: S) -> Result<Subscription>
+    where
+        S: FnOnce() -> Result<Stop>,
+    {
+        let (subscription, needs_start) = self.add_listener(Some(operation.clone()), handler);
+        if !needs_start {
+            return self.wait_for_start(&operation, subscription);
+        }
+
+        let stop = match start() {
+            Ok(stop) => stop,
+            Err(error) => {
+                self.fail(&operation, subscription.id, &error);
+                return Err(error);
+            }
+        };
+        let mut registry = self.registry();
+        match registry.operations.get_mut(&operation) {
+            Some(slot) => {
+                *slot = Some(stop);
+                drop(registry);
+                self.started.notify_all();
+            }
+            // Every listener went away while the operation was starting.
+            None => {
+                drop(registry);
+                stop();
+            }
+        }
+        Ok(subscription)
+    }
+
+    /// Blocks until the operation `subscription` joined is running, or fails
+    /// with the error it failed to start with.
+    fn wait_for_start(&self, operation: &Operation, subscription: Subscription) -> Result<Subscription> {
+        let mut registry = self.registry();
+        loop {
+            if let Some(error) = registry.failures.remove(&subscription.id) {
+                drop(registry);
+                return Err(error);
+            }
+            match registry.operations.get(operation) {
+                Some(None) => {
+                    registry = self.started.wait(registry).unwrap_or_else(|e| e.into_inner());
+                }
+                _ => return Ok(subscription),
+            }
+        }
+    }
+
+    /// Forgets an operation that failed to start and removes the listeners
+    /// waiting for it, handing each of them the error.
+    fn fail(&self, operation: &Operation, starter: u32, error: &Error) {
+        let mut registry = self.registry();
+        registry.operations.remove(operation);
+        let waiting: Vec<u32> = registry
+            .listeners
+            .iter()
+            .filter(|(_, listener)| listener.operation.as_ref() == Some(operation))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in waiting {
+            registry.listeners.remove(&id);
+            if id != starter {
+                registry.failures.insert(id, start_failure(error));
+            }
+        }
+        drop(registry);
+        self.started.notify_all();
+    }
+
+    fn unsubscribe(&self, id: u32) {
+        let stop = {
+            let mut registry = self.registry();
+            let Some(listener) = registry.listeners.remove(&id) else {
+                return;
+            };
+            let Some(operation) = listener.operation else {
+                return;
+            };
+            let still_needed = registry
+                .listeners
+                .values()
+                .any(|other| other.operation.as_ref() == Some(&operation));
+            if still_needed {
+                return;
+            }
+            registry.operations.remove(&operation).flatten()
+        };
+
+        if let Some(stop) = stop {
+            stop();
+        }
+    }
+}
+
+/// Handle to a registered event listener.
+///
+/// Dropping it removes the listener. When it was the last listener of location
+/// or heading updates or of a monitored region, the native updates or
+/// monitoring stop too.
+pub struct Subscription {
+    id: u32,
+    bus: Arc<EventBus>,
+}
+
+impl Subscription {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Removes the listener. Equivalent to dropping the handle.
+    pub fn unsubscribe(self) {}
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.bus.unsubscribe(self.id);
+    }
+}
+
+/// Subscriptions created from the frontend, keyed by the id handed back to JS.
+#[derive(Default)]
+pub(crate) struct JsSubscriptions(Mutex<HashMap<u32, Subscription>>);
+
+impl JsSubscriptions {
+    pub(crate) fn insert(&self, subscription: Subscription) -> u32 {
+        let id = subscription.id();
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, subscription);
+        id
+    }
+
+    pub(crate) fn remove(&self, id: u32) {
+        let subscription = self.0.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+        drop(subscription);
+    }
+}
+
+impl<R: Runtime> crate::Location<R> {
+    /// Registers `handler` for every location event without starting any native
+    /// updates.
+    pub fn listen<F>(&self, handler: F) -> Subscription
+    where
+        F: Fn(&LocationEvent) + Send + Sync + 'static,
+    {
+        self.0.events.listen(Arc::new(handler))
+    }
+
+    /// Starts location updates and delivers each fix, errors and authorization
+    /// changes to `handler`. Updates stop once every subscription to them has
+    /// been dropped.
+    ///
+    /// The native updates are started with `options` only if they aren't already
+    /// running for another subscription, in which case their options still apply.
+    pub fn start_location_updates<F>(&self, options: LocationOptions, handler: F) -> Result<Subscription>
+    where
+        F: Fn(&LocationEvent) + Send + Sync + 'static,
+    {
+        let app = self.0.app.clone();
+        self.0
+            .events
+            .subscribe(Operation::LocationUpdates, Arc::new(handler), || {
+                app.location().0.start_location_updates(options)?;
+                Ok(Box::new(move || {
+                    let _ = app.location().0.stop_location_updates();
+                }))
+            })
+    }
+
+    /// Starts significant-change location updates, which report a fix only when
+    /// the device has moved about 500 meters and keep running in the background.
+    /// They stop once every subscription to them has been dropped.
+    ///
+    /// The device reports a single stream of fixes, so subscribers of regular
+    /// updates receive these fixes as well, and the other way around.
+    pub fn start_significant_location_updates<F>(&self, handler: F) -> Result<Subscription>
+    where
+        F: Fn(&LocationEvent) + Send + Sync + 'static,
+    {
+        let app = self.0.app.clone();
+        self.0
+            .events
+            .subscribe(Operation::SignificantLocationUpdates, Arc::new(handler), || {
+                app.location().0.start_significant_location_updates()?;
+                Ok(Box::new(move || {
+                    let _ = app.location().0.stop_significant_location_updates();
+                }))
+            })
+    }
+
+    /// Starts compass updates and delivers each heading and calibration request
+    /// to `handler`. Updates stop once every subscription to them has been
+    /// dropped.
+    ///
+    /// The native updates are started with `options` only if they aren't already
+    /// running for another subscription, in which case their filter and
+    /// orientation still apply.
+    pub fn start_heading_updates<F>(&self, options: HeadingOptions, handler: F) -> Result<Subscription>
+    where
+        F: Fn(&LocationEvent) + Send + Sync + 'static,
+    {
+        let app = self.0.app.clone();
+        self.0
+            .events
+            .subscribe(Operation::HeadingUpdates, Arc::new(handler), || {
+                app.location().0.start_heading_updates(options)?;
+                Ok(Box::new(move || {
+                    let _ = app.location().0.stop_heading_updates();
+                }))
+            })
+    }
+
+    /// Monitors `region` and delivers its entry and exit events to `handler`.
+    /// Monitoring stops once every subscription to the region has been dropped.
+    ///
+    /// Regions are told apart by identifier: subscribing to a region that is
+    /// already monitored keeps its current center, radius and notifications.
+    pub fn start_monitoring_region<F>(&self, region: Region, handler: F) -> Result<Subscription>
+    where
+        F: Fn(&LocationEvent) + Send + Sync + 'static,
+    {
+        let app = self.0.app.clone();
+        let operation = Operation::Region {
+            identifier: region.identifier.clone(),
+        };
+        self.0
+            .events
+            .subscribe(operation, Arc::new(handler), || {
+                let identifier = region.identifier.clone();
+                app.location().0.start_monitoring_region(region)?;
+                Ok(Box::new(move || {
+                    let _ = app.location().0.stop_monitoring_region(&identifier);
+                }))
+            })
+    }
+    /// Runs location updates for the geofence engine and delivers the entry and
+    /// exit events of every geofence, along with location errors, to `handler`.
+    /// The updates stop once every subscription has been dropped.
+    ///
+    /// Entry and exit events of regions monitored by the OS are delivered too.
+    pub fn watch_geofences<F>(&self, handler: F) -> Result<Subscription>
+    where
+        F: Fn(&LocationEvent) + Send + Sync + 'static,
+    {
+        let app = self.0.app.clone();
+        self.0
+            .events
+            .subscribe(Operation::Geofences, Arc::new(handler), || {
+                let feeder = app.clone();
+                let updates = app.location().start_location_updates(LocationOptions::default(), move |event| {
+                    if let LocationEvent::LocationUpdate(location) = event {
+                        feeder.location().geofences().update(location);
+                    }
+                })?;
+                Ok(Box::new(move || drop(updates)))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::AtomicUsize, atomic::Ordering, mpsc},
+        thread,
+    };
+
+    use super::*;
+
+    fn handler() -> Handler {
+        Arc::new(|_| {})
+    }
+
+    /// Subscribes to location updates from another thread once `start` has begun, and waits
+    /// until its listener is registered.
+    fn join_updates(bus: &Arc<EventBus>) -> thread::JoinHandle<Result<u32>> {
+        let joiner = bus.clone();
+        let handle = thread::spawn(move || {
+            joiner
+                .subscribe(Operation::LocationUpdates, handler(), || panic!("the updates were started twice"))
+                .map(|subscription| subscription.id())
+        });
+        while bus.registry().listeners.len() < 2 {
+            thread::yield_now();
+        }
+        handle
+    }
+
+    #[test]
+    fn listeners_joining_a_failing_start_get_its_error() {
+        let bus = Arc::new(EventBus::default());
+        let (release, gate) = mpsc::channel::<()>();
+        let starter = {
+            let bus = bus.clone();
+            thread::spawn(move || {
+                bus.subscribe(Operation::LocationUpdates, handler(), move || {
+                    gate.recv().ok();
+                    Err(Error::PermissionDenied)
+                })
+                .map(|subscription| subscription.id())
+            })
+        };
+        while !bus.registry().operations.contains_key(&Operation::LocationUpdates) {
+            thread::yield_now();
+        }
+        let joiner = join_updates(&bus);
+        release.send(()).unwrap();
+
+        assert!(matches!(starter.join().unwrap(), Err(Error::PermissionDenied)));
+        assert!(matches!(joiner.join().unwrap(), Err(Error::PermissionDenied)));
+        let registry = bus.registry();
+        assert!(registry.listeners.is_empty());
+        assert!(registry.operations.is_empty());
+        assert!(registry.failures.is_empty());
+    }
+
+    #[test]
+    fn listeners_joining_a_start_share_the_operation() {
+        let bus = Arc::new(EventBus::default());
+        let stops = Arc::new(AtomicUsize::new(0));
+        let (release, gate) = mpsc::channel::<()>();
+        let starter = {
+            let (bus, stops) = (bus.clone(), stops.clone());
+            thread::spawn(move || {
+                bus.subscribe(Operation::LocationUpdates, handler(), move || {
+                    gate.recv().ok();
+                    Ok(Box::new(move || {
+                        stops.fetch_add(1, Ordering::SeqCst);
+                    }))
+                })
+            })
+        };
+        while !bus.registry().operations.contains_key(&Operation::LocationUpdates) {
+            thread::yield_now();
+        }
+        let joiner = join_updates(&bus);
+        release.send(()).unwrap();
+
+        let first = starter.join().unwrap().unwrap();
+        assert!(joiner.join().unwrap().is_ok());
+        assert_eq!(stops.load(Ordering::SeqCst), 0);
+        drop(first);
+        assert_eq!(stops.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,547 @@
+//! Geodesy in plain Rust, so it gives the same results on every platform:
+//! distances and bearings, destination points, bounding boxes, polyline
+//! encoding, track simplification and track statistics.
+//!
+//! Distances are in meters and angles in degrees, bearings clockwise from true
+//! north.
+
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{Coordinates, Error, LocationData, Result, TrackStatistics};
+
+/// Mean radius of the Earth, in meters.
+pub const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// WGS 84 semi-major axis, in meters.
+const WGS84_A: f64 = 6_378_137.0;
+
+const WGS84_F: f64 = 1.0 / 298.257223563;
+
+/// Slowest speed counted as moving, in meters per second.
+const MOVING_SPEED: f64 = 0.5;
+
+/// Smallest climb or descent counted towards the elevation totals, in meters,
+/// so altitude noise doesn't add up.
+const ELEVATION_THRESHOLD: f64 = 3.0;
+
+fn normalize_longitude(longitude: f64) -> f64 {
+    (longitude + 540.0).rem_euclid(360.0) - 180.0
+}
+
+/// Great-circle distance on a sphere of the Earth's mean radius. Within 0.5% of
+/// the ellipsoidal distance.
+pub fn haversine_distance(from: Coordinates, to: Coordinates) -> f64 {
+    let lat1 = from.latitude.to_radians();
+    let lat2 = to.latitude.to_radians();
+    let delta_lat = (to.latitude - from.latitude).to_radians();
+    let delta_lon = (to.longitude - from.longitude).to_radians();
+
+    let a = (delta_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (delta_lon / 2.0).sin().powi(2);
+    EARTH_RADIUS * 2.0 * a.sqrt().atan2((1.0 - a).sqrt())
+}
+
+/// Distance on the WGS 84 ellipsoid by Vincenty's inverse formula, accurate to
+/// a millimeter. `None` for nearly antipodal points, where it doesn't converge.
+pub fn vincenty_distance(from: Coordinates, to: Coordinates) -> Option<f64> {
+    let b = (1.0 - WGS84_F) * WGS84_A;
+    let l = (to.longitude - from.longitude).to_radians();
+    let u1 = ((1.0 - WGS84_F) * from.latitude.to_radians().tan()).atan();
+    let u2 = ((1.0 - WGS84_F) * to.latitude.to_radians().tan()).atan();
+    let (sin_u1, cos_u1) = u1.sin_cos();
+    let (sin_u2, cos_u2) = u2.sin_cos();
+
+    let mut lambda = l;
+    for _ in 0..200 {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        let sin_sigma = (cos_u2 * sin_lambda).hypot(cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda);
+        if sin_sigma == 0.0 {
+            return Some(0.0);
+        }
+        let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        let sigma = sin_sigma.atan2(cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        let cos2_alpha = 1.0 - sin_alpha * sin_alpha;
+        // On the equator the geodesic follows it and cos²α is 0.
+        let cos_2sigma_m = if cos2_alpha == 0.0 {
+            0.0
+        } else {
+            cos_sigma - 2.0 * sin_u1 * sin_u2 / cos2_alpha
+        };
+        let c = WGS84_F / 16.0 * cos2_alpha * (4.0 + WGS84_F * (4.0 - 3.0 * cos2_alpha));
+        let previous = lambda;
+        lambda = l
+            + (1.0 - c)
+                * WGS84_F
+                * sin_alpha
+                * (sigma + c * sin_sigma * (cos_2sigma_m + c * cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))));
+        if lambda.abs() > std::f64::consts::PI {
+            return None;
+        }
+        if (lambda - previous).abs() < 1e-12 {
+            let u2 = cos2_alpha * (WGS84_A * WGS84_A - b * b) / (b * b);
+            let a = 1.0 + u2 / 16384.0 * (4096.0 + u2 * (-768.0 + u2 * (320.0 - 175.0 * u2)));
+            let b_coefficient = u2 / 1024.0 * (256.0 + u2 * (-128.0 + u2 * (74.0 - 47.0 * u2)));
+            let delta_sigma = b_coefficient
+                * sin_sigma
+                * (cos_2sigma_m
+                    + b_coefficient / 4.0
+                        * (cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))
+                            - b_coefficient / 6.0
+                                * cos_2sigma_m
+                                * (-3.0 + 4.0 * sin_sigma.powi(2))
+                                * (-3.0 + 4.0 * cos_2sigma_m.powi(2))));
+            return Some(b * a * (sigma - delta_sigma));
+        }
+    }
+    None
+}
+
+/// Ellipsoidal distance, falling back to the great-circle distance where
+/// Vincenty's formula doesn't converge.
+pub fn distance(from: Coordinates, to: Coordinates) -> f64 {
+    vincenty_distance(from, to).unwrap_or_else(|| haversine_distance(from, to))
+}
+
+/// Bearing at the start of the great circle from one point to another.
+pub fn initial_bearing(from: Coordinates, to: Coordinates) -> f64 {
+    let lat1 = from.latitude.to_radians();
+    let lat2 = to.latitude.to_radians();
+    let delta_lon = (to.longitude - from.longitude).to_radians();
+
+    let y = delta_lon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * delta_lon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Bearing on arrival at `to` along the great circle from `from`.
+pub fn final_bearing(from: Coordinates, to: Coordinates) -> f64 {
+    (initial_bearing(to, from) + 180.0).rem_euclid(360.0)
+}
+
+/// The point reached by going `distance` along the great circle that starts
+/// out at `bearing`.
+pub fn destination(from: Coordinates, distance: f64, bearing: f64) -> Coordinates {
+    let angle = distance / EARTH_RADIUS;
+    let bearing = bearing.to_radians();
+    let lat1 = from.latitude.to_radians();
+    let lon1 = from.longitude.to_radians();
+
+    let lat2 = (lat1.sin() * angle.cos() + lat1.cos() * angle.sin() * bearing.cos()).asin();
+    let lon2 = lon1 + (bearing.sin() * angle.sin() * lat1.cos()).atan2(angle.cos() - lat1.sin() * lat2.sin());
+    Coordinates {
+        latitude: lat2.to_degrees(),
+        longitude: normalize_longitude(lon2.to_degrees()),
+    }
+}
+
+/// A latitude and longitude range. `west` is greater than `east` when the box
+/// crosses the antimeridian.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BoundingBox {
+    pub south: f64,
+    pub west: f64,
+    pub north: f64,
+    pub east: f64,
+}
+
+impl BoundingBox {
+    /// The smallest box holding every point within `radius` of `center`. It
+    /// spans every longitude when the circle reaches a pole.
+    pub fn around(center: Coordinates, radius: f64) -> Self {
+        let angle = (radius / EARTH_RADIUS).to_degrees();
+        let south = center.latitude - angle;
+        let north = center.latitude + angle;
+        if south <= -90.0 || north >= 90.0 {
+            return Self {
+                south: south.max(-90.0),
+                west: -180.0,
+                north: north.min(90.0),
+                east: 180.0,
+            };
+        }
+
+        let delta_lon = ((radius / EARTH_RADIUS).sin() / center.latitude.to_radians().cos())
+            .min(1.0)
+            .asin()
+            .to_degrees();
+        if delta_lon >= 180.0 {
+            return Self {
+                south,
+                west: -180.0,
+                north,
+                east: 180.0,
+            };
+        }
+        Self {
+            south,
+            west: normalize_longitude(center.longitude - delta_lon),
+            north,
+            east: normalize_longitude(center.longitude + delta_lon),
+        }
+    }
+
+    /// The box from the smallest to the largest latitude and longitude of
+    /// `points`, or `None` without any.
+    pub fn of(points: &[Coordinates]) -> Option<Self> {
+        let first = points.first()?;
+        let mut bounds = Self {
+            south: first.latitude,
+            west: first.longitude,
+            north: first.latitude,
+            east: first.longitude,
+        };
+        for point in &points[1..] {
+            bounds.south = bounds.south.min(point.latitude);
+            bounds.north = bounds.north.max(point.latitude);
+            bounds.west = bounds.west.min(point.longitude);
+            bounds.east = bounds.east.max(point.longitude);
+        }
+        Some(bounds)
+    }
+
+    pub fn contains(&self, point: Coordinates) -> bool {
+        let longitude = if self.west <= self.east {
+            (self.west..=self.east).contains(&point.longitude)
+        } else {
+            point.longitude >= self.west || point.longitude <= self.east
+        };
+        longitude && (self.south..=self.north).contains(&point.latitude)
+    }
+}
+
+/// Encodes points with Google's polyline algorithm. `precision` is the number
+/// of decimals kept: 5 for Google, 6 for OSRM and Valhalla.
+pub fn encode_polyline(points: &[Coordinates], precision: u32) -> String {
+    let factor = 10f64.powi(precision as i32);
+    let mut encoded = String::new();
+    let mut previous = (0i64, 0i64);
+    for point in points {
+        let current = (
+            (point.latitude * factor).round() as i64,
+            (point.longitude * factor).round() as i64,
+        );
+        for delta in [current.0 - previous.0, current.1 - previous.1] {
+            let mut value = if delta < 0 { !(delta << 1) } else { delta << 1 };
+            while value >= 0x20 {
+                encoded.push(char::from((0x20 | (value & 0x1f)) as u8 + 63));
+                value >>= 5;
+            }
+            encoded.push(char::from(value as u8 + 63));
+        }
+        previous = current;
+    }
+    encoded
+}
+
+/// Decodes a polyline encoded with `precision` decimals.
+pub fn decode_polyline(polyline: &str, precision: u32) -> Result<Vec<Coordinates>> {
+    let factor = 10f64.powi(precision as i32);
+    let mut bytes = polyline.bytes();
+    let mut next_value = || -> Result<Option<i64>> {
+        let mut value = 0i64;
+        let mut shift = 0;
+        loop {
+            let Some(byte) = bytes.next() else {
+                return match shift {
+                    0 => Ok(None),
+                    _ => Err(Error::InvalidInput("Truncated polyline".to_string())),
+                };
+            };
+            if !(63..=126).contains(&byte) || shift > 60 {
+                return Err(Error::InvalidInput(format!("Invalid polyline character {:?}", byte as char)));
+            }
+            let chunk = (byte - 63) as i64;
+            value |= (chunk & 0x1f) << shift;
+            shift += 5;
+            if chunk < 0x20 {
+                return Ok(Some(if value & 1 == 1 { !(value >> 1) } else { value >> 1 }));
+            }
+        }
+    };
+
+    let mut points = vec![];
+    let (mut latitude, mut longitude) = (0i64, 0i64);
+    while let Some(delta_lat) = next_value()? {
+        let delta_lon = next_value()?.ok_or_else(|| Error::InvalidInput("Truncated polyline".to_string()))?;
+        latitude += delta_lat;
+        longitude += delta_lon;
+        points.push(Coordinates {
+            latitude: latitude as f64 / factor,
+            longitude: longitude as f64 / factor,
+        });
+    }
+    Ok(points)
+}
+
+/// Positions around a reference point in meters east and north of it, close
+/// enough to a plane over a few kilometers.
+pub(crate) struct LocalPlane {
+    origin: Coordinates,
+    cos_latitude: f64,
+}
+
+impl LocalPlane {
+    pub(crate) fn new(origin: Coordinates) -> Self {
+        Self {
+            origin,
+            cos_latitude: origin.latitude.to_radians().cos(),
+        }
+    }
+
+    pub(crate) fn project(&self, point: Coordinates) -> (f64, f64) {
+        // Across the antimeridian, go the short way round.
+        let delta_lon = normalize_longitude(point.longitude - self.origin.longitude);
+        let x = EARTH_RADIUS * delta_lon.to_radians() * self.cos_latitude;
+        let y = EARTH_RADIUS * (point.latitude - self.origin.latitude).to_radians();
+        (x, y)
+    }
+}
+
+/// Distance from the origin to the segment between two points.
+pub(crate) fn distance_to_segment((ax, ay): (f64, f64), (bx, by): (f64, f64)) -> f64 {
+    let (dx, dy) = (bx - ax, by - ay);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 {
+        (-(ax * dx + ay * dy) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (ax + t * dx).hypot(ay + t * dy)
+}
+
+/// Which points Douglas-Peucker simplification keeps.
+fn kept(points: &[Coordinates], tolerance: f64) -> Vec<bool> {
+    let mut keep = vec![points.len() < 3; points.len()];
+    if points.len() < 3 {
+        return keep;
+    }
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    let mut ranges = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = ranges.pop() {
+        let farthest = (first + 1..last)
+            .map(|index| {
+                let plane = LocalPlane::new(points[index]);
+                let offset = distance_to_segment(plane.project(points[first]), plane.project(points[last]));
+                (index, offset)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((index, offset)) = farthest {
+            if offset > tolerance {
+                keep[index] = true;
+                ranges.push((first, index));
+                ranges.push((index, last));
+            }
+        }
+    }
+    keep
+}
+
+/// Drops the points that stray less than `tolerance` meters from the line
+/// through the points kept around them, by the Douglas-Peucker algorithm. The
+/// first and last points are always kept.
+pub fn simplify(points: &[Coordinates], tolerance: f64) -> Vec<Coordinates> {
+    kept(points, tolerance)
+        .into_iter()
+        .zip(points)
+        .filter(|(keep, _)| *keep)
+        .map(|(_, point)| *point)
+        .collect()
+}
+
+/// [`simplify`] for recorded fixes, keeping the whole of each fix that stays.
+pub fn simplify_track(track: &[LocationData], tolerance: f64) -> Vec<LocationData> {
+    let points: Vec<Coordinates> = track.iter().map(|fix| fix.coordinates).collect();
+    kept(&points, tolerance)
+        .into_iter()
+        .zip(track)
+        .filter(|(keep, _)| *keep)
+        .map(|(_, fix)| fix.clone())
+        .collect()
+}
+
+/// Totals of a recorded track, in order of the fixes. Times come from the
+/// timestamps of the fixes, so pairs of fixes without valid ones only count
+/// towards the distance.
+pub fn track_statistics(track: &[LocationData]) -> TrackStatistics {
+    let mut statistics = TrackStatistics::default();
+    let time = |fix: &LocationData| DateTime::parse_from_rfc3339(&fix.timestamp).ok();
+
+    for pair in track.windows(2) {
+        let meters = distance(pair[0].coordinates, pair[1].coordinates);
+        statistics.distance += meters;
+        if let (Some(start), Some(end)) = (time(&pair[0]), time(&pair[1])) {
+            let seconds = (end - start).num_milliseconds() as f64 / 1000.0;
+            if seconds > 0.0 && meters / seconds >= MOVING_SPEED {
+                statistics.moving_time += seconds;
+            }
+        }
+    }
+    if let (Some(start), Some(end)) = (track.first().and_then(time), track.last().and_then(time)) {
+        statistics.elapsed_time = ((end - start).num_milliseconds() as f64 / 1000.0).max(0.0);
+    }
+    if statistics.moving_time > 0.0 {
+        statistics.average_speed = statistics.distance / statistics.moving_time;
+    }
+
+    // Altitudes only count once they have moved by the threshold from the last
+    // one counted.
+    let mut reference: Option<f64> = None;
+    for altitude in track.iter().filter_map(|fix| fix.altitude) {
+        let Some(last) = reference else {
+            reference = Some(altitude);
+            continue;
+        };
+        let change = altitude - last;
+        if change >= ELEVATION_THRESHOLD {
+            statistics.elevation_gain += change;
+            reference = Some(altitude);
+        } else if -change >= ELEVATION_THRESHOLD {
+            statistics.elevation_loss -= change;
+            reference = Some(altitude);
+        }
+    }
+    statistics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(latitude: f64, longitude: f64) -> Coordinates {
+        Coordinates { latitude, longitude }
+    }
+
+    fn dms(degrees: f64, minutes: f64, seconds: f64) -> f64 {
+        degrees.signum() * (degrees.abs() + minutes / 60.0 + seconds / 3600.0)
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!((actual - expected).abs() <= tolerance, "{actual} instead of {expected}");
+    }
+
+    #[test]
+    fn vincenty_matches_known_geodesics() {
+        // Flinders Peak to Buninyong, the example of Vincenty's paper.
+        let flinders_peak = point(dms(-37.0, 57.0, 3.72030), dms(144.0, 25.0, 29.52440));
+        let buninyong = point(dms(-37.0, 39.0, 10.15610), dms(143.0, 55.0, 35.38390));
+        assert_close(vincenty_distance(flinders_peak, buninyong).unwrap(), 54_972.271, 0.001);
+
+        // A degree along the equator, and the quarter meridian.
+        assert_close(vincenty_distance(point(0.0, 0.0), point(0.0, 1.0)).unwrap(), 111_319.491, 0.001);
+        assert_close(vincenty_distance(point(0.0, 0.0), point(90.0, 0.0)).unwrap(), 10_001_965.729, 0.001);
+        assert_eq!(vincenty_distance(buninyong, buninyong), Some(0.0));
+    }
+
+    #[test]
+    fn nearly_antipodal_points_fall_back_to_the_great_circle() {
+        let (from, to) = (point(0.0, 0.0), point(0.5, 179.7));
+        assert_eq!(vincenty_distance(from, to), None);
+        assert_eq!(distance(from, to), haversine_distance(from, to));
+        assert_close(haversine_distance(point(0.0, 0.0), point(0.0, 180.0)), std::f64::consts::PI * EARTH_RADIUS, 1e-6);
+    }
+
+    #[test]
+    fn destinations_lie_at_the_distance_and_bearing_given() {
+        let start = point(51.4778, -0.0015);
+        let end = destination(start, 100_000.0, 60.0);
+        assert_close(haversine_distance(start, end), 100_000.0, 1e-6);
+        assert_close(initial_bearing(start, end), 60.0, 1e-9);
+        assert_close(final_bearing(point(0.0, 0.0), point(0.0, 10.0)), 90.0, 1e-9);
+
+        // Going east across the antimeridian.
+        let across = destination(point(0.0, 179.5), 111_195.0, 90.0);
+        assert_close(across.longitude, -179.5, 1e-3);
+    }
+
+    #[test]
+    fn bounding_boxes_wrap_around_the_antimeridian() {
+        let around = BoundingBox::around(point(0.0, 179.9), 50_000.0);
+        assert!(around.west > around.east);
+        assert!(around.contains(point(0.0, -179.9)));
+        assert!(!around.contains(point(0.0, 0.0)));
+
+        let polar = BoundingBox::around(point(89.9, 0.0), 50_000.0);
+        assert_eq!((polar.west, polar.east, polar.north), (-180.0, 180.0, 90.0));
+        assert_eq!(BoundingBox::of(&[]), None);
+    }
+
+    #[test]
+    fn polylines_match_the_reference_encoding() {
+        let encoded = "_p~iF~ps|U_ulLnnqC_mqNvxq`@";
+        let points = [point(38.5, -120.2), point(40.7, -120.95), point(43.252, -126.453)];
+
+        let decoded = decode_polyline(encoded, 5).unwrap();
+        assert_eq!(decoded.len(), 3);
+        for (decoded, expected) in decoded.iter().zip(&points) {
+            assert_close(decoded.latitude, expected.latitude, 1e-9);
+            assert_close(decoded.longitude, expected.longitude, 1e-9);
+        }
+        assert_eq!(encode_polyline(&points, 5), encoded);
+
+        let precise = [point(52.520_008, 13.404_954), point(-33.868_820, 151.209_295)];
+        let decoded = decode_polyline(&encode_polyline(&precise, 6), 6).unwrap();
+        assert_close(decoded[1].longitude, 151.209_295, 1e-9);
+
+        assert!(decode_polyline("_p~iF~ps|U_", 5).is_err());
+        assert!(decode_polyline("_p~iF", 5).is_err());
+        assert!(decode_polyline("_p~iF ~ps|U", 5).is_err());
+        assert!(decode_polyline("", 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn douglas_peucker_keeps_the_corners() {
+        // Along the equator with half a meter of noise, then north.
+        let path = [
+            point(0.0, 0.0),
+            point(0.0, 0.001),
+            point(0.000_005, 0.002),
+            point(0.0, 0.003),
+            point(0.001, 0.003),
+            point(0.002, 0.003),
+        ];
+
+        let simplified = simplify(&path, 5.0);
+        assert_eq!(latitudes_and_longitudes(&simplified), latitudes_and_longitudes(&[path[0], path[3], path[5]]));
+        assert_eq!(simplify(&path, 0.1).len(), 5);
+        assert_eq!(simplify(&path[..2], 1000.0).len(), 2);
+        assert_eq!(simplify(&path, 1_000_000.0).len(), 2);
+    }
+
+    fn latitudes_and_longitudes(points: &[Coordinates]) -> Vec<(f64, f64)> {
+        points.iter().map(|point| (point.latitude, point.longitude)).collect()
+    }
+
+    #[test]
+    fn track_statistics_ignore_altitude_noise() {
+        let fix = |longitude: f64, altitude: f64, timestamp: &str| LocationData {
+            coordinates: point(0.0, longitude),
+            altitude: Some(altitude),
+            accuracy: 5.0,
+            altitude_accuracy: None,
+            heading: None,
+            speed: None,
+            timestamp: timestamp.to_string(),
+            floor: None,
+        };
+        let track = [
+            fix(0.0, 100.0, "2025-01-01T00:00:00Z"),
+            fix(0.001, 102.0, "2025-01-01T00:01:00Z"),
+            fix(0.001, 101.0, "2025-01-01T00:02:00Z"),
+            fix(0.002, 110.0, "2025-01-01T00:03:00Z"),
+            fix(0.003, 104.0, "2025-01-01T00:04:00Z"),
+        ];
+
+        let statistics = track_statistics(&track);
+        let leg = vincenty_distance(point(0.0, 0.0), point(0.0, 0.001)).unwrap();
+        assert_close(statistics.distance, 3.0 * leg, 1e-6);
+        assert_eq!(statistics.elapsed_time, 240.0);
+        // The minute spent standing still doesn't count as moving.
+        assert_eq!(statistics.moving_time, 180.0);
+        assert_close(statistics.average_speed, 3.0 * leg / 180.0, 1e-9);
+        assert_eq!((statistics.elevation_gain, statistics.elevation_loss), (10.0, 6.0));
+    }
+}
@@ -0,0 +1,730 @@
+//! Geofencing evaluated in Rust.
+//!
+//! iOS monitors at most 20 circular regions per app. The [`GeofenceEngine`]
+//! instead checks every location update against any number of circles and
+//! polygons, waits out dwell times and applies hysteresis at the boundary. It
+//! only sees the fixes and the time it is given, so it can be driven by
+//! synthetic [`LocationData`] sequences as well as by the location updates of
+//! [`Location::watch_geofences`](crate::Location::watch_geofences).
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex, MutexGuard,
+    },
+    thread,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::{
+    events::EventBus,
+    geo::{distance_to_segment, LocalPlane},
+    Coordinates, Error, Geofence, GeofenceShape, LocationData, LocationEvent, Region, Result,
+};
+
+const DEFAULT_HYSTERESIS: f64 = 10.0;
+
+fn valid_coordinates(coordinates: &Coordinates) -> bool {
+    (-90.0..=90.0).contains(&coordinates.latitude) && (-180.0..=180.0).contains(&coordinates.longitude)
+}
+
+impl GeofenceShape {
+    /// Distance in meters from `point` to the boundary, negative inside.
+    pub fn signed_distance(&self, point: Coordinates) -> f64 {
+        let plane = LocalPlane::new(point);
+        match self {
+            GeofenceShape::Circle { center, radius } => {
+                let (x, y) = plane.project(*center);
+                x.hypot(y) - radius
+            }
+            GeofenceShape::Polygon { vertices } => {
+                let corners: Vec<(f64, f64)> = vertices.iter().map(|vertex| plane.project(*vertex)).collect();
+                let distance = corners
+                    .iter()
+                    .zip(corners.iter().cycle().skip(1))
+                    .map(|(a, b)| distance_to_segment(*a, *b))
+                    .fold(f64::INFINITY, f64::min);
+
+                // Seen from far away, a polygon across the antimeridian would be
+                // split in two, so the inside is found around its first vertex.
+                let anchor = LocalPlane::new(vertices[0]);
+                let (x, y) = anchor.project(point);
+                let ring: Vec<(f64, f64)> = vertices.iter().map(|vertex| anchor.project(*vertex)).collect();
+                let mut inside = false;
+                for (index, &a) in ring.iter().enumerate() {
+                    let b = ring[(index + 1) % ring.len()];
+                    // Casts a ray from the point along +x and counts the edges it crosses.
+                    if (a.1 > y) != (b.1 > y) && a.0 + (y - a.1) * (b.0 - a.0) / (b.1 - a.1) > x {
+                        inside = !inside;
+                    }
+                }
+                if inside {
+                    -distance
+                } else {
+                    distance
+                }
+            }
+        }
+    }
+
+    pub fn contains(&self, point: Coordinates) -> bool {
+        self.signed_distance(point) <= 0.0
+    }
+}
+
+impl Geofence {
+    fn validate(&self) -> Result<()> {
+        if self.identifier.is_empty() {
+            return Err(Error::InvalidInput("A geofence needs an identifier".to_string()));
+        }
+        let invalid = |message: &str| Error::InvalidInput(format!("Geofence {}: {message}", self.identifier));
+        match &self.shape {
+            GeofenceShape::Circle { center, radius } => {
+                if !valid_coordinates(center) {
+                    return Err(invalid("invalid center"));
+                }
+                if !radius.is_finite() || *radius <= 0.0 {
+                    return Err(invalid("the radius must be positive"));
+                }
+            }
+            GeofenceShape::Polygon { vertices } => {
+                if vertices.len() < 3 {
+                    return Err(invalid("a polygon needs at least 3 vertices"));
+                }
+                if !vertices.iter().all(valid_coordinates) {
+                    return Err(invalid("invalid vertex"));
+                }
+            }
+        }
+        if self.hysteresis.is_some_and(|hysteresis| !hysteresis.is_finite() || hysteresis < 0.0) {
+            return Err(invalid("the hysteresis can't be negative"));
+        }
+        Ok(())
+    }
+
+    /// The geofence as reported in `RegionEntered` and `RegionExited` events.
+    /// Polygons are described by the circle around them and their vertices.
+    pub fn region(&self) -> Region {
+        let (center, radius, vertices) = match &self.shape {
+            GeofenceShape::Circle { center, radius } => (*center, *radius, vec![]),
+            GeofenceShape::Polygon { vertices } => {
+                let count = vertices.len().max(1) as f64;
+                let center = Coordinates {
+                    latitude: vertices.iter().map(|vertex| vertex.latitude).sum::<f64>() / count,
+                    longitude: vertices.iter().map(|vertex| vertex.longitude).sum::<f64>() / count,
+                };
+                let plane = LocalPlane::new(center);
+                let radius = vertices
+                    .iter()
+                    .map(|vertex| {
+                        let (x, y) = plane.project(*vertex);
+                        x.hypot(y)
+                    })
+                    .fold(0.0, f64::max);
+                (center, radius, vertices.clone())
+            }
+        };
+        Region {
+            identifier: self.identifier.clone(),
+            center,
+            radius,
+            notify_on_entry: self.notify_on_entry,
+            notify_on_exit: self.notify_on_exit,
+            vertices,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Fence {
+    geofence: Geofence,
+    /// Whether the device was last reported inside.
+    inside: bool,
+    /// When the device crossed in, while it waits out the dwell time.
+    arrived: Option<DateTime<Utc>>,
+    /// Whether the last fix was within the shape, so the dwell time can run out
+    /// without another fix.
+    within: bool,
+}
+
+impl Fence {
+    fn dwell_time(&self) -> TimeDelta {
+        TimeDelta::milliseconds(self.geofence.dwell_time.unwrap_or_default() as i64)
+    }
+
+    /// Reports entering once the device has stayed within for the dwell time.
+    fn settle(&mut self, time: DateTime<Utc>, events: &mut Vec<LocationEvent>) {
+        let Some(arrived) = self.arrived else {
+            return;
+        };
+        if self.within && time - arrived >= self.dwell_time() {
+            self.inside = true;
+            self.arrived = None;
+            if self.geofence.notify_on_entry {
+                events.push(LocationEvent::RegionEntered(self.geofence.region()));
+            }
+        }
+    }
+}
+
+/// What is persisted across launches: the geofences and which of them the
+/// device is inside, so relaunching inside one doesn't report entering it again.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Snapshot {
+    geofences: Vec<Geofence>,
+    inside: Vec<String>,
+}
+
+/// Tracks the device against a set of geofences, one fix at a time.
+#[derive(Clone, Default)]
+pub struct GeofenceEngine {
+    fences: BTreeMap<String, Fence>,
+}
+
+impl GeofenceEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a geofence, replacing the one with the same identifier. The device
+    /// starts outside it and is reported entering with the next fix inside.
+    pub fn add(&mut self, geofence: Geofence) -> Result<()> {
+        geofence.validate()?;
+        self.fences.insert(
+            geofence.identifier.clone(),
+            Fence {
+                geofence,
+                inside: false,
+                arrived: None,
+                within: false,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn remove(&mut self, identifier: &str) -> Option<Geofence> {
+        self.fences.remove(identifier).map(|fence| fence.geofence)
+    }
+
+    pub fn clear(&mut self) {
+        self.fences.clear();
+    }
+
+    pub fn geofences(&self) -> Vec<Geofence> {
+        self.fences.values().map(|fence| fence.geofence.clone()).collect()
+    }
+
+    /// Identifiers of the geofences the device was last reported inside.
+    pub fn inside(&self) -> Vec<String> {
+        self.fences
+            .values()
+            .filter(|fence| fence.inside)
+            .map(|fence| fence.geofence.identifier.clone())
+            .collect()
+    }
+
+    /// Moves the device to `location` and returns the `RegionEntered` and
+    /// `RegionExited` events this causes, ordered by identifier. Dwell times are
+    /// measured with the timestamps of the fixes, or the current time for fixes
+    /// without a valid one.
+    pub fn update(&mut self, location: &LocationData) -> Vec<LocationEvent> {
+        let time = DateTime::parse_from_rfc3339(&location.timestamp)
+            .map(|time| time.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now());
+
+        let mut events = vec![];
+        for fence in self.fences.values_mut() {
+            let geofence = &fence.geofence;
+            let distance = geofence.shape.signed_distance(location.coordinates);
+            let hysteresis = geofence.hysteresis.unwrap_or(DEFAULT_HYSTERESIS);
+            fence.within = distance <= 0.0;
+
+            if fence.inside {
+                if distance > hysteresis {
+                    fence.inside = false;
+                    if geofence.notify_on_exit {
+                        events.push(LocationEvent::RegionExited(geofence.region()));
+                    }
+                }
+                continue;
+            }
+
+            if distance > 0.0 {
+                // A device waiting out the dwell time has to leave for good too.
+                if distance > hysteresis {
+                    fence.arrived = None;
+                }
+                continue;
+            }
+            fence.arrived.get_or_insert(time);
+            fence.settle(time, &mut events);
+        }
+        events
+    }
+
+    /// Advances the time to `now` without a new fix and returns the
+    /// `RegionEntered` events of the geofences whose dwell time ran out while
+    /// the device stayed within them.
+    pub fn tick(&mut self, now: DateTime<Utc>) -> Vec<LocationEvent> {
+        let mut events = vec![];
+        for fence in self.fences.values_mut().filter(|fence| !fence.inside) {
+            fence.settle(now, &mut events);
+        }
+        events
+    }
+
+    /// When the next dwell time runs out, if the device is waiting one out.
+    pub fn next_deadline(&self) -> Option<DateTime<Utc>> {
+        self.fences
+            .values()
+            .filter(|fence| !fence.inside && fence.within)
+            .filter_map(|fence| Some(fence.arrived? + fence.dwell_time()))
+            .min()
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            geofences: self.geofences(),
+            inside: self.inside(),
+        }
+    }
+
+    fn restore(snapshot: Snapshot) -> Self {
+        let mut engine = Self::new();
+        for geofence in snapshot.geofences {
+            let inside = snapshot.inside.contains(&geofence.identifier);
+            let identifier = geofence.identifier.clone();
+            if engine.add(geofence).is_ok() {
+                if let Some(fence) = engine.fences.get_mut(&identifier) {
+                    fence.inside = inside;
+                }
+            }
+        }
+        engine
+    }
+}
+
+fn lock(engine: &Mutex<GeofenceEngine>) -> MutexGuard<'_, GeofenceEngine> {
+    engine.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn save(path: Option<&Path>, snapshot: &Snapshot) -> Result<()> {
+    let Some(path) = path else {
+        return Ok(());
+    };
+    let failed = |e: &dyn std::fmt::Display| Error::OperationFailed(format!("Failed to save geofences: {e}"));
+    let data = serde_json::to_vec(snapshot).map_err(|e| failed(&e))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| failed(&e))?;
+    }
+    // Replace the file atomically so a crash can't leave it half written.
+    let temp = path.with_extension("json.tmp");
+    fs::write(&temp, data).map_err(|e| failed(&e))?;
+    fs::rename(&temp, path).map_err(|e| failed(&e))
+}
+
+/// Runs `f` on the engine and delivers the events it returns.
+fn evaluate(
+    engine: &Mutex<GeofenceEngine>,
+    path: Option<&Path>,
+    events: &EventBus,
+    f: impl FnOnce(&mut GeofenceEngine) -> Vec<LocationEvent>,
+) {
+    let crossings = {
+        let mut engine = lock(engine);
+        let inside = engine.inside();
+        let crossings = f(&mut engine);
+        // Only crossings change what is saved; failing to save them just means
+        // they may be reported again after a relaunch.
+        if engine.inside() != inside {
+            let _ = save(path, &engine.snapshot());
+        }
+        crossings
+    };
+
+    for event in &crossings {
+        events.dispatch(event);
+    }
+}
+
+/// Lets dwell times run out while no fixes arrive, until `stop` is dropped.
+fn run_timer(
+    engine: Arc<Mutex<GeofenceEngine>>,
+    path: Option<PathBuf>,
+    events: Arc<EventBus>,
+    mut deadline: DateTime<Utc>,
+    stop: Receiver<()>,
+) {
+    loop {
+        let wait = (deadline - Utc::now()).to_std().unwrap_or_default();
+        match stop.recv_timeout(wait) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+        }
+        evaluate(&engine, path.as_deref(), &events, |engine| engine.tick(Utc::now()));
+        match lock(&engine).next_deadline() {
+            Some(next) => deadline = next,
+            None => return,
+        }
+    }
+}
+
+/// The geofences of the app, persisted in its data directory.
+///
+/// Obtained through `app.location().geofences()`. Changes are saved right away;
+/// the events are delivered to the subscriptions of
+/// [`Location::watch_geofences`](crate::Location::watch_geofences). A device
+/// that stays inside a geofence is reported entering it once the dwell time
+/// runs out, even if no further fix arrives.
+pub struct Geofences {
+    engine: Arc<Mutex<GeofenceEngine>>,
+    path: Option<PathBuf>,
+    events: Arc<EventBus>,
+    /// The dwell time the timer waits for. Dropping the sender stops it.
+    timer: Mutex<Option<(DateTime<Utc>, Sender<()>)>>,
+}
+
+impl Geofences {
+    /// Loads the geofences saved by a previous launch. An unreadable file is
+    /// ignored rather than failing the plugin, and replaced on the next change.
+    pub(crate) fn load<R: Runtime>(app: &AppHandle<R>, events: Arc<EventBus>) -> Self {
+        let path = app
+            .path()
+            .app_data_dir()
+            .ok()
+            .map(|dir| dir.join("geofences.json"));
+        let snapshot = path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self::new(GeofenceEngine::restore(snapshot), path, events)
+    }
+
+    fn new(engine: GeofenceEngine, path: Option<PathBuf>, events: Arc<EventBus>) -> Self {
+        Self {
+            engine: Arc::new(Mutex::new(engine)),
+            path,
+            events,
+            timer: Mutex::new(None),
+        }
+    }
+
+    fn engine(&self) -> MutexGuard<'_, GeofenceEngine> {
+        lock(&self.engine)
+    }
+
+    /// Applies `f` and persists the result. Nothing is changed if saving fails.
+    fn write<T>(&self, f: impl FnOnce(&mut GeofenceEngine) -> Result<T>) -> Result<T> {
+        let mut engine = self.engine();
+        let mut updated = engine.clone();
+        let result = f(&mut updated)?;
+        self.save(&updated.snapshot())?;
+        *engine = updated;
+        Ok(result)
+    }
+
+    fn save(&self, snapshot: &Snapshot) -> Result<()> {
+        save(self.path.as_deref(), snapshot)
+    }
+
+    /// Sets the timer to the next dwell time that runs out, if it isn't already.
+    fn schedule(&self) {
+        let deadline = self.engine().next_deadline();
+        let mut timer = self.timer.lock().unwrap_or_else(|e| e.into_inner());
+        if timer.as_ref().map(|(current, _)| *current) == deadline {
+            return;
+        }
+        *timer = None;
+        let Some(deadline) = deadline else {
+            return;
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let (engine, path, events) = (self.engine.clone(), self.path.clone(), self.events.clone());
+        let spawned = thread::Builder::new()
+            .name("geofence-dwell".to_string())
+            .spawn(move || run_timer(engine, path, events, deadline, receiver));
+        if spawned.is_ok() {
+            *timer = Some((deadline, sender));
+        }
+    }
+
+    /// Adds a geofence, replacing the one with the same identifier.
+    pub fn add(&self, geofence: Geofence) -> Result<()> {
+        self.write(|engine| engine.add(geofence))
+    }
+
+    pub fn remove(&self, identifier: &str) -> Result<()> {
+        self.write(|engine| {
+            engine
+                .remove(identifier)
+                .map(drop)
+                .ok_or_else(|| Error::InvalidInput(format!("No geofence with identifier {identifier}")))
+        })
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        self.write(|engine| {
+            engine.clear();
+            Ok(())
+        })
+    }
+
+    pub fn list(&self) -> Vec<Geofence> {
+        self.engine().geofences()
+    }
+
+    /// Evaluates a fix and delivers the events it causes. Fixes normally come
+    /// from the location updates `watch_geofences` runs.
+    pub fn update(&self, location: &LocationData) {
+        evaluate(&self.engine, self.path.as_deref(), &self.events, |engine| engine.update(location));
+        self.schedule();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::track::format_timestamp;
+
+    fn point(latitude: f64, longitude: f64) -> Coordinates {
+        Coordinates { latitude, longitude }
+    }
+
+    fn fix(coordinates: Coordinates, time: DateTime<Utc>) -> LocationData {
+        LocationData {
+            coordinates,
+            altitude: None,
+            accuracy: 5.0,
+            altitude_accuracy: None,
+            heading: None,
+            speed: None,
+            timestamp: format_timestamp(time),
+            floor: None,
+        }
+    }
+
+    fn circle(identifier: &str, radius: f64) -> Geofence {
+        Geofence {
+            identifier: identifier.to_string(),
+            shape: GeofenceShape::Circle {
+                center: point(0.0, 0.0),
+                radius,
+            },
+            notify_on_entry: true,
+            notify_on_exit: true,
+            dwell_time: None,
+            hysteresis: None,
+        }
+    }
+
+    /// A point `meters` north of the center of [`circle`].
+    fn north(meters: f64) -> Coordinates {
+        point((meters / crate::geo::EARTH_RADIUS).to_degrees(), 0.0)
+    }
+
+    fn start() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2025-03-01T12:00:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    fn seconds(seconds: i64) -> DateTime<Utc> {
+        start() + TimeDelta::seconds(seconds)
+    }
+
+    fn describe(events: &[LocationEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| match event {
+                LocationEvent::RegionEntered(region) => format!("entered {}", region.identifier),
+                LocationEvent::RegionExited(region) => format!("exited {}", region.identifier),
+                _ => "other".to_string(),
+            })
+            .collect()
+    }
+
+    fn update(engine: &mut GeofenceEngine, coordinates: Coordinates, time: DateTime<Utc>) -> Vec<String> {
+        describe(&engine.update(&fix(coordinates, time)))
+    }
+
+    #[test]
+    fn crossings_are_reported_once_each_way() {
+        let mut engine = GeofenceEngine::new();
+        engine.add(circle("home", 100.0)).unwrap();
+        engine
+            .add(Geofence {
+                notify_on_exit: false,
+                ..circle("yard", 200.0)
+            })
+            .unwrap();
+
+        assert!(update(&mut engine, north(500.0), seconds(0)).is_empty());
+        assert_eq!(update(&mut engine, north(150.0), seconds(1)), ["entered yard"]);
+        assert_eq!(update(&mut engine, north(0.0), seconds(2)), ["entered home"]);
+        assert!(update(&mut engine, north(50.0), seconds(3)).is_empty());
+        assert_eq!(engine.inside(), ["home", "yard"]);
+        assert_eq!(update(&mut engine, north(500.0), seconds(4)), ["exited home"]);
+        assert!(engine.inside().is_empty());
+    }
+
+    #[test]
+    fn jitter_at_the_boundary_is_absorbed_by_the_hysteresis() {
+        let mut engine = GeofenceEngine::new();
+        engine.add(circle("home", 100.0)).unwrap();
+        engine
+            .add(Geofence {
+                hysteresis: Some(0.0),
+                ..circle("bare", 100.0)
+            })
+            .unwrap();
+
+        assert_eq!(update(&mut engine, north(99.0), seconds(0)), ["entered bare", "entered home"]);
+        let mut events = vec![];
+        for second in 1..10 {
+            let meters = if second % 2 == 0 { 99.0 } else { 105.0 };
+            events.extend(update(&mut engine, north(meters), seconds(second)));
+        }
+        assert!(events.iter().all(|event| event.ends_with("bare")), "{events:?}");
+        assert_eq!(events.len(), 9);
+        assert_eq!(update(&mut engine, north(111.0), seconds(10)), ["exited home"]);
+    }
+
+    #[test]
+    fn leaving_before_the_dwell_time_reports_nothing() {
+        let mut engine = GeofenceEngine::new();
+        engine
+            .add(Geofence {
+                dwell_time: Some(60_000),
+                ..circle("office", 100.0)
+            })
+            .unwrap();
+
+        assert!(update(&mut engine, north(0.0), seconds(0)).is_empty());
+        assert_eq!(engine.next_deadline(), Some(seconds(60)));
+        assert!(update(&mut engine, north(500.0), seconds(30)).is_empty());
+        assert_eq!(engine.next_deadline(), None);
+        assert!(describe(&engine.tick(seconds(90))).is_empty());
+
+        // The dwell time starts over with the next arrival.
+        assert!(update(&mut engine, north(0.0), seconds(100)).is_empty());
+        assert!(update(&mut engine, north(10.0), seconds(150)).is_empty());
+        assert_eq!(update(&mut engine, north(20.0), seconds(160)), ["entered office"]);
+    }
+
+    #[test]
+    fn the_dwell_time_runs_out_without_another_fix() {
+        let mut engine = GeofenceEngine::new();
+        engine
+            .add(Geofence {
+                dwell_time: Some(60_000),
+                ..circle("office", 100.0)
+            })
+            .unwrap();
+
+        assert!(update(&mut engine, north(0.0), seconds(0)).is_empty());
+        assert!(describe(&engine.tick(seconds(59))).is_empty());
+        assert_eq!(describe(&engine.tick(seconds(60))), ["entered office"]);
+        assert_eq!(engine.next_deadline(), None);
+
+        // Waiting just outside, within the hysteresis, doesn't count.
+        engine.add(circle("other", 100.0)).unwrap();
+        engine
+            .add(Geofence {
+                dwell_time: Some(60_000),
+                ..circle("office", 100.0)
+            })
+            .unwrap();
+        assert_eq!(update(&mut engine, north(0.0), seconds(100)), ["entered other"]);
+        assert!(update(&mut engine, north(105.0), seconds(110)).is_empty());
+        assert!(describe(&engine.tick(seconds(200))).is_empty());
+    }
+
+    #[test]
+    fn polygons_may_cross_the_antimeridian() {
+        let mut engine = GeofenceEngine::new();
+        let date_line = Geofence {
+            identifier: "date line".to_string(),
+            shape: GeofenceShape::Polygon {
+                vertices: vec![point(1.0, 179.0), point(1.0, -179.0), point(-1.0, -179.0), point(-1.0, 179.0)],
+            },
+            notify_on_entry: true,
+            notify_on_exit: true,
+            dwell_time: None,
+            hysteresis: None,
+        };
+        assert!(date_line.shape.contains(point(0.0, 179.9)));
+        assert!(date_line.shape.contains(point(0.0, -179.9)));
+        assert!(!date_line.shape.contains(point(0.0, 0.0)));
+        assert!(!date_line.shape.contains(point(0.0, 178.0)));
+        assert!(!date_line.shape.contains(point(2.0, 180.0)));
+        engine.add(date_line).unwrap();
+
+        assert_eq!(update(&mut engine, point(0.0, 179.5), seconds(0)), ["entered date line"]);
+        assert!(update(&mut engine, point(0.0, -179.5), seconds(1)).is_empty());
+        assert_eq!(update(&mut engine, point(0.0, -178.0), seconds(2)), ["exited date line"]);
+    }
+
+    #[test]
+    fn a_restored_engine_remembers_where_the_device_is() {
+        let mut engine = GeofenceEngine::new();
+        engine.add(circle("home", 100.0)).unwrap();
+        engine.add(circle("yard", 200.0)).unwrap();
+        engine
+            .add(Geofence {
+                identifier: "away".to_string(),
+                shape: GeofenceShape::Circle {
+                    center: point(10.0, 10.0),
+                    radius: 100.0,
+                },
+                ..circle("", 100.0)
+            })
+            .unwrap();
+        update(&mut engine, north(150.0), seconds(0));
+
+        let saved = serde_json::to_string(&engine.snapshot()).unwrap();
+        let mut restored = GeofenceEngine::restore(serde_json::from_str(&saved).unwrap());
+        assert_eq!(restored.geofences().len(), 3);
+        assert_eq!(restored.inside(), ["yard"]);
+        assert!(update(&mut restored, north(150.0), seconds(1)).is_empty());
+        assert_eq!(update(&mut restored, north(1000.0), seconds(2)), ["exited yard"]);
+
+        // Invalid geofences in a damaged file are left out.
+        let damaged = r#"{"geofences": [{"identifier": "", "shape": {"type": "circle",
+            "center": {"latitude": 0, "longitude": 0}, "radius": 10},
+            "notifyOnEntry": true, "notifyOnExit": true}], "inside": [""]}"#;
+        assert!(GeofenceEngine::restore(serde_json::from_str(damaged).unwrap()).geofences().is_empty());
+    }
+
+    #[test]
+    fn watched_geofences_are_entered_when_the_dwell_time_runs_out() {
+        let events = Arc::new(EventBus::default());
+        let mut engine = GeofenceEngine::new();
+        engine
+            .add(Geofence {
+                dwell_time: Some(200),
+                ..circle("office", 100.0)
+            })
+            .unwrap();
+        let geofences = Geofences::new(engine, None, events.clone());
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let _subscription = events.listen(Arc::new(move |event: &LocationEvent| {
+            let _ = sender.lock().unwrap().send(describe(std::slice::from_ref(event)));
+        }));
+        geofences.update(&fix(north(0.0), Utc::now()));
+
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(2)).unwrap(), ["entered office"]);
+        assert_eq!(geofences.engine().inside(), ["office"]);
+    }
+}
@@ -6,14 +6,27 @@ use tauri::{
 pub use models::*;
 
 mod error;
+mod events;
+pub mod geo;
+mod geofence;
 mod models;
 
 pub use error::{Error, Result};
+pub use events::Subscription;
+pub use geofence::{GeofenceEngine, Geofences};
 
 #[cfg(desktop)]
 mod desktop;
 #[cfg(mobile)]
 mod mobile;
+#[cfg(desktop)]
+mod simulator;
+#[cfg(desktop)]
+mod track;
+pub mod wmm;
+
+#[cfg(desktop)]
+pub use simulator::Simulator;
 
 mod commands;
 
@@ -37,6 +50,17 @@ type LocationImpl<R> = desktop::Location<R>;
 type LocationImpl<R> = mobile::Location<R>;
 
 impl<R: Runtime> Location<R> {
+    /// The simulated location that stands in for the device on desktop.
+    #[cfg(desktop)]
+    pub fn simulator(&self) -> &Simulator {
+        self.0.simulator()
+    }
+
+    /// The geofences evaluated in Rust, see [`Location::watch_geofences`].
+    pub fn geofences(&self) -> &Geofences {
+        &self.0.geofences
+    }
+
     pub fn check_permissions(&self) -> Result<LocationPermissions> {
         self.0.check_permissions()
     }
@@ -49,20 +73,10 @@ impl<R: Runtime> Location<R> {
         self.0.get_current_location(options)
     }
 
-    pub fn start_location_updates(&self, options: LocationOptions) -> Result<()> {
-        self.0.start_location_updates(options)
-    }
-
-    pub fn stop_location_updates(&self) -> Result<()> {
-        self.0.stop_location_updates()
-    }
-
-    pub fn start_monitoring_region(&self, region: Region) -> Result<()> {
-        self.0.start_monitoring_region(region)
-    }
-
-    pub fn stop_monitoring_region(&self, identifier: &str) -> Result<()> {
-        self.0.stop_monitoring_region(identifier)
+    /// The heading of the device once the compass has a reliable reading, or an
+    /// error after `options.timeout`.
+    pub fn get_current_heading(&self, options: HeadingOptions) -> Result<Heading> {
+        self.0.get_current_heading(options)
     }
 
     pub fn geocode_address(&self, address: &str) -> Result<Vec<GeocodingResult>> {
@@ -73,14 +87,16 @@ impl<R: Runtime> Location<R> {
         self.0.reverse_geocode(location)
     }
 
+    /// Distance in meters between two points on the WGS 84 ellipsoid. See the
+    /// [`geo`] module for the other geodesy utilities.
     pub fn get_distance(&self, from: Coordinates, to: Coordinates) -> Result<f64> {
-        self.0.get_distance(from, to)
+        Ok(geo::distance(from, to))
     }
 }
 
 /// Initializes the plugin.
-pub fn init<R: Runtime>() -> TauriPlugin<R> {
-    Builder::new("ios-location")
+pub fn init<R: Runtime>() -> TauriPlugin<R, Option<Config>> {
+    Builder::<R, Option<Config>>::new("ios-location")
         .invoke_handler(tauri::generate_handler![
             commands::check_permissions,
             commands::request_permissions,
@@ -92,11 +108,20 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             commands::start_monitoring_region,
             commands::stop_monitoring_region,
             commands::get_monitored_regions,
+            commands::get_current_heading,
             commands::start_heading_updates,
             commands::stop_heading_updates,
             commands::geocode_address,
             commands::reverse_geocode,
             commands::get_distance,
+            commands::add_geofence,
+            commands::remove_geofence,
+            commands::get_geofences,
+            commands::clear_geofences,
+            commands::watch_geofences,
+            commands::unwatch_geofences,
+            commands::listen,
+            commands::unlisten,
         ])
         .setup(|app, api| {
             #[cfg(mobile)]
@@ -105,6 +130,7 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             let location = desktop::init(app, api)?;
             
             app.manage(Location(location));
+            app.manage(events::JsSubscriptions::default());
             Ok(())
         })
         .build()
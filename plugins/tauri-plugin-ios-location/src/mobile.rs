@@ -1,10 +1,13 @@
+use std::sync::Arc;
+
 use serde::de::DeserializeOwned;
 use tauri::{
+    ipc::{Channel, InvokeResponseBody},
     plugin::{PluginApi, PluginHandle},
     AppHandle, Runtime,
 };
 
-use crate::{models::*, Result};
+use crate::{events::EventBus, geofence::Geofences, models::*, Result};
 
 #[cfg(target_os = "ios")]
 tauri::ios_plugin_binding!(init_plugin_ios_location);
@@ -18,45 +21,89 @@ pub fn init<R: Runtime, C: DeserializeOwned>(
     #[cfg(target_os = "android")]
     let handle = api.register_android_plugin("com.tauri.plugins.location", "LocationPlugin")?;
     
-    Ok(Location(handle))
+    // All native events arrive through a single channel and are fanned out to
+    // Rust and JS subscribers by the event bus.
+    let events = Arc::new(EventBus::default());
+    let bus = events.clone();
+    let handler = Channel::new(move |body: InvokeResponseBody| {
+        bus.dispatch(&body.deserialize::<LocationEvent>()?);
+        Ok(())
+    });
+    
+    #[derive(serde::Serialize)]
+    struct Args {
+        handler: Channel,
+    }
+    
+    handle.run_mobile_plugin::<()>("setEventHandler", Args { handler })?;
+    
+    Ok(Location {
+        app: app.clone(),
+        handle,
+        geofences: Geofences::load(app, events.clone()),
+        events,
+    })
 }
 
 /// Access to the location APIs on mobile.
-pub struct Location<R: Runtime>(PluginHandle<R>);
+pub struct Location<R: Runtime> {
+    pub(crate) app: AppHandle<R>,
+    handle: PluginHandle<R>,
+    pub(crate) events: Arc<EventBus>,
+    pub(crate) geofences: Geofences,
+}
 
 impl<R: Runtime> Location<R> {
     pub fn check_permissions(&self) -> Result<LocationPermissions> {
-        self.0
+        self.handle
             .run_mobile_plugin("checkPermissions", ())
             .map_err(Into::into)
     }
 
     pub fn request_permissions(&self, request: PermissionRequest) -> Result<LocationPermissions> {
-        self.0
+        self.handle
             .run_mobile_plugin("requestPermissions", request)
             .map_err(Into::into)
     }
 
     pub fn get_current_location(&self, options: LocationOptions) -> Result<LocationData> {
-        self.0
+        self.handle
             .run_mobile_plugin("getCurrentLocation", options)
             .map_err(Into::into)
     }
 
     pub fn start_location_updates(&self, options: LocationOptions) -> Result<()> {
-        self.0
+        self.handle
             .run_mobile_plugin("startLocationUpdates", options)
             .map_err(Into::into)
     }
 
     pub fn stop_location_updates(&self) -> Result<()> {
-        self.0
+        self.handle
             .run_mobile_plugin("stopLocationUpdates", ())
             .map_err(Into::into)
     }
 
+    pub fn get_current_heading(&self, options: HeadingOptions) -> Result<Heading> {
+        self.handle
+            .run_mobile_plugin("getCurrentHeading", options)
+            .map_err(Into::into)
+    }
+
+    pub fn start_heading_updates(&self, options: HeadingOptions) -> Result<()> {
+        self.handle
+            .run_mobile_plugin("startHeadingUpdates", options)
+            .map_err(Into::into)
+    }
+
+    pub fn stop_heading_updates(&self) -> Result<()> {
+        self.handle
+            .run_mobile_plugin("stopHeadingUpdates", ())
+            .map_err(Into::into)
+    }
+
     pub fn start_monitoring_region(&self, region: Region) -> Result<()> {
-        self.0
+        self.handle
             .run_mobile_plugin("startMonitoringRegion", region)
             .map_err(Into::into)
     }
@@ -67,7 +114,7 @@ impl<R: Runtime> Location<R> {
             identifier: &'a str,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("stopMonitoringRegion", StopRegionArgs { identifier })
             .map_err(Into::into)
     }
@@ -78,33 +125,32 @@ impl<R: Runtime> Location<R> {
             address: &'a str,
         }
         
-        self.0
+        self.handle
             .run_mobile_plugin("geocodeAddress", GeocodeArgs { address })
             .map_err(Into::into)
     }
 
     pub fn reverse_geocode(&self, location: Coordinates) -> Result<Vec<Placemark>> {
-        self.0
+        self.handle
             .run_mobile_plugin("reverseGeocode", location)
             .map_err(Into::into)
     }
 
-    pub fn get_distance(&self, from: Coordinates, to: Coordinates) -> Result<f64> {
-        let request = DistanceRequest { from, to };
-        self.0
-            .run_mobile_plugin("getDistance", request)
-            .map_err(Into::into)
-    }
-
     pub fn get_monitored_regions(&self) -> Result<Vec<Region>> {
-        self.0
+        self.handle
             .run_mobile_plugin("getMonitoredRegions", ())
             .map_err(Into::into)
     }
 
     pub fn start_significant_location_updates(&self) -> Result<()> {
-        self.0
+        self.handle
             .run_mobile_plugin("startSignificantLocationUpdates", ())
             .map_err(Into::into)
     }
+
+    pub fn stop_significant_location_updates(&self) -> Result<()> {
+        self.handle
+            .run_mobile_plugin("stopSignificantLocationUpdates", ())
+            .map_err(Into::into)
+    }
 }
\ No newline at end of file
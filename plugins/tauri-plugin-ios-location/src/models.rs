@@ -1,6 +1,23 @@
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 use tauri::plugin::PermissionState;
 
+/// Plugin configuration, under `plugins > ios-location` in `tauri.conf.json`.
+/// Only the desktop simulator reads it.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    /// A GPX file, NMEA log or JSON list of fixes replayed as the location of
+    /// the device.
+    pub simulated_track: Option<PathBuf>,
+    /// How many times faster than recorded the track is replayed. Defaults to 1.
+    pub replay_speed: Option<f64>,
+    /// Whether the track starts over after its last fix.
+    #[serde(default)]
+    pub loop_track: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LocationPermissions {
@@ -85,17 +102,95 @@ pub struct Region {
     pub radius: f64, // meters
     pub notify_on_entry: bool,
     pub notify_on_exit: bool,
+    /// The corners of a polygon geofence, whose `center` and `radius` are then
+    /// the circle around it. Empty for circular regions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vertices: Vec<Coordinates>,
+}
+
+/// A region watched by the geofence engine, which evaluates location updates
+/// in Rust instead of handing regions to the OS.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Geofence {
+    pub identifier: String,
+    pub shape: GeofenceShape,
+    pub notify_on_entry: bool,
+    pub notify_on_exit: bool,
+    /// How long the device has to stay inside before it counts as entered, in
+    /// milliseconds. Leaving earlier reports nothing.
+    #[serde(default)]
+    pub dwell_time: Option<u64>,
+    /// How far beyond the boundary the device has to go before it counts as
+    /// exited, in meters, so fixes jittering around the boundary don't report
+    /// it over and over. Defaults to 10.
+    #[serde(default)]
+    pub hysteresis: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum GeofenceShape {
+    Circle {
+        center: Coordinates,
+        radius: f64, // meters
+    },
+    /// A polygon given by its corners in order, without repeating the first.
+    Polygon { vertices: Vec<Coordinates> },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Heading {
     pub magnetic_heading: f64,
+    /// Negative while the location needed to compute it is unknown.
     pub true_heading: f64,
+    /// Maximum deviation in degrees, negative when the heading is unreliable.
     pub heading_accuracy: f64,
     pub timestamp: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeadingOptions {
+    /// Smallest change in degrees that is reported. Every change is reported
+    /// when unset.
+    pub heading_filter: Option<f64>,
+    /// How the device is held, headings being measured from its top edge in
+    /// that orientation.
+    #[serde(default)]
+    pub orientation: HeadingOrientation,
+    /// Whether the system may show its compass calibration screen when the
+    /// magnetometer needs calibrating. A `HeadingCalibrationNeeded` event is
+    /// delivered either way.
+    #[serde(default)]
+    pub show_calibration: bool,
+    pub timeout: Option<u32>, // milliseconds
+}
+
+impl Default for HeadingOptions {
+    fn default() -> Self {
+        Self {
+            heading_filter: None,
+            orientation: HeadingOrientation::Portrait,
+            show_calibration: false,
+            timeout: Some(10000),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HeadingOrientation {
+    #[default]
+    Portrait,
+    PortraitUpsideDown,
+    LandscapeLeft,
+    LandscapeRight,
+    FaceUp,
+    FaceDown,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GeocodingResult {
@@ -122,14 +217,37 @@ pub struct Placemark {
     pub formatted_address: Option<String>,
 }
 
+/// An event of the location services, delivered to subscriptions as
+/// `{ eventType, data }`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct LocationEvent {
-    pub event_type: LocationEventType,
-    pub data: serde_json::Value,
+#[serde(tag = "eventType", content = "data", rename_all = "camelCase")]
+pub enum LocationEvent {
+    LocationUpdate(LocationData),
+    HeadingUpdate(Heading),
+    RegionEntered(Region),
+    RegionExited(Region),
+    AuthorizationChanged(LocationPermissions),
+    /// The magnetometer needs calibrating, so headings are unreliable until the
+    /// user moves the device in a figure eight.
+    HeadingCalibrationNeeded,
+    Error { message: String },
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+impl LocationEvent {
+    pub fn event_type(&self) -> LocationEventType {
+        match self {
+            Self::LocationUpdate(_) => LocationEventType::LocationUpdate,
+            Self::HeadingUpdate(_) => LocationEventType::HeadingUpdate,
+            Self::RegionEntered(_) => LocationEventType::RegionEntered,
+            Self::RegionExited(_) => LocationEventType::RegionExited,
+            Self::AuthorizationChanged(_) => LocationEventType::AuthorizationChanged,
+            Self::HeadingCalibrationNeeded => LocationEventType::HeadingCalibrationNeeded,
+            Self::Error { .. } => LocationEventType::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum LocationEventType {
     LocationUpdate,
@@ -137,6 +255,7 @@ pub enum LocationEventType {
     RegionEntered,
     RegionExited,
     AuthorizationChanged,
+    HeadingCalibrationNeeded,
     Error,
 }
 
@@ -145,4 +264,21 @@ pub enum LocationEventType {
 pub struct DistanceRequest {
     pub from: Coordinates,
     pub to: Coordinates,
-}
\ No newline at end of file
+}
+/// Totals of a recorded track, from [`geo::track_statistics`](crate::geo::track_statistics).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackStatistics {
+    /// Meters along the track.
+    pub distance: f64,
+    /// Meters climbed, ignoring altitude changes under 3 meters.
+    pub elevation_gain: f64,
+    /// Meters descended, ignoring altitude changes under 3 meters.
+    pub elevation_loss: f64,
+    /// Seconds from the first fix to the last.
+    pub elapsed_time: f64,
+    /// Seconds spent moving faster than 0.5 m/s.
+    pub moving_time: f64,
+    /// Meters per second over the moving time.
+    pub average_speed: f64,
+}
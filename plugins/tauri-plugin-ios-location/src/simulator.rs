@@ -0,0 +1,649 @@
+//! Simulated location backing the desktop implementation.
+//!
+//! The [`Simulator`] replays a recorded track as the position of the device, so
+//! navigation features can run without one. Its clock keeps running whether or
+//! not anything listens, like a device that keeps moving, and can be paused,
+//! sped up or moved to another point of the track.
+//!
+//! The simulated device always points where the track goes, so headings follow
+//! the course of the current fix.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex, MutexGuard,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use tauri::plugin::PermissionState;
+
+use crate::{
+    events::EventBus,
+    geo,
+    track::{self, Track},
+    wmm, Coordinates, Error, Heading, HeadingOptions, LocationData, LocationEvent, LocationOptions,
+    LocationPermissions, PermissionRequest, Result,
+};
+
+/// Longest the replay waits before it looks at pauses, seeks and speed changes
+/// again.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time between the last fix of a looping track and its first one.
+const LOOP_GAP: Duration = Duration::from_secs(1);
+
+/// Distance the device moves between significant-change updates, in meters.
+const SIGNIFICANT_DISTANCE: f64 = 500.0;
+
+/// Accuracy of the simulated compass, in degrees.
+const HEADING_ACCURACY: f64 = 5.0;
+
+fn is_authorized(permissions: &LocationPermissions) -> bool {
+    permissions.when_in_use == PermissionState::Granted || permissions.always == PermissionState::Granted
+}
+
+/// The updates the replay can run, each at most once at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Updates {
+    Standard,
+    SignificantChanges,
+    Heading,
+}
+
+/// The heading of a device pointing along the course of `fix`, if it has one.
+fn heading_of(fix: &LocationData) -> Option<Heading> {
+    let course = fix.heading?;
+    let now = Utc::now();
+    let declination = wmm::declination(fix.coordinates, fix.altitude.unwrap_or_default(), now).ok()?;
+    Some(Heading {
+        magnetic_heading: (course - declination).rem_euclid(360.0),
+        true_heading: course,
+        heading_accuracy: HEADING_ACCURACY,
+        timestamp: track::format_timestamp(now),
+    })
+}
+
+/// Smallest angle between two headings, in degrees.
+fn angle_between(a: f64, b: f64) -> f64 {
+    let difference = (a - b).rem_euclid(360.0);
+    difference.min(360.0 - difference)
+}
+
+struct SimState {
+    track: Option<Track>,
+    /// Replay position when the clock was last changed.
+    base: Duration,
+    /// When the clock was last changed, or `None` while paused.
+    resumed: Option<Instant>,
+    speed: f64,
+    looping: bool,
+    /// Changes whenever the position jumps, so running updates report the new
+    /// position instead of every fix in between.
+    generation: u64,
+    permissions: LocationPermissions,
+    permission_response: Option<LocationPermissions>,
+    /// Dropping a sender stops its updates.
+    updates: HashMap<Updates, Sender<()>>,
+}
+
+impl Default for SimState {
+    fn default() -> Self {
+        Self {
+            track: None,
+            base: Duration::ZERO,
+            resumed: Some(Instant::now()),
+            speed: 1.0,
+            looping: false,
+            generation: 0,
+            permissions: LocationPermissions {
+                when_in_use: PermissionState::Prompt,
+                always: PermissionState::Prompt,
+            },
+            permission_response: None,
+            updates: HashMap::new(),
+        }
+    }
+}
+
+impl SimState {
+    /// How far the replay has got, counting every lap of a looping track.
+    fn position(&self) -> Duration {
+        let elapsed = self.resumed.map_or(Duration::ZERO, |resumed| resumed.elapsed().mul_f64(self.speed));
+        self.base + elapsed
+    }
+
+    /// Restarts the clock from the current position, before changing it.
+    fn rebase(&mut self) {
+        self.base = self.position();
+        if self.resumed.is_some() {
+            self.resumed = Some(Instant::now());
+        }
+    }
+
+    fn period(track: &Track) -> Duration {
+        track.duration() + LOOP_GAP
+    }
+
+    /// The offset into the track of a replay position.
+    fn offset(&self, track: &Track, position: Duration) -> Duration {
+        if self.looping {
+            let period = Self::period(track).as_nanos();
+            Duration::from_nanos((position.as_nanos() % period) as u64)
+        } else {
+            position.min(track.duration())
+        }
+    }
+
+    fn fix_at(&self, track: &Track, position: Duration) -> LocationData {
+        track.points()[track.index_at(self.offset(track, position))].1.clone()
+    }
+
+    /// The fixes passed after `from` up to and including `to`.
+    fn passed(&self, track: &Track, from: Duration, to: Duration) -> Vec<LocationData> {
+        if !self.looping {
+            return track
+                .points()
+                .iter()
+                .filter(|(offset, _)| *offset > from && *offset <= to)
+                .map(|(_, fix)| fix.clone())
+                .collect();
+        }
+
+        let period = Self::period(track);
+        let mut lap_start = period * (from.as_nanos() / period.as_nanos()) as u32;
+        let mut fixes = vec![];
+        while lap_start <= to {
+            for (offset, fix) in track.points() {
+                let position = lap_start + *offset;
+                if position > from && position <= to {
+                    fixes.push(fix.clone());
+                }
+            }
+            lap_start += period;
+        }
+        fixes
+    }
+
+    /// The position of the first fix after `position`, if there is one.
+    fn next_fix(&self, track: &Track, position: Duration) -> Option<Duration> {
+        let offset = self.offset(track, position);
+        let lap_start = position - offset;
+        match track.points().iter().find(|(point, _)| *point > offset) {
+            Some((point, _)) => Some(lap_start + *point),
+            None if self.looping => Some(lap_start + Self::period(track)),
+            None => None,
+        }
+    }
+}
+
+/// Scripting interface of the simulated location.
+///
+/// Obtained on desktop through `app.location().simulator()`. A track can also be
+/// loaded at startup with the `simulatedTrack` plugin config. Fixes are reported
+/// with the time they are replayed at, and those without a heading or speed get
+/// one derived from their neighbours.
+pub struct Simulator {
+    state: Arc<Mutex<SimState>>,
+    events: Arc<EventBus>,
+}
+
+impl Drop for Simulator {
+    fn drop(&mut self) {
+        self.state().updates.clear();
+    }
+}
+
+impl Simulator {
+    pub(crate) fn new(events: Arc<EventBus>) -> Self {
+        Self {
+            state: Default::default(),
+            events,
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, SimState> {
+        lock(&self.state)
+    }
+
+    /// Replays `fixes` from the start. Fixes are timed by their timestamps, or
+    /// one second apart if any of them has none.
+    pub fn load_track(&self, fixes: Vec<LocationData>) -> Result<()> {
+        let track = Track::new(fixes)?;
+        let mut state = self.state();
+        state.track = Some(track);
+        state.base = Duration::ZERO;
+        if state.resumed.is_some() {
+            state.resumed = Some(Instant::now());
+        }
+        state.generation += 1;
+        Ok(())
+    }
+
+    /// Replays the track points of a GPX file, or its route points or waypoints
+    /// if it has no track.
+    pub fn load_gpx(&self, gpx: &str) -> Result<()> {
+        self.load_track(track::parse_gpx(gpx)?)
+    }
+
+    /// Replays the RMC and GGA sentences of an NMEA 0183 log.
+    pub fn load_nmea(&self, nmea: &str) -> Result<()> {
+        self.load_track(track::parse_nmea(nmea)?)
+    }
+
+    /// Replays a JSON array of [`LocationData`].
+    pub fn load_json(&self, json: &str) -> Result<()> {
+        self.load_track(track::parse_json(json)?)
+    }
+
+    /// Replays a `.gpx`, `.json` or NMEA file, any other extension being read as
+    /// NMEA.
+    pub fn load_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| Error::OperationFailed(format!("Failed to read {}: {e}", path.display())))?;
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+        match extension.to_ascii_lowercase().as_str() {
+            "gpx" => self.load_gpx(&contents),
+            "json" => self.load_json(&contents),
+            _ => self.load_nmea(&contents),
+        }
+    }
+
+    /// Keeps the device at one place.
+    pub fn set_location(&self, location: LocationData) -> Result<()> {
+        self.load_track(vec![location])
+    }
+
+    /// Replays the track `speed` times faster than recorded.
+    pub fn set_speed(&self, speed: f64) -> Result<()> {
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(Error::InvalidInput(format!("Invalid replay speed {speed}")));
+        }
+        let mut state = self.state();
+        state.rebase();
+        state.speed = speed;
+        Ok(())
+    }
+
+    /// Whether the track starts over after its last fix, instead of staying
+    /// there.
+    pub fn set_looping(&self, looping: bool) {
+        let mut state = self.state();
+        if let Some(track) = &state.track {
+            let offset = state.offset(track, state.position());
+            state.base = offset;
+        }
+        if state.resumed.is_some() {
+            state.resumed = Some(Instant::now());
+        }
+        state.looping = looping;
+        state.generation += 1;
+    }
+
+    pub fn pause(&self) {
+        let mut state = self.state();
+        state.rebase();
+        state.resumed = None;
+    }
+
+    pub fn resume(&self) {
+        let mut state = self.state();
+        if state.resumed.is_none() {
+            state.resumed = Some(Instant::now());
+        }
+    }
+
+    /// Moves the replay to `offset` from the first fix.
+    pub fn seek(&self, offset: Duration) {
+        let mut state = self.state();
+        state.base = offset;
+        if state.resumed.is_some() {
+            state.resumed = Some(Instant::now());
+        }
+        state.generation += 1;
+    }
+
+    /// Offset of the replay from the first fix.
+    pub fn position(&self) -> Duration {
+        let state = self.state();
+        match &state.track {
+            Some(track) => state.offset(track, state.position()),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Changes the authorization, as if the user changed it in Settings.
+    /// Revoking it suspends the running updates with an error until it is
+    /// granted again.
+    pub fn set_permissions(&self, permissions: LocationPermissions) {
+        let revoked = {
+            let mut state = self.state();
+            let revoked = is_authorized(&state.permissions) && !is_authorized(&permissions) && !state.updates.is_empty();
+            state.permissions = permissions.clone();
+            revoked
+        };
+
+        self.events.dispatch(&LocationEvent::AuthorizationChanged(permissions));
+        if revoked {
+            self.events.dispatch(&LocationEvent::Error {
+                message: "Location permission denied".to_string(),
+            });
+        }
+    }
+
+    /// Sets the authorization `request_permissions` results in. By default
+    /// whatever is still to be asked is granted.
+    pub fn set_permission_response(&self, response: Option<LocationPermissions>) {
+        self.state().permission_response = response;
+    }
+
+    pub(crate) fn permissions(&self) -> LocationPermissions {
+        self.state().permissions.clone()
+    }
+
+    pub(crate) fn request_permissions(&self, request: &PermissionRequest) -> LocationPermissions {
+        let permissions = {
+            let state = self.state();
+            state.permission_response.clone().unwrap_or_else(|| {
+                let grant = |current: PermissionState, asked: bool| match current {
+                    PermissionState::Prompt | PermissionState::PromptWithRationale if asked => PermissionState::Granted,
+                    other => other,
+                };
+                LocationPermissions {
+                    when_in_use: grant(state.permissions.when_in_use, true),
+                    always: grant(state.permissions.always, request.background),
+                }
+            })
+        };
+        self.set_permissions(permissions.clone());
+        permissions
+    }
+
+    /// The fix at the current position of the replay.
+    pub(crate) fn current_location(&self) -> Result<LocationData> {
+        let state = self.state();
+        if !is_authorized(&state.permissions) {
+            return Err(Error::PermissionDenied);
+        }
+        let track = state
+            .track
+            .as_ref()
+            .ok_or_else(|| Error::OperationFailed("The simulator has no location, load a track first".to_string()))?;
+        let mut fix = state.fix_at(track, state.position());
+        fix.timestamp = track::format_timestamp(Utc::now());
+        Ok(fix)
+    }
+
+    /// Reports each fix as the replay passes it, replacing the updates of the
+    /// same kind that are running. Significant-change updates only report fixes
+    /// at least 500 meters apart.
+    pub(crate) fn start_updates(&self, updates: Updates, mut options: LocationOptions) -> Result<()> {
+        let mut state = self.state();
+        if !is_authorized(&state.permissions) {
+            return Err(Error::PermissionDenied);
+        }
+        if updates == Updates::SignificantChanges {
+            options.distance_filter = Some(SIGNIFICANT_DISTANCE);
+        }
+        let (sender, receiver) = mpsc::channel();
+        let shared = self.state.clone();
+        let events = self.events.clone();
+        thread::Builder::new()
+            .name("location-replay".to_string())
+            .spawn(move || replay_updates(shared, events, options, receiver))
+            .map_err(|e| Error::OperationFailed(e.to_string()))?;
+        state.updates.insert(updates, sender);
+        Ok(())
+    }
+
+    /// The heading along the course at the current position of the replay.
+    pub(crate) fn current_heading(&self) -> Result<Heading> {
+        let state = self.state();
+        let track = state
+            .track
+            .as_ref()
+            .ok_or_else(|| Error::OperationFailed("The simulator has no location, load a track first".to_string()))?;
+        heading_of(&state.fix_at(track, state.position()))
+            .ok_or_else(|| Error::OperationFailed("The simulated location has no course".to_string()))
+    }
+
+    /// Reports the heading whenever it turns by more than the heading filter,
+    /// replacing the heading updates that are running.
+    pub(crate) fn start_heading_updates(&self, options: HeadingOptions) -> Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        let shared = self.state.clone();
+        let events = self.events.clone();
+        thread::Builder::new()
+            .name("heading-replay".to_string())
+            .spawn(move || replay_heading(shared, events, options, receiver))
+            .map_err(|e| Error::OperationFailed(e.to_string()))?;
+        self.state().updates.insert(Updates::Heading, sender);
+        Ok(())
+    }
+
+    pub(crate) fn stop_updates(&self, updates: Updates) {
+        self.state().updates.remove(&updates);
+    }
+}
+
+fn lock(state: &Mutex<SimState>) -> MutexGuard<'_, SimState> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Delivers the fixes the replay passes until `stop` is dropped, skipping those
+/// closer than the distance filter to the last one delivered and those passed
+/// while the authorization is revoked.
+fn replay_updates(state: Arc<Mutex<SimState>>, events: Arc<EventBus>, options: LocationOptions, stop: Receiver<()>) {
+    let distance_filter = options.distance_filter.unwrap_or(0.0);
+    let mut cursor: Option<(u64, Duration)> = None;
+    let mut last: Option<Coordinates> = None;
+
+    loop {
+        let (fixes, wait) = {
+            let state = lock(&state);
+            match &state.track {
+                Some(track) => {
+                    let position = state.position();
+                    let fixes = match cursor {
+                        Some((generation, from)) if generation == state.generation => {
+                            state.passed(track, from, position)
+                        }
+                        // Just started, or the replay jumped: report where it is now.
+                        _ => vec![state.fix_at(track, position)],
+                    };
+                    cursor = Some((state.generation, position));
+
+                    let wait = match (state.resumed, state.next_fix(track, position)) {
+                        (Some(_), Some(next)) => (next - position).div_f64(state.speed).min(POLL_INTERVAL),
+                        _ => POLL_INTERVAL,
+                    };
+                    if is_authorized(&state.permissions) {
+                        (fixes, wait)
+                    } else {
+                        (vec![], wait)
+                    }
+                }
+                None => (vec![], POLL_INTERVAL),
+            }
+        };
+
+        for mut fix in fixes {
+            if last.is_some_and(|last| geo::distance(last, fix.coordinates) < distance_filter) {
+                continue;
+            }
+            last = Some(fix.coordinates);
+            fix.timestamp = track::format_timestamp(Utc::now());
+            events.dispatch(&LocationEvent::LocationUpdate(fix));
+        }
+
+        match stop.recv_timeout(wait) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+/// Delivers the heading of the replay until `stop` is dropped, whenever it has
+/// turned by the heading filter since the last one delivered.
+fn replay_heading(state: Arc<Mutex<SimState>>, events: Arc<EventBus>, options: HeadingOptions, stop: Receiver<()>) {
+    let heading_filter = options.heading_filter.unwrap_or(0.0);
+    let mut last: Option<f64> = None;
+
+    loop {
+        let heading = {
+            let state = lock(&state);
+            state
+                .track
+                .as_ref()
+                .and_then(|track| heading_of(&state.fix_at(track, state.position())))
+        };
+
+        if let Some(heading) = heading {
+            let turned = last.map_or(true, |last| {
+                let angle = angle_between(last, heading.true_heading);
+                angle > 0.0 && angle >= heading_filter
+            });
+            if turned {
+                last = Some(heading.true_heading);
+                events.dispatch(&LocationEvent::HeadingUpdate(heading));
+            }
+        }
+
+        match stop.recv_timeout(POLL_INTERVAL) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fix(latitude: f64, timestamp: &str) -> LocationData {
+        LocationData {
+            coordinates: Coordinates {
+                latitude,
+                longitude: 0.0,
+            },
+            altitude: None,
+            accuracy: 5.0,
+            altitude_accuracy: None,
+            heading: None,
+            speed: None,
+            timestamp: timestamp.to_string(),
+            floor: None,
+        }
+    }
+
+    /// Fixes at 0, 1 and 3 seconds.
+    fn track() -> Track {
+        Track::new(vec![
+            fix(0.0, "2024-05-01T10:00:00Z"),
+            fix(0.001, "2024-05-01T10:00:01Z"),
+            fix(0.002, "2024-05-01T10:00:03Z"),
+        ])
+        .unwrap()
+    }
+
+    fn latitudes(fixes: &[LocationData]) -> Vec<f64> {
+        fixes.iter().map(|fix| fix.coordinates.latitude).collect()
+    }
+
+    fn secs(seconds: f64) -> Duration {
+        Duration::from_secs_f64(seconds)
+    }
+
+    #[test]
+    fn a_finished_track_stays_at_its_last_fix() {
+        let (state, track) = (SimState::default(), track());
+
+        assert_eq!(state.offset(&track, secs(10.0)), secs(3.0));
+        assert_eq!(state.fix_at(&track, secs(2.5)).coordinates.latitude, 0.001);
+        assert_eq!(latitudes(&state.passed(&track, secs(0.5), secs(10.0))), [0.001, 0.002]);
+        assert_eq!(state.next_fix(&track, secs(0.5)), Some(secs(1.0)));
+        assert_eq!(state.next_fix(&track, secs(3.0)), None);
+    }
+
+    #[test]
+    fn a_looping_track_starts_over_after_a_gap() {
+        let track = track();
+        let state = SimState {
+            looping: true,
+            ..Default::default()
+        };
+
+        // Laps are 4 seconds long: 3 seconds of track and the gap.
+        assert_eq!(state.offset(&track, secs(5.5)), secs(1.5));
+        assert_eq!(state.fix_at(&track, secs(3.5)).coordinates.latitude, 0.002);
+        assert_eq!(
+            latitudes(&state.passed(&track, secs(2.0), secs(9.0))),
+            [0.002, 0.0, 0.001, 0.002, 0.0, 0.001]
+        );
+        assert_eq!(state.next_fix(&track, secs(3.5)), Some(secs(4.0)));
+        assert_eq!(state.next_fix(&track, secs(4.5)), Some(secs(5.0)));
+    }
+
+    #[test]
+    fn the_clock_runs_at_the_replay_speed_until_paused() {
+        let mut state = SimState {
+            speed: 4.0,
+            resumed: Some(Instant::now() - secs(0.5)),
+            ..Default::default()
+        };
+        let position = state.position();
+        assert!(position >= secs(2.0) && position < secs(3.0), "{position:?}");
+
+        state.rebase();
+        state.resumed = None;
+        let paused = state.position();
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(state.position(), paused);
+    }
+
+    #[test]
+    fn updates_are_delivered_as_the_replay_passes_each_fix() {
+        let events = Arc::new(EventBus::default());
+        let simulator = Simulator::new(events.clone());
+        simulator.set_permissions(LocationPermissions {
+            when_in_use: PermissionState::Granted,
+            always: PermissionState::Denied,
+        });
+        simulator
+            .load_track(vec![
+                fix(0.0, "2024-05-01T10:00:00Z"),
+                fix(0.001, "2024-05-01T10:00:00.300Z"),
+                fix(0.002, "2024-05-01T10:00:00.600Z"),
+            ])
+            .unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let _subscription = events.listen(Arc::new(move |event: &LocationEvent| {
+            if let LocationEvent::LocationUpdate(fix) = event {
+                let _ = sender.lock().unwrap().send((Instant::now(), fix.coordinates.latitude));
+            }
+        }));
+        let started = Instant::now();
+        simulator.start_updates(Updates::Standard, LocationOptions::default()).unwrap();
+
+        let updates: Vec<(Duration, f64)> = (0..3)
+            .map(|_| receiver.recv_timeout(Duration::from_secs(2)).unwrap())
+            .map(|(at, latitude)| (at - started, latitude))
+            .collect();
+        assert_eq!(updates.iter().map(|(_, latitude)| *latitude).collect::<Vec<_>>(), [0.0, 0.001, 0.002]);
+        assert!(updates[1].0 >= Duration::from_millis(250), "{updates:?}");
+        assert!(updates[2].0 >= Duration::from_millis(550), "{updates:?}");
+        assert!(updates[2].0 < Duration::from_millis(1500), "{updates:?}");
+
+        // The track is over, so nothing more comes.
+        assert!(receiver.recv_timeout(Duration::from_millis(300)).is_err());
+        simulator.stop_updates(Updates::Standard);
+    }
+}
@@ -0,0 +1,486 @@
+//! Tracks replayed by the desktop location simulator, read from GPX files, NMEA
+//! logs or JSON lists of fixes.
+
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, NaiveTime, SecondsFormat, TimeDelta, Utc};
+use quick_xml::{events::Event, Reader, XmlVersion};
+
+use crate::{geo, Coordinates, Error, LocationData, Result};
+
+/// Accuracy reported for fixes whose source doesn't say, in meters.
+const DEFAULT_ACCURACY: f64 = 5.0;
+
+/// Position error per unit of horizontal dilution of precision, in meters.
+const HDOP_ERROR: f64 = 5.0;
+
+/// Time between fixes of tracks without timestamps.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+const KNOTS_TO_METERS_PER_SECOND: f64 = 1852.0 / 3600.0;
+
+pub(crate) fn format_timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn parse_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp.trim())
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+fn invalid(format: &str, message: impl std::fmt::Display) -> Error {
+    Error::InvalidInput(format!("Invalid {format}: {message}"))
+}
+
+fn fix(latitude: f64, longitude: f64) -> LocationData {
+    LocationData {
+        coordinates: Coordinates { latitude, longitude },
+        altitude: None,
+        accuracy: DEFAULT_ACCURACY,
+        altitude_accuracy: None,
+        heading: None,
+        speed: None,
+        timestamp: String::new(),
+        floor: None,
+    }
+}
+
+/// A track ready to be replayed: each fix with its offset from the first one.
+#[derive(Debug, Clone)]
+pub(crate) struct Track {
+    points: Vec<(Duration, LocationData)>,
+}
+
+impl Track {
+    /// Times the fixes by their timestamps, or one second apart when any of them
+    /// has none or they go back in time, and derives the heading and speed of
+    /// fixes that don't have one from their neighbours.
+    pub(crate) fn new(fixes: Vec<LocationData>) -> Result<Self> {
+        if fixes.is_empty() {
+            return Err(Error::InvalidInput("The track has no fixes".to_string()));
+        }
+        for fix in &fixes {
+            let Coordinates { latitude, longitude } = fix.coordinates;
+            if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+                return Err(Error::InvalidInput(format!("Invalid coordinates {latitude}, {longitude}")));
+            }
+        }
+
+        let times: Option<Vec<DateTime<Utc>>> = fixes.iter().map(|fix| parse_timestamp(&fix.timestamp)).collect();
+        let offsets: Vec<Duration> = match times {
+            Some(times) if times.windows(2).all(|pair| pair[0] <= pair[1]) => times
+                .iter()
+                .map(|time| (*time - times[0]).to_std().unwrap_or_default())
+                .collect(),
+            _ => (0..fixes.len() as u32).map(|index| DEFAULT_INTERVAL * index).collect(),
+        };
+
+        let mut points: Vec<(Duration, LocationData)> = offsets.into_iter().zip(fixes).collect();
+        for index in 0..points.len() {
+            // The first fix heads towards the second.
+            let (from, to) = match index {
+                0 if points.len() > 1 => (0, 1),
+                0 => continue,
+                _ => (index - 1, index),
+            };
+            let (from_offset, from_fix) = &points[from];
+            let (to_offset, to_fix) = &points[to];
+            let meters = geo::distance(from_fix.coordinates, to_fix.coordinates);
+            let seconds = to_offset.saturating_sub(*from_offset).as_secs_f64();
+            let heading = (meters > 0.0).then(|| geo::initial_bearing(from_fix.coordinates, to_fix.coordinates));
+            let speed = (seconds > 0.0).then(|| meters / seconds);
+
+            let fix = &mut points[index].1;
+            fix.heading = fix.heading.or(heading);
+            fix.speed = fix.speed.or(speed);
+        }
+        Ok(Self { points })
+    }
+
+    pub(crate) fn points(&self) -> &[(Duration, LocationData)] {
+        &self.points
+    }
+
+    /// Offset of the last fix.
+    pub(crate) fn duration(&self) -> Duration {
+        self.points.last().map_or(Duration::ZERO, |(offset, _)| *offset)
+    }
+
+    /// Index of the fix current at `offset`, the last one at or before it.
+    pub(crate) fn index_at(&self, offset: Duration) -> usize {
+        self.points
+            .partition_point(|(point, _)| *point <= offset)
+            .saturating_sub(1)
+    }
+}
+
+/// Reads the track points of a GPX file, or its route points when it has no
+/// track, or else its waypoints.
+pub(crate) fn parse_gpx(gpx: &str) -> Result<Vec<LocationData>> {
+    let mut reader = Reader::from_str(gpx);
+    reader.config_mut().trim_text(true);
+
+    let mut points: [Vec<LocationData>; 3] = Default::default();
+    let mut current: Option<(usize, LocationData)> = None;
+    let mut element = String::new();
+
+    loop {
+        let event = reader.read_event().map_err(|e| invalid("GPX", e))?;
+        match &event {
+            Event::Start(start) | Event::Empty(start) => {
+                let kind = match start.local_name().as_ref() {
+                    "trkpt" => 0,
+                    "rtept" => 1,
+                    "wpt" => 2,
+                    name => {
+                        element = name.to_string();
+                        continue;
+                    }
+                };
+                let coordinate = |name: &str| -> Result<f64> {
+                    let attribute = start
+                        .try_get_attribute(name)
+                        .map_err(|e| invalid("GPX", e))?
+                        .ok_or_else(|| invalid("GPX", format!("point without {name}")))?;
+                    let value = attribute.normalized_value(XmlVersion::default()).map_err(|e| invalid("GPX", e))?;
+                    value.trim().parse().map_err(|_| invalid("GPX", format!("{name} {value}")))
+                };
+                let point = fix(coordinate("lat")?, coordinate("lon")?);
+                match event {
+                    // A self-closing point has nothing more to read.
+                    Event::Empty(_) => points[kind].push(point),
+                    _ => current = Some((kind, point)),
+                }
+            }
+            Event::Text(text) => {
+                let Some((_, point)) = current.as_mut() else {
+                    continue;
+                };
+                let value = text.trim();
+                let number = || value.parse::<f64>().map_err(|_| invalid("GPX", format!("{element} {value}")));
+                match element.as_str() {
+                    "ele" => point.altitude = Some(number()?),
+                    "time" => point.timestamp = value.to_string(),
+                    "course" => point.heading = Some(number()?),
+                    "speed" => point.speed = Some(number()?),
+                    "hdop" => point.accuracy = number()? * HDOP_ERROR,
+                    _ => {}
+                }
+            }
+            Event::End(end) => {
+                if matches!(end.local_name().as_ref(), "trkpt" | "rtept" | "wpt") {
+                    if let Some((kind, point)) = current.take() {
+                        points[kind].push(point);
+                    }
+                }
+                element.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(points
+        .into_iter()
+        .find(|points| !points.is_empty())
+        .unwrap_or_default())
+}
+
+/// Reads the fixes of an NMEA 0183 log from its RMC and GGA sentences.
+/// Sentences with a bad checksum or without a valid fix are skipped, and
+/// sentences for the same time are merged into one fix.
+pub(crate) fn parse_nmea(nmea: &str) -> Result<Vec<LocationData>> {
+    let mut fixes: Vec<LocationData> = vec![];
+    let mut date: Option<NaiveDate> = None;
+    // The time of the last fix, to merge sentences and to notice midnight in
+    // logs without a date.
+    let mut last: Option<(String, DateTime<Utc>)> = None;
+
+    for line in nmea.lines() {
+        let Some(sentence) = line.trim().strip_prefix('$') else {
+            continue;
+        };
+        let Some(body) = checked(sentence) else {
+            continue;
+        };
+        let fields: Vec<&str> = body.split(',').collect();
+        let kind = fields[0].get(2..).unwrap_or_default();
+        let (time, latitude, longitude, day) = match kind {
+            "RMC" if fields.len() >= 10 && fields[2] == "A" => (
+                fields[1],
+                (fields[3], fields[4]),
+                (fields[5], fields[6]),
+                NaiveDate::parse_from_str(fields[9], "%d%m%y").ok(),
+            ),
+            "GGA" if fields.len() >= 10 && !matches!(fields[6], "" | "0") => {
+                (fields[1], (fields[2], fields[3]), (fields[4], fields[5]), None)
+            }
+            _ => continue,
+        };
+        let (Some(latitude), Some(longitude)) = (nmea_angle(latitude, 'S'), nmea_angle(longitude, 'W')) else {
+            continue;
+        };
+        let Some(time) = nmea_time(time) else {
+            continue;
+        };
+
+        // Without a date, fixes are timed from 1970, and a time earlier than the
+        // last one is the next day.
+        let undated = |previous: Option<DateTime<Utc>>| {
+            let mut timestamp = NaiveDate::default().and_time(time).and_utc();
+            while previous.is_some_and(|previous| timestamp < previous) {
+                timestamp += TimeDelta::days(1);
+            }
+            timestamp
+        };
+        let same_fix = last.as_ref().is_some_and(|(previous, _)| previous == fields[1]);
+        if let (Some(day), Some((_, previous))) = (day, &mut last) {
+            let timestamp = day.and_time(time).and_utc();
+            if date.is_none() {
+                // The first date also dates the fixes read before it.
+                let shift = timestamp - if same_fix { *previous } else { undated(Some(*previous)) };
+                *previous += shift;
+                for point in &mut fixes {
+                    if let Some(timestamp) = parse_timestamp(&point.timestamp) {
+                        point.timestamp = format_timestamp(timestamp + shift);
+                    }
+                }
+            } else if same_fix && timestamp != *previous {
+                // The fix was read with the date of the day before midnight.
+                *previous = timestamp;
+                if let Some(point) = fixes.last_mut() {
+                    point.timestamp = format_timestamp(timestamp);
+                }
+            }
+        }
+        if day.is_some() {
+            date = day;
+        }
+
+        if !same_fix {
+            let timestamp = match date {
+                Some(date) => date.and_time(time).and_utc(),
+                None => undated(last.as_ref().map(|(_, previous)| *previous)),
+            };
+            let mut point = fix(latitude, longitude);
+            point.timestamp = format_timestamp(timestamp);
+            fixes.push(point);
+            last = Some((fields[1].to_string(), timestamp));
+        }
+
+        let Some(point) = fixes.last_mut() else {
+            continue;
+        };
+        point.coordinates = Coordinates { latitude, longitude };
+        if kind == "RMC" {
+            point.speed = fields[7].parse::<f64>().ok().map(|knots| knots * KNOTS_TO_METERS_PER_SECOND);
+            point.heading = fields[8].parse().ok();
+        } else {
+            if let Ok(hdop) = fields[8].parse::<f64>() {
+                point.accuracy = hdop * HDOP_ERROR;
+            }
+            point.altitude = fields[9].parse().ok();
+        }
+    }
+    Ok(fixes)
+}
+
+/// The sentence without its checksum, if the checksum matches or is missing.
+fn checked(sentence: &str) -> Option<&str> {
+    let Some((body, checksum)) = sentence.split_once('*') else {
+        return Some(sentence);
+    };
+    let expected = u8::from_str_radix(checksum.trim(), 16).ok()?;
+    let actual = body.bytes().fold(0, |sum, byte| sum ^ byte);
+    (actual == expected).then_some(body)
+}
+
+/// Converts a `dddmm.mmmm` angle and its hemisphere to degrees.
+fn nmea_angle((value, hemisphere): (&str, &str), negative: char) -> Option<f64> {
+    let value: f64 = value.parse().ok()?;
+    let degrees = (value / 100.0).trunc() + (value % 100.0) / 60.0;
+    match hemisphere.chars().next()? {
+        hemisphere if hemisphere == negative => Some(-degrees),
+        _ => Some(degrees),
+    }
+}
+
+fn nmea_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H%M%S%.f")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H%M%S"))
+        .ok()
+}
+
+pub(crate) fn parse_json(json: &str) -> Result<Vec<LocationData>> {
+    serde_json::from_str(json).map_err(|e| invalid("JSON track", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timestamps(fixes: &[LocationData]) -> Vec<&str> {
+        fixes.iter().map(|fix| fix.timestamp.as_str()).collect()
+    }
+
+    fn timed(latitude: f64, longitude: f64, timestamp: &str) -> LocationData {
+        let mut point = fix(latitude, longitude);
+        point.timestamp = timestamp.to_string();
+        point
+    }
+
+    #[test]
+    fn gpx_track_points_are_read_with_their_details() {
+        let gpx = r#"<?xml version="1.0"?>
+            <gpx version="1.1" xmlns="http://www.topografix.com/GPX/1/1">
+              <wpt lat="1" lon="2"/>
+              <trk><trkseg>
+                <trkpt lat="47.5" lon="-122.25">
+                  <ele>12.5</ele>
+                  <time>2024-05-01T10:00:00Z</time>
+                  <hdop>2</hdop>
+                </trkpt>
+                <trkpt lat=" 47.6 " lon="-122.3"/>
+              </trkseg></trk>
+            </gpx>"#;
+
+        let fixes = parse_gpx(gpx).unwrap();
+        assert_eq!(fixes.len(), 2);
+        assert_eq!((fixes[0].coordinates.latitude, fixes[0].coordinates.longitude), (47.5, -122.25));
+        assert_eq!(fixes[0].altitude, Some(12.5));
+        assert_eq!(fixes[0].accuracy, 2.0 * HDOP_ERROR);
+        assert_eq!(fixes[0].timestamp, "2024-05-01T10:00:00Z");
+        assert_eq!((fixes[1].coordinates.latitude, fixes[1].accuracy), (47.6, DEFAULT_ACCURACY));
+        assert!(fixes[1].timestamp.is_empty());
+    }
+
+    #[test]
+    fn gpx_without_a_track_falls_back_to_routes_then_waypoints() {
+        let route = r#"<gpx><wpt lat="1" lon="1"/><rte><rtept lat="2" lon="2"/></rte></gpx>"#;
+        assert_eq!(parse_gpx(route).unwrap()[0].coordinates.latitude, 2.0);
+
+        let waypoints = r#"<gpx><wpt lat="1" lon="1"><name>Start</name></wpt><wpt lat="3" lon="3"/></gpx>"#;
+        assert_eq!(parse_gpx(waypoints).unwrap().len(), 2);
+
+        assert!(parse_gpx(r#"<gpx><trkpt lat="1"/></gpx>"#).is_err());
+        assert!(parse_gpx(r#"<gpx><trkpt lat="1" lon="1"><ele>high</ele></trkpt></gpx>"#).is_err());
+    }
+
+    #[test]
+    fn nmea_sentences_of_one_time_are_merged() {
+        let nmea = "\
+            $GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\n\
+            $GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A\n\
+            $GPRMC,123520,A,4807.038,S,01131.000,W,0,0,230394,003.1,W*00\n\
+            $GPRMC,123521,V,4807.038,N,01131.000,E,0,0,230394,,\n\
+            $GPGGA,123522,4807.038,N,01131.000,E,0,08,0.9,545.4,M,46.9,M,,\n";
+
+        let fixes = parse_nmea(nmea).unwrap();
+        assert_eq!(fixes.len(), 1, "bad checksums and invalid fixes are skipped");
+        let fix = &fixes[0];
+        assert_eq!(fix.timestamp, "1994-03-23T12:35:19.000Z");
+        assert!((fix.coordinates.latitude - 48.1173).abs() < 1e-9);
+        assert!((fix.coordinates.longitude - 11.516_666_666_666_667).abs() < 1e-9);
+        assert_eq!(fix.altitude, Some(545.4));
+        assert_eq!(fix.accuracy, 0.9 * HDOP_ERROR);
+        assert_eq!(fix.heading, Some(84.4));
+        assert!((fix.speed.unwrap() - 22.4 * KNOTS_TO_METERS_PER_SECOND).abs() < 1e-9);
+    }
+
+    #[test]
+    fn nmea_fixes_take_the_date_of_their_rmc_sentence() {
+        let nmea = "\
+            $GPGGA,235958,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,\n\
+            $GPGGA,235959,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,\n\
+            $GPRMC,235959,A,4807.038,N,01131.000,E,0,0,230394,,\n\
+            $GPGGA,000000,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,\n\
+            $GPRMC,000000,A,4807.038,N,01131.000,E,0,0,240394,,\n";
+
+        let fixes = parse_nmea(nmea).unwrap();
+        assert_eq!(
+            timestamps(&fixes),
+            [
+                "1994-03-23T23:59:58.000Z",
+                "1994-03-23T23:59:59.000Z",
+                "1994-03-24T00:00:00.000Z"
+            ]
+        );
+
+        // A GGA sentence ahead of the RMC one of the same time.
+        let nmea = "\
+            $GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\n\
+            $GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A\n";
+        assert_eq!(timestamps(&parse_nmea(nmea).unwrap()), ["1994-03-23T12:35:19.000Z"]);
+    }
+
+    #[test]
+    fn undated_nmea_logs_roll_over_at_midnight() {
+        let nmea = "\
+            $GPGGA,235959.50,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,\n\
+            $GPGGA,000000.50,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,\n";
+
+        let fixes = parse_nmea(nmea).unwrap();
+        assert_eq!(
+            timestamps(&fixes),
+            ["1970-01-01T23:59:59.500Z", "1970-01-02T00:00:00.500Z"]
+        );
+    }
+
+    #[test]
+    fn json_tracks_are_lists_of_fixes() {
+        let json = r#"[
+            {"coordinates": {"latitude": 1.5, "longitude": 2.5}, "accuracy": 3, "timestamp": "2024-05-01T10:00:00Z"},
+            {"coordinates": {"latitude": 1.6, "longitude": 2.6}, "accuracy": 4, "timestamp": "", "speed": 1.5}
+        ]"#;
+
+        let fixes = parse_json(json).unwrap();
+        assert_eq!(fixes.len(), 2);
+        assert_eq!((fixes[0].coordinates.latitude, fixes[0].accuracy), (1.5, 3.0));
+        assert_eq!(fixes[1].speed, Some(1.5));
+        assert!(parse_json(r#"{"coordinates": {}}"#).is_err());
+    }
+
+    #[test]
+    fn tracks_are_timed_by_their_timestamps() {
+        let track = Track::new(vec![
+            timed(0.0, 0.0, "2024-05-01T10:00:00Z"),
+            timed(0.0, 0.001, "2024-05-01T10:00:02.500Z"),
+            timed(0.001, 0.001, "2024-05-01T10:00:10Z"),
+        ])
+        .unwrap();
+
+        let offsets: Vec<Duration> = track.points().iter().map(|(offset, _)| *offset).collect();
+        assert_eq!(offsets, [Duration::ZERO, Duration::from_millis(2500), Duration::from_secs(10)]);
+        assert_eq!(track.duration(), Duration::from_secs(10));
+        assert_eq!(track.index_at(Duration::from_secs(2)), 0);
+        assert_eq!(track.index_at(Duration::from_millis(2500)), 1);
+        assert_eq!(track.index_at(Duration::from_secs(60)), 2);
+
+        // Headings and speeds come from the neighbours.
+        let points = track.points();
+        assert!((points[0].1.heading.unwrap() - 90.0).abs() < 1e-6);
+        assert!((points[2].1.heading.unwrap() - 0.0).abs() < 1e-6);
+        let meters = geo::distance(points[0].1.coordinates, points[1].1.coordinates);
+        assert!((points[1].1.speed.unwrap() - meters / 2.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tracks_without_usable_timestamps_are_timed_a_second_apart() {
+        let backwards = Track::new(vec![
+            timed(0.0, 0.0, "2024-05-01T10:00:10Z"),
+            timed(0.0, 0.0, "2024-05-01T10:00:00Z"),
+        ])
+        .unwrap();
+        let partly = Track::new(vec![timed(0.0, 0.0, "2024-05-01T10:00:00Z"), fix(0.0, 0.0), fix(0.0, 0.0)]).unwrap();
+
+        assert_eq!(backwards.duration(), DEFAULT_INTERVAL);
+        assert_eq!(partly.duration(), DEFAULT_INTERVAL * 2);
+        // A device standing still has no heading.
+        assert_eq!(partly.points()[1].1.heading, None);
+        assert_eq!(partly.points()[1].1.speed, Some(0.0));
+
+        assert!(Track::new(vec![]).is_err());
+        assert!(Track::new(vec![fix(91.0, 0.0)]).is_err());
+    }
+}
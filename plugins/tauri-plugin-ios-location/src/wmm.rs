@@ -0,0 +1,262 @@
+//! Magnetic declination from the World Magnetic Model, to turn compass headings
+//! into true headings without the location services.
+//!
+//! The bundled coefficients are those of WMM2025, valid from 2025 to 2030. Dates
+//! outside that span are extrapolated, which gets less accurate the further
+//! they are.
+
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+
+use crate::{Coordinates, Error, Result};
+
+const COEFFICIENTS: &str = include_str!("../data/WMM.COF");
+
+const MAX_DEGREE: usize = 12;
+
+/// Geomagnetic reference radius, in kilometers.
+const REFERENCE_RADIUS: f64 = 6371.2;
+
+/// WGS 84 semi-major axis, in kilometers.
+const WGS84_A: f64 = 6378.137;
+
+const WGS84_F: f64 = 1.0 / 298.257223563;
+
+/// Closest the field is evaluated to a pole, where east and north are undefined.
+const MAX_LATITUDE: f64 = 90.0 - 1e-6;
+
+type Table = [[f64; MAX_DEGREE + 1]; MAX_DEGREE + 1];
+
+/// Gauss coefficients of the main field and their yearly change, in nanotesla,
+/// indexed by degree and order.
+struct Model {
+    epoch: f64,
+    g: Table,
+    h: Table,
+    g_dot: Table,
+    h_dot: Table,
+}
+
+fn model() -> &'static Model {
+    static MODEL: OnceLock<Model> = OnceLock::new();
+    MODEL.get_or_init(|| parse(COEFFICIENTS))
+}
+
+/// Reads a coefficient file in the format NOAA publishes the model in.
+fn parse(cof: &str) -> Model {
+    let mut lines = cof.lines();
+    let epoch = lines
+        .next()
+        .and_then(|header| header.split_whitespace().next())
+        .and_then(|epoch| epoch.parse().ok())
+        .expect("the bundled WMM coefficients have an epoch");
+
+    let mut model = Model {
+        epoch,
+        g: Default::default(),
+        h: Default::default(),
+        g_dot: Default::default(),
+        h_dot: Default::default(),
+    };
+    for line in lines {
+        if line.starts_with("9999") {
+            break;
+        }
+        let fields: Vec<f64> = line
+            .split_whitespace()
+            .map(|field| field.parse().expect("the bundled WMM coefficients are numbers"))
+            .collect();
+        let [n, m, g, h, g_dot, h_dot] = fields[..] else {
+            panic!("malformed line in the bundled WMM coefficients: {line}");
+        };
+        let (n, m) = (n as usize, m as usize);
+        model.g[n][m] = g;
+        model.h[n][m] = h;
+        model.g_dot[n][m] = g_dot;
+        model.h_dot[n][m] = h_dot;
+    }
+    model
+}
+
+fn decimal_year(time: DateTime<Utc>) -> f64 {
+    let year_start = |year: i32| {
+        NaiveDate::from_ymd_opt(year, 1, 1)
+            .unwrap_or_default()
+            .and_hms_opt(0, 0, 0)
+            .unwrap_or_default()
+            .and_utc()
+    };
+    let start = year_start(time.year());
+    let length = year_start(time.year() + 1) - start;
+    time.year() as f64 + (time - start).num_seconds() as f64 / length.num_seconds() as f64
+}
+
+/// Schmidt semi-normalized associated Legendre functions of `cos θ` and their
+/// derivatives with respect to the colatitude θ.
+fn legendre(cos_theta: f64, sin_theta: f64) -> (Table, Table) {
+    let mut p = Table::default();
+    let mut dp = Table::default();
+    p[0][0] = 1.0;
+
+    // Gauss-normalized recursion.
+    for n in 1..=MAX_DEGREE {
+        for m in 0..=n {
+            if n == m {
+                p[n][m] = sin_theta * p[n - 1][m - 1];
+                dp[n][m] = sin_theta * dp[n - 1][m - 1] + cos_theta * p[n - 1][m - 1];
+            } else if n == 1 {
+                p[n][m] = cos_theta * p[n - 1][m];
+                dp[n][m] = cos_theta * dp[n - 1][m] - sin_theta * p[n - 1][m];
+            } else {
+                let k = ((n - 1).pow(2) - m.pow(2)) as f64 / ((2 * n - 1) * (2 * n - 3)) as f64;
+                let (p2, dp2) = if m <= n - 2 { (p[n - 2][m], dp[n - 2][m]) } else { (0.0, 0.0) };
+                p[n][m] = cos_theta * p[n - 1][m] - k * p2;
+                dp[n][m] = cos_theta * dp[n - 1][m] - sin_theta * p[n - 1][m] - k * dp2;
+            }
+        }
+    }
+
+    // Converted to Schmidt semi-normalization.
+    let mut factor = 1.0;
+    for n in 1..=MAX_DEGREE {
+        factor *= (2 * n - 1) as f64 / n as f64;
+        let mut scale = factor;
+        for m in 0..=n {
+            if m > 0 {
+                let weight = if m == 1 { 2.0 } else { 1.0 };
+                scale *= ((n - m + 1) as f64 * weight / (n + m) as f64).sqrt();
+            }
+            p[n][m] *= scale;
+            dp[n][m] *= scale;
+        }
+    }
+    (p, dp)
+}
+
+/// Northward and eastward components of the field at a geodetic position, in
+/// nanotesla.
+fn horizontal_field(latitude: f64, longitude: f64, altitude: f64, year: f64) -> (f64, f64) {
+    let model = model();
+    let phi = latitude.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+    let lambda = longitude.to_radians();
+
+    // Geodetic to geocentric spherical coordinates.
+    let e2 = WGS84_F * (2.0 - WGS84_F);
+    let height = altitude / 1000.0;
+    let rc = WGS84_A / (1.0 - e2 * phi.sin().powi(2)).sqrt();
+    let p = (rc + height) * phi.cos();
+    let z = (rc * (1.0 - e2) + height) * phi.sin();
+    let r = p.hypot(z);
+    let phi_c = (z / r).asin();
+
+    let (legendre, derivative) = legendre(phi_c.sin(), phi_c.cos());
+    let dt = year - model.epoch;
+
+    let (mut north, mut east, mut down) = (0.0, 0.0, 0.0);
+    for n in 1..=MAX_DEGREE {
+        let ratio = (REFERENCE_RADIUS / r).powi(n as i32 + 2);
+        for m in 0..=n {
+            let g = model.g[n][m] + dt * model.g_dot[n][m];
+            let h = model.h[n][m] + dt * model.h_dot[n][m];
+            let (sin_m, cos_m) = (m as f64 * lambda).sin_cos();
+            let term = g * cos_m + h * sin_m;
+            north += ratio * term * derivative[n][m];
+            east += ratio * m as f64 * (g * sin_m - h * cos_m) * legendre[n][m];
+            down -= ratio * (n as f64 + 1.0) * term * legendre[n][m];
+        }
+    }
+    east /= phi_c.cos();
+
+    // Back from the geocentric to the geodetic frame.
+    let psi = phi_c - phi;
+    (north * psi.cos() - down * psi.sin(), east)
+}
+
+/// Angle between true north and magnetic north at `coordinates`, in degrees,
+/// positive when magnetic north is east of true north. `altitude` is in meters
+/// above the ellipsoid.
+pub fn declination(coordinates: Coordinates, altitude: f64, time: DateTime<Utc>) -> Result<f64> {
+    let Coordinates { latitude, longitude } = coordinates;
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) || !altitude.is_finite() {
+        return Err(Error::InvalidInput(format!(
+            "Invalid position {latitude}, {longitude} at {altitude} m"
+        )));
+    }
+    let (north, east) = horizontal_field(latitude, longitude, altitude, decimal_year(time));
+    Ok(east.atan2(north).to_degrees())
+}
+
+/// Converts a compass heading at `coordinates` to a heading relative to true
+/// north, both in degrees from 0 to 360.
+pub fn true_heading(magnetic_heading: f64, coordinates: Coordinates, altitude: f64, time: DateTime<Utc>) -> Result<f64> {
+    Ok((magnetic_heading + declination(coordinates, altitude, time)?).rem_euclid(360.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: u32) -> DateTime<Utc> {
+        NaiveDate::from_ymd_opt(year, month, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc()
+    }
+
+    fn declination_at(latitude: f64, longitude: f64, kilometers: f64, time: DateTime<Utc>) -> f64 {
+        declination(Coordinates { latitude, longitude }, kilometers * 1000.0, time).unwrap()
+    }
+
+    /// The test values published with WMM2025, at the start of 2025 and in
+    /// the middle of 2027. The longitude of 240° E is given as 120° W.
+    #[test]
+    fn declination_matches_the_wmm2025_test_values() {
+        let values = [
+            (at(2025, 1), 0.0, 80.0, 0.0, 1.28),
+            (at(2025, 1), 0.0, 0.0, 120.0, -0.16),
+            (at(2025, 1), 0.0, -80.0, -120.0, 68.78),
+            (at(2025, 1), 100.0, 80.0, 0.0, 0.85),
+            (at(2025, 1), 100.0, 0.0, 120.0, -0.15),
+            (at(2025, 1), 100.0, -80.0, -120.0, 68.21),
+            (at(2027, 7), 0.0, 80.0, 0.0, 2.59),
+            (at(2027, 7), 0.0, 0.0, 120.0, -0.24),
+            (at(2027, 7), 0.0, -80.0, -120.0, 68.49),
+            (at(2027, 7), 100.0, 80.0, 0.0, 2.16),
+            (at(2027, 7), 100.0, 0.0, 120.0, -0.23),
+            (at(2027, 7), 100.0, -80.0, -120.0, 67.93),
+        ];
+        for (time, kilometers, latitude, longitude, expected) in values {
+            let actual = declination_at(latitude, longitude, kilometers, time);
+            assert!(
+                (actual - expected).abs() <= 0.01,
+                "{latitude}, {longitude} at {kilometers} km on {time}: {actual} instead of {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn decimal_years_count_the_days_of_the_year() {
+        assert_eq!(decimal_year(at(2025, 1)), 2025.0);
+        assert!((decimal_year(at(2027, 7)) - (2027.0 + 181.0 / 365.0)).abs() < 1e-9);
+        assert!((decimal_year(at(2028, 7)) - (2028.0 + 182.0 / 366.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn true_headings_add_the_declination() {
+        let coordinates = Coordinates {
+            latitude: -80.0,
+            longitude: -120.0,
+        };
+        let offset = declination(coordinates, 0.0, at(2025, 1)).unwrap();
+        let heading = true_heading(350.0, coordinates, 0.0, at(2025, 1)).unwrap();
+        assert!((heading - (350.0 + offset - 360.0)).abs() < 1e-9);
+
+        // The poles have a declination, however meaningless.
+        assert!(declination_at(90.0, 0.0, 0.0, at(2025, 1)).is_finite());
+        assert!(true_heading(0.0, Coordinates { latitude: 91.0, longitude: 0.0 }, 0.0, at(2025, 1)).is_err());
+        assert!(declination(Coordinates { latitude: 0.0, longitude: 181.0 }, 0.0, at(2025, 1)).is_err());
+        assert!(declination(coordinates, f64::NAN, at(2025, 1)).is_err());
+    }
+}
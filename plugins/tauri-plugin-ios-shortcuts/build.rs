@@ -1,6 +1,27 @@
-const COMMANDS: &[&str] = &["ping"];
+const COMMANDS: &[&str] = &[
+    "donate_interaction",
+    "donate_shortcut",
+    "get_all_shortcuts",
+    "delete_shortcut",
+    "delete_all_shortcuts",
+    "get_voice_shortcuts",
+    "suggest_phrase",
+    "handle_user_activity",
+    "update_shortcut",
+    "get_shortcut_suggestions",
+    "set_shortcut_suggestions",
+    "create_app_intent",
+    "register_app_intents",
+    "handle_intent",
+    "get_donated_intents",
+    "delete_donated_intents",
+    "set_eligible_for_prediction",
+    "get_predictions",
+];
 
 fn main() {
+  tauri_ios_plugin_shared::build::verify_commands(COMMANDS);
+
   tauri_plugin::Builder::new(COMMANDS)
     .android_path("android")
     .ios_path("ios")